@@ -23,11 +23,19 @@ typedef enum {
     TTFP_TABLE_NAME_CHARACTER_TO_GLYPH_INDEX_MAPPING,
     TTFP_TABLE_NAME_COLOR_BITMAP_DATA,
     TTFP_TABLE_NAME_COLOR_BITMAP_LOCATION,
+    TTFP_TABLE_NAME_COLOR_LAYERS,
+    TTFP_TABLE_NAME_COLOR_PALETTE,
     TTFP_TABLE_NAME_COMPACT_FONT_FORMAT,
     TTFP_TABLE_NAME_COMPACT_FONT_FORMAT2,
+    TTFP_TABLE_NAME_CONTROL_VALUE,
+    TTFP_TABLE_NAME_CONTROL_VALUE_PROGRAM,
+    TTFP_TABLE_NAME_CONTROL_VALUE_VARIATIONS,
+    TTFP_TABLE_NAME_FONT_PROGRAM,
     TTFP_TABLE_NAME_FONT_VARIATIONS,
     TTFP_TABLE_NAME_GLYPH_DATA,
     TTFP_TABLE_NAME_GLYPH_DEFINITION,
+    TTFP_TABLE_NAME_GLYPH_POSITIONING,
+    TTFP_TABLE_NAME_GLYPH_SUBSTITUTION,
     TTFP_TABLE_NAME_GLYPH_VARIATIONS,
     TTFP_TABLE_NAME_HEADER,
     TTFP_TABLE_NAME_HORIZONTAL_HEADER,
@@ -41,6 +49,7 @@ typedef enum {
     TTFP_TABLE_NAME_POST_SCRIPT,
     TTFP_TABLE_NAME_SCALABLE_VECTOR_GRAPHICS,
     TTFP_TABLE_NAME_STANDARD_BITMAP_GRAPHICS,
+    TTFP_TABLE_NAME_STYLE_ATTRIBUTES,
     TTFP_TABLE_NAME_VERTICAL_HEADER,
     TTFP_TABLE_NAME_VERTICAL_METRICS,
     TTFP_TABLE_NAME_VERTICAL_METRICS_VARIATIONS,
@@ -79,7 +79,7 @@ fn process(args: Args) -> Result<(), Box<dyn std::error::Error>> {
     // Exclude IO operations.
     let now = std::time::Instant::now();
 
-    let mut font = ttf::Font::from_data(&font_data, 0).ok_or("failed to open a font")?;
+    let mut font = ttf::Font::from_data(&font_data, 0)?;
     if font.is_variable() {
         for variation in args.variations {
             font.set_variation(variation.axis, variation.value)
@@ -1,6 +1,9 @@
 //! Common types for GDEF, GPOS and GSUB tables.
+//!
+//! Based on the [OpenType Layout Common Table Formats](
+//! https://docs.microsoft.com/en-us/typography/opentype/spec/chapter2).
 
-use crate::GlyphId;
+use crate::{GlyphId, Tag};
 use crate::parser::*;
 
 
@@ -34,34 +37,50 @@ impl FromData for RangeRecord {
 
 /// A [Coverage Table](https://docs.microsoft.com/en-us/typography/opentype/spec/chapter2#coverage-table).
 #[derive(Clone, Copy, Debug)]
-pub(crate) struct CoverageTable<'a> {
+pub struct CoverageTable<'a> {
     data: &'a [u8],
 }
 
 impl<'a> CoverageTable<'a> {
-    pub fn new(data: &'a [u8]) -> Self {
+    pub(crate) fn new(data: &'a [u8]) -> Self {
         CoverageTable { data }
     }
 
+    /// Checks that glyph is present.
     pub fn contains(&self, glyph_id: GlyphId) -> bool {
-        let mut s = Stream::new(self.data);
-        let format: u16 = try_opt_or!(s.read(), false);
+        self.get(glyph_id).is_some()
+    }
 
+    /// Returns the coverage index of the glyph.
+    ///
+    /// Coverage index is used by lookup subtables to index their arrays.
+    pub fn get(&self, glyph_id: GlyphId) -> Option<u16> {
+        let mut s = Stream::new(self.data);
+        let format: u16 = s.read()?;
         match format {
             1 => {
-                let count = try_opt_or!(s.read::<u16>(), false);
-                s.read_array16::<GlyphId>(count).unwrap().binary_search(&glyph_id).is_some()
+                let count: u16 = s.read()?;
+                s.read_array16::<GlyphId>(count)?.binary_search(&glyph_id).map(|(idx, _)| idx)
             }
             2 => {
-                let count = try_opt_or!(s.read::<u16>(), false);
-                let records = try_opt_or!(s.read_array16::<RangeRecord>(count), false);
-                records.into_iter().any(|r| r.range().contains(&glyph_id))
+                let count: u16 = s.read()?;
+                let records = s.read_array16::<RangeRecord>(count)?;
+                let record = records.into_iter().find(|r| r.range().contains(&glyph_id))?;
+                let offset = glyph_id.0 - record.start_glyph_id.0;
+                record.value.checked_add(offset)
             }
-            _ => false,
+            _ => None,
         }
     }
 }
 
+impl<'a> FromSlice<'a> for CoverageTable<'a> {
+    #[inline]
+    fn parse(data: &'a [u8]) -> Option<Self> {
+        Some(CoverageTable::new(data))
+    }
+}
+
 
 /// A value of [Class Definition Table](https://docs.microsoft.com/en-us/typography/opentype/spec/chapter2#class-definition-table).
 #[repr(C)]
@@ -79,16 +98,18 @@ impl FromData for Class {
 
 
 /// A [Class Definition Table](https://docs.microsoft.com/en-us/typography/opentype/spec/chapter2#class-definition-table).
-#[derive(Clone, Copy)]
-pub(crate) struct ClassDefinitionTable<'a> {
+#[derive(Clone, Copy, Debug)]
+pub struct ClassDefinitionTable<'a> {
     data: &'a [u8],
 }
 
 impl<'a> ClassDefinitionTable<'a> {
-    pub fn new(data: &'a [u8]) -> Self {
+    pub(crate) fn new(data: &'a [u8]) -> Self {
         ClassDefinitionTable { data }
     }
 
+    /// Returns glyph's class.
+    ///
     /// Any glyph not included in the range of covered glyph IDs automatically belongs to Class 0.
     pub fn get(&self, glyph_id: GlyphId) -> Class {
         self.get_impl(glyph_id).unwrap_or(Class(0))
@@ -120,3 +141,740 @@ impl<'a> ClassDefinitionTable<'a> {
         }
     }
 }
+
+impl<'a> FromSlice<'a> for ClassDefinitionTable<'a> {
+    #[inline]
+    fn parse(data: &'a [u8]) -> Option<Self> {
+        Some(ClassDefinitionTable::new(data))
+    }
+}
+
+
+/// A common GSUB and GPOS table header.
+///
+/// Feature variations are not supported and will be ignored.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct LayoutTable<'a> {
+    /// A list of scripts.
+    pub scripts: ScriptList<'a>,
+    /// A list of features.
+    pub features: FeatureList<'a>,
+    /// A list of lookups.
+    pub lookups: LookupList<'a>,
+}
+
+impl<'a> LayoutTable<'a> {
+    pub(crate) fn parse(data: &'a [u8]) -> Option<Self> {
+        let mut s = Stream::new(data);
+
+        let major_version: u16 = s.read()?;
+        let minor_version: u16 = s.read()?;
+        if major_version != 1 || minor_version > 1 {
+            return None;
+        }
+
+        let scripts = RecordList::parse(data.get(s.read::<Offset16>()?.to_usize()..)?)?;
+        let features = RecordList::parse(data.get(s.read::<Offset16>()?.to_usize()..)?)?;
+        let lookups = LookupList::parse(data.get(s.read::<Offset16>()?.to_usize()..)?)?;
+        // minor_version == 1 has `featureVariationsOffset`, which we ignore.
+
+        Some(LayoutTable {
+            scripts,
+            features,
+            lookups,
+        })
+    }
+}
+
+
+#[derive(Clone, Copy)]
+struct TagRecord {
+    tag: Tag,
+    offset: Offset16,
+}
+
+impl FromData for TagRecord {
+    const SIZE: usize = 6;
+
+    #[inline]
+    fn parse(data: &[u8]) -> Option<Self> {
+        let mut s = Stream::new(data);
+        Some(TagRecord {
+            tag: s.read()?,
+            offset: s.read()?,
+        })
+    }
+}
+
+
+/// A trait for items stored in a `RecordList`.
+///
+/// This is a low-level, internal trait that should not be used directly.
+pub trait RecordListItem<'a>: Sized {
+    /// Parses an item from a raw data.
+    fn parse(tag: Tag, data: &'a [u8]) -> Option<Self>;
+}
+
+
+/// A list of tagged records.
+///
+/// Used by scripts, language systems and features.
+#[derive(Clone, Copy)]
+pub struct RecordList<'a, T> {
+    data: &'a [u8],
+    records: LazyArray16<'a, TagRecord>,
+    data_type: core::marker::PhantomData<T>,
+}
+
+impl<T> Default for RecordList<'_, T> {
+    #[inline]
+    fn default() -> Self {
+        RecordList {
+            data: &[],
+            records: LazyArray16::default(),
+            data_type: core::marker::PhantomData,
+        }
+    }
+}
+
+impl<'a, T: RecordListItem<'a>> RecordList<'a, T> {
+    fn parse(data: &'a [u8]) -> Option<Self> {
+        let mut s = Stream::new(data);
+        let count: u16 = s.read()?;
+        let records = s.read_array16(count)?;
+        Some(RecordList {
+            data,
+            records,
+            data_type: core::marker::PhantomData,
+        })
+    }
+
+    /// Returns the number of items.
+    #[inline]
+    pub fn len(&self) -> u16 {
+        self.records.len()
+    }
+
+    /// Checks if there are any items.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// Returns an item at `index`.
+    #[inline]
+    pub fn get(&self, index: u16) -> Option<T> {
+        let record = self.records.get(index)?;
+        T::parse(record.tag, self.data.get(record.offset.to_usize()..)?)
+    }
+
+    /// Returns the first item with the specified `tag`.
+    #[inline]
+    pub fn find(&self, tag: Tag) -> Option<T> {
+        self.get(self.index(tag)?)
+    }
+
+    /// Returns the index of the first item with the specified `tag`.
+    #[inline]
+    pub fn index(&self, tag: Tag) -> Option<u16> {
+        // Records are supposed to be sorted by tag,
+        // but it's not always the case, so we cannot use the binary search.
+        self.records.into_iter().position(|r| r.tag == tag).map(|idx| idx as u16)
+    }
+}
+
+impl<'a, T: RecordListItem<'a>> IntoIterator for RecordList<'a, T> {
+    type Item = T;
+    type IntoIter = RecordListIter<'a, T>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        RecordListIter {
+            list: self,
+            index: 0,
+        }
+    }
+}
+
+impl<'a, T: RecordListItem<'a> + core::fmt::Debug + Copy> core::fmt::Debug for RecordList<'a, T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_list().entries(*self).finish()
+    }
+}
+
+
+/// An iterator over `RecordList`.
+#[derive(Clone, Copy)]
+#[allow(missing_debug_implementations)]
+pub struct RecordListIter<'a, T> {
+    list: RecordList<'a, T>,
+    index: u16,
+}
+
+impl<'a, T: RecordListItem<'a>> Iterator for RecordListIter<'a, T> {
+    type Item = T;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.index < self.list.len() {
+            self.index += 1;
+            self.list.get(self.index - 1)
+        } else {
+            None
+        }
+    }
+}
+
+
+/// A [Script List Table](
+/// https://docs.microsoft.com/en-us/typography/opentype/spec/chapter2#script-list-table-and-script-record).
+pub type ScriptList<'a> = RecordList<'a, Script<'a>>;
+
+/// A list of [Language System Tables](
+/// https://docs.microsoft.com/en-us/typography/opentype/spec/chapter2#language-system-table).
+pub type LanguageSystemList<'a> = RecordList<'a, LanguageSystem<'a>>;
+
+/// A [Feature List Table](
+/// https://docs.microsoft.com/en-us/typography/opentype/spec/chapter2#feature-list-table).
+pub type FeatureList<'a> = RecordList<'a, Feature<'a>>;
+
+
+/// A [Script Table](
+/// https://docs.microsoft.com/en-us/typography/opentype/spec/chapter2#script-table-and-language-system-record).
+#[derive(Clone, Copy, Debug)]
+pub struct Script<'a> {
+    /// Script tag.
+    pub tag: Tag,
+    /// Default language system.
+    pub default_language: Option<LanguageSystem<'a>>,
+    /// A list of language systems.
+    pub languages: LanguageSystemList<'a>,
+}
+
+impl<'a> RecordListItem<'a> for Script<'a> {
+    fn parse(tag: Tag, data: &'a [u8]) -> Option<Self> {
+        let mut s = Stream::new(data);
+        let mut default_language = None;
+        if let Some(offset) = s.read::<Option<Offset16>>()? {
+            default_language = LanguageSystem::parse(
+                Tag::from_bytes(b"dflt"),
+                data.get(offset.to_usize()..)?,
+            );
+        }
+
+        let languages = RecordList::parse(s.tail()?)?;
+        // Offsets are from the beginning of the script table and not from the list start.
+        let languages = RecordList { data, ..languages };
+
+        Some(Script {
+            tag,
+            default_language,
+            languages,
+        })
+    }
+}
+
+
+/// A [Language System Table](
+/// https://docs.microsoft.com/en-us/typography/opentype/spec/chapter2#language-system-table).
+#[derive(Clone, Copy, Debug)]
+pub struct LanguageSystem<'a> {
+    /// Language tag.
+    ///
+    /// Set to `dflt` for the default language system.
+    pub tag: Tag,
+    /// Index of a feature required for this language system.
+    pub required_feature: Option<u16>,
+    /// Array of indices into the feature list.
+    pub feature_indices: LazyArray16<'a, u16>,
+}
+
+impl<'a> RecordListItem<'a> for LanguageSystem<'a> {
+    fn parse(tag: Tag, data: &'a [u8]) -> Option<Self> {
+        let mut s = Stream::new(data);
+        s.skip::<Offset16>(); // lookupOrderOffset (reserved)
+        let required_feature = match s.read::<u16>()? {
+            0xFFFF => None,
+            n => Some(n),
+        };
+        let count: u16 = s.read()?;
+        let feature_indices = s.read_array16(count)?;
+
+        Some(LanguageSystem {
+            tag,
+            required_feature,
+            feature_indices,
+        })
+    }
+}
+
+
+/// A [Feature Table](
+/// https://docs.microsoft.com/en-us/typography/opentype/spec/chapter2#feature-table).
+#[derive(Clone, Copy, Debug)]
+pub struct Feature<'a> {
+    /// Feature tag.
+    pub tag: Tag,
+    /// Array of indices into the lookup list.
+    pub lookup_indices: LazyArray16<'a, u16>,
+}
+
+impl<'a> RecordListItem<'a> for Feature<'a> {
+    fn parse(tag: Tag, data: &'a [u8]) -> Option<Self> {
+        let mut s = Stream::new(data);
+        s.skip::<Offset16>(); // featureParamsOffset
+        let count: u16 = s.read()?;
+        let lookup_indices = s.read_array16(count)?;
+
+        Some(Feature {
+            tag,
+            lookup_indices,
+        })
+    }
+}
+
+
+/// A [Lookup List Table](
+/// https://docs.microsoft.com/en-us/typography/opentype/spec/chapter2#lookup-list-table).
+pub type LookupList<'a> = LazyOffsetArray16<'a, Lookup<'a>>;
+
+
+/// A [Lookup Table](https://docs.microsoft.com/en-us/typography/opentype/spec/chapter2#lookup-table).
+#[derive(Clone, Copy, Debug)]
+pub struct Lookup<'a> {
+    /// Lookup type.
+    ///
+    /// The meaning depends on a table. Extension lookups are not resolved here.
+    pub kind: u16,
+    /// Lookup qualifiers.
+    pub flags: LookupFlags,
+    /// Lookup subtables.
+    pub subtables: LookupSubtables<'a>,
+    /// Index of a mark glyph set in the `GDEF` table.
+    ///
+    /// Set only when `flags.use_mark_filtering_set()` is `true`.
+    pub mark_filtering_set: Option<u16>,
+}
+
+impl<'a> FromSlice<'a> for Lookup<'a> {
+    fn parse(data: &'a [u8]) -> Option<Self> {
+        let mut s = Stream::new(data);
+        let kind: u16 = s.read()?;
+        let flags: LookupFlags = s.read()?;
+        let count: u16 = s.read()?;
+        let offsets = s.read_array16(count)?;
+
+        let mut mark_filtering_set = None;
+        if flags.use_mark_filtering_set() {
+            mark_filtering_set = Some(s.read()?);
+        }
+
+        Some(Lookup {
+            kind,
+            flags,
+            subtables: LookupSubtables { kind, data, offsets },
+            mark_filtering_set,
+        })
+    }
+}
+
+
+/// [Lookup qualifiers](https://docs.microsoft.com/en-us/typography/opentype/spec/chapter2#lookupFlags).
+#[derive(Clone, Copy, PartialEq, Default, Debug)]
+pub struct LookupFlags(pub u16);
+
+impl LookupFlags {
+    /// Checks that the cursive attachment should be processed right-to-left.
+    #[inline]
+    pub fn right_to_left(self) -> bool {
+        self.0 & 0x0001 != 0
+    }
+
+    /// Checks that base glyphs should be skipped.
+    #[inline]
+    pub fn ignore_base_glyphs(self) -> bool {
+        self.0 & 0x0002 != 0
+    }
+
+    /// Checks that ligatures should be skipped.
+    #[inline]
+    pub fn ignore_ligatures(self) -> bool {
+        self.0 & 0x0004 != 0
+    }
+
+    /// Checks that all combining marks should be skipped.
+    #[inline]
+    pub fn ignore_marks(self) -> bool {
+        self.0 & 0x0008 != 0
+    }
+
+    /// Checks that `Lookup::mark_filtering_set` is set.
+    #[inline]
+    pub fn use_mark_filtering_set(self) -> bool {
+        self.0 & 0x0010 != 0
+    }
+
+    /// Returns a mark attachment class to filter marks by.
+    ///
+    /// Zero means no filtering.
+    #[inline]
+    pub fn mark_attachment_type(self) -> u8 {
+        ((self.0 & 0xFF00) >> 8) as u8
+    }
+}
+
+impl FromData for LookupFlags {
+    const SIZE: usize = 2;
+
+    #[inline]
+    fn parse(data: &[u8]) -> Option<Self> {
+        u16::parse(data).map(LookupFlags)
+    }
+}
+
+
+/// A trait for lookup subtables.
+///
+/// Implemented by `gsub::SubstitutionSubtable` and `gpos::PositioningSubtable`.
+pub trait LookupSubtable<'a>: Sized {
+    /// Parses a subtable of a specified lookup type.
+    fn parse(data: &'a [u8], kind: u16) -> Option<Self>;
+}
+
+
+/// A list of lookup subtables.
+#[derive(Clone, Copy, Default)]
+pub struct LookupSubtables<'a> {
+    kind: u16,
+    data: &'a [u8],
+    offsets: LazyArray16<'a, Offset16>,
+}
+
+impl<'a> LookupSubtables<'a> {
+    /// Returns the number of subtables.
+    #[inline]
+    pub fn len(&self) -> u16 {
+        self.offsets.len()
+    }
+
+    /// Checks if there are any subtables.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.offsets.is_empty()
+    }
+
+    /// Parses a subtable at `index`.
+    ///
+    /// Returns `None` when the subtable is malformed or has an unknown type.
+    #[inline]
+    pub fn get<T: LookupSubtable<'a>>(&self, index: u16) -> Option<T> {
+        let offset = self.offsets.get(index)?.to_usize();
+        T::parse(self.data.get(offset..)?, self.kind)
+    }
+
+    /// Returns an iterator over subtables.
+    #[inline]
+    pub fn iter<T: LookupSubtable<'a>>(&self) -> LookupSubtablesIter<'a, T> {
+        LookupSubtablesIter {
+            list: *self,
+            index: 0,
+            data_type: core::marker::PhantomData,
+        }
+    }
+}
+
+impl core::fmt::Debug for LookupSubtables<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "LookupSubtables({})", self.len())
+    }
+}
+
+
+/// An iterator over lookup subtables.
+///
+/// Malformed subtables are skipped.
+#[derive(Clone, Copy)]
+#[allow(missing_debug_implementations)]
+pub struct LookupSubtablesIter<'a, T> {
+    list: LookupSubtables<'a>,
+    index: u16,
+    data_type: core::marker::PhantomData<T>,
+}
+
+impl<'a, T: LookupSubtable<'a>> Iterator for LookupSubtablesIter<'a, T> {
+    type Item = T;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.index < self.list.len() {
+            self.index += 1;
+            if let Some(v) = self.list.get(self.index - 1) {
+                return Some(v);
+            }
+        }
+
+        None
+    }
+}
+
+
+/// A [Sequence Lookup Record](
+/// https://docs.microsoft.com/en-us/typography/opentype/spec/chapter2#sequence-lookup-record).
+#[derive(Clone, Copy, Debug)]
+pub struct SequenceLookupRecord {
+    /// Index into the current glyph sequence.
+    pub sequence_index: u16,
+    /// Lookup to apply to that position.
+    pub lookup_list_index: u16,
+}
+
+impl FromData for SequenceLookupRecord {
+    const SIZE: usize = 4;
+
+    #[inline]
+    fn parse(data: &[u8]) -> Option<Self> {
+        let mut s = Stream::new(data);
+        Some(SequenceLookupRecord {
+            sequence_index: s.read()?,
+            lookup_list_index: s.read()?,
+        })
+    }
+}
+
+
+/// A [Sequence Rule Table](
+/// https://docs.microsoft.com/en-us/typography/opentype/spec/chapter2#sequence-context-format-1-simple-glyph-contexts).
+///
+/// Also used for class-based rules.
+#[derive(Clone, Copy, Debug)]
+pub struct SequenceRule<'a> {
+    /// Glyph IDs or classes of the input sequence, starting with the second one.
+    pub input: LazyArray16<'a, u16>,
+    /// Lookups to apply.
+    pub lookups: LazyArray16<'a, SequenceLookupRecord>,
+}
+
+impl<'a> FromSlice<'a> for SequenceRule<'a> {
+    fn parse(data: &'a [u8]) -> Option<Self> {
+        let mut s = Stream::new(data);
+        let glyph_count: u16 = s.read()?;
+        let lookup_count: u16 = s.read()?;
+        let input = s.read_array16(glyph_count.checked_sub(1)?)?;
+        let lookups = s.read_array16(lookup_count)?;
+        Some(SequenceRule { input, lookups })
+    }
+}
+
+/// A set of sequence rules.
+pub type SequenceRuleSet<'a> = LazyOffsetArray16<'a, SequenceRule<'a>>;
+
+
+/// A [Sequence Context Table](
+/// https://docs.microsoft.com/en-us/typography/opentype/spec/chapter2#sequence-context-formats).
+#[allow(missing_docs)]
+#[derive(Clone, Copy, Debug)]
+pub enum ContextLookup<'a> {
+    /// Simple glyph contexts.
+    Format1 {
+        coverage: CoverageTable<'a>,
+        /// Indexed by coverage index.
+        sets: LazyOffsetArray16<'a, SequenceRuleSet<'a>>,
+    },
+    /// Class-based glyph contexts.
+    Format2 {
+        coverage: CoverageTable<'a>,
+        classes: ClassDefinitionTable<'a>,
+        /// Indexed by class.
+        sets: LazyOffsetArray16<'a, SequenceRuleSet<'a>>,
+    },
+    /// Coverage-based glyph contexts.
+    Format3 {
+        coverage: CoverageTable<'a>,
+        /// Input coverages, including the first one.
+        coverages: LazyOffsetArray16<'a, CoverageTable<'a>>,
+        lookups: LazyArray16<'a, SequenceLookupRecord>,
+    },
+}
+
+impl<'a> ContextLookup<'a> {
+    pub(crate) fn parse(data: &'a [u8]) -> Option<Self> {
+        let mut s = Stream::new(data);
+        let format: u16 = s.read()?;
+        match format {
+            1 => {
+                let coverage = parse_coverage(data, &mut s)?;
+                let sets = LazyOffsetArray16::read(data, &mut s)?;
+                Some(ContextLookup::Format1 { coverage, sets })
+            }
+            2 => {
+                let coverage = parse_coverage(data, &mut s)?;
+                let classes = parse_class_def(data, &mut s)?;
+                let sets = LazyOffsetArray16::read(data, &mut s)?;
+                Some(ContextLookup::Format2 { coverage, classes, sets })
+            }
+            3 => {
+                let glyph_count: u16 = s.read()?;
+                let lookup_count: u16 = s.read()?;
+                let coverages = LazyOffsetArray16::new(data, s.read_array16(glyph_count)?);
+                let coverage = coverages.get(0)?;
+                let lookups = s.read_array16(lookup_count)?;
+                Some(ContextLookup::Format3 { coverage, coverages, lookups })
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the coverage of the first glyph in the sequence.
+    #[inline]
+    pub fn coverage(&self) -> CoverageTable<'a> {
+        match self {
+            ContextLookup::Format1 { coverage, .. } => *coverage,
+            ContextLookup::Format2 { coverage, .. } => *coverage,
+            ContextLookup::Format3 { coverage, .. } => *coverage,
+        }
+    }
+}
+
+
+/// A [Chained Sequence Rule Table](
+/// https://docs.microsoft.com/en-us/typography/opentype/spec/chapter2#chained-sequence-context-format-1-simple-glyph-contexts).
+///
+/// Also used for class-based rules.
+#[derive(Clone, Copy, Debug)]
+pub struct ChainedSequenceRule<'a> {
+    /// Glyph IDs or classes of the backtrack sequence, in reverse order.
+    pub backtrack: LazyArray16<'a, u16>,
+    /// Glyph IDs or classes of the input sequence, starting with the second one.
+    pub input: LazyArray16<'a, u16>,
+    /// Glyph IDs or classes of the lookahead sequence.
+    pub lookahead: LazyArray16<'a, u16>,
+    /// Lookups to apply.
+    pub lookups: LazyArray16<'a, SequenceLookupRecord>,
+}
+
+impl<'a> FromSlice<'a> for ChainedSequenceRule<'a> {
+    fn parse(data: &'a [u8]) -> Option<Self> {
+        let mut s = Stream::new(data);
+        let backtrack_count: u16 = s.read()?;
+        let backtrack = s.read_array16(backtrack_count)?;
+        let input_count: u16 = s.read()?;
+        let input = s.read_array16(input_count.checked_sub(1)?)?;
+        let lookahead_count: u16 = s.read()?;
+        let lookahead = s.read_array16(lookahead_count)?;
+        let lookup_count: u16 = s.read()?;
+        let lookups = s.read_array16(lookup_count)?;
+        Some(ChainedSequenceRule { backtrack, input, lookahead, lookups })
+    }
+}
+
+/// A set of chained sequence rules.
+pub type ChainedSequenceRuleSet<'a> = LazyOffsetArray16<'a, ChainedSequenceRule<'a>>;
+
+
+/// A [Chained Sequence Context Table](
+/// https://docs.microsoft.com/en-us/typography/opentype/spec/chapter2#chained-sequence-context-formats).
+#[allow(missing_docs)]
+#[derive(Clone, Copy, Debug)]
+pub enum ChainedContextLookup<'a> {
+    /// Simple glyph contexts.
+    Format1 {
+        coverage: CoverageTable<'a>,
+        /// Indexed by coverage index.
+        sets: LazyOffsetArray16<'a, ChainedSequenceRuleSet<'a>>,
+    },
+    /// Class-based glyph contexts.
+    Format2 {
+        coverage: CoverageTable<'a>,
+        backtrack_classes: ClassDefinitionTable<'a>,
+        input_classes: ClassDefinitionTable<'a>,
+        lookahead_classes: ClassDefinitionTable<'a>,
+        /// Indexed by input class.
+        sets: LazyOffsetArray16<'a, ChainedSequenceRuleSet<'a>>,
+    },
+    /// Coverage-based glyph contexts.
+    Format3 {
+        coverage: CoverageTable<'a>,
+        /// Backtrack coverages, in reverse order.
+        backtrack_coverages: LazyOffsetArray16<'a, CoverageTable<'a>>,
+        /// Input coverages, including the first one.
+        input_coverages: LazyOffsetArray16<'a, CoverageTable<'a>>,
+        lookahead_coverages: LazyOffsetArray16<'a, CoverageTable<'a>>,
+        lookups: LazyArray16<'a, SequenceLookupRecord>,
+    },
+}
+
+impl<'a> ChainedContextLookup<'a> {
+    pub(crate) fn parse(data: &'a [u8]) -> Option<Self> {
+        let mut s = Stream::new(data);
+        let format: u16 = s.read()?;
+        match format {
+            1 => {
+                let coverage = parse_coverage(data, &mut s)?;
+                let sets = LazyOffsetArray16::read(data, &mut s)?;
+                Some(ChainedContextLookup::Format1 { coverage, sets })
+            }
+            2 => {
+                let coverage = parse_coverage(data, &mut s)?;
+                let backtrack_classes = parse_class_def(data, &mut s)?;
+                let input_classes = parse_class_def(data, &mut s)?;
+                let lookahead_classes = parse_class_def(data, &mut s)?;
+                let sets = LazyOffsetArray16::read(data, &mut s)?;
+                Some(ChainedContextLookup::Format2 {
+                    coverage,
+                    backtrack_classes,
+                    input_classes,
+                    lookahead_classes,
+                    sets,
+                })
+            }
+            3 => {
+                let backtrack_coverages = LazyOffsetArray16::read(data, &mut s)?;
+                let input_coverages: LazyOffsetArray16<CoverageTable> =
+                    LazyOffsetArray16::read(data, &mut s)?;
+                let coverage = input_coverages.get(0)?;
+                let lookahead_coverages = LazyOffsetArray16::read(data, &mut s)?;
+                let lookup_count: u16 = s.read()?;
+                let lookups = s.read_array16(lookup_count)?;
+                Some(ChainedContextLookup::Format3 {
+                    coverage,
+                    backtrack_coverages,
+                    input_coverages,
+                    lookahead_coverages,
+                    lookups,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the coverage of the first input glyph.
+    #[inline]
+    pub fn coverage(&self) -> CoverageTable<'a> {
+        match self {
+            ChainedContextLookup::Format1 { coverage, .. } => *coverage,
+            ChainedContextLookup::Format2 { coverage, .. } => *coverage,
+            ChainedContextLookup::Format3 { coverage, .. } => *coverage,
+        }
+    }
+}
+
+
+/// Parses a coverage table via a non-null `Offset16` at the current stream position.
+///
+/// `data` is the data the offset is relative to.
+#[inline]
+pub(crate) fn parse_coverage<'a>(data: &'a [u8], s: &mut Stream) -> Option<CoverageTable<'a>> {
+    let offset: Offset16 = s.read()?;
+    data.get(offset.to_usize()..).map(CoverageTable::new)
+}
+
+/// Parses a class definition table via an `Offset16` at the current stream position.
+///
+/// A null offset will produce an empty table, which assigns Class 0 to all glyphs.
+#[inline]
+fn parse_class_def<'a>(data: &'a [u8], s: &mut Stream) -> Option<ClassDefinitionTable<'a>> {
+    match s.read::<Option<Offset16>>()? {
+        Some(offset) => data.get(offset.to_usize()..).map(ClassDefinitionTable::new),
+        None => Some(ClassDefinitionTable::new(&[])),
+    }
+}
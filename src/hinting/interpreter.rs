@@ -0,0 +1,2199 @@
+//! A TrueType bytecode interpreter.
+//!
+//! https://docs.microsoft.com/en-us/typography/opentype/spec/tt_instructions
+//!
+//! The behavior follows FreeType wherever the specification is vague,
+//! since most fonts are tested against it.
+
+use core::convert::TryFrom;
+use std::vec::Vec;
+
+use crate::NormalizedCoord;
+use crate::instructions::Instructions;
+use super::HintingMode;
+use super::math;
+
+/// The maximum number of instructions executed by a single program.
+///
+/// Protects us from infinite loops in malformed fonts.
+const MAX_INSTRUCTIONS: u32 = 1_000_000;
+
+/// The maximum function calls depth. The same as in FreeType.
+const MAX_CALL_DEPTH: usize = 32;
+
+pub const ON_CURVE: u8 = 0x01;
+const TOUCHED_X: u8 = 0x02;
+const TOUCHED_Y: u8 = 0x04;
+
+const TWILIGHT_ZONE: u8 = 0;
+
+// Indices of zone pointers.
+const ZP0: usize = 0;
+const ZP1: usize = 1;
+const ZP2: usize = 2;
+
+const X_AXIS: Vector = Vector { x: 0x4000, y: 0 };
+const Y_AXIS: Vector = Vector { x: 0, y: 0x4000 };
+
+/// A point in 26.6 format.
+#[derive(Clone, Copy, PartialEq, Default, Debug)]
+pub struct Point {
+    pub x: i32,
+    pub y: i32,
+}
+
+impl Point {
+    #[inline]
+    fn get(self, is_x: bool) -> i32 {
+        if is_x { self.x } else { self.y }
+    }
+
+    #[inline]
+    fn set(&mut self, is_x: bool, value: i32) {
+        if is_x { self.x = value } else { self.y = value }
+    }
+}
+
+/// A unit vector in 2.14 format.
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct Vector {
+    x: i32,
+    y: i32,
+}
+
+impl Vector {
+    #[inline]
+    fn from_tuple(v: (i32, i32)) -> Self {
+        Vector { x: v.0, y: v.1 }
+    }
+}
+
+/// A set of points.
+///
+/// The twilight zone is allocated once per font, while the glyph zone
+/// contains the current glyph points followed by four phantom points.
+#[derive(Clone, Default)]
+pub struct Zone {
+    /// Points in font units. Used to measure original distances.
+    pub unscaled: Vec<Point>,
+    /// Scaled points before hinting.
+    pub original: Vec<Point>,
+    /// Hinted points.
+    pub current: Vec<Point>,
+    pub flags: Vec<u8>,
+    /// Indices of the last point of each contour.
+    pub contour_ends: Vec<u16>,
+}
+
+impl Zone {
+    pub fn with_len(len: usize) -> Self {
+        Zone {
+            unscaled: vec![Point::default(); len],
+            original: vec![Point::default(); len],
+            current: vec![Point::default(); len],
+            flags: vec![0; len],
+            contour_ends: Vec::new(),
+        }
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.current.len()
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum RoundState {
+    HalfGrid,
+    Grid,
+    DoubleGrid,
+    DownToGrid,
+    UpToGrid,
+    Off,
+    Super,
+    Super45,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct GraphicsState {
+    proj_vector: Vector,
+    dual_vector: Vector,
+    free_vector: Vector,
+    rp0: i32,
+    rp1: i32,
+    rp2: i32,
+    zp: [u8; 3],
+    loop_counter: u32,
+    min_distance: i32,
+    round_state: RoundState,
+    // Super rounding parameters.
+    period: i32,
+    phase: i32,
+    threshold: i32,
+    control_value_cutin: i32,
+    single_width_cutin: i32,
+    single_width_value: i32,
+    delta_base: i32,
+    delta_shift: i32,
+    auto_flip: bool,
+    pub instruct_control: u8,
+}
+
+impl Default for GraphicsState {
+    fn default() -> Self {
+        GraphicsState {
+            proj_vector: X_AXIS,
+            dual_vector: X_AXIS,
+            free_vector: X_AXIS,
+            rp0: 0,
+            rp1: 0,
+            rp2: 0,
+            zp: [1, 1, 1],
+            loop_counter: 1,
+            min_distance: 64,
+            round_state: RoundState::Grid,
+            period: 64,
+            phase: 0,
+            threshold: 0,
+            control_value_cutin: 68,
+            single_width_cutin: 0,
+            single_width_value: 0,
+            delta_base: 9,
+            delta_shift: 3,
+            auto_flip: true,
+            instruct_control: 0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Program {
+    Font = 0,
+    ControlValue = 1,
+    Glyph = 2,
+}
+
+/// A function or an instruction definition.
+#[derive(Clone, Copy, Debug)]
+pub struct Definition {
+    program: Program,
+    /// An offset of the first instruction after `FDEF` or `IDEF`.
+    start: usize,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct CallFrame {
+    program: Program,
+    return_pc: usize,
+    definition: Definition,
+    count: u32,
+}
+
+/// A state produced by the font and control value programs.
+///
+/// Each glyph program starts with a copy of it.
+#[derive(Clone)]
+pub struct State {
+    pub functions: Vec<Option<Definition>>,
+    pub instruction_defs: Vec<(u8, Definition)>,
+    pub max_instruction_defs: usize,
+    /// Scaled control values.
+    pub cvt: Vec<i32>,
+    pub storage: Vec<i32>,
+    pub graphics_state: GraphicsState,
+    pub twilight: Zone,
+}
+
+/// Font-wide interpreter settings.
+#[derive(Clone, Copy)]
+pub struct Settings<'a> {
+    pub ppem: u16,
+    /// A font units to 26.6 scale in 16.16 format.
+    pub scale: i32,
+    pub mode: HintingMode,
+    pub max_stack: usize,
+    pub font_program: &'a [u8],
+    pub control_value_program: &'a [u8],
+    pub coords: &'a [NormalizedCoord],
+    pub is_variable: bool,
+}
+
+pub struct Interpreter<'a> {
+    pub state: State,
+    pub glyph: Zone,
+    settings: Settings<'a>,
+    glyph_program: &'a [u8],
+    stack: Vec<i32>,
+    call_stack: Vec<CallFrame>,
+    gs: GraphicsState,
+    /// The program that is being run.
+    run_program: Program,
+    /// The program that contains the current instruction.
+    /// Differs from `run_program` inside function calls.
+    program: Program,
+    pc: usize,
+    /// A scale of unscaled points. Differs from the font scale for composite glyphs.
+    unscaled_scale: i32,
+    is_composite: bool,
+    backward_compatibility: bool,
+    iup_x_called: bool,
+    iup_y_called: bool,
+}
+
+impl<'a> Interpreter<'a> {
+    pub fn new(state: State, settings: Settings<'a>) -> Self {
+        Interpreter {
+            state,
+            glyph: Zone::default(),
+            settings,
+            glyph_program: &[],
+            stack: Vec::with_capacity(settings.max_stack),
+            call_stack: Vec::new(),
+            gs: GraphicsState::default(),
+            run_program: Program::Font,
+            program: Program::Font,
+            pc: 0,
+            unscaled_scale: settings.scale,
+            is_composite: false,
+            backward_compatibility: false,
+            iup_x_called: false,
+            iup_y_called: false,
+        }
+    }
+
+    /// Runs the font and control value programs.
+    ///
+    /// The resulting graphics state is stored in the interpreter state.
+    pub fn run_font_programs(&mut self) -> Option<()> {
+        self.gs = GraphicsState::default();
+        self.run(Program::Font)?;
+
+        // The control value program starts with a default graphics state,
+        // but the font program can still modify storage, CVT and twilight points.
+        self.gs = GraphicsState::default();
+        self.run(Program::ControlValue)?;
+        self.state.graphics_state = self.gs;
+        Some(())
+    }
+
+    /// Hints the glyph zone using the specified glyph program.
+    ///
+    /// Errors are not reported and the glyph is left partially hinted, like FreeType does.
+    pub fn hint_glyph(&mut self, program: Option<&'a [u8]>, is_composite: bool) {
+        let len = self.glyph.len();
+        if len < 4 {
+            return;
+        }
+
+        let phantoms_start = len - 4;
+        let mut phantoms = [Point::default(); 4];
+        phantoms.copy_from_slice(&self.glyph.current[phantoms_start..]);
+
+        // Phantom points are always rounded before hinting.
+        {
+            let cur = &mut self.glyph.current;
+            cur[len - 4].x = pix_round(cur[len - 4].x);
+            cur[len - 3].x = pix_round(cur[len - 3].x);
+            cur[len - 2].y = pix_round(cur[len - 2].y);
+            cur[len - 1].y = pix_round(cur[len - 1].y);
+        }
+
+        let instruct_control = self.state.graphics_state.instruct_control;
+        self.backward_compatibility = self.settings.mode == HintingMode::BackwardCompatibility
+            && instruct_control & 4 == 0;
+
+        if let Some(program) = program {
+            // The first bit disables glyph programs.
+            if instruct_control & 1 == 0 {
+                self.glyph_program = program;
+                self.is_composite = is_composite;
+                self.unscaled_scale = if is_composite { 0x10000 } else { self.settings.scale };
+
+                // The second bit forces the default graphics state.
+                self.gs = if instruct_control & 2 != 0 {
+                    GraphicsState::default()
+                } else {
+                    self.state.graphics_state
+                };
+
+                let _ = self.run(Program::Glyph);
+            }
+        }
+
+        // Backward compatibility mode preserves original metrics.
+        if self.backward_compatibility {
+            self.glyph.current[phantoms_start..].copy_from_slice(&phantoms);
+        }
+    }
+
+    fn run(&mut self, program: Program) -> Option<()> {
+        // Per-program defaults. The rest of the graphics state is preserved.
+        self.gs.zp = [1, 1, 1];
+        self.gs.proj_vector = X_AXIS;
+        self.gs.dual_vector = X_AXIS;
+        self.gs.free_vector = X_AXIS;
+        self.gs.round_state = RoundState::Grid;
+        self.gs.loop_counter = 1;
+
+        // Like in FreeType, the backward compatibility mode affects all programs,
+        // unless it was disabled by the control value program.
+        self.backward_compatibility = self.settings.mode == HintingMode::BackwardCompatibility
+            && self.gs.instruct_control & 4 == 0;
+        self.iup_x_called = false;
+        self.iup_y_called = false;
+        self.stack.clear();
+        self.call_stack.clear();
+        self.run_program = program;
+        self.program = program;
+        self.pc = 0;
+
+        let mut count = 0;
+        loop {
+            if self.pc >= self.code().len() {
+                if self.call_stack.is_empty() {
+                    return Some(());
+                } else {
+                    // A function without `ENDF`.
+                    return None;
+                }
+            }
+
+            count += 1;
+            if count > MAX_INSTRUCTIONS {
+                return None;
+            }
+
+            self.step()?;
+        }
+    }
+
+    #[inline]
+    fn code(&self) -> &'a [u8] {
+        match self.program {
+            Program::Font => self.settings.font_program,
+            Program::ControlValue => self.settings.control_value_program,
+            Program::Glyph => self.glyph_program,
+        }
+    }
+
+    fn step(&mut self) -> Option<()> {
+        let mut iter = Instructions::new_at(self.code(), self.pc);
+        let instruction = iter.next()?;
+        let start = self.pc;
+        self.pc = iter.offset();
+
+        let opcode = instruction.opcode;
+        match opcode {
+            // SVTCA, SPVTCA, SFVTCA
+            0x00..=0x05 => {
+                let v = if opcode & 1 == 0 { Y_AXIS } else { X_AXIS };
+                if opcode < 0x04 {
+                    self.gs.proj_vector = v;
+                    self.gs.dual_vector = v;
+                }
+
+                if !(0x02..=0x03).contains(&opcode) {
+                    self.gs.free_vector = v;
+                }
+            }
+            // SPVTL, SFVTL
+            0x06..=0x09 => {
+                let p2 = self.pop()?;
+                let p1 = self.pop()?;
+                if let Some(v) = self.line_vector(p1, p2, opcode & 1 != 0, false) {
+                    if opcode < 0x08 {
+                        self.gs.proj_vector = v;
+                        self.gs.dual_vector = v;
+                    } else {
+                        self.gs.free_vector = v;
+                    }
+                }
+            }
+            // SPVFS, SFVFS
+            0x0A | 0x0B => {
+                let y = self.pop()? as i16;
+                let x = self.pop()? as i16;
+                if let Some(v) = math::normalize(i32::from(x), i32::from(y)) {
+                    let v = Vector::from_tuple(v);
+                    if opcode == 0x0A {
+                        self.gs.proj_vector = v;
+                        self.gs.dual_vector = v;
+                    } else {
+                        self.gs.free_vector = v;
+                    }
+                }
+            }
+            // GPV, GFV
+            0x0C | 0x0D => {
+                let v = if opcode == 0x0C { self.gs.proj_vector } else { self.gs.free_vector };
+                self.push(v.x)?;
+                self.push(v.y)?;
+            }
+            // SFVTPV
+            0x0E => self.gs.free_vector = self.gs.proj_vector,
+            0x0F => self.ins_isect()?,
+            // SRP0, SRP1, SRP2
+            0x10 => self.gs.rp0 = self.pop()?,
+            0x11 => self.gs.rp1 = self.pop()?,
+            0x12 => self.gs.rp2 = self.pop()?,
+            // SZP0, SZP1, SZP2
+            0x13..=0x15 => {
+                let zone = zone_index(self.pop()?)?;
+                self.gs.zp[usize::from(opcode - 0x13)] = zone;
+            }
+            // SZPS
+            0x16 => {
+                let zone = zone_index(self.pop()?)?;
+                self.gs.zp = [zone; 3];
+            }
+            // SLOOP
+            0x17 => {
+                let n = self.pop()?;
+                if n < 0 {
+                    return None;
+                }
+
+                self.gs.loop_counter = n.min(0xFFFF) as u32;
+            }
+            0x18 => self.gs.round_state = RoundState::Grid,
+            0x19 => self.gs.round_state = RoundState::HalfGrid,
+            0x1A => self.gs.min_distance = self.pop()?,
+            // ELSE
+            0x1B => self.skip_conditional(false)?,
+            // JMPR
+            0x1C => {
+                let offset = self.pop()?;
+                self.jump(start, offset)?;
+            }
+            0x1D => self.gs.control_value_cutin = self.pop()?,
+            0x1E => self.gs.single_width_cutin = self.pop()?,
+            0x1F => {
+                let value = self.pop()?;
+                self.gs.single_width_value = math::mul_fix(value, self.settings.scale);
+            }
+            // DUP
+            0x20 => {
+                let v = *self.stack.last()?;
+                self.push(v)?;
+            }
+            // POP
+            0x21 => {
+                self.pop()?;
+            }
+            // CLEAR
+            0x22 => self.stack.clear(),
+            // SWAP
+            0x23 => {
+                let b = self.pop()?;
+                let a = self.pop()?;
+                self.push(b)?;
+                self.push(a)?;
+            }
+            // DEPTH
+            0x24 => {
+                let depth = self.stack.len() as i32;
+                self.push(depth)?;
+            }
+            // CINDEX
+            0x25 => {
+                let k = self.pop()?;
+                let v = self.stack_index(k).map(|i| self.stack[i]).unwrap_or(0);
+                self.push(v)?;
+            }
+            // MINDEX
+            0x26 => {
+                let k = self.pop()?;
+                let index = self.stack_index(k)?;
+                let v = self.stack.remove(index);
+                self.push(v)?;
+            }
+            0x27 => self.ins_alignpts()?,
+            0x29 => self.ins_utp()?,
+            // LOOPCALL
+            0x2A => {
+                let index = self.pop()?;
+                let definition = self.function(index)?;
+                let count = self.pop()?;
+                if count > 0 {
+                    self.call(definition, count as u32)?;
+                }
+            }
+            // CALL
+            0x2B => {
+                let index = self.pop()?;
+                let definition = self.function(index)?;
+                self.call(definition, 1)?;
+            }
+            0x2C => self.ins_fdef()?,
+            0x2D => self.ins_endf()?,
+            0x2E | 0x2F => self.ins_mdap(opcode & 1 != 0)?,
+            0x30 | 0x31 => self.ins_iup(opcode & 1 != 0),
+            0x32 | 0x33 => self.ins_shp(opcode & 1 != 0)?,
+            0x34 | 0x35 => self.ins_shc(opcode & 1 != 0)?,
+            0x36 | 0x37 => self.ins_shz(opcode & 1 != 0)?,
+            0x38 => self.ins_shpix()?,
+            0x39 => self.ins_ip()?,
+            0x3A | 0x3B => self.ins_msirp(opcode & 1 != 0)?,
+            0x3C => self.ins_alignrp()?,
+            0x3D => self.gs.round_state = RoundState::DoubleGrid,
+            0x3E | 0x3F => self.ins_miap(opcode & 1 != 0)?,
+            // NPUSHB, NPUSHW, PUSHB, PUSHW
+            0x40 | 0x41 | 0xB0..=0xBF => {
+                for v in instruction.push_values() {
+                    self.push(v)?;
+                }
+            }
+            // WS
+            0x42 => {
+                let value = self.pop()?;
+                let index = self.pop()?;
+                if let Some(v) = usize::try_from(index).ok().and_then(|i| self.state.storage.get_mut(i)) {
+                    *v = value;
+                }
+            }
+            // RS
+            0x43 => {
+                let index = self.pop()?;
+                let v = usize::try_from(index).ok()
+                    .and_then(|i| self.state.storage.get(i).cloned())
+                    .unwrap_or(0);
+                self.push(v)?;
+            }
+            // WCVTP, WCVTF
+            0x44 | 0x70 => {
+                let mut value = self.pop()?;
+                let index = self.pop()?;
+                if opcode == 0x70 {
+                    value = math::mul_fix(value, self.settings.scale);
+                }
+
+                if let Some(v) = usize::try_from(index).ok().and_then(|i| self.state.cvt.get_mut(i)) {
+                    *v = value;
+                }
+            }
+            // RCVT
+            0x45 => {
+                let index = self.pop()?;
+                let v = self.cvt(index).unwrap_or(0);
+                self.push(v)?;
+            }
+            // GC
+            0x46 | 0x47 => {
+                let index = self.pop()?;
+                let v = match self.point(ZP2, index) {
+                    Some(i) => {
+                        let zone = self.zone(ZP2);
+                        if opcode & 1 == 0 {
+                            self.project(zone.current[i], Point::default())
+                        } else {
+                            self.dual_project(zone.original[i], Point::default())
+                        }
+                    }
+                    None => 0,
+                };
+                self.push(v)?;
+            }
+            0x48 => self.ins_scfs()?,
+            0x49 | 0x4A => self.ins_md(opcode & 1 != 0)?,
+            // MPPEM, MPS
+            0x4B | 0x4C => self.push(i32::from(self.settings.ppem))?,
+            0x4D => self.gs.auto_flip = true,
+            0x4E => self.gs.auto_flip = false,
+            // DEBUG
+            0x4F => {
+                self.pop()?;
+            }
+            // LT, LTEQ, GT, GTEQ, EQ, NEQ
+            0x50..=0x55 => {
+                let b = self.pop()?;
+                let a = self.pop()?;
+                let v = match opcode {
+                    0x50 => a < b,
+                    0x51 => a <= b,
+                    0x52 => a > b,
+                    0x53 => a >= b,
+                    0x54 => a == b,
+                    _ => a != b,
+                };
+                self.push(v as i32)?;
+            }
+            // ODD, EVEN
+            0x56 | 0x57 => {
+                let v = self.pop()?;
+                let expected = if opcode == 0x56 { 64 } else { 0 };
+                self.push((self.round(v) & 127 == expected) as i32)?;
+            }
+            // IF
+            0x58 => {
+                if self.pop()? == 0 {
+                    self.skip_conditional(true)?;
+                }
+            }
+            // EIF
+            0x59 => {}
+            // AND, OR
+            0x5A | 0x5B => {
+                let b = self.pop()? != 0;
+                let a = self.pop()? != 0;
+                let v = if opcode == 0x5A { a && b } else { a || b };
+                self.push(v as i32)?;
+            }
+            // NOT
+            0x5C => {
+                let v = self.pop()?;
+                self.push((v == 0) as i32)?;
+            }
+            0x5D => self.ins_deltap(0)?,
+            0x5E => self.gs.delta_base = self.pop()? & 0xFFFF,
+            // SDS
+            0x5F => {
+                let v = self.pop()?;
+                if !(0..=6).contains(&v) {
+                    return None;
+                }
+
+                self.gs.delta_shift = v;
+            }
+            // ADD, SUB, DIV, MUL
+            0x60..=0x63 => {
+                let b = self.pop()?;
+                let a = self.pop()?;
+                let v = match opcode {
+                    0x60 => a.wrapping_add(b),
+                    0x61 => a.wrapping_sub(b),
+                    0x62 => {
+                        if b == 0 {
+                            return None;
+                        }
+
+                        math::mul_div_no_round(a, 64, b)
+                    }
+                    _ => math::mul_div(a, b, 64),
+                };
+                self.push(v)?;
+            }
+            // ABS, NEG, FLOOR, CEILING
+            0x64..=0x67 => {
+                let a = self.pop()?;
+                let v = match opcode {
+                    0x64 => a.wrapping_abs(),
+                    0x65 => a.wrapping_neg(),
+                    0x66 => a & !63,
+                    _ => a.wrapping_add(63) & !63,
+                };
+                self.push(v)?;
+            }
+            // ROUND
+            0x68..=0x6B => {
+                let v = self.pop()?;
+                let v = self.round(v);
+                self.push(v)?;
+            }
+            // NROUND. Engine compensation is not used, so the value stays the same.
+            0x6C..=0x6F => {
+                self.stack.last()?;
+            }
+            0x71 => self.ins_deltap(16)?,
+            0x72 => self.ins_deltap(32)?,
+            0x73 => self.ins_deltac(0)?,
+            0x74 => self.ins_deltac(16)?,
+            0x75 => self.ins_deltac(32)?,
+            // SROUND, S45ROUND
+            0x76 | 0x77 => {
+                let selector = self.pop()?;
+                if opcode == 0x76 {
+                    self.set_super_round(0x4000, selector);
+                    self.gs.round_state = RoundState::Super;
+                } else {
+                    self.set_super_round(0x2D41, selector);
+                    self.gs.round_state = RoundState::Super45;
+                }
+            }
+            // JROT, JROF
+            0x78 | 0x79 => {
+                let condition = self.pop()? != 0;
+                let offset = self.pop()?;
+                if condition == (opcode == 0x78) {
+                    self.jump(start, offset)?;
+                }
+            }
+            0x7A => self.gs.round_state = RoundState::Off,
+            0x7C => self.gs.round_state = RoundState::UpToGrid,
+            0x7D => self.gs.round_state = RoundState::DownToGrid,
+            // SANGW, AA
+            0x7E | 0x7F => {
+                self.pop()?;
+            }
+            0x80 => self.ins_flippt()?,
+            0x81 | 0x82 => self.ins_fliprg(opcode == 0x81)?,
+            // SCANCTRL, SCANTYPE
+            0x85 | 0x8D => {
+                self.pop()?;
+            }
+            0x86 | 0x87 => self.ins_sdpvtl(opcode & 1 != 0)?,
+            0x88 => self.ins_getinfo()?,
+            0x89 => self.ins_idef()?,
+            // ROLL
+            0x8A => {
+                let a = self.pop()?;
+                let b = self.pop()?;
+                let c = self.pop()?;
+                self.push(b)?;
+                self.push(a)?;
+                self.push(c)?;
+            }
+            // MAX, MIN
+            0x8B | 0x8C => {
+                let b = self.pop()?;
+                let a = self.pop()?;
+                self.push(if opcode == 0x8B { a.max(b) } else { a.min(b) })?;
+            }
+            0x8E => self.ins_instctrl()?,
+            // GETVARIATION
+            0x91 if self.settings.is_variable => {
+                for coord in self.settings.coords {
+                    self.push(i32::from(coord.get()))?;
+                }
+            }
+            0xC0..=0xDF => self.ins_mdrp(opcode)?,
+            0xE0..=0xFF => self.ins_mirp(opcode)?,
+            _ => {
+                // Undefined opcodes can be defined by the font.
+                let definition = self.state.instruction_defs.iter()
+                    .find(|(op, _)| *op == opcode)
+                    .map(|(_, definition)| *definition)?;
+                self.call(definition, 1)?;
+            }
+        }
+
+        Some(())
+    }
+
+    #[inline]
+    fn pop(&mut self) -> Option<i32> {
+        self.stack.pop()
+    }
+
+    #[inline]
+    fn push(&mut self, value: i32) -> Option<()> {
+        if self.stack.len() >= self.settings.max_stack {
+            return None;
+        }
+
+        self.stack.push(value);
+        Some(())
+    }
+
+    /// Converts a 1-based index from the top of the stack into an absolute one.
+    #[inline]
+    fn stack_index(&self, k: i32) -> Option<usize> {
+        let k = usize::try_from(k).ok()?;
+        if k == 0 || k > self.stack.len() {
+            return None;
+        }
+
+        Some(self.stack.len() - k)
+    }
+
+    /// Returns the number of iterations of a looping instruction
+    /// and resets the loop counter.
+    #[inline]
+    fn take_loop(&mut self) -> u32 {
+        let n = self.gs.loop_counter;
+        self.gs.loop_counter = 1;
+        n
+    }
+
+    #[inline]
+    fn cvt(&self, index: i32) -> Option<i32> {
+        self.state.cvt.get(usize::try_from(index).ok()?).cloned()
+    }
+
+    #[inline]
+    fn zone(&self, zp: usize) -> &Zone {
+        if self.gs.zp[zp] == TWILIGHT_ZONE { &self.state.twilight } else { &self.glyph }
+    }
+
+    #[inline]
+    fn zone_mut(&mut self, zp: usize) -> &mut Zone {
+        if self.gs.zp[zp] == TWILIGHT_ZONE { &mut self.state.twilight } else { &mut self.glyph }
+    }
+
+    /// Checks that a point index is valid for the specified zone.
+    #[inline]
+    fn point(&self, zp: usize, index: i32) -> Option<usize> {
+        let index = usize::try_from(index).ok()?;
+        if index < self.zone(zp).len() { Some(index) } else { None }
+    }
+
+    #[inline]
+    fn is_twilight(&self, zp: usize) -> bool {
+        self.gs.zp[zp] == TWILIGHT_ZONE
+    }
+
+    #[inline]
+    fn project(&self, a: Point, b: Point) -> i32 {
+        let v = self.gs.proj_vector;
+        math::dot14(a.x.wrapping_sub(b.x), a.y.wrapping_sub(b.y), v.x, v.y)
+    }
+
+    #[inline]
+    fn dual_project(&self, a: Point, b: Point) -> i32 {
+        let v = self.gs.dual_vector;
+        math::dot14(a.x.wrapping_sub(b.x), a.y.wrapping_sub(b.y), v.x, v.y)
+    }
+
+    /// Returns a scalar product of the freedom and projection vectors.
+    #[inline]
+    fn f_dot_p(&self) -> i32 {
+        let f = self.gs.free_vector;
+        let p = self.gs.proj_vector;
+        let v = (i64::from(p.x) * i64::from(f.x) + i64::from(p.y) * i64::from(f.y)) >> 14;
+        if v.abs() < 0x400 {
+            0x4000
+        } else {
+            v as i32
+        }
+    }
+
+    /// Moves a point by a distance along the freedom vector.
+    fn move_point(&mut self, zp: usize, index: usize, distance: i32) {
+        let free = self.gs.free_vector;
+        let f_dot_p = self.f_dot_p();
+        let allow_x = !self.backward_compatibility;
+        let allow_y = !(self.backward_compatibility && self.iup_x_called && self.iup_y_called);
+        let zone = self.zone_mut(zp);
+
+        if free.x != 0 {
+            if allow_x {
+                let p = &mut zone.current[index];
+                p.x = p.x.wrapping_add(math::mul_div(distance, free.x, f_dot_p));
+            }
+
+            zone.flags[index] |= TOUCHED_X;
+        }
+
+        if free.y != 0 {
+            if allow_y {
+                let p = &mut zone.current[index];
+                p.y = p.y.wrapping_add(math::mul_div(distance, free.y, f_dot_p));
+            }
+
+            zone.flags[index] |= TOUCHED_Y;
+        }
+    }
+
+    /// Moves an original point by a distance along the freedom vector.
+    fn move_original(&mut self, zp: usize, index: usize, distance: i32) {
+        let free = self.gs.free_vector;
+        let f_dot_p = self.f_dot_p();
+        let p = &mut self.zone_mut(zp).original[index];
+
+        if free.x != 0 {
+            p.x = p.x.wrapping_add(math::mul_div(distance, free.x, f_dot_p));
+        }
+
+        if free.y != 0 {
+            p.y = p.y.wrapping_add(math::mul_div(distance, free.y, f_dot_p));
+        }
+    }
+
+    /// Shifts a point from the `zp2` zone.
+    fn move_zp2_point(&mut self, index: usize, dx: i32, dy: i32, touch: bool) {
+        let free = self.gs.free_vector;
+        let allow_x = !self.backward_compatibility;
+        let allow_y = !(self.backward_compatibility && self.iup_x_called && self.iup_y_called);
+        let zone = self.zone_mut(ZP2);
+
+        if free.x != 0 {
+            if allow_x {
+                zone.current[index].x = zone.current[index].x.wrapping_add(dx);
+            }
+
+            if touch {
+                zone.flags[index] |= TOUCHED_X;
+            }
+        }
+
+        if free.y != 0 {
+            if allow_y {
+                zone.current[index].y = zone.current[index].y.wrapping_add(dy);
+            }
+
+            if touch {
+                zone.flags[index] |= TOUCHED_Y;
+            }
+        }
+    }
+
+    fn round(&self, d: i32) -> i32 {
+        match self.gs.round_state {
+            RoundState::Grid => round_to_grid(d),
+            RoundState::HalfGrid => {
+                if d >= 0 {
+                    let v = (d & !63).wrapping_add(32);
+                    if v < 0 { 32 } else { v }
+                } else {
+                    let v = ((d.wrapping_neg() & !63).wrapping_add(32)).wrapping_neg();
+                    if v > 0 { -32 } else { v }
+                }
+            }
+            RoundState::DoubleGrid => {
+                if d >= 0 {
+                    let v = d.wrapping_add(16) & !31;
+                    if v < 0 { 0 } else { v }
+                } else {
+                    let v = (d.wrapping_neg().wrapping_add(16) & !31).wrapping_neg();
+                    if v > 0 { 0 } else { v }
+                }
+            }
+            RoundState::DownToGrid => {
+                if d >= 0 {
+                    let v = d & !63;
+                    if v < 0 { 0 } else { v }
+                } else {
+                    let v = (d.wrapping_neg() & !63).wrapping_neg();
+                    if v > 0 { 0 } else { v }
+                }
+            }
+            RoundState::UpToGrid => {
+                if d >= 0 {
+                    let v = d.wrapping_add(63) & !63;
+                    if v < 0 { 0 } else { v }
+                } else {
+                    let v = (d.wrapping_neg().wrapping_add(63) & !63).wrapping_neg();
+                    if v > 0 { 0 } else { v }
+                }
+            }
+            RoundState::Off => d,
+            RoundState::Super => {
+                let (period, phase, threshold) = (self.gs.period, self.gs.phase, self.gs.threshold);
+                if d >= 0 {
+                    let v = (d.wrapping_sub(phase).wrapping_add(threshold) & -period)
+                        .wrapping_add(phase);
+                    if v < 0 { phase } else { v }
+                } else {
+                    let v = ((phase.wrapping_sub(d).wrapping_add(threshold) & -period)
+                        .wrapping_add(phase)).wrapping_neg();
+                    if v > 0 { -phase } else { v }
+                }
+            }
+            RoundState::Super45 => {
+                let (period, phase, threshold) = (self.gs.period, self.gs.phase, self.gs.threshold);
+                if d >= 0 {
+                    let v = (d.wrapping_sub(phase).wrapping_add(threshold) / period)
+                        .wrapping_mul(period).wrapping_add(phase);
+                    if v < 0 { phase } else { v }
+                } else {
+                    let v = ((phase.wrapping_sub(d).wrapping_add(threshold) / period)
+                        .wrapping_mul(period).wrapping_add(phase)).wrapping_neg();
+                    if v > 0 { -phase } else { v }
+                }
+            }
+        }
+    }
+
+    fn set_super_round(&mut self, grid_period: i32, selector: i32) {
+        let period = match selector & 0xC0 {
+            0x00 => grid_period / 2,
+            0x40 => grid_period,
+            0x80 => grid_period * 2,
+            _ => grid_period, // Reserved.
+        };
+
+        let phase = match selector & 0x30 {
+            0x00 => 0,
+            0x10 => period / 4,
+            0x20 => period / 2,
+            _ => period * 3 / 4,
+        };
+
+        let threshold = if selector & 0x0F == 0 {
+            period - 1
+        } else {
+            ((selector & 0x0F) - 4) * period / 8
+        };
+
+        self.gs.period = period >> 8;
+        self.gs.phase = phase >> 8;
+        self.gs.threshold = threshold >> 8;
+    }
+
+    /// Returns a unit vector defined by two points.
+    fn line_vector(&self, p1: i32, p2: i32, perpendicular: bool, original: bool) -> Option<Vector> {
+        let p1 = self.point(ZP1, p1)?;
+        let p2 = self.point(ZP2, p2)?;
+
+        let (a, b) = if original {
+            (self.zone(ZP1).original[p1], self.zone(ZP2).original[p2])
+        } else {
+            (self.zone(ZP1).current[p1], self.zone(ZP2).current[p2])
+        };
+
+        let mut dx = a.x.wrapping_sub(b.x);
+        let mut dy = a.y.wrapping_sub(b.y);
+        if dx == 0 && dy == 0 {
+            return Some(X_AXIS);
+        }
+
+        if perpendicular {
+            let tmp = dy;
+            dy = dx;
+            dx = tmp.wrapping_neg();
+        }
+
+        math::normalize(dx, dy).map(Vector::from_tuple)
+    }
+
+    fn jump(&mut self, start: usize, offset: i32) -> Option<()> {
+        let pc = i64::try_from(start).ok()? + i64::from(offset);
+        let pc = usize::try_from(pc).ok()?;
+        if pc > self.code().len() {
+            return None;
+        }
+
+        self.pc = pc;
+        Some(())
+    }
+
+    /// Skips instructions until the matching `EIF`, or `ELSE` when `stop_at_else` is set.
+    fn skip_conditional(&mut self, stop_at_else: bool) -> Option<()> {
+        let mut iter = Instructions::new_at(self.code(), self.pc);
+        let mut depth = 1u32;
+        loop {
+            let instruction = iter.next()?;
+            match instruction.opcode {
+                // IF
+                0x58 => depth += 1,
+                // ELSE
+                0x1B if depth == 1 && stop_at_else => break,
+                // EIF
+                0x59 => {
+                    depth -= 1;
+                    if depth == 0 {
+                        break;
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        self.pc = iter.offset();
+        Some(())
+    }
+
+    /// Skips instructions until the end of a function or an instruction definition.
+    fn skip_definition(&mut self) -> Option<()> {
+        let mut iter = Instructions::new_at(self.code(), self.pc);
+        loop {
+            let instruction = iter.next()?;
+            match instruction.opcode {
+                // Nested FDEF and IDEF are not allowed.
+                0x2C | 0x89 => return None,
+                // ENDF
+                0x2D => break,
+                _ => {}
+            }
+        }
+
+        self.pc = iter.offset();
+        Some(())
+    }
+
+    #[inline]
+    fn function(&self, index: i32) -> Option<Definition> {
+        *self.state.functions.get(usize::try_from(index).ok()?)?
+    }
+
+    fn call(&mut self, definition: Definition, count: u32) -> Option<()> {
+        if self.call_stack.len() >= MAX_CALL_DEPTH {
+            return None;
+        }
+
+        self.call_stack.push(CallFrame {
+            program: self.program,
+            return_pc: self.pc,
+            definition,
+            count,
+        });
+
+        self.program = definition.program;
+        self.pc = definition.start;
+        Some(())
+    }
+
+    fn ins_fdef(&mut self) -> Option<()> {
+        // Functions can be defined only by the font and control value programs.
+        if self.run_program == Program::Glyph {
+            return None;
+        }
+
+        let index = usize::try_from(self.pop()?).ok()?;
+        if index >= self.state.functions.len() {
+            return None;
+        }
+
+        let definition = Definition { program: self.program, start: self.pc };
+        self.skip_definition()?;
+        self.state.functions[index] = Some(definition);
+        Some(())
+    }
+
+    fn ins_idef(&mut self) -> Option<()> {
+        if self.run_program == Program::Glyph {
+            return None;
+        }
+
+        let opcode = u8::try_from(self.pop()?).ok()?;
+        let definition = Definition { program: self.program, start: self.pc };
+        self.skip_definition()?;
+
+        let defs = &mut self.state.instruction_defs;
+        if let Some(def) = defs.iter_mut().find(|(op, _)| *op == opcode) {
+            def.1 = definition;
+        } else if defs.len() < self.state.max_instruction_defs {
+            defs.push((opcode, definition));
+        } else {
+            return None;
+        }
+
+        Some(())
+    }
+
+    fn ins_endf(&mut self) -> Option<()> {
+        let frame = self.call_stack.last_mut()?;
+        if frame.count > 1 {
+            frame.count -= 1;
+            self.program = frame.definition.program;
+            self.pc = frame.definition.start;
+        } else {
+            let frame = self.call_stack.pop()?;
+            self.program = frame.program;
+            self.pc = frame.return_pc;
+        }
+
+        Some(())
+    }
+
+    // Instructions that pop invalid point indices do nothing, like in FreeType.
+
+    fn ins_isect(&mut self) -> Option<()> {
+        let b1 = self.pop()?;
+        let b0 = self.pop()?;
+        let a1 = self.pop()?;
+        let a0 = self.pop()?;
+        let point = self.pop()?;
+
+        let (point, a0, a1, b0, b1) = match (
+            self.point(ZP2, point),
+            self.point(ZP1, a0),
+            self.point(ZP1, a1),
+            self.point(ZP0, b0),
+            self.point(ZP0, b1),
+        ) {
+            (Some(p), Some(a0), Some(a1), Some(b0), Some(b1)) => (p, a0, a1, b0, b1),
+            _ => return Some(()),
+        };
+
+        let a0 = self.zone(ZP1).current[a0];
+        let a1 = self.zone(ZP1).current[a1];
+        let b0 = self.zone(ZP0).current[b0];
+        let b1 = self.zone(ZP0).current[b1];
+
+        let dbx = b1.x.wrapping_sub(b0.x);
+        let dby = b1.y.wrapping_sub(b0.y);
+        let dax = a1.x.wrapping_sub(a0.x);
+        let day = a1.y.wrapping_sub(a0.y);
+        let dx = b0.x.wrapping_sub(a0.x);
+        let dy = b0.y.wrapping_sub(a0.y);
+
+        let discriminant = math::mul_div(dax, dby.wrapping_neg(), 0x40)
+            .wrapping_add(math::mul_div(day, dbx, 0x40));
+        let dot_product = math::mul_div(dax, dbx, 0x40)
+            .wrapping_add(math::mul_div(day, dby, 0x40));
+
+        let zone = self.zone_mut(ZP2);
+        // Reject grazing intersections, when the angle between lines is less than ~3 degrees.
+        if 19 * i64::from(discriminant).abs() > i64::from(dot_product).abs() {
+            let v = math::mul_div(dx, dby.wrapping_neg(), 0x40)
+                .wrapping_add(math::mul_div(dy, dbx, 0x40));
+            zone.current[point] = Point {
+                x: a0.x.wrapping_add(math::mul_div(v, dax, discriminant)),
+                y: a0.y.wrapping_add(math::mul_div(v, day, discriminant)),
+            };
+        } else {
+            // Use the middle of the middle points of lines.
+            zone.current[point] = Point {
+                x: ((i64::from(a0.x) + i64::from(a1.x) + i64::from(b0.x) + i64::from(b1.x)) / 4) as i32,
+                y: ((i64::from(a0.y) + i64::from(a1.y) + i64::from(b0.y) + i64::from(b1.y)) / 4) as i32,
+            };
+        }
+
+        zone.flags[point] |= TOUCHED_X | TOUCHED_Y;
+        Some(())
+    }
+
+    fn ins_alignpts(&mut self) -> Option<()> {
+        let p2 = self.pop()?;
+        let p1 = self.pop()?;
+        let (p1, p2) = match (self.point(ZP1, p1), self.point(ZP0, p2)) {
+            (Some(p1), Some(p2)) => (p1, p2),
+            _ => return Some(()),
+        };
+
+        let distance = self.project(self.zone(ZP0).current[p2], self.zone(ZP1).current[p1]) / 2;
+        self.move_point(ZP1, p1, distance);
+        self.move_point(ZP0, p2, distance.wrapping_neg());
+        Some(())
+    }
+
+    fn ins_utp(&mut self) -> Option<()> {
+        let index = self.pop()?;
+        let index = try_opt_or!(self.point(ZP0, index), Some(()));
+
+        let mut mask = !0;
+        if self.gs.free_vector.x != 0 {
+            mask &= !TOUCHED_X;
+        }
+
+        if self.gs.free_vector.y != 0 {
+            mask &= !TOUCHED_Y;
+        }
+
+        self.zone_mut(ZP0).flags[index] &= mask;
+        Some(())
+    }
+
+    fn ins_mdap(&mut self, round: bool) -> Option<()> {
+        let index = self.pop()?;
+        let point = try_opt_or!(self.point(ZP0, index), Some(()));
+
+        let distance = if round {
+            let d = self.project(self.zone(ZP0).current[point], Point::default());
+            self.round(d).wrapping_sub(d)
+        } else {
+            0
+        };
+
+        self.move_point(ZP0, point, distance);
+        self.gs.rp0 = index;
+        self.gs.rp1 = index;
+        Some(())
+    }
+
+    fn ins_iup(&mut self, is_x: bool) {
+        // IUP can be called only once per axis in the backward compatibility mode.
+        if self.backward_compatibility {
+            if self.iup_x_called && self.iup_y_called {
+                return;
+            }
+
+            if is_x {
+                self.iup_x_called = true;
+            } else {
+                self.iup_y_called = true;
+            }
+        }
+
+        let touch = if is_x { TOUCHED_X } else { TOUCHED_Y };
+        let zone = &mut self.glyph;
+        // Phantom points are not a part of any contour.
+        let points_len = zone.len().saturating_sub(4);
+
+        let mut start = 0;
+        for i in 0..zone.contour_ends.len() {
+            let end = usize::from(zone.contour_ends[i]).min(points_len.saturating_sub(1));
+            if start > end {
+                continue;
+            }
+
+            let first_touched = match (start..=end).find(|i| zone.flags[*i] & touch != 0) {
+                Some(v) => v,
+                None => {
+                    start = end + 1;
+                    continue;
+                }
+            };
+
+            let mut cur_touched = first_touched;
+            for point in first_touched + 1..=end {
+                if zone.flags[point] & touch != 0 {
+                    iup_interpolate(zone, is_x, cur_touched + 1, point - 1, cur_touched, point);
+                    cur_touched = point;
+                }
+            }
+
+            if cur_touched == first_touched {
+                iup_shift(zone, is_x, start, end, cur_touched);
+            } else {
+                iup_interpolate(zone, is_x, cur_touched + 1, end, cur_touched, first_touched);
+                if first_touched > 0 {
+                    iup_interpolate(zone, is_x, start, first_touched - 1, cur_touched, first_touched);
+                }
+            }
+
+            start = end + 1;
+        }
+    }
+
+    /// Returns a displacement of a reference point along the freedom vector.
+    ///
+    /// Returns the displacement, the reference point zone and index.
+    fn point_displacement(&self, use_rp1: bool) -> Option<(i32, i32, u8, usize)> {
+        let (zp, rp) = if use_rp1 { (ZP0, self.gs.rp1) } else { (ZP1, self.gs.rp2) };
+        let index = self.point(zp, rp)?;
+        let zone = self.zone(zp);
+        let d = self.project(zone.current[index], zone.original[index]);
+        let free = self.gs.free_vector;
+        let f_dot_p = self.f_dot_p();
+        Some((
+            math::mul_div(d, free.x, f_dot_p),
+            math::mul_div(d, free.y, f_dot_p),
+            self.gs.zp[zp],
+            index,
+        ))
+    }
+
+    fn ins_shp(&mut self, use_rp1: bool) -> Option<()> {
+        let count = self.take_loop();
+        let displacement = self.point_displacement(use_rp1);
+        for _ in 0..count {
+            let index = self.pop()?;
+            if let (Some((dx, dy, _, _)), Some(point)) = (displacement, self.point(ZP2, index)) {
+                self.move_zp2_point(point, dx, dy, true);
+            }
+        }
+
+        Some(())
+    }
+
+    fn ins_shc(&mut self, use_rp1: bool) -> Option<()> {
+        let contour = self.pop()?;
+        let (dx, dy, ref_zone, ref_point) = try_opt_or!(self.point_displacement(use_rp1), Some(()));
+        let contour = try_opt_or!(usize::try_from(contour).ok(), Some(()));
+
+        let zone = self.zone(ZP2);
+        let (start, end) = if self.is_twilight(ZP2) {
+            // The twilight zone has a single contour.
+            if contour != 0 {
+                return Some(());
+            }
+
+            (0, zone.len())
+        } else {
+            let end = match zone.contour_ends.get(contour) {
+                Some(v) => usize::from(*v) + 1,
+                None => return Some(()),
+            };
+
+            let start = match contour.checked_sub(1) {
+                Some(prev) => usize::from(zone.contour_ends[prev]) + 1,
+                None => 0,
+            };
+
+            (start, end)
+        };
+
+        for i in start..end {
+            if ref_zone != self.gs.zp[ZP2] || ref_point != i {
+                self.move_zp2_point(i, dx, dy, true);
+            }
+        }
+
+        Some(())
+    }
+
+    fn ins_shz(&mut self, use_rp1: bool) -> Option<()> {
+        let zone = self.pop()?;
+        if !(0..=1).contains(&zone) {
+            return Some(());
+        }
+
+        let (dx, dy, ref_zone, ref_point) = try_opt_or!(self.point_displacement(use_rp1), Some(()));
+
+        // Phantom points are not shifted.
+        let zone = self.zone(ZP2);
+        let len = if self.is_twilight(ZP2) {
+            zone.len()
+        } else {
+            zone.contour_ends.last().map(|end| usize::from(*end) + 1).unwrap_or(0)
+        };
+
+        for i in 0..len {
+            if ref_zone != self.gs.zp[ZP2] || ref_point != i {
+                self.move_zp2_point(i, dx, dy, false);
+            }
+        }
+
+        Some(())
+    }
+
+    fn ins_shpix(&mut self) -> Option<()> {
+        let amount = self.pop()?;
+        let free = self.gs.free_vector;
+        let dx = math::mul14(amount, free.x);
+        let dy = math::mul14(amount, free.y);
+        let in_twilight = self.gs.zp == [TWILIGHT_ZONE; 3];
+
+        let count = self.take_loop();
+        for _ in 0..count {
+            let index = self.pop()?;
+            let point = match self.point(ZP2, index) {
+                Some(v) => v,
+                None => continue,
+            };
+
+            if self.backward_compatibility {
+                // Allow only vertical moves of points that were already touched vertically.
+                let after_iup = self.iup_x_called && self.iup_y_called;
+                let touched_y = self.zone(ZP2).flags[point] & TOUCHED_Y != 0;
+                if in_twilight
+                    || (!after_iup && ((self.is_composite && free.y != 0) || touched_y))
+                {
+                    self.move_zp2_point(point, 0, dy, true);
+                }
+            } else {
+                self.move_zp2_point(point, dx, dy, true);
+            }
+        }
+
+        Some(())
+    }
+
+    fn ins_ip(&mut self) -> Option<()> {
+        let count = self.take_loop();
+
+        let rp1 = self.point(ZP0, self.gs.rp1);
+        let rp2 = self.point(ZP1, self.gs.rp2);
+        let (rp1, rp2) = match (rp1, rp2) {
+            (Some(rp1), Some(rp2)) => (rp1, rp2),
+            _ => {
+                for _ in 0..count {
+                    self.pop()?;
+                }
+
+                return Some(());
+            }
+        };
+
+        // Twilight points have no unscaled coordinates.
+        let in_twilight = self.gs.zp.contains(&TWILIGHT_ZONE);
+
+        let orus_base = if in_twilight {
+            self.zone(ZP0).original[rp1]
+        } else {
+            self.zone(ZP0).unscaled[rp1]
+        };
+        let cur_base = self.zone(ZP0).current[rp1];
+
+        // Unscaled distances are not scaled, since only their ratio matters.
+        let old_range = if in_twilight {
+            self.dual_project(self.zone(ZP1).original[rp2], orus_base)
+        } else {
+            self.dual_project(self.zone(ZP1).unscaled[rp2], orus_base)
+        };
+        let cur_range = self.project(self.zone(ZP1).current[rp2], cur_base);
+
+        for _ in 0..count {
+            let index = self.pop()?;
+            let point = match self.point(ZP2, index) {
+                Some(v) => v,
+                None => continue,
+            };
+
+            let zone = self.zone(ZP2);
+            let org_dist = if in_twilight {
+                self.dual_project(zone.original[point], orus_base)
+            } else {
+                self.dual_project(zone.unscaled[point], orus_base)
+            };
+            let cur_dist = self.project(zone.current[point], cur_base);
+
+            let new_dist = if org_dist == 0 {
+                0
+            } else if old_range == 0 {
+                // The same as what the MS rasterizer does in this case.
+                org_dist
+            } else {
+                math::mul_div(org_dist, cur_range, old_range)
+            };
+
+            self.move_point(ZP2, point, new_dist.wrapping_sub(cur_dist));
+        }
+
+        Some(())
+    }
+
+    /// Projects a distance between unscaled points and scales it.
+    #[inline]
+    fn unscaled_dual_project(&self, a: Point, b: Point) -> i32 {
+        math::mul_fix(self.dual_project(a, b), self.unscaled_scale)
+    }
+
+    fn ins_msirp(&mut self, set_rp0: bool) -> Option<()> {
+        let distance = self.pop()?;
+        let index = self.pop()?;
+
+        let (point, rp0) = match (self.point(ZP1, index), self.point(ZP0, self.gs.rp0)) {
+            (Some(p), Some(rp0)) => (p, rp0),
+            _ => return Some(()),
+        };
+
+        if self.is_twilight(ZP1) {
+            let org = self.zone(ZP0).original[rp0];
+            self.zone_mut(ZP1).original[point] = org;
+            self.move_original(ZP1, point, distance);
+            let zone = self.zone_mut(ZP1);
+            zone.current[point] = zone.original[point];
+        }
+
+        let d = self.project(self.zone(ZP1).current[point], self.zone(ZP0).current[rp0]);
+        self.move_point(ZP1, point, distance.wrapping_sub(d));
+
+        self.gs.rp1 = self.gs.rp0;
+        self.gs.rp2 = index;
+        if set_rp0 {
+            self.gs.rp0 = index;
+        }
+
+        Some(())
+    }
+
+    fn ins_alignrp(&mut self) -> Option<()> {
+        let count = self.take_loop();
+        let rp0 = self.point(ZP0, self.gs.rp0);
+        for _ in 0..count {
+            let index = self.pop()?;
+            if let (Some(rp0), Some(point)) = (rp0, self.point(ZP1, index)) {
+                let d = self.project(self.zone(ZP1).current[point], self.zone(ZP0).current[rp0]);
+                self.move_point(ZP1, point, d.wrapping_neg());
+            }
+        }
+
+        Some(())
+    }
+
+    fn ins_miap(&mut self, round: bool) -> Option<()> {
+        let cvt_index = self.pop()?;
+        let index = self.pop()?;
+
+        let (point, mut distance) = match (self.point(ZP0, index), self.cvt(cvt_index)) {
+            (Some(p), Some(d)) => (p, d),
+            _ => {
+                self.gs.rp0 = index;
+                self.gs.rp1 = index;
+                return Some(());
+            }
+        };
+
+        if self.is_twilight(ZP0) {
+            let free = self.gs.free_vector;
+            let p = Point { x: math::mul14(distance, free.x), y: math::mul14(distance, free.y) };
+            let zone = self.zone_mut(ZP0);
+            zone.original[point] = p;
+            zone.current[point] = p;
+        }
+
+        let org_dist = self.project(self.zone(ZP0).current[point], Point::default());
+        if round {
+            if (distance.wrapping_sub(org_dist)).wrapping_abs() > self.gs.control_value_cutin {
+                distance = org_dist;
+            }
+
+            distance = self.round(distance);
+        }
+
+        self.move_point(ZP0, point, distance.wrapping_sub(org_dist));
+        self.gs.rp0 = index;
+        self.gs.rp1 = index;
+        Some(())
+    }
+
+    fn ins_scfs(&mut self) -> Option<()> {
+        let value = self.pop()?;
+        let index = self.pop()?;
+        let point = try_opt_or!(self.point(ZP2, index), Some(()));
+
+        let d = self.project(self.zone(ZP2).current[point], Point::default());
+        self.move_point(ZP2, point, value.wrapping_sub(d));
+
+        // UNDOCUMENTED! The MS rasterizer does that with twilight points.
+        if self.is_twilight(ZP2) {
+            let zone = self.zone_mut(ZP2);
+            zone.original[point] = zone.current[point];
+        }
+
+        Some(())
+    }
+
+    fn ins_md(&mut self, use_current: bool) -> Option<()> {
+        let k = self.pop()?;
+        let l = self.pop()?;
+
+        let d = match (self.point(ZP0, l), self.point(ZP1, k)) {
+            (Some(l), Some(k)) => {
+                if use_current {
+                    self.project(self.zone(ZP0).current[l], self.zone(ZP1).current[k])
+                } else if self.is_twilight(ZP0) || self.is_twilight(ZP1) {
+                    self.dual_project(self.zone(ZP0).original[l], self.zone(ZP1).original[k])
+                } else {
+                    self.unscaled_dual_project(self.zone(ZP0).unscaled[l], self.zone(ZP1).unscaled[k])
+                }
+            }
+            _ => 0,
+        };
+
+        self.push(d)
+    }
+
+    fn ins_deltap(&mut self, base_offset: i32) -> Option<()> {
+        let n = self.pop()?;
+        let ppem = i32::from(self.settings.ppem);
+
+        for _ in 0..n.max(0) {
+            if self.stack.len() < 2 {
+                // Not enough arguments. Ignore the rest, like FreeType does.
+                self.stack.clear();
+                break;
+            }
+
+            let index = self.pop()?;
+            let arg = self.pop()?;
+            let point = match self.point(ZP0, index) {
+                Some(v) => v,
+                None => continue,
+            };
+
+            let c = ((arg & 0xF0) >> 4) + base_offset + self.gs.delta_base;
+            if c != ppem {
+                continue;
+            }
+
+            let mut b = (arg & 0x0F) - 8;
+            if b >= 0 {
+                b += 1;
+            }
+            let b = b * (1 << (6 - self.gs.delta_shift));
+
+            if self.backward_compatibility {
+                let after_iup = self.iup_x_called && self.iup_y_called;
+                let touched_y = self.zone(ZP0).flags[point] & TOUCHED_Y != 0;
+                let free_y = self.gs.free_vector.y != 0;
+                if !after_iup && ((self.is_composite && free_y) || touched_y) {
+                    self.move_point(ZP0, point, b);
+                }
+            } else {
+                self.move_point(ZP0, point, b);
+            }
+        }
+
+        Some(())
+    }
+
+    fn ins_deltac(&mut self, base_offset: i32) -> Option<()> {
+        let n = self.pop()?;
+        let ppem = i32::from(self.settings.ppem);
+
+        for _ in 0..n.max(0) {
+            if self.stack.len() < 2 {
+                self.stack.clear();
+                break;
+            }
+
+            let index = self.pop()?;
+            let arg = self.pop()?;
+
+            let c = ((arg & 0xF0) >> 4) + base_offset + self.gs.delta_base;
+            if c != ppem {
+                continue;
+            }
+
+            let mut b = (arg & 0x0F) - 8;
+            if b >= 0 {
+                b += 1;
+            }
+            let b = b * (1 << (6 - self.gs.delta_shift));
+
+            if let Some(v) = usize::try_from(index).ok().and_then(|i| self.state.cvt.get_mut(i)) {
+                *v = v.wrapping_add(b);
+            }
+        }
+
+        Some(())
+    }
+
+    fn ins_flippt(&mut self) -> Option<()> {
+        let count = self.take_loop();
+        let skip = self.backward_compatibility && self.iup_x_called && self.iup_y_called;
+        for _ in 0..count {
+            let index = self.pop()?;
+            if skip {
+                continue;
+            }
+
+            if let Some(flags) = usize::try_from(index).ok().and_then(|i| self.glyph.flags.get_mut(i)) {
+                *flags ^= ON_CURVE;
+            }
+        }
+
+        Some(())
+    }
+
+    fn ins_fliprg(&mut self, on_curve: bool) -> Option<()> {
+        let high = self.pop()?;
+        let low = self.pop()?;
+
+        if self.backward_compatibility && self.iup_x_called && self.iup_y_called {
+            return Some(());
+        }
+
+        let (low, high) = match (usize::try_from(low), usize::try_from(high)) {
+            (Ok(low), Ok(high)) if low <= high && high < self.glyph.len() => (low, high),
+            _ => return Some(()),
+        };
+
+        for flags in &mut self.glyph.flags[low..=high] {
+            if on_curve {
+                *flags |= ON_CURVE;
+            } else {
+                *flags &= !ON_CURVE;
+            }
+        }
+
+        Some(())
+    }
+
+    fn ins_sdpvtl(&mut self, perpendicular: bool) -> Option<()> {
+        let p2 = self.pop()?;
+        let p1 = self.pop()?;
+
+        let original = self.line_vector(p1, p2, perpendicular, true);
+        let current = self.line_vector(p1, p2, perpendicular, false);
+        if let (Some(original), Some(current)) = (original, current) {
+            self.gs.dual_vector = original;
+            self.gs.proj_vector = current;
+        }
+
+        Some(())
+    }
+
+    fn ins_getinfo(&mut self) -> Option<()> {
+        let selector = self.pop()?;
+        let mut v = 0;
+
+        // Interpreter version.
+        if selector & 1 != 0 {
+            v = match self.settings.mode {
+                HintingMode::Full => 35,
+                HintingMode::BackwardCompatibility => 40,
+            };
+        }
+
+        // Glyph rotated and stretched bits are never set.
+
+        // Font variations.
+        if selector & 8 != 0 && self.settings.is_variable {
+            v |= 1 << 10;
+        }
+
+        match self.settings.mode {
+            HintingMode::Full => {
+                // Grayscale rendering.
+                if selector & 32 != 0 {
+                    v |= 1 << 12;
+                }
+            }
+            HintingMode::BackwardCompatibility => {
+                // Subpixel hinting.
+                if selector & 64 != 0 {
+                    v |= 1 << 13;
+                }
+
+                // Subpixel positioning.
+                if selector & 1024 != 0 {
+                    v |= 1 << 17;
+                }
+
+                // Symmetrical smoothing.
+                if selector & 2048 != 0 {
+                    v |= 1 << 18;
+                }
+
+                // ClearType hinting and grayscale rendering.
+                if selector & 4096 != 0 {
+                    v |= 1 << 19;
+                }
+            }
+        }
+
+        self.push(v)
+    }
+
+    fn ins_instctrl(&mut self) -> Option<()> {
+        let selector = self.pop()?;
+        let value = self.pop()?;
+
+        if !(1..=3).contains(&selector) {
+            return Some(());
+        }
+
+        let bit = 1 << (selector - 1);
+        let value = if value != 0 { bit } else { 0 };
+
+        // Can be used only by the control value program.
+        if self.run_program == Program::ControlValue {
+            self.gs.instruct_control = (self.gs.instruct_control & !bit) | value;
+        }
+
+        Some(())
+    }
+
+    fn ins_mdrp(&mut self, opcode: u8) -> Option<()> {
+        let index = self.pop()?;
+
+        let (point, rp0) = match (self.point(ZP1, index), self.point(ZP0, self.gs.rp0)) {
+            (Some(p), Some(rp0)) => (p, rp0),
+            _ => {
+                self.gs.rp1 = self.gs.rp0;
+                self.gs.rp2 = index;
+                if opcode & 16 != 0 {
+                    self.gs.rp0 = index;
+                }
+
+                return Some(());
+            }
+        };
+
+        let mut org_dist = if self.is_twilight(ZP0) || self.is_twilight(ZP1) {
+            self.dual_project(self.zone(ZP1).original[point], self.zone(ZP0).original[rp0])
+        } else {
+            self.unscaled_dual_project(self.zone(ZP1).unscaled[point], self.zone(ZP0).unscaled[rp0])
+        };
+
+        // Single width cut-in test.
+        let sw = self.gs.single_width_value;
+        if (org_dist.wrapping_sub(sw)).wrapping_abs() < self.gs.single_width_cutin {
+            org_dist = if org_dist >= 0 { sw } else { sw.wrapping_neg() };
+        }
+
+        let mut distance = if opcode & 4 != 0 { self.round(org_dist) } else { org_dist };
+
+        // Minimum distance flag.
+        if opcode & 8 != 0 {
+            distance = self.apply_min_distance(org_dist, distance);
+        }
+
+        let cur_dist = self.project(self.zone(ZP1).current[point], self.zone(ZP0).current[rp0]);
+        self.move_point(ZP1, point, distance.wrapping_sub(cur_dist));
+
+        self.gs.rp1 = self.gs.rp0;
+        self.gs.rp2 = index;
+        if opcode & 16 != 0 {
+            self.gs.rp0 = index;
+        }
+
+        Some(())
+    }
+
+    fn ins_mirp(&mut self, opcode: u8) -> Option<()> {
+        let cvt_index = self.pop()?;
+        let index = self.pop()?;
+
+        // A CVT index of -1 means zero distance.
+        let cvt_value = if cvt_index == -1 { Some(0) } else { self.cvt(cvt_index) };
+
+        let (point, rp0, mut cvt_dist) = match (
+            self.point(ZP1, index),
+            self.point(ZP0, self.gs.rp0),
+            cvt_value,
+        ) {
+            (Some(p), Some(rp0), Some(d)) => (p, rp0, d),
+            _ => {
+                self.gs.rp1 = self.gs.rp0;
+                if opcode & 16 != 0 {
+                    self.gs.rp0 = index;
+                }
+                self.gs.rp2 = index;
+                return Some(());
+            }
+        };
+
+        // Single width cut-in test.
+        let sw = self.gs.single_width_value;
+        if (cvt_dist.wrapping_sub(sw)).wrapping_abs() < self.gs.single_width_cutin {
+            cvt_dist = if cvt_dist >= 0 { sw } else { sw.wrapping_neg() };
+        }
+
+        // UNDOCUMENTED! The twilight point is placed relative to the reference point.
+        if self.is_twilight(ZP1) {
+            let free = self.gs.free_vector;
+            let org = self.zone(ZP0).original[rp0];
+            let p = Point {
+                x: org.x.wrapping_add(math::mul14(cvt_dist, free.x)),
+                y: org.y.wrapping_add(math::mul14(cvt_dist, free.y)),
+            };
+
+            let zone = self.zone_mut(ZP1);
+            zone.original[point] = p;
+            zone.current[point] = p;
+        }
+
+        let org_dist = self.dual_project(self.zone(ZP1).original[point], self.zone(ZP0).original[rp0]);
+        let cur_dist = self.project(self.zone(ZP1).current[point], self.zone(ZP0).current[rp0]);
+
+        // Auto-flip test.
+        if self.gs.auto_flip && (org_dist ^ cvt_dist) < 0 {
+            cvt_dist = cvt_dist.wrapping_neg();
+        }
+
+        let mut distance = if opcode & 4 != 0 {
+            // Control value cut-in test is performed only when the twilight zone is not used.
+            if self.gs.zp[ZP0] == self.gs.zp[ZP1]
+                && (cvt_dist.wrapping_sub(org_dist)).wrapping_abs() > self.gs.control_value_cutin
+            {
+                cvt_dist = org_dist;
+            }
+
+            self.round(cvt_dist)
+        } else {
+            cvt_dist
+        };
+
+        // Minimum distance flag.
+        if opcode & 8 != 0 {
+            distance = self.apply_min_distance(org_dist, distance);
+        }
+
+        self.move_point(ZP1, point, distance.wrapping_sub(cur_dist));
+
+        self.gs.rp1 = self.gs.rp0;
+        if opcode & 16 != 0 {
+            self.gs.rp0 = index;
+        }
+        self.gs.rp2 = index;
+
+        Some(())
+    }
+
+    #[inline]
+    fn apply_min_distance(&self, org_dist: i32, distance: i32) -> i32 {
+        let min = self.gs.min_distance;
+        if org_dist >= 0 {
+            distance.max(min)
+        } else {
+            distance.min(min.wrapping_neg())
+        }
+    }
+}
+
+#[inline]
+fn zone_index(n: i32) -> Option<u8> {
+    match n {
+        0 => Some(0),
+        1 => Some(1),
+        _ => None,
+    }
+}
+
+/// Rounds a 26.6 number to the nearest integer.
+#[inline]
+pub fn pix_round(d: i32) -> i32 {
+    d.wrapping_add(32) & !63
+}
+
+#[inline]
+fn round_to_grid(d: i32) -> i32 {
+    if d >= 0 {
+        let v = d.wrapping_add(32) & !63;
+        if v < 0 { 0 } else { v }
+    } else {
+        let v = (d.wrapping_neg().wrapping_add(32) & !63).wrapping_neg();
+        if v > 0 { 0 } else { v }
+    }
+}
+
+/// Shifts untouched points of a contour by the touched point displacement.
+fn iup_shift(zone: &mut Zone, is_x: bool, start: usize, end: usize, touched: usize) {
+    let delta = zone.current[touched].get(is_x)
+        .wrapping_sub(zone.original[touched].get(is_x));
+    if delta == 0 {
+        return;
+    }
+
+    for i in (start..=end).filter(|i| *i != touched) {
+        let v = zone.current[i].get(is_x).wrapping_add(delta);
+        zone.current[i].set(is_x, v);
+    }
+}
+
+/// Interpolates untouched points between two touched points.
+fn iup_interpolate(
+    zone: &mut Zone,
+    is_x: bool,
+    start: usize,
+    end: usize,
+    mut ref1: usize,
+    mut ref2: usize,
+) {
+    if start > end {
+        return;
+    }
+
+    let mut orus1 = zone.unscaled[ref1].get(is_x);
+    let mut orus2 = zone.unscaled[ref2].get(is_x);
+    if orus1 > orus2 {
+        core::mem::swap(&mut orus1, &mut orus2);
+        core::mem::swap(&mut ref1, &mut ref2);
+    }
+
+    let org1 = zone.original[ref1].get(is_x);
+    let org2 = zone.original[ref2].get(is_x);
+    let cur1 = zone.current[ref1].get(is_x);
+    let cur2 = zone.current[ref2].get(is_x);
+    let delta1 = cur1.wrapping_sub(org1);
+    let delta2 = cur2.wrapping_sub(org2);
+
+    let mut scale = None;
+    for i in start..=end {
+        let org = zone.original[i].get(is_x);
+        let v = if org <= org1 {
+            org.wrapping_add(delta1)
+        } else if org >= org2 {
+            org.wrapping_add(delta2)
+        } else if cur1 == cur2 || orus1 == orus2 {
+            cur1
+        } else {
+            let scale = *scale.get_or_insert_with(|| {
+                math::div_fix(cur2.wrapping_sub(cur1), orus2.wrapping_sub(orus1))
+            });
+
+            let orus = zone.unscaled[i].get(is_x);
+            cur1.wrapping_add(math::mul_fix(orus.wrapping_sub(orus1), scale))
+        };
+
+        zone.current[i].set(is_x, v);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SVTCA_Y: u8 = 0x00;
+    const SVTCA_X: u8 = 0x01;
+    const JMPR: u8 = 0x1C;
+    const CALL: u8 = 0x2B;
+    const FDEF: u8 = 0x2C;
+    const ENDF: u8 = 0x2D;
+    const MDAP_RND: u8 = 0x2F;
+    const IUP_Y: u8 = 0x30;
+    const PUSHB_1: u8 = 0xB0;
+    const PUSHB_2: u8 = 0xB1;
+    const PUSHW_1: u8 = 0xB8;
+
+    fn hint(
+        mode: HintingMode,
+        font_program: &[u8],
+        program: &[u8],
+        points: &[(i32, i32)],
+    ) -> Option<Vec<(i32, i32)>> {
+        let state = State {
+            functions: vec![None; 4],
+            instruction_defs: Vec::new(),
+            max_instruction_defs: 0,
+            cvt: Vec::new(),
+            storage: vec![0; 4],
+            graphics_state: GraphicsState::default(),
+            twilight: Zone::with_len(4),
+        };
+
+        let settings = Settings {
+            ppem: 16,
+            // Unscaled points are already in 26.6.
+            scale: 0x10000,
+            mode,
+            max_stack: 16,
+            font_program,
+            control_value_program: &[],
+            coords: &[],
+            is_variable: false,
+        };
+
+        let mut interpreter = Interpreter::new(state, settings);
+        interpreter.run_font_programs()?;
+
+        let mut zone = Zone::with_len(points.len() + 4);
+        for (i, (x, y)) in points.iter().enumerate() {
+            zone.unscaled[i] = Point { x: *x, y: *y };
+            zone.flags[i] = ON_CURVE;
+        }
+        zone.original = zone.unscaled.clone();
+        zone.current = zone.unscaled.clone();
+        zone.contour_ends.push(u16::try_from(points.len() - 1).ok()?);
+
+        interpreter.glyph = zone;
+        interpreter.hint_glyph(Some(program), false);
+
+        let zone = interpreter.glyph;
+        Some(zone.current.iter().take(points.len()).map(|p| (p.x, p.y)).collect())
+    }
+
+    #[test]
+    fn round_point() {
+        let program = [
+            PUSHB_1, 0, SVTCA_X, MDAP_RND,
+            PUSHB_1, 0, SVTCA_Y, MDAP_RND,
+        ];
+
+        let points = hint(HintingMode::Full, &[], &program, &[(70, 100)]).unwrap();
+        assert_eq!(points, [(64, 128)]);
+
+        // Horizontal movements are ignored.
+        let points = hint(HintingMode::BackwardCompatibility, &[], &program, &[(70, 100)]).unwrap();
+        assert_eq!(points, [(70, 128)]);
+    }
+
+    #[test]
+    fn interpolate_untouched_points() {
+        let program = [SVTCA_Y, PUSHB_2, 0, 2, MDAP_RND, MDAP_RND, IUP_Y];
+        let points = hint(HintingMode::Full, &[], &program, &[(0, 10), (0, 100), (0, 190)]).unwrap();
+        assert_eq!(points, [(0, 0), (0, 96), (0, 192)]);
+    }
+
+    #[test]
+    fn call_function() {
+        let font_program = [PUSHB_1, 0, FDEF, SVTCA_X, MDAP_RND, ENDF];
+        let program = [PUSHB_2, 0, 0, CALL];
+        let points = hint(HintingMode::Full, &font_program, &program, &[(70, 100)]).unwrap();
+        assert_eq!(points, [(64, 100)]);
+    }
+
+    #[test]
+    fn function_definition_in_glyph_program() {
+        // Only the font and control value programs can define functions.
+        let program = [PUSHB_1, 0, FDEF, ENDF, SVTCA_X, PUSHB_1, 0, MDAP_RND];
+        let points = hint(HintingMode::Full, &[], &program, &[(70, 100)]).unwrap();
+        assert_eq!(points, [(70, 100)]);
+    }
+
+    #[test]
+    fn infinite_loop() {
+        // Jumps back to the start of the program.
+        let program = [PUSHW_1, 0xFF, 0xFD, JMPR];
+        let points = hint(HintingMode::Full, &[], &program, &[(70, 100)]).unwrap();
+        assert_eq!(points, [(70, 100)]);
+    }
+
+    #[test]
+    fn stack_underflow() {
+        let program = [SVTCA_X, MDAP_RND];
+        let points = hint(HintingMode::Full, &[], &program, &[(70, 100)]).unwrap();
+        assert_eq!(points, [(70, 100)]);
+    }
+
+    #[test]
+    fn invalid_font_program() {
+        let font_program = [MDAP_RND];
+        assert!(hint(HintingMode::Full, &font_program, &[], &[(70, 100)]).is_none());
+    }
+}
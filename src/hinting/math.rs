@@ -0,0 +1,160 @@
+//! Fixed-point arithmetic used by the interpreter.
+//!
+//! Rounding rules are the same as in FreeType, so we get the same results.
+
+use core::convert::TryFrom;
+
+#[inline]
+fn saturate(n: i64) -> i32 {
+    i32::try_from(n).unwrap_or(if n < 0 { core::i32::MIN } else { core::i32::MAX })
+}
+
+/// Computes `a * b / c` with rounding.
+///
+/// Returns `i32::MAX` on division by zero.
+#[inline]
+pub fn mul_div(a: i32, b: i32, c: i32) -> i32 {
+    if c == 0 {
+        return core::i32::MAX;
+    }
+
+    let (a, b, c) = (i64::from(a), i64::from(b), i64::from(c));
+    let n = (a * b).abs() + c.abs() / 2;
+    let v = n / c.abs();
+    saturate(if (a < 0) ^ (b < 0) ^ (c < 0) { -v } else { v })
+}
+
+/// Computes `a * b / c` without rounding.
+///
+/// Returns `i32::MAX` on division by zero.
+#[inline]
+pub fn mul_div_no_round(a: i32, b: i32, c: i32) -> i32 {
+    if c == 0 {
+        return core::i32::MAX;
+    }
+
+    let (a, b, c) = (i64::from(a), i64::from(b), i64::from(c));
+    let v = (a * b).abs() / c.abs();
+    saturate(if (a < 0) ^ (b < 0) ^ (c < 0) { -v } else { v })
+}
+
+/// Multiplies a number by a 16.16 fixed-point number.
+#[inline]
+pub fn mul_fix(a: i32, b: i32) -> i32 {
+    mul_div(a, b, 0x10000)
+}
+
+/// Divides a number by another number, producing a 16.16 fixed-point number.
+#[inline]
+pub fn div_fix(a: i32, b: i32) -> i32 {
+    mul_div(a, 0x10000, b)
+}
+
+/// Multiplies a number by a 2.14 fixed-point number.
+#[inline]
+pub fn mul14(a: i32, b: i32) -> i32 {
+    let m = i64::from(a) * i64::from(b);
+    saturate((m + 0x2000 + (m >> 63)) >> 14)
+}
+
+/// Computes a dot product of a vector and a 2.14 unit vector.
+#[inline]
+pub fn dot14(ax: i32, ay: i32, bx: i32, by: i32) -> i32 {
+    let m = i64::from(ax) * i64::from(bx) + i64::from(ay) * i64::from(by);
+    saturate((m + 0x2000 + (m >> 63)) >> 14)
+}
+
+/// Converts a vector into a 2.14 unit vector.
+///
+/// Uses the same fixed-point approximation as FreeType, so results are identical.
+///
+/// Returns `None` for a zero vector.
+pub fn normalize(x: i32, y: i32) -> Option<(i32, i32)> {
+    if x == 0 && y == 0 {
+        return None;
+    }
+
+    let (nx, ny) = normalize_16_16(x, y);
+    Some((nx / 4, ny / 4))
+}
+
+// Based on `FT_Vector_NormLen`.
+fn normalize_16_16(x: i32, y: i32) -> (i32, i32) {
+    let sx = if x < 0 { -1 } else { 1 };
+    let sy = if y < 0 { -1 } else { 1 };
+    let mut ux = x.wrapping_abs() as u32;
+    let mut uy = y.wrapping_abs() as u32;
+
+    if ux == 0 {
+        return (0, sy * 0x10000);
+    } else if uy == 0 {
+        return (sx * 0x10000, 0);
+    }
+
+    // Estimate length and prenormalize by shifting so that
+    // the new approximate length is between 2/3 and 4/3.
+    let mut l = if ux > uy { ux + (uy >> 1) } else { uy + (ux >> 1) };
+    let mut shift = l.leading_zeros() as i32;
+    shift -= 15 + (l >= (0xAAAAAAAAu32 >> shift)) as i32;
+
+    if shift > 0 {
+        ux <<= shift;
+        uy <<= shift;
+        // Re-estimate length for tiny vectors.
+        l = if ux > uy { ux + (uy >> 1) } else { uy + (ux >> 1) };
+    } else {
+        ux >>= -shift;
+        uy >>= -shift;
+        l >>= -shift;
+    }
+
+    // Lower linear approximation for reciprocal length minus one.
+    let mut b = 0x10000i32.wrapping_sub(l as i32);
+    let (x, y) = (ux as i32, uy as i32);
+
+    // Newton's iterations.
+    let (mut u, mut v);
+    loop {
+        u = x.wrapping_add(x.wrapping_mul(b) >> 16) as u32;
+        v = y.wrapping_add(y.wrapping_mul(b) >> 16) as u32;
+
+        let z = (u.wrapping_mul(u).wrapping_add(v.wrapping_mul(v)) as i32).wrapping_neg() / 0x200;
+        let z = z.wrapping_mul((0x10000 + b) >> 8) / 0x10000;
+        b = b.wrapping_add(z);
+
+        if z <= 0 {
+            break;
+        }
+    }
+
+    (sx * u as i32, sy * v as i32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mul_div_rounding() {
+        assert_eq!(mul_div(3, 1, 2), 2);
+        assert_eq!(mul_div(-3, 1, 2), -2);
+        assert_eq!(mul_div_no_round(3, 1, 2), 1);
+        assert_eq!(mul_div_no_round(-3, 1, 2), -1);
+        assert_eq!(mul_div(1, 1, 0), core::i32::MAX);
+    }
+
+    #[test]
+    fn fixed_14() {
+        assert_eq!(mul14(64, 0x4000), 64);
+        assert_eq!(mul14(-64, 0x2000), -32);
+        assert_eq!(dot14(64, 64, 0, 0x4000), 64);
+    }
+
+    #[test]
+    fn normalize_vector() {
+        assert_eq!(normalize(10, 0), Some((0x4000, 0)));
+        assert_eq!(normalize(0, -3), Some((0, -0x4000)));
+        assert_eq!(normalize(1, 1), Some((11585, 11585)));
+        assert_eq!(normalize(0, 0), None);
+    }
+}
@@ -0,0 +1,504 @@
+/*!
+A [TrueType hinting](https://docs.microsoft.com/en-us/typography/opentype/spec/ttch01)
+implementation.
+
+Runs `fpgm`, `prep` and glyph programs against `glyf` outlines (with `gvar` variations applied)
+at a specific pixels per em size, producing grid-fitted outlines.
+
+Available only with the `hinting` feature enabled.
+
+```
+use ttf_parser::hinting::{HintingInstance, HintingMode};
+
+struct Builder(usize);
+
+impl ttf_parser::OutlineBuilder for Builder {
+    fn move_to(&mut self, _: f32, _: f32) { self.0 += 1; }
+    fn line_to(&mut self, _: f32, _: f32) { self.0 += 1; }
+    fn quad_to(&mut self, _: f32, _: f32, _: f32, _: f32) { self.0 += 1; }
+    fn curve_to(&mut self, _: f32, _: f32, _: f32, _: f32, _: f32, _: f32) { self.0 += 1; }
+    fn close(&mut self) {}
+}
+
+let data = std::fs::read("examples/wasm/TTC.ttc").unwrap();
+let font = ttf_parser::Font::from_data(&data, 0).unwrap();
+let instance = HintingInstance::new(&font, 12, HintingMode::BackwardCompatibility).unwrap();
+
+let mut builder = Builder(0);
+let glyph_id = font.glyph_index('.').unwrap();
+let metrics = instance.outline_glyph(glyph_id, &mut builder).unwrap();
+assert!(builder.0 > 0);
+// The advance is rounded to whole pixels.
+assert_eq!(metrics.hor_advance.fract(), 0.0);
+```
+*/
+
+use core::convert::TryFrom;
+use core::fmt;
+use std::vec::Vec;
+
+use crate::{glyf, gvar, maxp, Font, GlyphId, OutlineBuilder};
+
+mod interpreter;
+mod math;
+
+use interpreter::{Interpreter, Point, Settings, State, Zone};
+
+/// A hinting mode.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum HintingMode {
+    /// Glyphs are grid-fitted in both directions.
+    ///
+    /// Matches the FreeType v35 interpreter.
+    Full,
+
+    /// Glyphs are grid-fitted only vertically.
+    ///
+    /// Horizontal movements are ignored, which preserves glyph shapes and advances
+    /// horizontally. Fonts can opt out of this mode via the `INSTCTRL` instruction
+    /// in the control value program.
+    ///
+    /// Matches the FreeType v40 interpreter, also known as the "backward compatibility" mode
+    /// of ClearType.
+    BackwardCompatibility,
+}
+
+/// Hinted glyph metrics.
+///
+/// All values are in pixels.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct HintedGlyphMetrics {
+    /// A horizontal advance. Always rounded to whole pixels.
+    pub hor_advance: f32,
+    /// A vertical advance. Always rounded to whole pixels.
+    pub ver_advance: f32,
+}
+
+/// A font prepared for hinting at a specific size.
+///
+/// Creation runs the font (`fpgm`) and control value (`prep`) programs,
+/// so it should be done once per font size and reused for all glyphs.
+///
+/// Variation coordinates of a font are captured at creation.
+#[derive(Clone)]
+pub struct HintingInstance<'a> {
+    font: Font<'a>,
+    ppem: u16,
+    mode: HintingMode,
+    scale: i32,
+    is_default_instance: bool,
+    max_stack: usize,
+    state: State,
+}
+
+impl<'a> HintingInstance<'a> {
+    /// Creates a new hinting instance.
+    ///
+    /// Returns `None` when:
+    ///
+    /// - `ppem` is zero
+    /// - a font doesn't have `glyf` and `loca` tables
+    /// - a font doesn't have a version 1.0 `maxp` table
+    /// - a font or control value program has failed
+    pub fn new(font: &Font<'a>, ppem: u16, mode: HintingMode) -> Option<Self> {
+        if ppem == 0 || font.glyf.is_none() || font.loca.is_none() {
+            return None;
+        }
+
+        let limits = maxp::parse_interpreter_limits(font.maxp)?;
+        let units_per_em = font.units_per_em()?;
+        let scale = math::div_fix(i32::from(ppem) * 64, i32::from(units_per_em));
+
+        let state = State {
+            functions: vec![None; usize::from(limits.max_function_defs)],
+            instruction_defs: Vec::new(),
+            max_instruction_defs: usize::from(limits.max_instruction_defs),
+            cvt: font.control_values().map(|v| scale_f32(v, scale)).collect(),
+            storage: vec![0; usize::from(limits.max_storage)],
+            graphics_state: Default::default(),
+            // FreeType adds four more points, since fonts tend to underestimate the number.
+            twilight: Zone::with_len(usize::from(limits.max_twilight_points) + 4),
+        };
+
+        let mut instance = HintingInstance {
+            font: font.clone(),
+            ppem,
+            mode,
+            scale,
+            is_default_instance: font.coords().iter().all(|c| c.get() == 0),
+            // FreeType adds some headroom, since fonts tend to underestimate the stack size.
+            max_stack: usize::from(limits.max_stack_elements) + 32,
+            state,
+        };
+
+        let mut interpreter = Interpreter::new(instance.state.clone(), instance.settings());
+        interpreter.run_font_programs()?;
+        instance.state = interpreter.state;
+        Some(instance)
+    }
+
+    /// Returns the pixels per em size.
+    #[inline]
+    pub fn ppem(&self) -> u16 {
+        self.ppem
+    }
+
+    /// Returns the hinting mode.
+    #[inline]
+    pub fn mode(&self) -> HintingMode {
+        self.mode
+    }
+
+    /// Outlines a hinted glyph and returns its metrics.
+    ///
+    /// Coordinates are in pixels, with the origin moved to the hinted horizontal origin.
+    ///
+    /// Unlike `Font::outline_glyph()`, returns metrics for empty glyphs too.
+    ///
+    /// Returns `None` when a glyph is malformed or doesn't exist.
+    /// When a glyph program fails, the glyph is left partially hinted, like FreeType does.
+    pub fn outline_glyph(
+        &self,
+        glyph_id: GlyphId,
+        builder: &mut dyn OutlineBuilder,
+    ) -> Option<HintedGlyphMetrics> {
+        let mut interpreter = Interpreter::new(self.state.clone(), self.settings());
+        let zone = self.load_glyph(&mut interpreter, glyph_id, 0)?;
+
+        let len = zone.len();
+        let pp1 = zone.current[len - 4];
+        let pp2 = zone.current[len - 3];
+        let pp3 = zone.current[len - 2];
+        let pp4 = zone.current[len - 1];
+
+        let mut b = glyf::Builder::new(glyf::Transform::default(), None, builder);
+        let mut start = 0;
+        for end in &zone.contour_ends {
+            let end = usize::from(*end);
+            for i in start..=end {
+                let p = zone.current[i];
+                let x = p.x.wrapping_sub(pp1.x) as f32 / 64.0;
+                let y = p.y as f32 / 64.0;
+                b.push_point(x, y, zone.flags[i] & interpreter::ON_CURVE != 0, i == end);
+            }
+
+            start = end + 1;
+        }
+
+        Some(HintedGlyphMetrics {
+            hor_advance: interpreter::pix_round(pp2.x.wrapping_sub(pp1.x)) as f32 / 64.0,
+            ver_advance: interpreter::pix_round(pp3.y.wrapping_sub(pp4.y)) as f32 / 64.0,
+        })
+    }
+
+    fn settings(&self) -> Settings<'_> {
+        Settings {
+            ppem: self.ppem,
+            scale: self.scale,
+            mode: self.mode,
+            max_stack: self.max_stack,
+            font_program: self.font.fpgm.unwrap_or_default(),
+            control_value_program: self.font.prep.unwrap_or_default(),
+            coords: self.font.coords(),
+            is_variable: self.font.is_variable(),
+        }
+    }
+
+    #[inline]
+    fn scale(&self, v: f32) -> i32 {
+        scale_f32(v, self.scale)
+    }
+
+    /// Scales a glyph point coordinate.
+    ///
+    /// Variation deltas produce fractional coordinates, which are rounded
+    /// to 26.6 before scaling, just like in FreeType.
+    #[inline]
+    fn scale_point(&self, v: f32) -> i32 {
+        if self.is_default_instance {
+            self.scale(v)
+        } else {
+            let v = (f64::from(v) * 64.0).round() as i32;
+            math::mul_fix(v, self.scale).wrapping_add(32) >> 6
+        }
+    }
+
+    /// Loads and hints a glyph.
+    ///
+    /// Returns a zone with hinted points, followed by four phantom points.
+    fn load_glyph<'b>(
+        &'b self,
+        interpreter: &mut Interpreter<'b>,
+        glyph_id: GlyphId,
+        depth: u8,
+    ) -> Option<Zone> {
+        if depth >= glyf::MAX_COMPONENTS {
+            return None;
+        }
+
+        let loca = self.font.loca?;
+        let glyf_table = self.font.glyf?;
+
+        if let Some(components) = glyf::components(loca, glyf_table, glyph_id) {
+            return self.load_composite_glyph(interpreter, glyph_id, components, depth);
+        }
+
+        let points = self.font.glyph_points(glyph_id)?;
+
+        let mut zone = Zone {
+            contour_ends: points.contour_ends().into_iter().collect(),
+            ..Zone::default()
+        };
+        for p in points {
+            zone.unscaled.push(Point { x: p.x.round() as i32, y: p.y.round() as i32 });
+            zone.original.push(Point { x: self.scale_point(p.x), y: self.scale_point(p.y) });
+            zone.flags.push(if p.on_curve_point { interpreter::ON_CURVE } else { 0 });
+        }
+        zone.current = zone.original.clone();
+
+        let points_len = zone.len().checked_sub(4)?;
+        if zone.contour_ends.iter().any(|end| usize::from(*end) >= points_len) {
+            // Malformed or single point contours cannot be hinted.
+            zone.contour_ends.clear();
+        }
+
+        // Empty glyphs are not hinted.
+        if zone.contour_ends.is_empty() {
+            return Some(zone);
+        }
+
+        let instructions = glyf::glyph_instructions(loca, glyf_table, glyph_id);
+        interpreter.glyph = zone;
+        interpreter.hint_glyph(instructions, false);
+        // `mem::take` requires Rust 1.40.
+        #[allow(clippy::mem_replace_with_default)]
+        let zone = core::mem::replace(&mut interpreter.glyph, Zone::default());
+        Some(zone)
+    }
+
+    fn load_composite_glyph<'b>(
+        &'b self,
+        interpreter: &mut Interpreter<'b>,
+        glyph_id: GlyphId,
+        components: glyf::Components<'b>,
+        depth: u8,
+    ) -> Option<Zone> {
+        let loca = self.font.loca?;
+        let glyf_table = self.font.glyf?;
+
+        let components_count = u16::try_from(components.count()).ok()?;
+        let mut tuples = match self.font.gvar {
+            Some(ref gvar_table) => {
+                gvar::composite_tuples(gvar_table, self.font.coords(), glyph_id, components_count)
+            }
+            None => None,
+        };
+
+        let mut zone = Zone::default();
+        let mut phantoms = None;
+        for component in components {
+            let (dx, dy) = match tuples {
+                Some(ref mut tuples) => tuples.apply_null()?,
+                None => (0.0, 0.0),
+            };
+
+            let child = self.load_glyph(interpreter, component.glyph_id, depth + 1)?;
+            let child_len = child.len() - 4;
+
+            if component.flags.use_my_metrics() {
+                let mut points = [Point::default(); 4];
+                points.copy_from_slice(&child.current[child_len..]);
+                phantoms = Some(points);
+            }
+
+            let base = zone.len();
+            let base_u16 = u16::try_from(base).ok()?;
+            for end in &child.contour_ends {
+                zone.contour_ends.push(end.checked_add(base_u16)?);
+            }
+
+            let ts = component.transform;
+            let has_transform = ts.a != 1.0 || ts.b != 0.0 || ts.c != 0.0 || ts.d != 1.0;
+            let matrix = [to_fixed(ts.a), to_fixed(ts.b), to_fixed(ts.c), to_fixed(ts.d)];
+            for (p, flags) in child.current.iter().zip(child.flags.iter()).take(child_len) {
+                let mut p = *p;
+                if has_transform {
+                    p = Point {
+                        x: math::mul_fix(p.x, matrix[0]).wrapping_add(math::mul_fix(p.y, matrix[2])),
+                        y: math::mul_fix(p.x, matrix[1]).wrapping_add(math::mul_fix(p.y, matrix[3])),
+                    };
+                }
+
+                zone.current.push(p);
+                zone.flags.push(flags & interpreter::ON_CURVE);
+            }
+
+            let (x, y) = match component.matching_points {
+                Some((parent, child)) => {
+                    // The parent point must belong to the previous components.
+                    let parent = usize::from(parent);
+                    let child = usize::from(child);
+                    if parent >= base || child >= child_len {
+                        return None;
+                    }
+
+                    let p1 = zone.current[parent];
+                    let p2 = zone.current[base + child];
+                    (p1.x.wrapping_sub(p2.x), p1.y.wrapping_sub(p2.y))
+                }
+                None => {
+                    let mut x = self.scale((ts.e + dx).round());
+                    let mut y = self.scale((ts.f + dy).round());
+                    if component.flags.round_xy_to_grid() {
+                        // Rounding the horizontal offset would change the advance.
+                        if self.mode == HintingMode::Full {
+                            x = interpreter::pix_round(x);
+                        }
+
+                        y = interpreter::pix_round(y);
+                    }
+
+                    (x, y)
+                }
+            };
+
+            if x != 0 || y != 0 {
+                for p in &mut zone.current[base..] {
+                    p.x = p.x.wrapping_add(x);
+                    p.y = p.y.wrapping_add(y);
+                }
+            }
+        }
+
+        let phantoms = match phantoms {
+            Some(v) => v,
+            None => {
+                let bbox = glyf::glyph_bbox(loca, glyf_table, glyph_id)?;
+                let metrics = self.font.phantom_metrics(glyph_id)?;
+                let mut phantoms = [Point::default(); 4];
+                for (p, (x, y)) in phantoms.iter_mut().zip(glyf::phantom_points(bbox, metrics).iter()) {
+                    let (dx, dy) = match tuples {
+                        Some(ref mut tuples) => tuples.apply_null()?,
+                        None => (0.0, 0.0),
+                    };
+
+                    *p = Point { x: self.scale_point(x + dx), y: self.scale_point(y + dy) };
+                }
+
+                phantoms
+            }
+        };
+
+        zone.current.extend_from_slice(&phantoms);
+        zone.flags.extend_from_slice(&[0; 4]);
+
+        if let Some(instructions) = glyf::glyph_instructions(loca, glyf_table, glyph_id) {
+            // Composite glyph instructions refer to already hinted components.
+            zone.original = zone.current.clone();
+            zone.unscaled = zone.current.clone();
+            interpreter.glyph = zone;
+            interpreter.hint_glyph(Some(instructions), true);
+            #[allow(clippy::mem_replace_with_default)]
+            {
+                zone = core::mem::replace(&mut interpreter.glyph, Zone::default());
+            }
+        }
+
+        Some(zone)
+    }
+}
+
+impl fmt::Debug for HintingInstance<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "HintingInstance({}, {:?})", self.ppem, self.mode)
+    }
+}
+
+/// Scales a value in font units to 26.6.
+#[inline]
+fn scale_f32(v: f32, scale: i32) -> i32 {
+    (f64::from(v) * f64::from(scale) / 65536.0).round() as i32
+}
+
+/// Converts a number to 16.16.
+#[inline]
+fn to_fixed(v: f32) -> i32 {
+    (v * 65536.0).round() as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::glyf::tests::Builder;
+    use std::string::String;
+
+    // Expected values were checked against FreeType 2 at 12 ppem using
+    // `FT_LOAD_TARGET_NORMAL` with the `interpreter-version` property set
+    // to 35 for `HintingMode::Full` and to 40 for `HintingMode::BackwardCompatibility`.
+    // FreeType reports 26.6 values, so they were divided by 64.
+    fn outline(mode: HintingMode, glyph_id: u16) -> (HintedGlyphMetrics, String) {
+        let data = std::fs::read("examples/wasm/TTC.ttc").unwrap();
+        let font = Font::from_data(&data, 0).unwrap();
+        let instance = HintingInstance::new(&font, 12, mode).unwrap();
+        let mut builder = Builder(String::new());
+        let metrics = instance.outline_glyph(GlyphId(glyph_id), &mut builder).unwrap();
+        (metrics, builder.0)
+    }
+
+    #[test]
+    fn simple_glyph() {
+        let (metrics, path) = outline(HintingMode::Full, 4);
+        assert_eq!(metrics, HintedGlyphMetrics { hor_advance: 3.0, ver_advance: 12.0 });
+        assert_eq!(path, "M 0.65625 1.46875 L 2.234375 1.46875 L 2.234375 0 \
+                          L 0.65625 0 L 0.65625 1.46875 Z ");
+    }
+
+    #[test]
+    fn empty_glyph() {
+        let (metrics, path) = outline(HintingMode::Full, 3);
+        assert_eq!(metrics, HintedGlyphMetrics { hor_advance: 3.0, ver_advance: 12.0 });
+        assert_eq!(path, "");
+    }
+
+    #[test]
+    fn composite_glyph_full() {
+        let (metrics, path) = outline(HintingMode::Full, 5);
+        assert_eq!(metrics, HintedGlyphMetrics { hor_advance: 9.0, ver_advance: 12.0 });
+        assert_eq!(path, "M 0.65625 1.46875 L 2.234375 1.46875 L 2.234375 0 \
+                          L 0.65625 0 L 0.65625 1.46875 Z \
+                          M 3.65625 1.46875 L 5.234375 1.46875 L 5.234375 0 \
+                          L 3.65625 0 L 3.65625 1.46875 Z \
+                          M 6.65625 1.46875 L 8.234375 1.46875 L 8.234375 0 \
+                          L 6.65625 0 L 6.65625 1.46875 Z ");
+    }
+
+    #[test]
+    fn composite_glyph_backward_compatibility() {
+        // Component offsets are rounded only vertically.
+        let (metrics, path) = outline(HintingMode::BackwardCompatibility, 5);
+        assert_eq!(metrics, HintedGlyphMetrics { hor_advance: 9.0, ver_advance: 12.0 });
+        assert_eq!(path, "M 0.65625 1.46875 L 2.234375 1.46875 L 2.234375 0 \
+                          L 0.65625 0 L 0.65625 1.46875 Z \
+                          M 3.546875 1.46875 L 5.125 1.46875 L 5.125 0 \
+                          L 3.546875 0 L 3.546875 1.46875 Z \
+                          M 6.4375 1.46875 L 8.015625 1.46875 L 8.015625 0 \
+                          L 6.4375 0 L 6.4375 1.46875 Z ");
+    }
+
+    #[test]
+    fn zero_ppem() {
+        let data = std::fs::read("examples/wasm/TTC.ttc").unwrap();
+        let font = Font::from_data(&data, 0).unwrap();
+        assert!(HintingInstance::new(&font, 0, HintingMode::Full).is_none());
+    }
+
+    #[test]
+    fn font_without_programs() {
+        let data = std::fs::read("fonts/SourceSansPro-Regular-Tiny.ttf").unwrap();
+        let font = Font::from_data(&data, 0).unwrap();
+        let instance = HintingInstance::new(&font, 16, HintingMode::Full).unwrap();
+        let glyph_id = font.glyph_index('A').unwrap();
+        let metrics = instance.outline_glyph(glyph_id, &mut Builder(String::new())).unwrap();
+        assert_eq!(metrics.hor_advance.fract(), 0.0);
+    }
+}
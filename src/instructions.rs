@@ -0,0 +1,400 @@
+/*!
+A [TrueType instructions](https://docs.microsoft.com/en-us/typography/opentype/spec/tt_instructions)
+disassembler.
+
+Instructions are stored in the `fpgm` and `prep` tables and in `glyf` glyphs.
+They are accessible via `Font::font_program()`, `Font::control_value_program()`
+and `Font::glyph_instructions()` respectively.
+
+```
+use ttf_parser::instructions::Instructions;
+
+// PUSHB[001] 1 2, SVTCA[1], MIRP[01101]
+let data = [0xB1, 1, 2, 0x01, 0xED];
+let mut iter = Instructions::new(&data);
+assert_eq!(iter.next().unwrap().to_string(), "PUSHB[001] 1 2");
+assert_eq!(iter.next().unwrap().to_string(), "SVTCA[1]");
+assert_eq!(iter.next().unwrap().to_string(), "MIRP[01101]");
+assert!(iter.next().is_none());
+```
+*/
+
+use core::fmt;
+
+use crate::parser::Stream;
+
+/// An iterator over TrueType instructions.
+///
+/// Iteration stops at the end of data or at a truncated push instruction.
+/// Use `Instructions::is_malformed` to distinguish them.
+#[derive(Clone, Copy, Debug)]
+pub struct Instructions<'a> {
+    data: &'a [u8],
+    offset: usize,
+    is_malformed: bool,
+}
+
+impl<'a> Instructions<'a> {
+    /// Creates a new instructions iterator.
+    #[inline]
+    pub fn new(data: &'a [u8]) -> Self {
+        Instructions { data, offset: 0, is_malformed: false }
+    }
+
+    /// Creates a new instructions iterator that starts at the specified offset.
+    #[cfg(feature = "hinting")]
+    #[inline]
+    pub(crate) fn new_at(data: &'a [u8], offset: usize) -> Self {
+        Instructions { data, offset, is_malformed: false }
+    }
+
+    /// Returns an offset of the next instruction.
+    #[cfg(feature = "hinting")]
+    #[inline]
+    pub(crate) fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Checks that the iteration was stopped because of a truncated push instruction.
+    #[inline]
+    pub fn is_malformed(&self) -> bool {
+        self.is_malformed
+    }
+}
+
+impl<'a> Iterator for Instructions<'a> {
+    type Item = Instruction<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.is_malformed {
+            return None;
+        }
+
+        let mut s = Stream::new_at(self.data, self.offset)?;
+        let offset = self.offset;
+        let opcode: u8 = s.read()?;
+
+        let data_len = match opcode {
+            NPUSHB | NPUSHW => {
+                let count: u8 = match s.read() {
+                    Some(v) => v,
+                    None => {
+                        self.is_malformed = true;
+                        return None;
+                    }
+                };
+
+                push_data_len(opcode, count)
+            }
+            PUSHB_START..=PUSHW_END => push_data_len(opcode, (opcode & 0x07) + 1),
+            _ => 0,
+        };
+
+        let data = match s.read_bytes(data_len) {
+            Some(v) => v,
+            None => {
+                self.is_malformed = true;
+                return None;
+            }
+        };
+
+        self.offset = s.offset();
+        Some(Instruction { offset, opcode, data })
+    }
+}
+
+const NPUSHB: u8 = 0x40;
+const NPUSHW: u8 = 0x41;
+const PUSHB_START: u8 = 0xB0;
+const PUSHW_START: u8 = 0xB8;
+const PUSHW_END: u8 = 0xBF;
+
+#[inline]
+fn is_push_words(opcode: u8) -> bool {
+    opcode == NPUSHW || (PUSHW_START..=PUSHW_END).contains(&opcode)
+}
+
+#[inline]
+fn push_data_len(opcode: u8, count: u8) -> usize {
+    if is_push_words(opcode) {
+        usize::from(count) * 2
+    } else {
+        usize::from(count)
+    }
+}
+
+
+/// A TrueType instruction.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Instruction<'a> {
+    /// An instruction offset in the bytecode.
+    pub offset: usize,
+    /// An instruction opcode.
+    pub opcode: u8,
+    /// Inline data of push instructions.
+    ///
+    /// Doesn't include the values count of `NPUSHB` and `NPUSHW`.
+    /// Empty for all other instructions.
+    pub data: &'a [u8],
+}
+
+impl<'a> Instruction<'a> {
+    /// Returns an instruction mnemonic, like `MIRP`.
+    ///
+    /// Returns `None` for undefined opcodes.
+    #[inline]
+    pub fn name(&self) -> Option<&'static str> {
+        opcode_info(self.opcode).map(|(name, _)| name)
+    }
+
+    /// Returns an iterator over values pushed by a push instruction.
+    ///
+    /// Bytes are unsigned and words are signed.
+    #[inline]
+    pub fn push_values(&self) -> PushValues<'a> {
+        PushValues { stream: Stream::new(self.data), is_words: is_push_words(self.opcode) }
+    }
+}
+
+impl fmt::Display for Instruction<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match opcode_info(self.opcode) {
+            Some((name, flag_bits)) => {
+                write!(f, "{}", name)?;
+                if flag_bits != 0 {
+                    // Print flag bits the same way as the TrueType specification does.
+                    // `MD` is the only instruction which range doesn't start at an aligned opcode.
+                    let flags = if name == "MD" {
+                        self.opcode - 0x49
+                    } else {
+                        self.opcode & ((1u8 << flag_bits) - 1)
+                    };
+                    write!(f, "[{:0width$b}]", flags, width = usize::from(flag_bits))?;
+                }
+            }
+            None => write!(f, "INS_{:02X}", self.opcode)?,
+        }
+
+        for value in self.push_values() {
+            write!(f, " {}", value)?;
+        }
+
+        Ok(())
+    }
+}
+
+
+/// An iterator over values pushed by a push instruction.
+#[allow(missing_debug_implementations)]
+#[derive(Clone, Copy)]
+pub struct PushValues<'a> {
+    stream: Stream<'a>,
+    is_words: bool,
+}
+
+impl<'a> Iterator for PushValues<'a> {
+    type Item = i32;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.is_words {
+            self.stream.read::<i16>().map(i32::from)
+        } else {
+            self.stream.read::<u8>().map(i32::from)
+        }
+    }
+}
+
+
+/// Returns an instruction mnemonic and the number of flag bits in the opcode.
+fn opcode_info(opcode: u8) -> Option<(&'static str, u8)> {
+    let info = match opcode {
+        0x00..=0x01 => ("SVTCA", 1),
+        0x02..=0x03 => ("SPVTCA", 1),
+        0x04..=0x05 => ("SFVTCA", 1),
+        0x06..=0x07 => ("SPVTL", 1),
+        0x08..=0x09 => ("SFVTL", 1),
+        0x0A => ("SPVFS", 0),
+        0x0B => ("SFVFS", 0),
+        0x0C => ("GPV", 0),
+        0x0D => ("GFV", 0),
+        0x0E => ("SFVTPV", 0),
+        0x0F => ("ISECT", 0),
+        0x10 => ("SRP0", 0),
+        0x11 => ("SRP1", 0),
+        0x12 => ("SRP2", 0),
+        0x13 => ("SZP0", 0),
+        0x14 => ("SZP1", 0),
+        0x15 => ("SZP2", 0),
+        0x16 => ("SZPS", 0),
+        0x17 => ("SLOOP", 0),
+        0x18 => ("RTG", 0),
+        0x19 => ("RTHG", 0),
+        0x1A => ("SMD", 0),
+        0x1B => ("ELSE", 0),
+        0x1C => ("JMPR", 0),
+        0x1D => ("SCVTCI", 0),
+        0x1E => ("SSWCI", 0),
+        0x1F => ("SSW", 0),
+        0x20 => ("DUP", 0),
+        0x21 => ("POP", 0),
+        0x22 => ("CLEAR", 0),
+        0x23 => ("SWAP", 0),
+        0x24 => ("DEPTH", 0),
+        0x25 => ("CINDEX", 0),
+        0x26 => ("MINDEX", 0),
+        0x27 => ("ALIGNPTS", 0),
+        0x29 => ("UTP", 0),
+        0x2A => ("LOOPCALL", 0),
+        0x2B => ("CALL", 0),
+        0x2C => ("FDEF", 0),
+        0x2D => ("ENDF", 0),
+        0x2E..=0x2F => ("MDAP", 1),
+        0x30..=0x31 => ("IUP", 1),
+        0x32..=0x33 => ("SHP", 1),
+        0x34..=0x35 => ("SHC", 1),
+        0x36..=0x37 => ("SHZ", 1),
+        0x38 => ("SHPIX", 0),
+        0x39 => ("IP", 0),
+        0x3A..=0x3B => ("MSIRP", 1),
+        0x3C => ("ALIGNRP", 0),
+        0x3D => ("RTDG", 0),
+        0x3E..=0x3F => ("MIAP", 1),
+        0x40 => ("NPUSHB", 0),
+        0x41 => ("NPUSHW", 0),
+        0x42 => ("WS", 0),
+        0x43 => ("RS", 0),
+        0x44 => ("WCVTP", 0),
+        0x45 => ("RCVT", 0),
+        0x46..=0x47 => ("GC", 1),
+        0x48 => ("SCFS", 0),
+        0x49..=0x4A => ("MD", 1),
+        0x4B => ("MPPEM", 0),
+        0x4C => ("MPS", 0),
+        0x4D => ("FLIPON", 0),
+        0x4E => ("FLIPOFF", 0),
+        0x4F => ("DEBUG", 0),
+        0x50 => ("LT", 0),
+        0x51 => ("LTEQ", 0),
+        0x52 => ("GT", 0),
+        0x53 => ("GTEQ", 0),
+        0x54 => ("EQ", 0),
+        0x55 => ("NEQ", 0),
+        0x56 => ("ODD", 0),
+        0x57 => ("EVEN", 0),
+        0x58 => ("IF", 0),
+        0x59 => ("EIF", 0),
+        0x5A => ("AND", 0),
+        0x5B => ("OR", 0),
+        0x5C => ("NOT", 0),
+        0x5D => ("DELTAP1", 0),
+        0x5E => ("SDB", 0),
+        0x5F => ("SDS", 0),
+        0x60 => ("ADD", 0),
+        0x61 => ("SUB", 0),
+        0x62 => ("DIV", 0),
+        0x63 => ("MUL", 0),
+        0x64 => ("ABS", 0),
+        0x65 => ("NEG", 0),
+        0x66 => ("FLOOR", 0),
+        0x67 => ("CEILING", 0),
+        0x68..=0x6B => ("ROUND", 2),
+        0x6C..=0x6F => ("NROUND", 2),
+        0x70 => ("WCVTF", 0),
+        0x71 => ("DELTAP2", 0),
+        0x72 => ("DELTAP3", 0),
+        0x73 => ("DELTAC1", 0),
+        0x74 => ("DELTAC2", 0),
+        0x75 => ("DELTAC3", 0),
+        0x76 => ("SROUND", 0),
+        0x77 => ("S45ROUND", 0),
+        0x78 => ("JROT", 0),
+        0x79 => ("JROF", 0),
+        0x7A => ("ROFF", 0),
+        0x7C => ("RUTG", 0),
+        0x7D => ("RDTG", 0),
+        0x7E => ("SANGW", 0),
+        0x7F => ("AA", 0),
+        0x80 => ("FLIPPT", 0),
+        0x81 => ("FLIPRGON", 0),
+        0x82 => ("FLIPRGOFF", 0),
+        0x85 => ("SCANCTRL", 0),
+        0x86..=0x87 => ("SDPVTL", 1),
+        0x88 => ("GETINFO", 0),
+        0x89 => ("IDEF", 0),
+        0x8A => ("ROLL", 0),
+        0x8B => ("MAX", 0),
+        0x8C => ("MIN", 0),
+        0x8D => ("SCANTYPE", 0),
+        0x8E => ("INSTCTRL", 0),
+        0x91 => ("GETVARIATION", 0),
+        0xB0..=0xB7 => ("PUSHB", 3),
+        0xB8..=0xBF => ("PUSHW", 3),
+        0xC0..=0xDF => ("MDRP", 5),
+        0xE0..=0xFF => ("MIRP", 5),
+        _ => return None,
+    };
+
+    Some(info)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::string::ToString;
+    use std::vec::Vec;
+
+    #[test]
+    fn push_instructions() {
+        let data = [
+            0x40, 3, 1, 2, 255, // NPUSHB
+            0x41, 2, 0xFF, 0xFE, 0x01, 0x00, // NPUSHW
+            0xB8, 0x80, 0x00, // PUSHW[000]
+        ];
+
+        let instructions: Vec<_> = Instructions::new(&data).collect();
+        assert_eq!(instructions.len(), 3);
+        assert_eq!(instructions[0].offset, 0);
+        assert_eq!(instructions[0].push_values().collect::<Vec<_>>(), &[1, 2, 255]);
+        assert_eq!(instructions[1].offset, 5);
+        assert_eq!(instructions[1].push_values().collect::<Vec<_>>(), &[-2, 256]);
+        assert_eq!(instructions[2].offset, 11);
+        assert_eq!(instructions[2].to_string(), "PUSHW[000] -32768");
+    }
+
+    #[test]
+    fn flags() {
+        let data = [0x2E, 0x6A, 0xC5, 0x86, 0x00, 0x49, 0x4A, 0x91];
+        let names: Vec<_> = Instructions::new(&data).map(|i| i.to_string()).collect();
+        assert_eq!(names, &["MDAP[0]", "ROUND[10]", "MDRP[00101]", "SDPVTL[0]", "SVTCA[0]",
+                           "MD[0]", "MD[1]", "GETVARIATION"]);
+    }
+
+    #[test]
+    fn undefined_opcode() {
+        let data = [0x28, 0xA0];
+        let instructions: Vec<_> = Instructions::new(&data).collect();
+        assert_eq!(instructions[0].name(), None);
+        assert_eq!(instructions[0].to_string(), "INS_28");
+        assert_eq!(instructions[1].to_string(), "INS_A0");
+    }
+
+    #[test]
+    fn truncated_push() {
+        let data = [0x4B, 0xB2, 1, 2];
+        let mut iter = Instructions::new(&data);
+        assert_eq!(iter.next().unwrap().name(), Some("MPPEM"));
+        assert!(iter.next().is_none());
+        assert!(iter.is_malformed());
+
+        let data = [0x40];
+        let mut iter = Instructions::new(&data);
+        assert!(iter.next().is_none());
+        assert!(iter.is_malformed());
+
+        let mut iter = Instructions::new(&[]);
+        assert!(iter.next().is_none());
+        assert!(!iter.is_malformed());
+    }
+}
@@ -7,7 +7,9 @@ A high-level, safe, zero-allocation TrueType font parser.
   Basically, no direct access to font tables.
 - Zero heap allocations.
 - Zero unsafe.
-- Zero dependencies.
+- Zero dependencies by default.
+  The optional `woff` and `woff2` features add zlib and Brotli decoders to support WOFF and WOFF2 fonts.
+- Optional TrueType hinting via the `hinting` feature.
 - `no_std`/WASM compatible.
 - Fast.
 - Stateless. All parsing methods are immutable methods.
@@ -46,7 +48,10 @@ extern crate std;
 
 #[cfg(feature = "std")]
 use std::string::String;
+#[cfg(feature = "std")]
+use std::vec::Vec;
 
+use core::convert::TryFrom;
 use core::fmt;
 use core::num::NonZeroU16;
 
@@ -60,22 +65,40 @@ macro_rules! try_opt_or {
 }
 
 mod ggg;
+pub mod instructions;
 mod parser;
 mod tables;
 mod var_store;
 
+#[cfg(feature = "std")]
+mod owned;
 #[cfg(feature = "std")]
 mod writer;
 
+#[cfg(feature = "woff")]
+pub mod woff;
+
+#[cfg(feature = "woff2")]
+pub mod woff2;
+
+#[cfg(feature = "hinting")]
+pub mod hinting;
+
 use tables::*;
 use parser::{Stream, FromData, NumFrom, TryNumFrom, i16_bound, f32_bound};
+pub use parser::{LazyArray16, LazyArrayIter16, LazyArray32, LazyArrayIter32};
+pub use parser::{LazyOffsetArray16, LazyOffsetArrayIter16};
+
 use head::IndexToLocationFormat;
-pub use fvar::{VariationAxes, VariationAxis};
+pub use fvar::{VariationAxes, VariationAxis, NamedInstance, NamedInstances, NamedInstanceCoordinates};
 pub use gdef::GlyphClass;
 pub use ggg::*;
 pub use name::*;
 pub use os2::*;
-pub use tables::kern;
+pub use tables::{cff, cmap, colr, cvt, glyf, gpos, gsub, kern, stat};
+
+#[cfg(feature = "std")]
+pub use owned::OwnedFont;
 
 
 /// A type-safe wrapper for glyph ID.
@@ -290,6 +313,26 @@ pub struct LineMetrics {
 }
 
 
+/// A RGBA color.
+#[repr(C)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[allow(missing_docs)]
+pub struct RgbaColor {
+    pub red: u8,
+    pub green: u8,
+    pub blue: u8,
+    pub alpha: u8,
+}
+
+impl RgbaColor {
+    /// Creates a new `RgbaColor`.
+    #[inline]
+    pub const fn new(red: u8, green: u8, blue: u8, alpha: u8) -> Self {
+        RgbaColor { red, green, blue, alpha }
+    }
+}
+
+
 /// A rectangle.
 #[repr(C)]
 #[derive(Clone, Copy, PartialEq, Debug)]
@@ -445,11 +488,19 @@ pub enum TableName {
     CharacterToGlyphIndexMapping,
     ColorBitmapData,
     ColorBitmapLocation,
+    ColorLayers,
+    ColorPalette,
     CompactFontFormat,
     CompactFontFormat2,
+    ControlValue,
+    ControlValueProgram,
+    ControlValueVariations,
+    FontProgram,
     FontVariations,
     GlyphData,
     GlyphDefinition,
+    GlyphPositioning,
+    GlyphSubstitution,
     GlyphVariations,
     Header,
     HorizontalHeader,
@@ -463,6 +514,7 @@ pub enum TableName {
     PostScript,
     ScalableVectorGraphics,
     StandardBitmapGraphics,
+    StyleAttributes,
     VerticalHeader,
     VerticalMetrics,
     VerticalMetricsVariations,
@@ -470,6 +522,130 @@ pub enum TableName {
     WindowsMetrics,
 }
 
+impl TableName {
+    fn from_tag(tag: Tag) -> Option<Self> {
+        match &tag.to_bytes() {
+            b"CBDT" => Some(TableName::ColorBitmapData),
+            b"CBLC" => Some(TableName::ColorBitmapLocation),
+            b"CFF " => Some(TableName::CompactFontFormat),
+            b"CFF2" => Some(TableName::CompactFontFormat2),
+            b"COLR" => Some(TableName::ColorLayers),
+            b"CPAL" => Some(TableName::ColorPalette),
+            b"GDEF" => Some(TableName::GlyphDefinition),
+            b"GPOS" => Some(TableName::GlyphPositioning),
+            b"GSUB" => Some(TableName::GlyphSubstitution),
+            b"HVAR" => Some(TableName::HorizontalMetricsVariations),
+            b"MVAR" => Some(TableName::MetricsVariations),
+            b"OS/2" => Some(TableName::WindowsMetrics),
+            b"STAT" => Some(TableName::StyleAttributes),
+            b"SVG " => Some(TableName::ScalableVectorGraphics),
+            b"VORG" => Some(TableName::VerticalOrigin),
+            b"VVAR" => Some(TableName::VerticalMetricsVariations),
+            b"avar" => Some(TableName::AxisVariations),
+            b"cmap" => Some(TableName::CharacterToGlyphIndexMapping),
+            b"cvar" => Some(TableName::ControlValueVariations),
+            b"cvt " => Some(TableName::ControlValue),
+            b"fpgm" => Some(TableName::FontProgram),
+            b"fvar" => Some(TableName::FontVariations),
+            b"glyf" => Some(TableName::GlyphData),
+            b"gvar" => Some(TableName::GlyphVariations),
+            b"head" => Some(TableName::Header),
+            b"hhea" => Some(TableName::HorizontalHeader),
+            b"hmtx" => Some(TableName::HorizontalMetrics),
+            b"kern" => Some(TableName::Kerning),
+            b"loca" => Some(TableName::IndexToLocation),
+            b"maxp" => Some(TableName::MaximumProfile),
+            b"name" => Some(TableName::Naming),
+            b"post" => Some(TableName::PostScript),
+            b"prep" => Some(TableName::ControlValueProgram),
+            b"sbix" => Some(TableName::StandardBitmapGraphics),
+            b"vhea" => Some(TableName::VerticalHeader),
+            b"vmtx" => Some(TableName::VerticalMetrics),
+            _ => None,
+        }
+    }
+}
+
+const TABLE_NAMES: [TableName; 36] = [
+    TableName::AxisVariations,
+    TableName::CharacterToGlyphIndexMapping,
+    TableName::ColorBitmapData,
+    TableName::ColorBitmapLocation,
+    TableName::ColorLayers,
+    TableName::ColorPalette,
+    TableName::CompactFontFormat,
+    TableName::CompactFontFormat2,
+    TableName::ControlValue,
+    TableName::ControlValueProgram,
+    TableName::ControlValueVariations,
+    TableName::FontProgram,
+    TableName::FontVariations,
+    TableName::GlyphData,
+    TableName::GlyphDefinition,
+    TableName::GlyphPositioning,
+    TableName::GlyphSubstitution,
+    TableName::GlyphVariations,
+    TableName::Header,
+    TableName::HorizontalHeader,
+    TableName::HorizontalMetrics,
+    TableName::HorizontalMetricsVariations,
+    TableName::IndexToLocation,
+    TableName::Kerning,
+    TableName::MaximumProfile,
+    TableName::MetricsVariations,
+    TableName::Naming,
+    TableName::PostScript,
+    TableName::ScalableVectorGraphics,
+    TableName::StandardBitmapGraphics,
+    TableName::StyleAttributes,
+    TableName::VerticalHeader,
+    TableName::VerticalMetrics,
+    TableName::VerticalMetricsVariations,
+    TableName::VerticalOrigin,
+    TableName::WindowsMetrics,
+];
+
+
+/// A list of font face parsing errors.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum FaceParsingError {
+    /// An attempt to read out of bounds detected.
+    ///
+    /// Should occur only on malformed fonts.
+    MalformedFont,
+
+    /// Font data must start with `0x00010000`, `OTTO` or `ttcf`.
+    UnknownMagic,
+
+    /// The face index is larger than the number of faces in the font collection.
+    FaceIndexOutOfBounds,
+
+    /// The `head` table is missing or malformed.
+    NoHeadTable,
+
+    /// The `hhea` table is missing or malformed.
+    NoHheaTable,
+
+    /// The `maxp` table is missing or malformed.
+    NoMaxpTable,
+}
+
+impl fmt::Display for FaceParsingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FaceParsingError::MalformedFont => write!(f, "malformed font"),
+            FaceParsingError::UnknownMagic => write!(f, "unknown magic"),
+            FaceParsingError::FaceIndexOutOfBounds => write!(f, "face index is out of bounds"),
+            FaceParsingError::NoHeadTable => write!(f, "the head table is missing or malformed"),
+            FaceParsingError::NoHheaTable => write!(f, "the hhea table is missing or malformed"),
+            FaceParsingError::NoMaxpTable => write!(f, "the maxp table is missing or malformed"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for FaceParsingError {}
+
 
 #[derive(Clone, Copy)]
 struct TableRecord {
@@ -496,6 +672,55 @@ impl FromData for TableRecord {
 }
 
 
+/// A prebuilt glyph ID to code points index.
+///
+/// Can be created via `Font::codepoints_index()`.
+#[cfg(feature = "std")]
+#[derive(Clone, Default, Debug)]
+pub struct CodepointsIndex {
+    // Code points of the glyph N are stored at `codepoints[offsets[N]..offsets[N + 1]]`.
+    offsets: Vec<u32>,
+    codepoints: Vec<char>,
+}
+
+#[cfg(feature = "std")]
+impl CodepointsIndex {
+    fn new(font: &Font) -> Self {
+        let mut mappings = Vec::new();
+        font.codepoints(|c, glyph_id| mappings.push((glyph_id, c)));
+        mappings.sort_unstable();
+
+        let glyphs_count = mappings.last().map(|(glyph_id, _)| usize::from(glyph_id.0) + 1)
+            .unwrap_or(0);
+
+        // Count code points per glyph and then convert counts into offsets.
+        let mut offsets = vec![0u32; glyphs_count + 1];
+        for (glyph_id, _) in &mappings {
+            offsets[usize::from(glyph_id.0) + 1] += 1;
+        }
+
+        for i in 1..offsets.len() {
+            offsets[i] += offsets[i - 1];
+        }
+
+        let codepoints = mappings.into_iter().map(|(_, c)| c).collect();
+        CodepointsIndex { offsets, codepoints }
+    }
+
+    /// Returns sorted code points that are mapped to the specified glyph.
+    #[inline]
+    pub fn glyph_codepoints(&self, glyph_id: GlyphId) -> &[char] {
+        let id = usize::from(glyph_id.0);
+        match (self.offsets.get(id), self.offsets.get(id + 1)) {
+            (Some(start), Some(end)) => {
+                &self.codepoints[usize::num_from(*start)..usize::num_from(*end)]
+            }
+            _ => &[],
+        }
+    }
+}
+
+
 const MAX_VAR_COORDS: u8 = 32;
 
 #[derive(Clone, Default)]
@@ -524,11 +749,18 @@ pub struct Font<'a> {
     avar: Option<avar::Table<'a>>,
     cbdt: Option<&'a [u8]>,
     cblc: Option<&'a [u8]>,
-    cff_: Option<cff::Metadata<'a>>,
+    cff_: Option<cff::Cff<'a>>,
     cff2: Option<cff2::Metadata<'a>>,
     cmap: Option<cmap::Table<'a>>,
+    colr: Option<colr::Table<'a>>,
+    cpal: Option<cpal::Table<'a>>,
+    cvar: Option<&'a [u8]>,
+    cvt: Option<LazyArray16<'a, i16>>,
+    fpgm: Option<&'a [u8]>,
     fvar: Option<fvar::Table<'a>>,
     gdef: Option<gdef::Table<'a>>,
+    gpos: Option<LayoutTable<'a>>,
+    gsub: Option<LayoutTable<'a>>,
     glyf: Option<&'a [u8]>,
     gvar: Option<gvar::Table<'a>>,
     head: &'a [u8],
@@ -537,18 +769,24 @@ pub struct Font<'a> {
     hvar: Option<hvar::Table<'a>>,
     kern: Option<kern::Subtables<'a>>,
     loca: Option<loca::Table<'a>>,
+    #[cfg(feature = "hinting")]
+    maxp: &'a [u8],
     mvar: Option<mvar::Table<'a>>,
     name: Option<name::Names<'a>>,
     os_2: Option<os2::Table<'a>>,
     post: Option<post::Table<'a>>,
+    prep: Option<&'a [u8]>,
     vhea: Option<&'a [u8]>,
     vmtx: Option<hmtx::Table<'a>>,
     sbix: Option<&'a [u8]>,
+    stat: Option<stat::Table<'a>>,
     svg_: Option<&'a [u8]>,
     vorg: Option<vorg::Table<'a>>,
     vvar: Option<hvar::Table<'a>>,
     number_of_glyphs: NonZeroU16,
     coordinates: VarCoords,
+    // A bitmask of `TableName`s.
+    skipped_tables: u64,
 }
 
 impl<'a> Font<'a> {
@@ -562,7 +800,8 @@ impl<'a> Font<'a> {
     /// Required tables: `head`, `hhea` and `maxp`.
     ///
     /// If an optional table has an invalid data it will be skipped.
-    pub fn from_data(data: &'a [u8], index: u32) -> Option<Self> {
+    /// Such tables can be checked via `Font::skipped_tables`.
+    pub fn from_data(data: &'a [u8], index: u32) -> Result<Self, FaceParsingError> {
         const OFFSET_TABLE_SIZE: usize = 12;
 
         let table_data = if let Some(n) = fonts_in_collection(data) {
@@ -570,10 +809,12 @@ impl<'a> Font<'a> {
                 // https://docs.microsoft.com/en-us/typography/opentype/spec/otff#ttc-header
                 const OFFSET_32_SIZE: usize = 4;
                 let offset = OFFSET_TABLE_SIZE + OFFSET_32_SIZE * usize::num_from(index);
-                let font_offset: u32 = Stream::read_at(data, offset)?;
-                data.get(usize::num_from(font_offset) .. data.len())?
+                let font_offset: u32 = Stream::read_at(data, offset)
+                    .ok_or(FaceParsingError::MalformedFont)?;
+                data.get(usize::num_from(font_offset) .. data.len())
+                    .ok_or(FaceParsingError::MalformedFont)?
             } else {
-                return None;
+                return Err(FaceParsingError::FaceIndexOutOfBounds);
             }
         } else {
             data
@@ -581,7 +822,7 @@ impl<'a> Font<'a> {
 
         // https://docs.microsoft.com/en-us/typography/opentype/spec/otff#organization-of-an-opentype-font
         if data.len() < OFFSET_TABLE_SIZE {
-            return None;
+            return Err(FaceParsingError::MalformedFont);
         }
 
         // https://docs.microsoft.com/en-us/typography/opentype/spec/otff#organization-of-an-opentype-font
@@ -590,14 +831,15 @@ impl<'a> Font<'a> {
 
         let mut s = Stream::new(table_data);
 
-        let sfnt_version: u32 = s.read()?;
+        let sfnt_version: u32 = s.read().ok_or(FaceParsingError::MalformedFont)?;
         if sfnt_version != SFNT_VERSION_TRUE_TYPE && sfnt_version != SFNT_VERSION_OPEN_TYPE {
-            return None;
+            return Err(FaceParsingError::UnknownMagic);
         }
 
-        let num_tables: u16 = s.read()?;
+        let num_tables: u16 = s.read().ok_or(FaceParsingError::MalformedFont)?;
         s.advance(6); // searchRange (u16) + entrySelector (u16) + rangeShift (u16)
-        let tables = s.read_array16::<TableRecord>(num_tables)?;
+        let tables = s.read_array16::<TableRecord>(num_tables)
+            .ok_or(FaceParsingError::MalformedFont)?;
 
         let mut font = Font {
             avar: None,
@@ -606,8 +848,15 @@ impl<'a> Font<'a> {
             cff_: None,
             cff2: None,
             cmap: None,
+            colr: None,
+            cpal: None,
+            cvar: None,
+            cvt: None,
+            fpgm: None,
             fvar: None,
             gdef: None,
+            gpos: None,
+            gsub: None,
             glyf: None,
             gvar: None,
             head: &[],
@@ -616,18 +865,23 @@ impl<'a> Font<'a> {
             hvar: None,
             kern: None,
             loca: None,
+            #[cfg(feature = "hinting")]
+            maxp: &[],
             mvar: None,
             name: None,
             os_2: None,
             post: None,
+            prep: None,
             vhea: None,
             vmtx: None,
             sbix: None,
+            stat: None,
             svg_: None,
             vorg: None,
             vvar: None,
             number_of_glyphs: NonZeroU16::new(1).unwrap(), // dummy
             coordinates: VarCoords::default(),
+            skipped_tables: 0,
         };
 
         let mut number_of_glyphs = None;
@@ -643,28 +897,47 @@ impl<'a> Font<'a> {
             match &table.table_tag.to_bytes() {
                 b"CBDT" => font.cbdt = data.get(range),
                 b"CBLC" => font.cblc = data.get(range),
-                b"CFF " => font.cff_ = data.get(range).and_then(|data| cff::parse_metadata(data)),
+                b"CFF " => font.cff_ = data.get(range).and_then(cff::Cff::parse),
                 b"CFF2" => font.cff2 = data.get(range).and_then(|data| cff2::parse_metadata(data)),
+                b"COLR" => font.colr = data.get(range).and_then(colr::Table::parse),
+                b"CPAL" => font.cpal = data.get(range).and_then(cpal::Table::parse),
                 b"GDEF" => font.gdef = data.get(range).and_then(|data| gdef::Table::parse(data)),
+                b"GPOS" => font.gpos = data.get(range).and_then(LayoutTable::parse),
+                b"GSUB" => font.gsub = data.get(range).and_then(LayoutTable::parse),
                 b"HVAR" => font.hvar = data.get(range).and_then(|data| hvar::Table::parse(data)),
                 b"MVAR" => font.mvar = data.get(range).and_then(|data| mvar::Table::parse(data)),
                 b"OS/2" => font.os_2 = data.get(range).and_then(|data| os2::Table::parse(data)),
+                b"STAT" => font.stat = data.get(range).and_then(stat::Table::parse),
                 b"SVG " => font.svg_ = data.get(range),
                 b"VORG" => font.vorg = data.get(range).and_then(|data| vorg::Table::parse(data)),
                 b"VVAR" => font.vvar = data.get(range).and_then(|data| hvar::Table::parse(data)),
                 b"avar" => font.avar = data.get(range).and_then(|data| avar::Table::parse(data)),
                 b"cmap" => font.cmap = data.get(range).and_then(|data| cmap::Table::parse(data)),
+                b"cvar" => font.cvar = data.get(range)
+                    .filter(|data| Stream::read_at::<u16>(data, 0) == Some(1)), // major version
+                b"cvt " => font.cvt = data.get(range).and_then(cvt::parse),
+                b"fpgm" => font.fpgm = data.get(range),
                 b"fvar" => font.fvar = data.get(range).and_then(|data| fvar::Table::parse(data)),
                 b"glyf" => font.glyf = data.get(range),
                 b"gvar" => font.gvar = data.get(range).and_then(|data| gvar::Table::parse(data)),
-                b"head" => font.head = data.get(range).and_then(|data| head::parse(data))?,
-                b"hhea" => font.hhea = data.get(range).and_then(|data| hhea::parse(data))?,
+                b"head" => font.head = data.get(range).and_then(|data| head::parse(data))
+                    .ok_or(FaceParsingError::NoHeadTable)?,
+                b"hhea" => font.hhea = data.get(range).and_then(|data| hhea::parse(data))
+                    .ok_or(FaceParsingError::NoHheaTable)?,
                 b"hmtx" => hmtx = data.get(range),
                 b"kern" => font.kern = data.get(range).and_then(|data| kern::parse(data)),
                 b"loca" => loca = data.get(range),
-                b"maxp" => number_of_glyphs = data.get(range).and_then(|data| maxp::parse(data)),
+                b"maxp" => {
+                    let data = data.get(range);
+                    number_of_glyphs = data.and_then(|data| maxp::parse(data));
+                    #[cfg(feature = "hinting")]
+                    {
+                        font.maxp = data.unwrap_or_default();
+                    }
+                }
                 b"name" => font.name = data.get(range).and_then(|data| name::parse(data)),
                 b"post" => font.post = data.get(range).and_then(|data| post::Table::parse(data)),
+                b"prep" => font.prep = data.get(range),
                 b"sbix" => font.sbix = data.get(range),
                 b"vhea" => font.vhea = data.get(range).and_then(|data| vhea::parse(data)),
                 b"vmtx" => vmtx = data.get(range),
@@ -672,11 +945,23 @@ impl<'a> Font<'a> {
             }
         }
 
-        if font.head.is_empty() || font.hhea.is_empty() || number_of_glyphs.is_none() {
-            return None;
+        if font.head.is_empty() {
+            return Err(FaceParsingError::NoHeadTable);
+        }
+
+        if font.hhea.is_empty() {
+            return Err(FaceParsingError::NoHheaTable);
         }
 
-        font.number_of_glyphs = number_of_glyphs?;
+        font.number_of_glyphs = number_of_glyphs.ok_or(FaceParsingError::NoMaxpTable)?;
+
+        if let Some(ref mut cff) = font.cff_ {
+            // Report the same units per em as the rest of the font.
+            // Outlines are not affected by it.
+            if let Some(units_per_em) = head::units_per_em(font.head) {
+                cff.units_per_em = units_per_em;
+            }
+        }
 
         if let Some(ref fvar) = font.fvar {
             font.coordinates.len = fvar.axes().count().min(MAX_VAR_COORDS as usize) as u8;
@@ -700,7 +985,15 @@ impl<'a> Font<'a> {
             }
         }
 
-        Some(font)
+        for table in tables {
+            if let Some(name) = TableName::from_tag(table.table_tag) {
+                if !font.has_table(name) {
+                    font.skipped_tables |= 1 << name as u64;
+                }
+            }
+        }
+
+        Ok(font)
     }
 
     /// Checks that font has a specified table.
@@ -716,11 +1009,19 @@ impl<'a> Font<'a> {
             TableName::CharacterToGlyphIndexMapping => self.cmap.is_some(),
             TableName::ColorBitmapData              => self.cbdt.is_some(),
             TableName::ColorBitmapLocation          => self.cblc.is_some(),
+            TableName::ColorLayers                  => self.colr.is_some(),
+            TableName::ColorPalette                 => self.cpal.is_some(),
             TableName::CompactFontFormat            => self.cff_.is_some(),
             TableName::CompactFontFormat2           => self.cff2.is_some(),
+            TableName::ControlValue                 => self.cvt.is_some(),
+            TableName::ControlValueProgram          => self.prep.is_some(),
+            TableName::ControlValueVariations       => self.cvar.is_some(),
+            TableName::FontProgram                  => self.fpgm.is_some(),
             TableName::FontVariations               => self.fvar.is_some(),
             TableName::GlyphData                    => self.glyf.is_some(),
             TableName::GlyphDefinition              => self.gdef.is_some(),
+            TableName::GlyphPositioning             => self.gpos.is_some(),
+            TableName::GlyphSubstitution            => self.gsub.is_some(),
             TableName::GlyphVariations              => self.gvar.is_some(),
             TableName::HorizontalMetrics            => self.hmtx.is_some(),
             TableName::HorizontalMetricsVariations  => self.hvar.is_some(),
@@ -731,6 +1032,7 @@ impl<'a> Font<'a> {
             TableName::PostScript                   => self.post.is_some(),
             TableName::ScalableVectorGraphics       => self.svg_.is_some(),
             TableName::StandardBitmapGraphics       => self.sbix.is_some(),
+            TableName::StyleAttributes              => self.stat.is_some(),
             TableName::VerticalHeader               => self.vhea.is_some(),
             TableName::VerticalMetrics              => self.vmtx.is_some(),
             TableName::VerticalMetricsVariations    => self.vvar.is_some(),
@@ -739,6 +1041,22 @@ impl<'a> Font<'a> {
         }
     }
 
+    /// Checks that a table is present in the font, but was skipped,
+    /// because its data is invalid or required tables are missing.
+    #[inline]
+    pub fn is_table_skipped(&self, name: TableName) -> bool {
+        self.skipped_tables & (1 << name as u64) != 0
+    }
+
+    /// Returns an iterator over tables that are present in the font, but were skipped,
+    /// because their data is invalid or required tables are missing.
+    ///
+    /// Skipped tables are treated as missing, so `Font::has_table` will return `false` for them.
+    #[inline]
+    pub fn skipped_tables(&self) -> impl Iterator<Item = TableName> + '_ {
+        TABLE_NAMES.iter().cloned().filter(move |name| self.is_table_skipped(*name))
+    }
+
     /// Returns an iterator over [Name Records].
     ///
     /// An iterator can be empty.
@@ -1032,12 +1350,95 @@ impl<'a> Font<'a> {
     ///
     /// Returns `None` instead of `0` when glyph is not found.
     ///
-    /// All subtable formats except Mixed Coverage (8) are supported.
+    /// All subtable formats except Unicode Variation Sequences (14) are supported.
     #[inline]
     pub fn glyph_index(&self, c: char) -> Option<GlyphId> {
         cmap::glyph_index(self.cmap.as_ref()?, c)
     }
 
+    /// Calls `f` for each code point that has a glyph.
+    ///
+    /// Reports exactly the mappings that `glyph_index()` would resolve,
+    /// i.e. each code point is reported only once, even when it's present
+    /// in multiple Unicode subtables. Code points are not sorted.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let data = std::fs::read("fonts/SourceSansPro-Regular-Tiny.ttf").unwrap();
+    /// let font = ttf_parser::Font::from_data(&data, 0).unwrap();
+    /// let mut count = 0;
+    /// font.codepoints(|c, glyph_id| {
+    ///     assert_eq!(font.glyph_index(c), Some(glyph_id));
+    ///     count += 1;
+    /// });
+    /// assert!(count > 0);
+    /// ```
+    #[inline]
+    pub fn codepoints<F: FnMut(char, GlyphId)>(&self, mut f: F) {
+        if let Some(ref table) = self.cmap {
+            cmap::codepoints(table, |c, glyph_id| {
+                if let Some(c) = core::char::from_u32(c) {
+                    f(c, glyph_id);
+                }
+            });
+        }
+    }
+
+    /// Calls `f` for each code point that is mapped to the specified glyph.
+    ///
+    /// This method has to walk the whole `cmap` table,
+    /// so use `codepoints_index()` for repeated queries.
+    ///
+    /// Code points are not sorted.
+    #[inline]
+    pub fn glyph_codepoints<F: FnMut(char)>(&self, glyph_id: GlyphId, mut f: F) {
+        self.codepoints(|c, id| {
+            if id == glyph_id {
+                f(c);
+            }
+        });
+    }
+
+    /// Builds a glyph ID to code points index.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let data = std::fs::read("fonts/SourceSansPro-Regular-Tiny.ttf").unwrap();
+    /// let font = ttf_parser::Font::from_data(&data, 0).unwrap();
+    /// let index = font.codepoints_index();
+    /// let glyph_id = font.glyph_index('A').unwrap();
+    /// assert_eq!(index.glyph_codepoints(glyph_id), &['A']);
+    /// ```
+    #[cfg(feature = "std")]
+    #[inline]
+    pub fn codepoints_index(&self) -> CodepointsIndex {
+        CodepointsIndex::new(self)
+    }
+
+    /// Returns an iterator over character encoding subtables.
+    ///
+    /// Unlike `glyph_index()`, includes non-Unicode subtables as well,
+    /// like Macintosh Roman, Shift-JIS or Windows Symbol.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use ttf_parser::{Font, PlatformId};
+    ///
+    /// let data = std::fs::read("fonts/SourceSansPro-Regular-Tiny.ttf").unwrap();
+    /// let font = Font::from_data(&data, 0).unwrap();
+    /// let subtable = font.character_map_subtables()
+    ///     .find(|s| s.platform_id == PlatformId::Windows)
+    ///     .unwrap();
+    /// assert_eq!(subtable.glyph_index(0x41), font.glyph_index('A'));
+    /// ```
+    #[inline]
+    pub fn character_map_subtables(&self) -> cmap::Subtables<'a> {
+        self.cmap.map(|cmap| cmap.subtables()).unwrap_or_default()
+    }
+
     /// Resolves a variation of a Glyph ID from two code points.
     ///
     /// Implemented according to
@@ -1050,11 +1451,44 @@ impl<'a> Font<'a> {
         cmap::glyph_variation_index(self.cmap.as_ref()?, c, variation)
     }
 
+    /// Returns an iterator over all
+    /// [Unicode Variation Sequences](
+    /// https://docs.microsoft.com/en-us/typography/opentype/spec/cmap#format-14-unicode-variation-sequences)
+    /// variation selectors.
+    ///
+    /// Can be used to list all variants of a character.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use ttf_parser::Font;
+    ///
+    /// let data = std::fs::read("font.ttf").unwrap();
+    /// let font = Font::from_data(&data, 0).unwrap();
+    /// let c = '\u{845B}';
+    /// for selector in font.variation_selectors() {
+    ///     let variation = std::char::from_u32(selector.variation).unwrap();
+    ///     if let Some(id) = font.glyph_variation_index(c, variation) {
+    ///         println!("U+{:04X} U+{:04X} -> {}", c as u32, selector.variation, id.0);
+    ///     }
+    /// }
+    /// ```
+    #[inline]
+    pub fn variation_selectors(&self) -> cmap::VariationSelectors<'a> {
+        self.cmap.as_ref().map(cmap::variation_selectors).unwrap_or_default()
+    }
+
     /// Returns glyph's horizontal advance.
     ///
+    /// When a `glyf` composite glyph has a component with the `USE_MY_METRICS` flag,
+    /// the component's advance is returned.
+    /// This requires a `glyf` lookup, but for non-composite glyphs
+    /// only the number of contours is read.
+    ///
     /// This method is affected by variation axes.
     #[inline]
     pub fn glyph_hor_advance(&self, glyph_id: GlyphId) -> Option<u16> {
+        let glyph_id = self.metrics_glyph_id(glyph_id);
         let mut advance = self.hmtx?.advance(glyph_id)? as f32;
 
         if self.is_variable() {
@@ -1070,9 +1504,15 @@ impl<'a> Font<'a> {
 
     /// Returns glyph's vertical advance.
     ///
+    /// When a `glyf` composite glyph has a component with the `USE_MY_METRICS` flag,
+    /// the component's advance is returned.
+    /// This requires a `glyf` lookup, but for non-composite glyphs
+    /// only the number of contours is read.
+    ///
     /// This method is affected by variation axes.
     #[inline]
     pub fn glyph_ver_advance(&self, glyph_id: GlyphId) -> Option<u16> {
+        let glyph_id = self.metrics_glyph_id(glyph_id);
         let mut advance = self.vmtx?.advance(glyph_id)? as f32;
 
         if self.is_variable() {
@@ -1088,9 +1528,15 @@ impl<'a> Font<'a> {
 
     /// Returns glyph's horizontal side bearing.
     ///
+    /// When a `glyf` composite glyph has a component with the `USE_MY_METRICS` flag,
+    /// the component's side bearing is returned.
+    /// This requires a `glyf` lookup, but for non-composite glyphs
+    /// only the number of contours is read.
+    ///
     /// This method is affected by variation axes.
     #[inline]
     pub fn glyph_hor_side_bearing(&self, glyph_id: GlyphId) -> Option<i16> {
+        let glyph_id = self.metrics_glyph_id(glyph_id);
         let mut bearing = self.hmtx?.side_bearing(glyph_id)? as f32;
 
         if self.is_variable() {
@@ -1106,9 +1552,15 @@ impl<'a> Font<'a> {
 
     /// Returns glyph's vertical side bearing.
     ///
+    /// When a `glyf` composite glyph has a component with the `USE_MY_METRICS` flag,
+    /// the component's side bearing is returned.
+    /// This requires a `glyf` lookup, but for non-composite glyphs
+    /// only the number of contours is read.
+    ///
     /// This method is affected by variation axes.
     #[inline]
     pub fn glyph_ver_side_bearing(&self, glyph_id: GlyphId) -> Option<i16> {
+        let glyph_id = self.metrics_glyph_id(glyph_id);
         let mut bearing = self.vmtx?.side_bearing(glyph_id)? as f32;
 
         if self.is_variable() {
@@ -1122,6 +1574,14 @@ impl<'a> Font<'a> {
         i16::try_num_from(bearing)
     }
 
+    #[inline]
+    fn metrics_glyph_id(&self, glyph_id: GlyphId) -> GlyphId {
+        match (self.loca, self.glyf) {
+            (Some(loca), Some(glyf_table)) => glyf::metrics_glyph_id(loca, glyf_table, glyph_id),
+            _ => glyph_id,
+        }
+    }
+
     /// Returns glyph's vertical origin according to
     /// [Vertical Origin Table](https://docs.microsoft.com/en-us/typography/opentype/spec/vorg).
     pub fn glyph_y_origin(&self, glyph_id: GlyphId) -> Option<i16> {
@@ -1130,12 +1590,49 @@ impl<'a> Font<'a> {
 
     /// Returns glyph's name.
     ///
-    /// Uses the `post` table as a source.
+    /// Uses the `post` table as a source, with a fallback to the `CFF` charset.
     ///
     /// Returns `None` when no name is associated with a `glyph`.
     #[inline]
     pub fn glyph_name(&self, glyph_id: GlyphId) -> Option<&str> {
-        self.post.and_then(|post| post.glyph_name(glyph_id))
+        if let Some(name) = self.post.and_then(|post| post.glyph_name(glyph_id)) {
+            return Some(name);
+        }
+
+        self.cff_.as_ref().and_then(|cff| cff.glyph_name(glyph_id))
+    }
+
+    /// Returns glyph's CID.
+    ///
+    /// Returns `None` for non-CID-keyed `CFF` fonts and for fonts without `CFF`.
+    #[inline]
+    pub fn glyph_cid(&self, glyph_id: GlyphId) -> Option<u16> {
+        self.cff_.as_ref().and_then(|cff| cff.glyph_cid(glyph_id))
+    }
+
+    /// Resolves a Glyph ID for a glyph name.
+    ///
+    /// Uses the `post` table as a source, with a fallback to the `CFF` charset.
+    ///
+    /// Returns `None` when no glyph has such name.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use ttf_parser::Font;
+    ///
+    /// let data = std::fs::read("benches/fonts/SourceSansPro-Regular.otf").unwrap();
+    /// let font = Font::from_data(&data, 0).unwrap();
+    /// let glyph_id = font.glyph_index_by_name("A").unwrap();
+    /// assert_eq!(font.glyph_name(glyph_id), Some("A"));
+    /// ```
+    #[inline]
+    pub fn glyph_index_by_name(&self, name: &str) -> Option<GlyphId> {
+        if let Some(id) = self.post.and_then(|post| post.glyph_index_by_name(name)) {
+            return Some(id);
+        }
+
+        self.cff_.as_ref().and_then(|cff| cff.glyph_index_by_name(name))
     }
 
     /// Checks that font has
@@ -1177,6 +1674,76 @@ impl<'a> Font<'a> {
         try_opt_or!(self.gdef, false).is_mark_glyph(glyph_id, set_index)
     }
 
+    /// Returns a [Glyph Substitution Table](
+    /// https://docs.microsoft.com/en-us/typography/opentype/spec/gsub).
+    ///
+    /// Lookup subtables should be parsed as `gsub::SubstitutionSubtable`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use ttf_parser::{Font, GlyphId, Tag, gsub::SubstitutionSubtable};
+    ///
+    /// let data = std::fs::read("fonts/SourceSansPro-Regular-Tiny.ttf").unwrap();
+    /// let font = Font::from_data(&data, 0).unwrap();
+    /// let table = font.glyph_substitutions().unwrap();
+    /// let script = table.scripts.find(Tag::from_bytes(b"latn")).unwrap();
+    /// for index in script.default_language.unwrap().feature_indices {
+    ///     let feature = table.features.get(index).unwrap();
+    ///     for index in feature.lookup_indices {
+    ///         let lookup = table.lookups.get(index).unwrap();
+    ///         for subtable in lookup.subtables.iter::<SubstitutionSubtable>() {
+    ///             let _ = subtable.coverage().contains(GlyphId(1));
+    ///         }
+    ///     }
+    /// }
+    /// ```
+    #[inline]
+    pub fn glyph_substitutions(&self) -> Option<LayoutTable<'a>> {
+        self.gsub
+    }
+
+    /// Returns a [Glyph Positioning Table](
+    /// https://docs.microsoft.com/en-us/typography/opentype/spec/gpos).
+    ///
+    /// Lookup subtables should be parsed as `gpos::PositioningSubtable`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use ttf_parser::{Font, GlyphId, gpos::PositioningSubtable};
+    ///
+    /// let data = std::fs::read("fonts/SourceSansPro-Regular-Tiny.ttf").unwrap();
+    /// let font = Font::from_data(&data, 0).unwrap();
+    /// let table = font.glyph_positioning().unwrap();
+    /// for lookup in table.lookups {
+    ///     for subtable in lookup.subtables.iter::<PositioningSubtable>() {
+    ///         if let PositioningSubtable::Pair(pair) = subtable {
+    ///             let _ = pair.values(GlyphId(1), GlyphId(2));
+    ///         }
+    ///     }
+    /// }
+    /// ```
+    #[inline]
+    pub fn glyph_positioning(&self) -> Option<LayoutTable<'a>> {
+        self.gpos
+    }
+
+    /// Resolves a variation delta from the `GDEF` Item Variation Store.
+    ///
+    /// Used to resolve `gpos::Device::Variation` and similar indices
+    /// at the current variation coordinates.
+    ///
+    /// Returns `None` when font is not variable or `GDEF` doesn't have a variation store.
+    #[inline]
+    pub fn glyph_variation_delta(&self, outer_index: u16, inner_index: u16) -> Option<f32> {
+        if !self.is_variable() {
+            return None;
+        }
+
+        self.gdef?.variation_delta(outer_index, inner_index, self.coords())
+    }
+
     /// Returns a iterator over kerning subtables.
     ///
     /// Supports both
@@ -1188,6 +1755,112 @@ impl<'a> Font<'a> {
         self.kern.unwrap_or_default()
     }
 
+    /// Returns a horizontal kerning for a pair of glyphs.
+    ///
+    /// Uses `GPOS` pair adjustment subtables of the `kern` feature
+    /// and falls back to the `kern` table only when `GPOS` has no `kern` feature lookups.
+    /// Only the `DFLT` or `latn` script's default language is checked.
+    ///
+    /// For `GPOS`, the first glyph's horizontal advance and the second glyph's
+    /// horizontal placement are used. The second glyph's advance adjustment is ignored,
+    /// since it affects the spacing after the pair and not between the glyphs.
+    ///
+    /// For the `kern` table, only horizontal subtables without
+    /// a state machine and cross-stream values are used.
+    ///
+    /// This method is affected by variation axes.
+    ///
+    /// Returns `None` when there is no kerning for this pair.
+    pub fn glyphs_kerning(&self, left: GlyphId, right: GlyphId) -> Option<i16> {
+        if let Some(kerning) = self.gpos_kerning(left, right) {
+            return kerning;
+        }
+
+        let mut kerning = 0i32;
+        let mut found = false;
+        for subtable in self.kerning_subtables() {
+            if !subtable.is_horizontal() || subtable.is_variable() || subtable.has_cross_stream() {
+                continue;
+            }
+
+            if let Some(value) = subtable.glyphs_kerning(left, right) {
+                kerning += i32::from(value);
+                found = true;
+            }
+        }
+
+        if found {
+            i16::try_from(kerning).ok()
+        } else {
+            None
+        }
+    }
+
+    /// Returns `None` when `GPOS` has no `kern` feature lookups
+    /// and `Some(None)` when there is no kerning for this pair.
+    fn gpos_kerning(&self, left: GlyphId, right: GlyphId) -> Option<Option<i16>> {
+        let table = self.gpos?;
+        let script = table.scripts.find(Tag::from_bytes(b"DFLT"))
+            .or_else(|| table.scripts.find(Tag::from_bytes(b"latn")))?;
+        let language = script.default_language?;
+
+        let mut kerning = 0.0;
+        let mut found = false;
+        let mut has_lookups = false;
+        for feature_index in language.feature_indices {
+            let feature = match table.features.get(feature_index) {
+                Some(feature) if feature.tag == Tag::from_bytes(b"kern") => feature,
+                _ => continue,
+            };
+
+            for lookup_index in feature.lookup_indices {
+                let lookup = match table.lookups.get(lookup_index) {
+                    Some(lookup) => lookup,
+                    None => continue,
+                };
+
+                has_lookups = true;
+
+                // Only the first matching subtable of a lookup is applied.
+                for subtable in lookup.subtables.iter::<gpos::PositioningSubtable>() {
+                    let pair = match subtable {
+                        gpos::PositioningSubtable::Pair(pair) => pair,
+                        _ => continue,
+                    };
+
+                    if let Some((record1, record2)) = pair.values(left, right) {
+                        kerning += self.gpos_value(record1.x_advance, record1.x_advance_device);
+                        kerning += self.gpos_value(record2.x_placement, record2.x_placement_device);
+                        found = true;
+                        break;
+                    }
+                }
+            }
+        }
+
+        if !has_lookups {
+            return None;
+        }
+
+        if found {
+            // We can't use `round()` in `no_std`, so this is the next best thing.
+            let kerning = if kerning < 0.0 { kerning - 0.5 } else { kerning + 0.5 };
+            Some(i16::try_num_from(kerning))
+        } else {
+            Some(None)
+        }
+    }
+
+    #[inline]
+    fn gpos_value(&self, value: i16, device: Option<gpos::Device>) -> f32 {
+        let mut value = f32::from(value);
+        if let Some(gpos::Device::Variation { outer_index, inner_index }) = device {
+            value += self.glyph_variation_delta(outer_index, inner_index).unwrap_or(0.0);
+        }
+
+        value
+    }
+
     /// Outlines a glyph and returns its tight bounding box.
     ///
     /// **Warning**: since `ttf-parser` is a pull parser,
@@ -1253,7 +1926,7 @@ impl<'a> Font<'a> {
         }
 
         if let Some(ref metadata) = self.cff_ {
-            return cff::outline(metadata, glyph_id, builder);
+            return metadata.outline(glyph_id, builder);
         }
 
         if let Some(ref metadata) = self.cff2 {
@@ -1263,6 +1936,130 @@ impl<'a> Font<'a> {
         None
     }
 
+    /// Returns an iterator over `glyf` composite glyph components.
+    ///
+    /// Returns an empty iterator when a glyph is not a composite one
+    /// or a font doesn't have a `glyf` table.
+    ///
+    /// Components are not resolved recursively,
+    /// so a component can be a composite glyph itself.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let data = std::fs::read("fonts/SourceSansPro-Regular-Tiny.ttf").unwrap();
+    /// let font = ttf_parser::Font::from_data(&data, 0).unwrap();
+    /// let component = font.glyph_components(ttf_parser::GlyphId(36)).next().unwrap();
+    /// assert_eq!(component.glyph_id, ttf_parser::GlyphId(81));
+    /// assert!(component.flags.use_my_metrics());
+    /// assert!(font.glyph_components(ttf_parser::GlyphId(13)).next().is_none());
+    /// ```
+    #[inline]
+    pub fn glyph_components(&self, glyph_id: GlyphId) -> glyf::Components<'a> {
+        self.loca.and_then(|loca| glyf::components(loca, self.glyf?, glyph_id)).unwrap_or_default()
+    }
+
+    /// Returns glyph's TrueType instructions.
+    ///
+    /// Returns `None` when a glyph doesn't have instructions
+    /// or a font doesn't have a `glyf` table.
+    ///
+    /// Instructions can be decoded via `instructions::Instructions`.
+    #[inline]
+    pub fn glyph_instructions(&self, glyph_id: GlyphId) -> Option<&'a [u8]> {
+        glyf::glyph_instructions(self.loca?, self.glyf?, glyph_id)
+    }
+
+    /// Returns the Font Program (`fpgm`) instructions.
+    #[inline]
+    pub fn font_program(&self) -> Option<&'a [u8]> {
+        self.fpgm
+    }
+
+    /// Returns the Control Value Program (`prep`) instructions.
+    #[inline]
+    pub fn control_value_program(&self) -> Option<&'a [u8]> {
+        self.prep
+    }
+
+    /// Returns an iterator over control values from the `cvt ` table.
+    ///
+    /// Returns an empty iterator when a font doesn't have a `cvt ` table.
+    ///
+    /// This method is affected by variation axes.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let data = std::fs::read("examples/wasm/TTC.ttc").unwrap();
+    /// let font = ttf_parser::Font::from_data(&data, 0).unwrap();
+    /// assert!(font.control_values().count() > 0);
+    /// ```
+    #[inline]
+    pub fn control_values(&self) -> cvt::ControlValues<'a> {
+        let cvar = if self.is_variable() { self.cvar } else { None };
+        self.cvt
+            .map(|values| cvt::ControlValues::new(values, cvar, self.coords()))
+            .unwrap_or_default()
+    }
+
+    /// Returns an iterator over raw `glyf` glyph points.
+    ///
+    /// Unlike `outline_glyph()`, provides points as they are stored in the font,
+    /// including off-curve points and phantom points.
+    /// See `glyf::OutlinePoints` for details.
+    ///
+    /// Returns `None` for composite glyphs, whose points are defined by components,
+    /// and when a font doesn't have a `glyf` table.
+    ///
+    /// This method is affected by variation axes.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let data = std::fs::read("fonts/SourceSansPro-Regular-Tiny.ttf").unwrap();
+    /// let font = ttf_parser::Font::from_data(&data, 0).unwrap();
+    /// let points = font.glyph_points(ttf_parser::GlyphId(13)).unwrap();
+    /// assert_eq!(points.contour_ends().into_iter().collect::<Vec<_>>(), vec![5]);
+    /// // Six outline points and four phantom points.
+    /// assert_eq!(points.count(), 10);
+    /// ```
+    pub fn glyph_points(&self, glyph_id: GlyphId) -> Option<glyf::OutlinePoints<'a>> {
+        let metrics = self.phantom_metrics(glyph_id)?;
+        let points = glyf::outline_points(self.loca?, self.glyf?, glyph_id, metrics)?;
+        match self.gvar {
+            Some(ref gvar_table) if self.is_variable() => {
+                gvar::outline_points(gvar_table, self.coords(), glyph_id, points)
+            }
+            _ => Some(points),
+        }
+    }
+
+    /// Returns metrics used to calculate glyph's phantom points.
+    fn phantom_metrics(&self, glyph_id: GlyphId) -> Option<glyf::PhantomMetrics> {
+        if glyph_id.0 >= self.number_of_glyphs.get() {
+            return None;
+        }
+
+        let ver_metrics = self.vmtx.and_then(|vmtx| {
+            Some((vmtx.advance(glyph_id)?, vmtx.side_bearing(glyph_id)?))
+        });
+
+        // Use `OS/2` typographic metrics when available, like FreeType does.
+        let (ascender, descender) = match self.os_2 {
+            Some(os_2) => (os_2.typo_ascender(), os_2.typo_descender()),
+            None => (hhea::ascender(self.hhea), hhea::descender(self.hhea)),
+        };
+
+        Some(glyf::PhantomMetrics {
+            hor_advance: self.hmtx?.advance(glyph_id)?,
+            hor_side_bearing: self.hmtx?.side_bearing(glyph_id)?,
+            ver_metrics,
+            ascender,
+            descender,
+        })
+    }
+
     /// Returns a tight glyph bounding box.
     ///
     /// Unless the current font has a `glyf` table, this is just a shorthand for `outline_glyph()`
@@ -1335,12 +2132,111 @@ impl<'a> Font<'a> {
         self.svg_.and_then(|svg_data| svg::parse(svg_data, glyph_id))
     }
 
+    /// Returns an iterator over glyph's color layers.
+    ///
+    /// Supports only the `COLR` version 0 layers.
+    /// Use `paint_color_glyph()` to draw version 1 glyphs as well.
+    ///
+    /// Returns `None` when glyph doesn't have any layers.
+    #[inline]
+    pub fn glyph_color_layers(&self, glyph_id: GlyphId) -> Option<colr::Layers<'a>> {
+        self.colr?.layers(glyph_id)
+    }
+
+    /// Paints a color glyph using the `COLR` table.
+    ///
+    /// Supports both version 0 and version 1 glyphs, with version 1 having a priority.
+    ///
+    /// `palette` is a `CPAL` palette index. Palette 0 is the default one.
+    /// `foreground` is a text foreground color.
+    ///
+    /// This method is affected by variation axes.
+    ///
+    /// Returns `None` when glyph is not a color glyph or the paint graph is malformed.
+    /// In the latter case `painter` may already have received some commands.
+    #[inline]
+    pub fn paint_color_glyph<'b>(
+        &'b self,
+        glyph_id: GlyphId,
+        palette: u16,
+        foreground: RgbaColor,
+        painter: &mut dyn colr::Painter<'b>,
+    ) -> Option<()> {
+        colr::paint(self.colr?, self.cpal, palette, foreground, self.coords(), glyph_id, painter)
+    }
+
+    /// Returns the number of `CPAL` palettes.
+    ///
+    /// Returns 0 when font doesn't have a `CPAL` table.
+    #[inline]
+    pub fn number_of_palettes(&self) -> u16 {
+        self.cpal.map(|cpal| cpal.palettes()).unwrap_or(0)
+    }
+
+    /// Returns a `CPAL` palette color.
+    #[inline]
+    pub fn palette_color(&self, palette_index: u16, palette_entry: u16) -> Option<RgbaColor> {
+        self.cpal?.get(palette_index, palette_entry)
+    }
+
     /// Returns an iterator over variation axes.
     #[inline]
     pub fn variation_axes(&self) -> VariationAxes {
         self.fvar.map(|fvar| fvar.axes()).unwrap_or_default()
     }
 
+    /// Returns an iterator over variation named instances.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use ttf_parser::Font;
+    ///
+    /// let data = std::fs::read("benches/fonts/SourceSansVariable-Roman.ttf").unwrap();
+    /// let mut font = Font::from_data(&data, 0).unwrap();
+    /// let index = font.named_instances().position(|instance| {
+    ///     font.names().any(|name| {
+    ///         name.name_id() == instance.subfamily_name_id &&
+    ///         name.name_utf8().as_ref().map(String::as_str) == Some("Bold")
+    ///     })
+    /// }).unwrap();
+    /// font.set_named_instance(index as u16).unwrap();
+    /// ```
+    #[inline]
+    pub fn named_instances(&self) -> NamedInstances<'a> {
+        self.fvar.map(|fvar| fvar.named_instances()).unwrap_or_default()
+    }
+
+    /// Returns style attributes from the `STAT` table.
+    ///
+    /// Can be used to name positions on variation axes and to compose style names.
+    ///
+    /// Returns `None` when `STAT` table is not present or invalid.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use ttf_parser::{Font, stat::AxisValueData};
+    ///
+    /// let data = std::fs::read("benches/fonts/SourceSansVariable-Roman.ttf").unwrap();
+    /// let font = Font::from_data(&data, 0).unwrap();
+    /// let attrs = font.style_attributes().unwrap();
+    /// for value in attrs.axis_values() {
+    ///     if let AxisValueData::Range { axis_index, nominal_value, .. } = value.data {
+    ///         let axis = attrs.axes().get(axis_index).unwrap();
+    ///         let name = attrs.name_utf8(value.value_name_id).unwrap();
+    ///         println!("{} {} = {}", axis.tag, nominal_value, name);
+    ///     }
+    /// }
+    ///
+    /// let name_id = attrs.elided_fallback_name_id().unwrap();
+    /// assert_eq!(attrs.name_utf8(name_id).unwrap(), "Regular");
+    /// ```
+    #[inline]
+    pub fn style_attributes(&self) -> Option<stat::StyleAttributes<'a>> {
+        Some(stat::StyleAttributes::new(self.stat?, self.name.unwrap_or_default()))
+    }
+
     /// Sets a variation axis coordinate.
     ///
     /// This is the only mutable method in the library.
@@ -1375,6 +2271,26 @@ impl<'a> Font<'a> {
         Some(())
     }
 
+    /// Sets variation axes coordinates from a named instance.
+    ///
+    /// Coordinates of all axes are replaced.
+    ///
+    /// Returns `None` when font is not variable or doesn't have such instance.
+    pub fn set_named_instance(&mut self, index: u16) -> Option<()> {
+        let instance = self.named_instances().get(index)?;
+        let axes = self.fvar?.axes().take(usize::from(MAX_VAR_COORDS));
+        for (i, (axis, value)) in axes.zip(instance.coordinates()).enumerate() {
+            self.coordinates.data[i] = axis.normalized_value(value);
+        }
+
+        if let Some(avar) = self.avar {
+            // Ignore error.
+            let _ = avar.map_coordinates(self.coordinates.as_mut_slice());
+        }
+
+        Some(())
+    }
+
     #[inline]
     fn metrics_var_offset(&self, tag: Tag) -> f32 {
         self.mvar.and_then(|table| table.metrics_offset(tag, self.coords())).unwrap_or(0.0)
@@ -1432,7 +2348,7 @@ mod tests {
 
     #[test]
     fn empty_font() {
-        assert!(Font::from_data(&[], 0).is_none());
+        assert_eq!(Font::from_data(&[], 0).unwrap_err(), FaceParsingError::MalformedFont);
     }
 
     #[test]
@@ -1446,7 +2362,7 @@ mod tests {
         ]);
 
         for i in 0..data.len() {
-            assert!(Font::from_data(&data[0..i], 0).is_none());
+            assert_eq!(Font::from_data(&data[0..i], 0).unwrap_err(), FaceParsingError::MalformedFont);
         }
     }
 
@@ -1460,7 +2376,7 @@ mod tests {
             UInt16(0), // rangeShift
         ]);
 
-        assert!(Font::from_data(&data, 0).is_none());
+        assert_eq!(Font::from_data(&data, 0).unwrap_err(), FaceParsingError::NoHeadTable);
     }
 
     #[test]
@@ -1473,7 +2389,7 @@ mod tests {
             UInt16(0), // rangeShift
         ]);
 
-        assert!(Font::from_data(&data, 0).is_none());
+        assert_eq!(Font::from_data(&data, 0).unwrap_err(), FaceParsingError::MalformedFont);
     }
 
     #[test]
@@ -1486,7 +2402,7 @@ mod tests {
             UInt16(0), // rangeShift
         ]);
 
-        assert!(Font::from_data(&data, 0).is_none());
+        assert_eq!(Font::from_data(&data, 0).unwrap_err(), FaceParsingError::NoHeadTable);
     }
 
     #[test]
@@ -1499,7 +2415,7 @@ mod tests {
             UInt16(0), // rangeShift
         ]);
 
-        assert!(Font::from_data(&data, 0).is_none());
+        assert_eq!(Font::from_data(&data, 0).unwrap_err(), FaceParsingError::UnknownMagic);
     }
 
     #[test]
@@ -1512,7 +2428,7 @@ mod tests {
         ]);
 
         assert_eq!(fonts_in_collection(&data), Some(0));
-        assert!(Font::from_data(&data, 0).is_none());
+        assert_eq!(Font::from_data(&data, 0).unwrap_err(), FaceParsingError::FaceIndexOutOfBounds);
     }
 
     #[test]
@@ -1525,7 +2441,7 @@ mod tests {
         ]);
 
         assert_eq!(fonts_in_collection(&data), Some(std::u32::MAX));
-        assert!(Font::from_data(&data, 0).is_none());
+        assert_eq!(Font::from_data(&data, 0).unwrap_err(), FaceParsingError::MalformedFont);
     }
 
     #[test]
@@ -1538,7 +2454,7 @@ mod tests {
         ]);
 
         assert_eq!(fonts_in_collection(&data), Some(1));
-        assert!(Font::from_data(&data, std::u32::MAX).is_none());
+        assert_eq!(Font::from_data(&data, std::u32::MAX).unwrap_err(), FaceParsingError::FaceIndexOutOfBounds);
     }
 
     #[test]
@@ -1551,6 +2467,392 @@ mod tests {
         ]);
 
         assert_eq!(fonts_in_collection(&data), Some(std::u32::MAX));
-        assert!(Font::from_data(&data, std::u32::MAX - 1).is_none());
+        assert_eq!(Font::from_data(&data, std::u32::MAX - 1).unwrap_err(), FaceParsingError::MalformedFont);
+    }
+
+    // Replaces a table record's tag or length in the tiny font.
+    fn patch_table_record(tag: &[u8; 4], new_tag: &[u8; 4], new_length: Option<u32>) -> std::vec::Vec<u8> {
+        let mut data = std::fs::read("fonts/SourceSansPro-Regular-Tiny.ttf").unwrap();
+        let number_of_tables: u16 = Stream::read_at(&data, 4).unwrap();
+        for i in 0..usize::from(number_of_tables) {
+            let offset = 12 + i * 16;
+            if &data[offset..offset + 4] == tag {
+                data[offset..offset + 4].copy_from_slice(new_tag);
+                if let Some(length) = new_length {
+                    data[offset + 12..offset + 16].copy_from_slice(&length.to_be_bytes());
+                }
+            }
+        }
+
+        data
+    }
+
+    #[test]
+    fn missing_required_tables() {
+        let data = patch_table_record(b"head", b"xxxx", None);
+        assert_eq!(Font::from_data(&data, 0).unwrap_err(), FaceParsingError::NoHeadTable);
+
+        let data = patch_table_record(b"hhea", b"xxxx", None);
+        assert_eq!(Font::from_data(&data, 0).unwrap_err(), FaceParsingError::NoHheaTable);
+
+        let data = patch_table_record(b"maxp", b"maxp", Some(2));
+        assert_eq!(Font::from_data(&data, 0).unwrap_err(), FaceParsingError::NoMaxpTable);
+    }
+
+    #[test]
+    fn skipped_tables() {
+        let data = std::fs::read("fonts/SourceSansPro-Regular-Tiny.ttf").unwrap();
+        let font = Font::from_data(&data, 0).unwrap();
+        assert_eq!(font.skipped_tables().count(), 0);
+
+        let data = patch_table_record(b"OS/2", b"OS/2", Some(2));
+        let font = Font::from_data(&data, 0).unwrap();
+        assert!(!font.has_table(TableName::WindowsMetrics));
+        assert!(font.is_table_skipped(TableName::WindowsMetrics));
+        assert!(!font.is_table_skipped(TableName::Naming));
+        assert_eq!(font.skipped_tables().collect::<std::vec::Vec<_>>(), &[TableName::WindowsMetrics]);
+    }
+
+    #[test]
+    fn face_parsing_error_display() {
+        assert_eq!(std::string::ToString::to_string(&FaceParsingError::FaceIndexOutOfBounds), "face index is out of bounds");
+        assert_eq!(std::string::ToString::to_string(&FaceParsingError::NoHheaTable), "the hhea table is missing or malformed");
+    }
+
+    #[test]
+    fn codepoints_index() {
+        let data = std::fs::read("fonts/SourceSansPro-Regular-Tiny.ttf").unwrap();
+        let font = Font::from_data(&data, 0).unwrap();
+        let index = font.codepoints_index();
+        for id in 0..font.number_of_glyphs() + 1 {
+            let mut codepoints = std::vec::Vec::new();
+            font.glyph_codepoints(GlyphId(id), |c| codepoints.push(c));
+            codepoints.sort();
+            assert_eq!(index.glyph_codepoints(GlyphId(id)), codepoints.as_slice());
+        }
+
+        assert_eq!(index.glyph_codepoints(GlyphId(0)), &[]);
+    }
+
+    #[test]
+    fn truetype_instructions() {
+        let data = std::fs::read("examples/wasm/TTC.ttc").unwrap();
+        let font = Font::from_data(&data, 0).unwrap();
+        assert!(font.has_table(TableName::ControlValue));
+        assert!(font.has_table(TableName::ControlValueProgram));
+        assert!(font.has_table(TableName::FontProgram));
+        assert!(!font.has_table(TableName::ControlValueVariations));
+
+        for program in &[font.font_program().unwrap(), font.control_value_program().unwrap()] {
+            let mut iter = instructions::Instructions::new(program);
+            assert!(iter.by_ref().count() > 0);
+            assert!(!iter.is_malformed());
+        }
+
+        let cvt = font.cvt.unwrap();
+        assert_eq!(font.control_values().count(), usize::from(cvt.len()));
+        assert!(font.control_values().zip(cvt).all(|(a, b)| a == f32::from(b)));
+
+        let glyph_id = (0..font.number_of_glyphs()).map(GlyphId)
+            .find(|id| font.glyph_instructions(*id).is_some()).unwrap();
+        let mut iter = instructions::Instructions::new(font.glyph_instructions(glyph_id).unwrap());
+        assert!(iter.by_ref().count() > 0);
+        assert!(!iter.is_malformed());
+    }
+
+    fn gen_gpos(subtable: &[writer::TtfType]) -> std::vec::Vec<u8> {
+        let mut data = writer::convert(&[
+            UInt16(1), // major version
+            UInt16(0), // minor version
+            UInt16(10), // script list offset
+            UInt16(30), // feature list offset
+            UInt16(44), // lookup list offset
+            // Script list
+            UInt16(1), // count
+            Raw(b"DFLT"), // script tag
+            UInt16(8), // script offset
+            // Script
+            UInt16(4), // default language offset
+            UInt16(0), // language count
+            // Language
+            UInt16(0), // lookup order
+            UInt16(0xFFFF), // required feature index
+            UInt16(1), // feature index count
+            UInt16(0), // feature index
+            // Feature list
+            UInt16(1), // count
+            Raw(b"kern"), // feature tag
+            UInt16(8), // feature offset
+            // Feature
+            UInt16(0), // feature params offset
+            UInt16(1), // lookup index count
+            UInt16(0), // lookup index
+            // Lookup list
+            UInt16(1), // count
+            UInt16(4), // lookup offset
+            // Lookup
+            UInt16(2), // lookup type: pair adjustment
+            UInt16(0), // lookup flags
+            UInt16(1), // subtables count
+            UInt16(8), // subtable offset
+        ]);
+
+        data.extend_from_slice(&writer::convert(subtable));
+        data
+    }
+
+    #[test]
+    fn gpos_kerning_format1() {
+        let gpos = gen_gpos(&[
+            UInt16(1), // format
+            UInt16(12), // coverage offset
+            UInt16(0x0044), // value format 1: xAdvance, xAdvanceDevice
+            UInt16(0x0001), // value format 2: xPlacement
+            UInt16(1), // pair sets count
+            UInt16(18), // pair set offset
+            // Coverage
+            UInt16(1), // format
+            UInt16(1), // count
+            UInt16(1), // glyph
+            // Pair set
+            UInt16(1), // count
+            UInt16(2), // second glyph
+            Int16(-50), // xAdvance
+            UInt16(10), // xAdvanceDevice offset
+            Int16(5), // xPlacement
+            // Device
+            UInt16(0), // deltaSetOuterIndex
+            UInt16(0), // deltaSetInnerIndex
+            UInt16(0x8000), // deltaFormat
+        ]);
+
+        let gdef = writer::convert(&[
+            UInt32(0x00010003), // version
+            UInt16(0), // glyph class definition offset
+            UInt16(0), // attachment list offset
+            UInt16(0), // ligature caret list offset
+            UInt16(0), // mark attachment class definition offset
+            UInt16(0), // mark glyph sets definition offset
+            UInt32(18), // item variation store offset
+            // Item variation store
+            UInt16(1), // format
+            UInt32(12), // region list offset
+            UInt16(1), // data count
+            UInt32(22), // data offset
+            // Region list
+            UInt16(1), // axis count
+            UInt16(1), // region count
+            Int16(0), // start
+            Int16(0x4000), // peak
+            Int16(0x4000), // end
+            // Item variation data
+            UInt16(1), // item count
+            UInt16(1), // short delta count
+            UInt16(1), // region index count
+            UInt16(0), // region index
+            Int16(-30), // delta
+        ]);
+
+        let fvar = writer::convert(&[
+            UInt32(0x00010000), // version
+            UInt16(16), // axes array offset
+            UInt16(2), // reserved
+            UInt16(1), // axis count
+            UInt16(20), // axis size
+            UInt16(0), // instance count
+            UInt16(0), // instance size
+            Raw(b"wght"), // axis tag
+            Int32(100 << 16), // min value
+            Int32(400 << 16), // default value
+            Int32(900 << 16), // max value
+            UInt16(0), // flags
+            UInt16(256), // axis name ID
+        ]);
+
+        let data = std::fs::read("fonts/SourceSansPro-Regular-Tiny.ttf").unwrap();
+        let mut font = Font::from_data(&data, 0).unwrap();
+        font.gpos = LayoutTable::parse(&gpos);
+        font.gdef = gdef::Table::parse(&gdef);
+
+        // The second glyph's placement is included as well.
+        assert_eq!(font.glyphs_kerning(GlyphId(1), GlyphId(2)), Some(-45));
+        assert_eq!(font.glyphs_kerning(GlyphId(1), GlyphId(3)), None);
+        assert_eq!(font.glyphs_kerning(GlyphId(2), GlyphId(1)), None);
+
+        // Variation deltas are resolved via the `GDEF` item variation store.
+        font.fvar = fvar::Table::parse(&fvar);
+        font.coordinates.len = 1;
+        font.set_variation(Tag::from_bytes(b"wght"), 900.0).unwrap();
+        assert_eq!(font.glyphs_kerning(GlyphId(1), GlyphId(2)), Some(-75));
+    }
+
+    #[test]
+    fn gpos_kerning_format2() {
+        let gpos = gen_gpos(&[
+            UInt16(2), // format
+            UInt16(24), // coverage offset
+            UInt16(0x0004), // value format 1: xAdvance
+            UInt16(0x0000), // value format 2
+            UInt16(30), // class definition 1 offset
+            UInt16(38), // class definition 2 offset
+            UInt16(2), // class 1 count
+            UInt16(2), // class 2 count
+            Int16(0), Int16(0), // class 1 = 0
+            Int16(0), Int16(-40), // class 1 = 1
+            // Coverage
+            UInt16(1), // format
+            UInt16(1), // count
+            UInt16(1), // glyph
+            // Class definition 1
+            UInt16(1), // format
+            UInt16(1), // start glyph
+            UInt16(1), // count
+            UInt16(1), // class
+            // Class definition 2
+            UInt16(1), // format
+            UInt16(2), // start glyph
+            UInt16(1), // count
+            UInt16(1), // class
+        ]);
+
+        let data = std::fs::read("fonts/SourceSansPro-Regular-Tiny.ttf").unwrap();
+        let mut font = Font::from_data(&data, 0).unwrap();
+        font.gpos = LayoutTable::parse(&gpos);
+
+        assert_eq!(font.glyphs_kerning(GlyphId(1), GlyphId(2)), Some(-40));
+        assert_eq!(font.glyphs_kerning(GlyphId(1), GlyphId(3)), Some(0));
+        assert_eq!(font.glyphs_kerning(GlyphId(2), GlyphId(2)), None);
+    }
+
+    #[test]
+    fn kerning_fallback() {
+        let gpos = gen_gpos(&[
+            UInt16(1), // format
+            UInt16(12), // coverage offset
+            UInt16(0x0004), // value format 1: xAdvance
+            UInt16(0x0000), // value format 2
+            UInt16(1), // pair sets count
+            UInt16(18), // pair set offset
+            // Coverage
+            UInt16(1), // format
+            UInt16(1), // count
+            UInt16(1), // glyph
+            // Pair set
+            UInt16(1), // count
+            UInt16(2), // second glyph
+            Int16(-50), // xAdvance
+        ]);
+
+        let kern = writer::convert(&[
+            UInt16(0), // version
+            UInt16(1), // number of subtables
+            // Subtable
+            UInt16(0), // version
+            UInt16(26), // length
+            UInt8(0), // format
+            UInt8(1), // coverage: horizontal
+            UInt16(2), // number of pairs
+            UInt16(0), // search range
+            UInt16(0), // entry selector
+            UInt16(0), // range shift
+            UInt16(1), UInt16(2), Int16(-10),
+            UInt16(4), UInt16(5), Int16(-20),
+        ]);
+
+        let data = std::fs::read("fonts/SourceSansPro-Regular-Tiny.ttf").unwrap();
+        let mut font = Font::from_data(&data, 0).unwrap();
+        font.gpos = LayoutTable::parse(&gpos);
+        font.kern = kern::parse(&kern);
+
+        // `kern` is ignored when `GPOS` has `kern` feature lookups.
+        assert_eq!(font.glyphs_kerning(GlyphId(1), GlyphId(2)), Some(-50));
+        assert_eq!(font.glyphs_kerning(GlyphId(4), GlyphId(5)), None);
+        assert_eq!(font.glyphs_kerning(GlyphId(4), GlyphId(6)), None);
+
+        // And used otherwise.
+        font.gpos = None;
+        assert_eq!(font.glyphs_kerning(GlyphId(1), GlyphId(2)), Some(-10));
+        assert_eq!(font.glyphs_kerning(GlyphId(4), GlyphId(5)), Some(-20));
+        assert_eq!(font.glyphs_kerning(GlyphId(4), GlyphId(6)), None);
+    }
+
+    #[test]
+    fn glyph_points_ignore_gvar_without_fvar() {
+        let data = std::fs::read("fonts/SourceSansPro-Regular-Tiny.ttf").unwrap();
+        let mut font = Font::from_data(&data, 0).unwrap();
+        let number_of_glyphs = font.number_of_glyphs();
+        let expected: Vec<_> = font.glyph_points(GlyphId(13)).unwrap().map(|p| (p.x, p.y)).collect();
+
+        // Move all points of glyph 13 by 10 horizontally.
+        let mut gvar = vec![
+            UInt32(0x00010000), // version
+            UInt16(1), // axis count
+            UInt16(0), // shared tuple count
+            UInt32(0), // offset to shared tuples
+            UInt16(number_of_glyphs), // glyph count
+            UInt16(0), // flags
+            UInt32(20 + (u32::from(number_of_glyphs) + 1) * 2), // offset to glyph variation data
+        ];
+        // Glyph variation data offsets.
+        for i in 0..=number_of_glyphs {
+            gvar.push(UInt16(if i > 13 { 10 } else { 0 }));
+        }
+        gvar.extend_from_slice(&[
+            UInt16(1), // tuple variation count
+            UInt16(10), // offset to serialized data
+            UInt16(10), // variation data size
+            UInt16(0x8000), // EMBEDDED_PEAK_TUPLE
+            Int16(0x4000), // peak tuple
+            // Serialized data.
+            UInt8(9), // x deltas control
+            Int8(10), Int8(10), Int8(10), Int8(10), Int8(10),
+            Int8(10), Int8(10), Int8(10), Int8(10), Int8(10),
+            UInt8(0x80 | 9), // y deltas control
+        ]);
+
+        let gvar = writer::convert(&gvar);
+        font.gvar = gvar::Table::parse(&gvar);
+        assert!(font.gvar.is_some());
+
+        // `gvar` is ignored in a non-variable font.
+        let points: Vec<_> = font.glyph_points(GlyphId(13)).unwrap().map(|p| (p.x, p.y)).collect();
+        assert_eq!(points, expected);
+    }
+
+    #[test]
+    fn use_my_metrics() {
+        let (glyf, loca) = glyf::tests::build_glyf(&glyf::tests::test_glyphs());
+        let number_of_glyphs = NonZeroU16::new(loca.len() as u16 / 4 - 1).unwrap();
+
+        // Each glyph has unique metrics.
+        let mut hmtx = std::vec::Vec::new();
+        let mut vmtx = std::vec::Vec::new();
+        for i in 0..number_of_glyphs.get() {
+            hmtx.extend_from_slice(&[UInt16(100 + i), Int16(i as i16)]);
+            vmtx.extend_from_slice(&[UInt16(200 + i), Int16(-(i as i16))]);
+        }
+        let hmtx = writer::convert(&hmtx);
+        let vmtx = writer::convert(&vmtx);
+
+        let data = std::fs::read("fonts/SourceSansPro-Regular-Tiny.ttf").unwrap();
+        let mut font = Font::from_data(&data, 0).unwrap();
+        font.glyf = Some(&glyf);
+        font.loca = loca::Table::parse(&loca, number_of_glyphs, IndexToLocationFormat::Long);
+        font.hmtx = hmtx::Table::parse(&hmtx, number_of_glyphs, number_of_glyphs);
+        font.vmtx = hmtx::Table::parse(&vmtx, number_of_glyphs, number_of_glyphs);
+
+        let metrics = |id| (
+            font.glyph_hor_advance(GlyphId(id)),
+            font.glyph_hor_side_bearing(GlyphId(id)),
+            font.glyph_ver_advance(GlyphId(id)),
+            font.glyph_ver_side_bearing(GlyphId(id)),
+        );
+
+        assert_eq!(metrics(1), (Some(101), Some(1), Some(201), Some(-1)));
+        // A composite glyph without `USE_MY_METRICS`.
+        assert_eq!(metrics(3), (Some(103), Some(3), Some(203), Some(-3)));
+        // Metrics of glyph 2 are used by glyph 7 and, through it, by glyph 8.
+        assert_eq!(metrics(7), (Some(102), Some(2), Some(202), Some(-2)));
+        assert_eq!(metrics(8), (Some(102), Some(2), Some(202), Some(-2)));
     }
 }
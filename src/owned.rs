@@ -0,0 +1,162 @@
+use core::fmt;
+
+use std::sync::Arc;
+use std::vec::Vec;
+
+use crate::{Font, FaceParsingError, Tag, VarCoords};
+
+/// A font data handle that owns its data.
+///
+/// Unlike `Font`, doesn't borrow the input buffer, so it can be stored in long-lived caches
+/// and moved between threads. Cloning is cheap, since the data is shared.
+///
+/// The query API is provided by a `Font` that borrows this object.
+/// It's created on each `as_font` call, which is cheap, since only the table directory
+/// and table headers are parsed.
+///
+/// Available only with the `std` feature enabled.
+///
+/// ```no_run
+/// let data = std::fs::read("font.ttf").unwrap();
+/// let font = ttf_parser::OwnedFont::from_vec(data, 0).unwrap();
+/// std::thread::spawn(move || {
+///     println!("{:?}", font.as_font().glyph_index('A'));
+/// });
+/// ```
+#[derive(Clone)]
+pub struct OwnedFont {
+    data: Arc<[u8]>,
+    index: u32,
+    coordinates: VarCoords,
+}
+
+impl OwnedFont {
+    /// Creates an `OwnedFont` object from a raw data.
+    ///
+    /// The same as `Font::from_data`, but takes the data ownership.
+    pub fn from_vec(data: Vec<u8>, index: u32) -> Result<Self, FaceParsingError> {
+        Self::from_arc(Arc::from(data), index)
+    }
+
+    /// Creates an `OwnedFont` object from a shared raw data.
+    ///
+    /// Can be used to load multiple fonts from a single font collection
+    /// without copying the data.
+    pub fn from_arc(data: Arc<[u8]>, index: u32) -> Result<Self, FaceParsingError> {
+        let coordinates = Font::from_data(&data, index)?.coordinates;
+        Ok(OwnedFont { data, index, coordinates })
+    }
+
+    /// Returns a `Font` that borrows this object.
+    ///
+    /// Variation coordinates set via `OwnedFont::set_variation` and
+    /// `OwnedFont::set_named_instance` are preserved.
+    pub fn as_font(&self) -> Font<'_> {
+        // The data is immutable and was already successfully parsed in the constructor.
+        let mut font = Font::from_data(&self.data, self.index).unwrap();
+        font.coordinates = self.coordinates.clone();
+        font
+    }
+
+    /// Returns the font data.
+    #[inline]
+    pub fn data(&self) -> &Arc<[u8]> {
+        &self.data
+    }
+
+    /// Returns the font index in a font collection.
+    #[inline]
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    /// Sets a variation axis coordinate.
+    ///
+    /// The same as `Font::set_variation`.
+    pub fn set_variation(&mut self, axis: Tag, value: f32) -> Option<()> {
+        let mut font = self.as_font();
+        font.set_variation(axis, value)?;
+        self.coordinates = font.coordinates;
+        Some(())
+    }
+
+    /// Sets variation axes coordinates from a named instance.
+    ///
+    /// The same as `Font::set_named_instance`.
+    pub fn set_named_instance(&mut self, index: u16) -> Option<()> {
+        let mut font = self.as_font();
+        font.set_named_instance(index)?;
+        self.coordinates = font.coordinates;
+        Some(())
+    }
+}
+
+impl fmt::Debug for OwnedFont {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "OwnedFont()")
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_vec() {
+        let data = std::fs::read("fonts/SourceSansPro-Regular-Tiny.ttf").unwrap();
+        let font = OwnedFont::from_vec(data.clone(), 0).unwrap();
+        assert_eq!(font.as_font().glyph_index('A'), Font::from_data(&data, 0).unwrap().glyph_index('A'));
+        assert_eq!(font.data().len(), data.len());
+        assert_eq!(font.index(), 0);
+
+        assert_eq!(OwnedFont::from_vec(data[..100].to_vec(), 0).unwrap_err(),
+                   FaceParsingError::MalformedFont);
+    }
+
+    #[test]
+    fn send_and_sync() {
+        fn check<T: Send + Sync + 'static>(_: &T) {}
+
+        let data = std::fs::read("fonts/SourceSansPro-Regular-Tiny.ttf").unwrap();
+        let font = OwnedFont::from_vec(data, 0).unwrap();
+        check(&font);
+
+        let clone = font.clone();
+        let id = std::thread::spawn(move || clone.as_font().glyph_index('A')).join().unwrap();
+        assert_eq!(id, font.as_font().glyph_index('A'));
+    }
+
+    #[test]
+    fn collection() {
+        let data: Arc<[u8]> = Arc::from(std::fs::read("examples/wasm/TTC.ttc").unwrap());
+        let font1 = OwnedFont::from_arc(data.clone(), 0).unwrap();
+        let font2 = OwnedFont::from_arc(data.clone(), 1).unwrap();
+        assert!(Arc::ptr_eq(font1.data(), font2.data()));
+        assert_eq!(font2.index(), 1);
+        assert_eq!(OwnedFont::from_arc(data, 2).unwrap_err(),
+                   FaceParsingError::FaceIndexOutOfBounds);
+    }
+
+    #[test]
+    fn variation() {
+        // `benches` fonts are not packaged.
+        let data = match std::fs::read("benches/fonts/SourceSansVariable-Roman.ttf") {
+            Ok(data) => data,
+            Err(_) => return,
+        };
+        let mut font = OwnedFont::from_vec(data, 0).unwrap();
+        assert_eq!(font.as_font().coords()[0].get(), 0);
+
+        font.set_variation(Tag::from_bytes(b"wght"), 900.0).unwrap();
+        assert_ne!(font.as_font().coords()[0].get(), 0);
+        assert!(font.set_variation(Tag::from_bytes(b"wdth"), 100.0).is_none());
+
+        // Coordinates are copied on clone.
+        let clone = font.clone();
+        assert_eq!(clone.as_font().coords(), font.as_font().coords());
+
+        font.set_named_instance(0).unwrap();
+        assert!(font.set_named_instance(100).is_none());
+    }
+}
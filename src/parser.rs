@@ -69,6 +69,17 @@ impl FromData for i32 {
 }
 
 
+/// A trait for parsing variable-sized binary data.
+///
+/// Unlike `FromData`, the parsed object can borrow the input data.
+///
+/// This is a low-level, internal trait that should not be used directly.
+pub trait FromSlice<'a>: Sized {
+    /// Parses an object from a raw data.
+    fn parse(data: &'a [u8]) -> Option<Self>;
+}
+
+
 // https://docs.microsoft.com/en-us/typography/opentype/spec/otff#data-types
 #[derive(Clone, Copy, Debug)]
 pub struct U24(pub u32);
@@ -142,6 +153,13 @@ pub trait TryNumFrom<T>: Sized {
     fn try_num_from(_: T) -> Option<Self>;
 }
 
+impl TryNumFrom<f32> for u8 {
+    #[inline]
+    fn try_num_from(v: f32) -> Option<Self> {
+        i32::try_num_from(v).and_then(|v| u8::try_from(v).ok())
+    }
+}
+
 impl TryNumFrom<f32> for i16 {
     #[inline]
     fn try_num_from(v: f32) -> Option<Self> {
@@ -347,6 +365,136 @@ impl<'a, T: FromData> Iterator for LazyArrayIter16<'a, T> {
 }
 
 
+impl<'a, T: FromData> FromSlice<'a> for LazyArray16<'a, T> {
+    /// Parses a `u16` count followed by the array itself.
+    #[inline]
+    fn parse(data: &'a [u8]) -> Option<Self> {
+        let mut s = Stream::new(data);
+        let count: u16 = s.read()?;
+        s.read_array16(count)
+    }
+}
+
+
+/// A slice-like container of offsets to tables that are parsed only on access.
+///
+/// Offsets are from the beginning of the `data`. Null offsets are resolved to `None`.
+///
+/// This is a low-level, internal structure that should not be used directly.
+#[derive(Clone, Copy)]
+pub struct LazyOffsetArray16<'a, T> {
+    data: &'a [u8],
+    offsets: LazyArray16<'a, Option<Offset16>>,
+    data_type: core::marker::PhantomData<T>,
+}
+
+impl<T> Default for LazyOffsetArray16<'_, T> {
+    #[inline]
+    fn default() -> Self {
+        LazyOffsetArray16 {
+            data: &[],
+            offsets: LazyArray16::default(),
+            data_type: core::marker::PhantomData,
+        }
+    }
+}
+
+impl<'a, T: FromSlice<'a>> LazyOffsetArray16<'a, T> {
+    /// Creates a new `LazyOffsetArray16`.
+    #[inline]
+    pub fn new(data: &'a [u8], offsets: LazyArray16<'a, Option<Offset16>>) -> Self {
+        LazyOffsetArray16 {
+            data,
+            offsets,
+            data_type: core::marker::PhantomData,
+        }
+    }
+
+    /// Parses an array of `u16` offsets at the current stream position.
+    ///
+    /// `data` is the data offsets are relative to.
+    #[inline]
+    pub(crate) fn read(data: &'a [u8], s: &mut Stream<'a>) -> Option<Self> {
+        let count: u16 = s.read()?;
+        let offsets = s.read_array16(count)?;
+        Some(Self::new(data, offsets))
+    }
+
+    /// Returns a value at `index`.
+    #[inline]
+    pub fn get(&self, index: u16) -> Option<T> {
+        let offset = self.offsets.get(index)??.to_usize();
+        self.data.get(offset..).and_then(T::parse)
+    }
+
+    /// Returns array's length.
+    #[inline]
+    pub fn len(&self) -> u16 {
+        self.offsets.len()
+    }
+
+    /// Checks if array is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.offsets.is_empty()
+    }
+}
+
+impl<'a, T: FromSlice<'a>> FromSlice<'a> for LazyOffsetArray16<'a, T> {
+    /// Parses a `u16` count followed by offsets relative to the `data` start.
+    #[inline]
+    fn parse(data: &'a [u8]) -> Option<Self> {
+        Self::read(data, &mut Stream::new(data))
+    }
+}
+
+impl<'a, T: FromSlice<'a> + core::fmt::Debug + Copy> core::fmt::Debug for LazyOffsetArray16<'a, T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_list().entries(*self).finish()
+    }
+}
+
+impl<'a, T: FromSlice<'a>> IntoIterator for LazyOffsetArray16<'a, T> {
+    type Item = T;
+    type IntoIter = LazyOffsetArrayIter16<'a, T>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        LazyOffsetArrayIter16 {
+            array: self,
+            index: 0,
+        }
+    }
+}
+
+
+/// An iterator over `LazyOffsetArray16`.
+///
+/// Null and malformed offsets are skipped.
+#[derive(Clone, Copy)]
+#[allow(missing_debug_implementations)]
+pub struct LazyOffsetArrayIter16<'a, T> {
+    array: LazyOffsetArray16<'a, T>,
+    index: u16,
+}
+
+impl<'a, T: FromSlice<'a>> Iterator for LazyOffsetArrayIter16<'a, T> {
+    type Item = T;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.index < self.array.len() {
+            self.index += 1;
+            if let Some(v) = self.array.get(self.index - 1) {
+                return Some(v);
+            }
+        }
+
+        None
+    }
+}
+
+
 /// A slice-like container that converts internal binary data only on access.
 ///
 /// This is a low-level, internal structure that should not be used directly.
@@ -394,6 +542,12 @@ impl<'a, T: FromData> LazyArray32<'a, T> {
         (self.data.len() / T::SIZE) as u32
     }
 
+    /// Checks if array is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
     /// Performs a binary search by specified `key`.
     #[inline]
     pub fn binary_search(&self, key: &T) -> Option<(u32, T)>
@@ -509,11 +663,6 @@ impl<'a> Stream<'a> {
         self.offset >= self.data.len()
     }
 
-    #[inline]
-    pub fn jump_to_end(&mut self) {
-        self.offset = self.data.len();
-    }
-
     #[inline]
     pub fn offset(&self) -> usize {
         self.offset
@@ -662,3 +811,22 @@ pub fn f32_bound(min: f32, val: f32, max: f32) -> f32 {
 
     val
 }
+
+/// Returns `searchRange`, `entrySelector` and `rangeShift` of an sfnt header.
+///
+/// Returns `None` when the values do not fit into the header,
+/// which happens when there are more than 4095 tables.
+#[cfg(any(feature = "woff", feature = "woff2"))]
+pub fn sfnt_search_params(number_of_tables: u16) -> Option<(u16, u16, u16)> {
+    use core::convert::TryFrom;
+
+    let number_of_tables = u32::from(number_of_tables);
+    let mut entry_selector = 0;
+    while (2u32 << entry_selector) <= number_of_tables {
+        entry_selector += 1;
+    }
+
+    let search_range = (1u32 << entry_selector) * 16;
+    let range_shift = (number_of_tables * 16).saturating_sub(search_range);
+    Some((u16::try_from(search_range).ok()?, entry_selector, u16::try_from(range_shift).ok()?))
+}
@@ -1,3 +1,11 @@
+/*!
+A [Compact Font Format Table](
+https://docs.microsoft.com/en-us/typography/opentype/spec/cff) implementation.
+
+Unlike other tables, `CFF` can be used without an OpenType wrapper,
+like in PDF files, so `Cff` can be parsed from a bare `CFF` data directly.
+*/
+
 // Useful links:
 // http://wwwimages.adobe.com/content/dam/Adobe/en/devnet/font/pdfs/5176.CFF.pdf
 // http://wwwimages.adobe.com/content/dam/Adobe/en/devnet/font/pdfs/5177.Type2.pdf
@@ -7,7 +15,7 @@ use core::convert::TryFrom;
 use core::ops::Range;
 
 use crate::{GlyphId, OutlineBuilder, Rect, BBox};
-use crate::parser::{Stream, U24, Fixed, FromData, NumFrom, TryNumFrom};
+use crate::parser::{Stream, U24, Fixed, FromData, LazyArray16, NumFrom, TryNumFrom};
 
 // Limits according to the Adobe Technical Note #5176, chapter 4 DICT Data.
 const MAX_OPERANDS_LEN: u8 = 48;
@@ -56,8 +64,20 @@ mod operator {
 /// Enumerates some operators defined in the Adobe Technical Note #5176,
 /// Table 9 Top DICT Operator Entries
 mod top_dict_operator {
+    pub const CHARSET_OFFSET: u16               = 15;
     pub const CHAR_STRINGS_OFFSET: u16          = 17;
     pub const PRIVATE_DICT_SIZE_AND_OFFSET: u16 = 18;
+    pub const FONT_MATRIX: u16                  = 1207;
+    pub const ROS: u16                          = 1230;
+    pub const FD_ARRAY: u16                     = 1236;
+    pub const FD_SELECT: u16                    = 1237;
+}
+
+/// Enumerates Charset IDs defined in the Adobe Technical Note #5176, Table 22
+mod charset_id {
+    pub const ISO_ADOBE: usize      = 0;
+    pub const EXPERT: usize         = 1;
+    pub const EXPERT_SUBSET: usize  = 2;
 }
 
 /// Enumerates some operators defined in the Adobe Technical Note #5176,
@@ -69,7 +89,7 @@ mod private_dict_operator {
 
 /// A list of errors that can occur during a CFF table parsing.
 #[derive(Clone, Copy, Debug)]
-pub enum CFFError {
+pub(crate) enum CFFError {
     ReadOutOfBounds,
     ZeroBBox,
     InvalidOperator,
@@ -85,17 +105,279 @@ pub enum CFFError {
     InvalidItemVariationDataIndex,
     InvalidNumberOfBlendOperands,
     BlendRegionsLimitReached,
+    NoLocalSubroutines,
+    InvalidSeacCode,
+}
+
+
+/// A 2D affine transformation matrix.
+///
+/// Maps `(x, y)` to `(a * x + c * y + e, b * x + d * y + f)`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[allow(missing_docs)]
+pub struct Matrix {
+    pub a: f32,
+    pub b: f32,
+    pub c: f32,
+    pub d: f32,
+    pub e: f32,
+    pub f: f32,
 }
 
+impl Matrix {
+    #[inline]
+    fn new_scale(sx: f32, sy: f32) -> Self {
+        Matrix { a: sx, b: 0.0, c: 0.0, d: sy, e: 0.0, f: 0.0 }
+    }
+
+    // Returns a matrix that applies `m2` first and `m1` after it.
+    #[inline]
+    fn combine(m1: Self, m2: Self) -> Self {
+        Matrix {
+            a: m1.a * m2.a + m1.c * m2.b,
+            b: m1.b * m2.a + m1.d * m2.b,
+            c: m1.a * m2.c + m1.c * m2.d,
+            d: m1.b * m2.c + m1.d * m2.d,
+            e: m1.a * m2.e + m1.c * m2.f + m1.e,
+            f: m1.b * m2.e + m1.d * m2.f + m1.f,
+        }
+    }
+
+    #[inline]
+    fn apply(&self, x: f32, y: f32) -> (f32, f32) {
+        (self.a * x + self.c * y + self.e, self.b * x + self.d * y + self.f)
+    }
+
+    #[inline]
+    fn is_identity(&self) -> bool {
+        // A direct float comparison is fine in our case.
+           self.a == 1.0
+        && self.b == 0.0
+        && self.c == 0.0
+        && self.d == 1.0
+        && self.e == 0.0
+        && self.f == 0.0
+    }
+}
+
+impl Default for Matrix {
+    /// Returns the default `FontMatrix` value, which is `[0.001 0 0 0.001 0 0]`.
+    #[inline]
+    fn default() -> Self {
+        Matrix::new_scale(0.001, 0.001)
+    }
+}
+
+
+// Applies a `FontMatrix` to all points before passing them to the actual builder.
+struct TransformBuilder<'a> {
+    builder: &'a mut dyn OutlineBuilder,
+    ts: Matrix,
+    // A bbox of transformed points.
+    bbox: BBox,
+}
+
+impl TransformBuilder<'_> {
+    #[inline]
+    fn apply(&mut self, x: f32, y: f32) -> (f32, f32) {
+        let (x, y) = self.ts.apply(x, y);
+        self.bbox.extend_by(x, y);
+        (x, y)
+    }
+}
+
+impl OutlineBuilder for TransformBuilder<'_> {
+    #[inline]
+    fn move_to(&mut self, x: f32, y: f32) {
+        let (x, y) = self.apply(x, y);
+        self.builder.move_to(x, y);
+    }
+
+    #[inline]
+    fn line_to(&mut self, x: f32, y: f32) {
+        let (x, y) = self.apply(x, y);
+        self.builder.line_to(x, y);
+    }
+
+    #[inline]
+    fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
+        let (x1, y1) = self.apply(x1, y1);
+        let (x, y) = self.apply(x, y);
+        self.builder.quad_to(x1, y1, x, y);
+    }
+
+    #[inline]
+    fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) {
+        let (x1, y1) = self.apply(x1, y1);
+        let (x2, y2) = self.apply(x2, y2);
+        let (x, y) = self.apply(x, y);
+        self.builder.curve_to(x1, y1, x2, y2, x, y);
+    }
+
+    #[inline]
+    fn close(&mut self) {
+        self.builder.close();
+    }
+}
 
+
+/// A [Compact Font Format Table](
+/// https://docs.microsoft.com/en-us/typography/opentype/spec/cff).
+///
+/// Can be parsed from a bare `CFF` data as well, like the one embedded into PDF files.
 #[derive(Clone, Copy, Default, Debug)]
-pub struct Metadata<'a> {
+pub struct Cff<'a> {
+    // The whole CFF table.
+    // Used to resolve a local subroutines offset for CID fonts.
+    table_data: &'a [u8],
+    names: DataIndex<'a>,
+    font_matrix: Option<Matrix>,
+    // The first glyph's `FontMatrix` scale. Outline transforms are normalized by it.
+    font_matrix_scale: f32,
+    // Will be set to `head.unitsPerEm` when used as an OpenType table.
+    pub(crate) units_per_em: u16,
     global_subrs: DataIndex<'a>,
-    local_subrs: DataIndex<'a>,
     char_strings: DataIndex<'a>,
+    strings: DataIndex<'a>,
+    charset: Option<Charset<'a>>,
+    kind: FontKind<'a>,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum FontKind<'a> {
+    Sid(SIDMetadata<'a>),
+    Cid(CIDMetadata<'a>),
+}
+
+impl Default for FontKind<'_> {
+    #[inline]
+    fn default() -> Self {
+        FontKind::Sid(SIDMetadata::default())
+    }
+}
+
+#[derive(Clone, Copy, Default, Debug)]
+struct SIDMetadata<'a> {
+    local_subrs: DataIndex<'a>,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct CIDMetadata<'a> {
+    fd_array: DataIndex<'a>,
+    fd_select: FDSelect<'a>,
+}
+
+impl<'a> Cff<'a> {
+    /// Parses a `CFF` table from raw data.
+    ///
+    /// Only the first font in a `FontSet` is used.
+    ///
+    /// Returns `None` when data is malformed or when this is a `CFF2` table.
+    #[inline]
+    pub fn parse(data: &'a [u8]) -> Option<Self> {
+        parse_metadata(data)
+    }
+
+    /// Returns a total number of glyphs in the font.
+    #[inline]
+    pub fn number_of_glyphs(&self) -> u16 {
+        self.char_strings.len()
+    }
+
+    /// Returns font's PostScript name from the Name INDEX.
+    ///
+    /// Returns `None` when the name is not set or not a valid UTF-8 string.
+    #[inline]
+    pub fn name(&self) -> Option<&'a str> {
+        core::str::from_utf8(self.names.get(0)?).ok()
+    }
+
+    /// Returns font's `FontMatrix` from the Top DICT.
+    ///
+    /// Falls back to the default `[0.001 0 0 0.001 0 0]` matrix when not set.
+    ///
+    /// CID-keyed fonts can also have a `FontMatrix` for each Font DICT,
+    /// which is applied before this one.
+    #[inline]
+    pub fn font_matrix(&self) -> Matrix {
+        self.font_matrix.unwrap_or_default()
+    }
+
+    /// Returns font's units per em.
+    ///
+    /// Resolved from the `FontMatrix` of the first glyph.
+    /// Falls back to 1000 when the matrix has an unexpected scale.
+    ///
+    /// When used as an OpenType table, it's `head.unitsPerEm` instead.
+    #[inline]
+    pub fn units_per_em(&self) -> u16 {
+        self.units_per_em
+    }
+
+    /// Outlines a glyph and returns its tight bounding box.
+    ///
+    /// Coordinates are in font units. `FontMatrix` is normalized by its own scale,
+    /// so only non-default matrices, like skewed ones or a Font DICT matrix
+    /// that differs from the Top DICT one, transform outlines.
+    #[inline]
+    pub fn outline(&self, glyph_id: GlyphId, builder: &mut dyn OutlineBuilder) -> Option<Rect> {
+        let data = self.char_strings.get(glyph_id.0)?;
+        parse_char_string(data, self, glyph_id, builder).ok()
+    }
+
+    /// Returns glyph's CID.
+    ///
+    /// Returns `None` for non-CID-keyed fonts.
+    pub fn glyph_cid(&self, glyph_id: GlyphId) -> Option<u16> {
+        match self.kind {
+            // In CID-keyed fonts, charset maps glyphs to CIDs and not to String IDs.
+            FontKind::Cid(_) => self.charset?.gid_to_sid(glyph_id).map(|id| id.0),
+            FontKind::Sid(_) => None,
+        }
+    }
+
+    /// Returns glyph's name using the charset.
+    ///
+    /// Returns `None` for CID-keyed fonts.
+    pub fn glyph_name(&self, glyph_id: GlyphId) -> Option<&'a str> {
+        // CID-keyed fonts do not have glyph names.
+        if let FontKind::Cid(_) = self.kind {
+            return None;
+        }
+
+        let sid = self.charset?.gid_to_sid(glyph_id)?;
+        let sid = usize::from(sid.0);
+        match STANDARD_NAMES.get(sid) {
+            Some(name) => Some(name),
+            None => {
+                let index = u16::try_from(sid - STANDARD_NAMES.len()).ok()?;
+                let name = self.strings.get(index)?;
+                core::str::from_utf8(name).ok()
+            }
+        }
+    }
+
+    /// Resolves a Glyph ID for a glyph name using the charset.
+    ///
+    /// Returns `None` for CID-keyed fonts.
+    pub fn glyph_index_by_name(&self, name: &str) -> Option<GlyphId> {
+        if let FontKind::Cid(_) = self.kind {
+            return None;
+        }
+
+        let sid = if let Some(index) = STANDARD_NAMES.iter().position(|n| *n == name) {
+            index
+        } else {
+            let index = self.strings.into_iter().position(|n| n == name.as_bytes())?;
+            STANDARD_NAMES.len() + index
+        };
+
+        let sid = StringId(u16::try_from(sid).ok()?);
+        self.charset?.sid_to_gid(sid, self.char_strings.len())
+    }
 }
 
-pub(crate) fn parse_metadata(data: &[u8]) -> Option<Metadata> {
+pub(crate) fn parse_metadata(data: &[u8]) -> Option<Cff> {
     let mut s = Stream::new(data);
 
     // Parse Header.
@@ -113,64 +395,194 @@ pub(crate) fn parse_metadata(data: &[u8]) -> Option<Metadata> {
         s.advance(usize::from(header_size) - 4);
     }
 
-    // Skip Name INDEX.
-    skip_index(&mut s)?;
+    // Parse Name INDEX.
+    let names = parse_index(&mut s)?;
 
-    let (char_strings_offset, private_dict_range) = parse_top_dict(&mut s)?;
+    let top_dict = parse_top_dict(&mut s)?;
 
     // Must be set, otherwise there are nothing to parse.
-    if char_strings_offset == 0 {
+    if top_dict.char_strings_offset == 0 {
         return None;
     }
 
-    let subroutines_offset = if let Some(range) = private_dict_range.clone() {
-        parse_private_dict(data.get(range)?)
-    } else {
-        None
-    };
+    let mut metadata = Cff::default();
+    metadata.table_data = data;
+    metadata.names = names;
+    metadata.font_matrix = top_dict.font_matrix;
 
-    // Skip String INDEX.
-    skip_index(&mut s)?;
+    // Parse String INDEX.
+    metadata.strings = parse_index(&mut s)?;
 
     // Parse Global Subroutines INDEX.
-    let mut metadata = Metadata::default();
     metadata.global_subrs = parse_index(&mut s)?;
 
-    match (private_dict_range, subroutines_offset) {
-        (Some(private_dict_range), Some(subroutines_offset)) => {
-            // 'The local subroutines offset is relative to the beginning
-            // of the Private DICT data.'
-            if let Some(start) = private_dict_range.start.checked_add(subroutines_offset) {
-                let data = data.get(start..data.len())?;
-                let mut s = Stream::new(data);
-                metadata.local_subrs = parse_index(&mut s)?;
-            }
-        }
-        _ => {}
-    }
-
     // TODO: check that index is not default
     metadata.char_strings = {
-        let mut s = Stream::new_at(data, char_strings_offset)?;
+        let mut s = Stream::new_at(data, top_dict.char_strings_offset)?;
         parse_index(&mut s)?
     };
 
+    metadata.kind = if top_dict.has_ros {
+        FontKind::Cid(parse_cid_metadata(data, &top_dict, metadata.char_strings.len())?)
+    } else {
+        FontKind::Sid(parse_sid_metadata(data, &top_dict)?)
+    };
+
+    metadata.font_matrix_scale = resolve_font_matrix_scale(&metadata);
+    metadata.units_per_em = (1.0 / metadata.font_matrix_scale + 0.5) as u16;
+
+    // Charset is optional for glyph outlines, so it's fine if it's malformed.
+    let number_of_glyphs = metadata.char_strings.len();
+    metadata.charset = match top_dict.charset_offset {
+        Some(charset_id::ISO_ADOBE) | None => Some(Charset::ISOAdobe),
+        Some(charset_id::EXPERT) => Some(Charset::Expert),
+        Some(charset_id::EXPERT_SUBSET) => Some(Charset::ExpertSubset),
+        Some(offset) => data.get(offset..).and_then(|data| parse_charset(number_of_glyphs, data)),
+    };
+
     Some(metadata)
 }
 
 
-pub fn outline(
-    metadata: &Metadata,
+fn parse_sid_metadata<'a>(data: &'a [u8], top_dict: &TopDict) -> Option<SIDMetadata<'a>> {
+    let mut metadata = SIDMetadata::default();
+
+    let private_dict_range = match top_dict.private_dict_range.clone() {
+        Some(range) => range,
+        None => return Some(metadata),
+    };
+
+    if let Some(local_subrs) = parse_local_subrs(data, private_dict_range) {
+        metadata.local_subrs = local_subrs?;
+    }
+
+    Some(metadata)
+}
+
+fn parse_cid_metadata<'a>(
+    data: &'a [u8],
+    top_dict: &TopDict,
+    number_of_glyphs: u16,
+) -> Option<CIDMetadata<'a>> {
+    let (fd_array_offset, fd_select_offset) =
+        match (top_dict.fd_array_offset, top_dict.fd_select_offset) {
+            (Some(a), Some(b)) => (a, b),
+            _ => return None,
+        };
+
+    let fd_array = {
+        let mut s = Stream::new_at(data, fd_array_offset)?;
+        parse_index(&mut s)?
+    };
+
+    let fd_select = {
+        let s = Stream::new_at(data, fd_select_offset)?;
+        parse_fd_select(number_of_glyphs, s)?
+    };
+
+    Some(CIDMetadata {
+        fd_array,
+        fd_select,
+    })
+}
+
+// Returns `None` when Private DICT doesn't have local subroutines
+// and `Some(None)` when they are malformed.
+fn parse_local_subrs(data: &[u8], private_dict_range: Range<usize>) -> Option<Option<DataIndex<'_>>> {
+    let subroutines_offset = parse_private_dict(data.get(private_dict_range.clone())?)?;
+
+    // 'The local subroutines offset is relative to the beginning
+    // of the Private DICT data.'
+    let start = private_dict_range.start.checked_add(subroutines_offset)?;
+    let data = match data.get(start..data.len()) {
+        Some(data) => data,
+        None => return Some(None),
+    };
+
+    let mut s = Stream::new(data);
+    Some(parse_index(&mut s))
+}
+
+// CID-keyed fonts store local subroutines in a Private DICT of each Font DICT.
+// Since we do not allocate, they are resolved for each glyph.
+//
+// Returns glyph's local subroutines and Font DICT's `FontMatrix`.
+fn parse_cid_font_dict<'a>(
+    data: &'a [u8],
     glyph_id: GlyphId,
-    builder: &mut dyn OutlineBuilder,
-) -> Option<Rect> {
-    let data = metadata.char_strings.get(glyph_id.0)?;
-    parse_char_string(data, metadata, builder).ok()
+    cid: &CIDMetadata,
+) -> Option<(DataIndex<'a>, Option<Matrix>)> {
+    let font_dict_index = cid.fd_select.font_dict_index(glyph_id)?;
+    let font_dict_data = cid.fd_array.get(u16::from(font_dict_index))?;
+    let font_dict = parse_font_dict(font_dict_data)?;
+
+    let local_subrs = match font_dict.private_dict_range {
+        Some(range) => match parse_local_subrs(data, range) {
+            Some(local_subrs) => local_subrs?,
+            None => DataIndex::default(),
+        },
+        None => DataIndex::default(),
+    };
+
+    Some((local_subrs, font_dict.font_matrix))
+}
+
+// Combines the Top DICT `FontMatrix` with the Font DICT one.
+//
+// Font DICT without a `FontMatrix` inherits the Top DICT one,
+// and the Top DICT matrix is applied only when explicitly set, like in FreeType.
+//
+// Returns `None` when neither of them is set.
+fn resolve_font_matrix(
+    top_matrix: Option<Matrix>,
+    font_dict_matrix: Option<Matrix>,
+) -> Option<Matrix> {
+    match (top_matrix, font_dict_matrix) {
+        (Some(top), Some(font_dict)) => Some(Matrix::combine(top, font_dict)),
+        (Some(m), None) | (None, Some(m)) => Some(m),
+        (None, None) => None,
+    }
+}
+
+// Returns the scale of the first glyph's `FontMatrix`, which defines font's units per em.
+//
+// Like in FreeType, the vertical scale is used, or the horizontal skew when it's zero.
+fn resolve_font_matrix_scale(metadata: &Cff) -> f32 {
+    const DEFAULT: f32 = 0.001;
+
+    let font_dict_matrix = match metadata.kind {
+        FontKind::Sid(_) => None,
+        FontKind::Cid(ref cid) => {
+            parse_cid_font_dict(metadata.table_data, GlyphId(0), cid).and_then(|v| v.1)
+        }
+    };
+
+    let matrix = resolve_font_matrix(metadata.font_matrix, font_dict_matrix).unwrap_or_default();
+    let scale = if matrix.d != 0.0 { matrix.d.abs() } else { matrix.b.abs() };
+
+    // Same range as in `head.unitsPerEm`.
+    // Will also filter out zero and NaN scales.
+    let units_per_em = 1.0 / scale + 0.5;
+    if (16.0..16385.0).contains(&units_per_em) {
+        scale
+    } else {
+        DEFAULT
+    }
 }
 
-fn parse_top_dict(s: &mut Stream) -> Option<(usize, Option<Range<usize>>)> {
-    let mut char_strings_offset = 0;
-    let mut private_dict_range = None;
+#[derive(Default)]
+struct TopDict {
+    char_strings_offset: usize,
+    private_dict_range: Option<Range<usize>>,
+    charset_offset: Option<usize>,
+    has_ros: bool,
+    fd_array_offset: Option<usize>,
+    fd_select_offset: Option<usize>,
+    font_matrix: Option<Matrix>,
+}
+
+fn parse_top_dict(s: &mut Stream) -> Option<TopDict> {
+    let mut top_dict = TopDict::default();
 
     let index = parse_index(s)?;
 
@@ -185,7 +597,7 @@ fn parse_top_dict(s: &mut Stream) -> Option<(usize, Option<Range<usize>>)> {
                 let operands = dict_parser.operands();
 
                 if operands.len() == 1 {
-                    char_strings_offset = usize::try_from(operands[0]).ok()?;
+                    top_dict.char_strings_offset = usize::try_from(operands[0]).ok()?;
                 }
             }
             top_dict_operator::PRIVATE_DICT_SIZE_AND_OFFSET => {
@@ -196,18 +608,80 @@ fn parse_top_dict(s: &mut Stream) -> Option<(usize, Option<Range<usize>>)> {
                     let len = usize::try_from(operands[0]).ok()?;
                     let start = usize::try_from(operands[1]).ok()?;
                     let end = start.checked_add(len)?;
-                    private_dict_range = Some(start..end);
+                    top_dict.private_dict_range = Some(start..end);
+                }
+            }
+            top_dict_operator::CHARSET_OFFSET => {
+                dict_parser.parse_operands()?;
+                let operands = dict_parser.operands();
+
+                if operands.len() == 1 {
+                    top_dict.charset_offset = usize::try_from(operands[0]).ok();
+                }
+            }
+            top_dict_operator::FONT_MATRIX => {
+                top_dict.font_matrix = dict_parser.parse_font_matrix();
+            }
+            top_dict_operator::ROS => {
+                // Registry and Ordering are not used,
+                // we only need to know that this is a CID-keyed font.
+                top_dict.has_ros = true;
+            }
+            top_dict_operator::FD_ARRAY => {
+                dict_parser.parse_operands()?;
+                let operands = dict_parser.operands();
+
+                if operands.len() == 1 {
+                    top_dict.fd_array_offset = usize::try_from(operands[0]).ok();
+                }
+            }
+            top_dict_operator::FD_SELECT => {
+                dict_parser.parse_operands()?;
+                let operands = dict_parser.operands();
+
+                if operands.len() == 1 {
+                    top_dict.fd_select_offset = usize::try_from(operands[0]).ok();
                 }
             }
             _ => {}
         }
+    }
 
-        if char_strings_offset != 0 && private_dict_range.is_some() {
-            break;
+    Some(top_dict)
+}
+
+#[derive(Default)]
+struct FontDict {
+    private_dict_range: Option<Range<usize>>,
+    font_matrix: Option<Matrix>,
+}
+
+// Adobe Technical Note #5176, Table 10 CIDFont Operator Extensions
+fn parse_font_dict(data: &[u8]) -> Option<FontDict> {
+    let mut font_dict = FontDict::default();
+
+    let mut dict_parser = DictionaryParser::new(data);
+    while let Some(operator) = dict_parser.parse_next() {
+        match operator.get() {
+            top_dict_operator::PRIVATE_DICT_SIZE_AND_OFFSET => {
+                dict_parser.parse_operands()?;
+                let operands = dict_parser.operands();
+
+                if operands.len() == 2 {
+                    let len = usize::try_from(operands[0]).ok()?;
+                    let start = usize::try_from(operands[1]).ok()?;
+                    let end = start.checked_add(len)?;
+                    font_dict.private_dict_range = Some(start..end);
+                }
+            }
+            top_dict_operator::FONT_MATRIX => {
+                font_dict.font_matrix = dict_parser.parse_font_matrix();
+            }
+            _ => {}
         }
     }
 
-    Some((char_strings_offset, private_dict_range))
+    Some(font_dict)
 }
 
 fn parse_private_dict(data: &[u8]) -> Option<usize> {
@@ -230,7 +704,8 @@ fn parse_private_dict(data: &[u8]) -> Option<usize> {
 }
 
 struct CharStringParserContext<'a> {
-    metadata: &'a Metadata<'a>,
+    metadata: &'a Cff<'a>,
+    local_subrs: DataIndex<'a>,
     is_first_move_to: bool,
     has_move_to: bool,
     width_parsed: bool,
@@ -240,11 +715,42 @@ struct CharStringParserContext<'a> {
 
 fn parse_char_string(
     data: &[u8],
-    metadata: &Metadata,
+    metadata: &Cff,
+    glyph_id: GlyphId,
     builder: &mut dyn OutlineBuilder,
 ) -> Result<Rect, CFFError> {
+    let (local_subrs, font_dict_matrix) = match metadata.kind {
+        FontKind::Sid(ref sid) => (sid.local_subrs, None),
+        FontKind::Cid(ref cid) => {
+            parse_cid_font_dict(metadata.table_data, glyph_id, cid)
+                .ok_or(CFFError::NoLocalSubroutines)?
+        }
+    };
+
+    // Normalize `FontMatrix` by the font's own scale, so the default one becomes an identity.
+    // This way `head.unitsPerEm` doesn't affect outlines, just like in FreeType.
+    let matrix = resolve_font_matrix(metadata.font_matrix, font_dict_matrix).unwrap_or_default();
+    let scale = metadata.font_matrix_scale;
+    let ts = Matrix {
+        a: matrix.a / scale,
+        b: matrix.b / scale,
+        c: matrix.c / scale,
+        d: matrix.d / scale,
+        e: matrix.e / scale,
+        f: matrix.f / scale,
+    };
+
+    // Most fonts use the default matrix, so there is nothing to transform.
+    let mut ts_builder = None;
+    let builder: &mut dyn OutlineBuilder = if ts.is_identity() {
+        builder
+    } else {
+        ts_builder.get_or_insert(TransformBuilder { builder, ts, bbox: BBox::new() })
+    };
+
     let mut ctx = CharStringParserContext {
         metadata,
+        local_subrs,
         is_first_move_to: true,
         has_move_to: false,
         width_parsed: false,
@@ -268,7 +774,12 @@ fn parse_char_string(
         return Err(CFFError::MissingEndChar);
     }
 
-    let bbox = inner_builder.bbox;
+    let mut bbox = inner_builder.bbox;
+
+    // The bbox must be calculated using transformed points.
+    if let Some(ts_builder) = ts_builder {
+        bbox = ts_builder.bbox;
+    }
 
     // Check that bbox was changed.
     if bbox.is_default() {
@@ -488,9 +999,9 @@ fn _parse_char_string(
                     return Err(CFFError::NestingLimitReached);
                 }
 
-                let subroutine_bias = calc_subroutine_bias(ctx.metadata.local_subrs.len());
+                let subroutine_bias = calc_subroutine_bias(ctx.local_subrs.len());
                 let index = conv_subroutine_index(stack.pop(), subroutine_bias)?;
-                let char_string = ctx.metadata.local_subrs.get(index)
+                let char_string = ctx.local_subrs.get(index)
                     .ok_or(CFFError::InvalidSubroutineIndex)?;
                 let pos = _parse_char_string(ctx, char_string, x, y, stack, depth + 1, builder)?;
                 x = pos.0;
@@ -632,7 +1143,36 @@ fn _parse_char_string(
                 }
             }
             operator::ENDCHAR => {
-                if !stack.is_empty() && !ctx.width_parsed {
+                if stack.len() == 4 || (stack.len() == 5 && !ctx.width_parsed) {
+                    // adx ady bchar achar endchar
+                    //
+                    // A deprecated 'seac' form, which composes a glyph
+                    // from a base and an accent glyphs.
+
+                    let accent_char = seac_code_to_glyph_id(ctx.metadata, stack.pop())
+                        .ok_or(CFFError::InvalidSeacCode)?;
+                    let base_char = seac_code_to_glyph_id(ctx.metadata, stack.pop())
+                        .ok_or(CFFError::InvalidSeacCode)?;
+                    let dy = stack.pop();
+                    let dx = stack.pop();
+
+                    if !stack.is_empty() {
+                        stack.clear();
+                        ctx.width_parsed = true;
+                    }
+
+                    if depth == STACK_LIMIT {
+                        return Err(CFFError::NestingLimitReached);
+                    }
+
+                    let base_char_string = ctx.metadata.char_strings.get(base_char.0)
+                        .ok_or(CFFError::InvalidSeacCode)?;
+                    parse_seac_component(ctx, base_char_string, 0.0, 0.0, stack, depth + 1, builder)?;
+
+                    let accent_char_string = ctx.metadata.char_strings.get(accent_char.0)
+                        .ok_or(CFFError::InvalidSeacCode)?;
+                    parse_seac_component(ctx, accent_char_string, dx, dy, stack, depth + 1, builder)?;
+                } else if !stack.is_empty() && !ctx.width_parsed {
                     stack.clear();
                     ctx.width_parsed = true;
                 }
@@ -994,8 +1534,59 @@ fn _parse_char_string(
     Ok((x, y))
 }
 
+// Parses a base or an accent glyph of a 'seac' composite glyph.
+//
+// Each component is a standalone glyph, so it has its own width and hints.
+fn parse_seac_component(
+    ctx: &mut CharStringParserContext,
+    char_string: &[u8],
+    x: f32,
+    y: f32,
+    stack: &mut ArgumentsStack,
+    depth: u8,
+    builder: &mut Builder,
+) -> Result<(), CFFError> {
+    if !ctx.is_first_move_to {
+        ctx.is_first_move_to = true;
+        builder.close();
+    }
+
+    ctx.width_parsed = false;
+    ctx.stems_len = 0;
+    ctx.has_endchar = false;
+
+    stack.clear();
+    _parse_char_string(ctx, char_string, x, y, stack, depth, builder)?;
+
+    if !ctx.has_endchar {
+        return Err(CFFError::MissingEndChar);
+    }
+
+    // Width is defined by the composite glyph itself.
+    ctx.width_parsed = true;
+
+    Ok(())
+}
+
+// 'seac' components are referenced by Standard Encoding codes,
+// which are mapped to glyphs using String IDs and charset.
+fn seac_code_to_glyph_id(metadata: &Cff, n: f32) -> Option<GlyphId> {
+    // CID-keyed fonts do not support 'seac', since they do not have String IDs.
+    if let FontKind::Cid(_) = metadata.kind {
+        return None;
+    }
+
+    let code = u8::try_num_from(n)?;
+    let sid = STANDARD_ENCODING[usize::from(code)];
+    if sid == 0 {
+        return None;
+    }
+
+    metadata.charset?.sid_to_gid(StringId(u16::from(sid)), metadata.char_strings.len())
+}
+
 #[inline]
-pub fn conv_subroutine_index(index: f32, bias: u16) -> Result<u16, CFFError> {
+pub(crate) fn conv_subroutine_index(index: f32, bias: u16) -> Result<u16, CFFError> {
     let mut index = i32::try_num_from(index).ok_or(CFFError::InvalidSubroutineIndex)?;
     index += i32::from(bias);
     u16::try_from(index).map_err(|_| CFFError::InvalidSubroutineIndex)
@@ -1003,7 +1594,7 @@ pub fn conv_subroutine_index(index: f32, bias: u16) -> Result<u16, CFFError> {
 
 // Adobe Technical Note #5176, Chapter 16 "Local / Global Subrs INDEXes"
 #[inline]
-pub fn calc_subroutine_bias(len: u16) -> u16 {
+pub(crate) fn calc_subroutine_bias(len: u16) -> u16 {
     if len < 1240 {
         107
     } else if len < 33900 {
@@ -1023,7 +1614,7 @@ fn parse_index<'a>(s: &mut Stream<'a>) -> Option<DataIndex<'a>> {
 }
 
 #[inline]
-pub fn parse_index_impl<'a>(count: u32, s: &mut Stream<'a>) -> Option<DataIndex<'a>> {
+pub(crate) fn parse_index_impl<'a>(count: u32, s: &mut Stream<'a>) -> Option<DataIndex<'a>> {
     let offset_size: OffsetSize = try_parse_offset_size(s)?;
     let offsets_len = (count + 1).checked_mul(offset_size.to_u32())?;
     let offsets = VarOffsets {
@@ -1043,27 +1634,9 @@ pub fn parse_index_impl<'a>(count: u32, s: &mut Stream<'a>) -> Option<DataIndex<
     }
 }
 
-fn skip_index(s: &mut Stream) -> Option<()> {
-    let count: u16 = s.read()?;
-    if count != 0 && count != core::u16::MAX {
-        let offset_size: OffsetSize = try_parse_offset_size(s)?;
-        let offsets_len = (u32::from(count) + 1).checked_mul(offset_size.to_u32())?;
-        let offsets = VarOffsets {
-            data: &s.read_bytes(usize::num_from(offsets_len))?,
-            offset_size,
-        };
-
-        if let Some(last_offset) = offsets.last() {
-            s.advance(usize::num_from(last_offset));
-        }
-    }
-
-    Some(())
-}
-
 
 #[derive(Clone, Copy, Debug)]
-pub struct VarOffsets<'a> {
+pub(crate) struct VarOffsets<'a> {
     pub data: &'a [u8],
     pub offset_size: OffsetSize,
 }
@@ -1117,7 +1690,7 @@ impl<'a> VarOffsets<'a> {
 
 
 #[derive(Clone, Copy, Debug)]
-pub struct DataIndex<'a> {
+pub(crate) struct DataIndex<'a> {
     pub data: &'a [u8],
     pub offsets: VarOffsets<'a>,
 }
@@ -1172,7 +1745,7 @@ impl<'a> DataIndex<'a> {
     }
 }
 
-pub struct DataIndexIter<'a> {
+pub(crate) struct DataIndexIter<'a> {
     data: DataIndex<'a>,
     offset: u16,
 }
@@ -1194,7 +1767,7 @@ impl<'a> Iterator for DataIndexIter<'a> {
 
 
 #[derive(Clone, Copy, Debug)]
-pub enum OffsetSize {
+pub(crate) enum OffsetSize {
     Size1 = 1,
     Size2 = 2,
     Size3 = 3,
@@ -1219,7 +1792,7 @@ fn try_parse_offset_size(s: &mut Stream) -> Option<OffsetSize> {
 
 
 #[derive(Clone, Copy, Debug)]
-pub struct Operator(pub u16);
+pub(crate) struct Operator(pub u16);
 
 impl Operator {
     #[inline]
@@ -1314,11 +1887,41 @@ impl<'a> DictionaryParser<'a> {
     fn operands(&self) -> &[i32] {
         &self.operands[..usize::from(self.operands_len)]
     }
+
+    /// Parses operands of the current operator as a `FontMatrix`.
+    ///
+    /// Unlike `parse_operands`, preserves real numbers.
+    fn parse_font_matrix(&mut self) -> Option<Matrix> {
+        let mut s = Stream::new_at(self.data, self.operands_offset)?;
+        let mut values = [0.0; 6];
+        for value in &mut values {
+            let b: u8 = s.read()?;
+            if is_dict_one_byte_op(b) {
+                return None;
+            }
+
+            *value = if b == 30 {
+                parse_float(&mut s)?
+            } else {
+                parse_number(b, &mut s)? as f32
+            };
+        }
+
+        // Make sure that there are exactly 6 operands.
+        if !is_dict_one_byte_op(s.read()?) {
+            return None;
+        }
+
+        Some(Matrix {
+            a: values[0], b: values[1], c: values[2],
+            d: values[3], e: values[4], f: values[5],
+        })
+    }
 }
 
 // One-byte CFF DICT Operators according to the
 // Adobe Technical Note #5176, Appendix H CFF DICT Encoding.
-pub fn is_dict_one_byte_op(b: u8) -> bool {
+pub(crate) fn is_dict_one_byte_op(b: u8) -> bool {
     match b {
         0..=27 => true,
         28..=30 => false, // numbers
@@ -1329,7 +1932,7 @@ pub fn is_dict_one_byte_op(b: u8) -> bool {
 }
 
 // Adobe Technical Note #5177, Table 3 Operand Encoding
-pub fn parse_number(b0: u8, s: &mut Stream) -> Option<i32> {
+pub(crate) fn parse_number(b0: u8, s: &mut Stream) -> Option<i32> {
     match b0 {
         28 => {
             let n = i32::from(s.read::<i16>()?);
@@ -1370,8 +1973,83 @@ pub fn parse_number(b0: u8, s: &mut Stream) -> Option<i32> {
     }
 }
 
+// Adobe Technical Note #5176, Table 5 Nibble Definitions
+//
+// Parses a real number without the core::num::dec2flt dependency.
+fn parse_float(s: &mut Stream) -> Option<f32> {
+    // Mantissa digits after the 9th one are ignored, since they do not fit into f32 anyway.
+    const MAX_DIGITS: u8 = 9;
+
+    let mut mantissa: i64 = 0;
+    let mut digits: u8 = 0;
+    let mut scale: i32 = 0;
+    let mut exponent: i32 = 0;
+    let mut is_negative = false;
+    let mut is_exponent_negative = false;
+    let mut has_point = false;
+    let mut has_exponent = false;
+
+    'outer: loop {
+        let b: u8 = s.read()?;
+        for &nibble in &[b >> 4, b & 15] {
+            match nibble {
+                0..=9 if has_exponent => {
+                    exponent = exponent.saturating_mul(10).saturating_add(i32::from(nibble));
+                }
+                0..=9 => {
+                    if digits < MAX_DIGITS {
+                        mantissa = mantissa * 10 + i64::from(nibble);
+                        if mantissa != 0 {
+                            digits += 1;
+                        }
+
+                        if has_point {
+                            scale -= 1;
+                        }
+                    } else if !has_point {
+                        scale += 1;
+                    }
+                }
+                0xa => has_point = true,
+                0xb => has_exponent = true,
+                0xc => {
+                    has_exponent = true;
+                    is_exponent_negative = true;
+                }
+                0xd => return None, // Reserved.
+                0xe => is_negative = true,
+                END_OF_FLOAT_FLAG => break 'outer,
+                _ => unreachable!(),
+            }
+        }
+    }
+
+    if is_exponent_negative {
+        exponent = -exponent;
+    }
+
+    // Exponents outside the -64..64 range produce either zero or infinity in f32 anyway.
+    let exponent = scale.saturating_add(exponent);
+    let mut n = mantissa as f64;
+    if exponent < 0 {
+        for _ in exponent.max(-64)..0 {
+            n /= 10.0;
+        }
+    } else {
+        for _ in 0..exponent.min(64) {
+            n *= 10.0;
+        }
+    }
+
+    if is_negative {
+        n = -n;
+    }
+
+    Some(n as f32)
+}
+
 // Just like `parse_number`, but doesn't actually parses the data.
-pub fn skip_number(b0: u8, s: &mut Stream) -> Option<()> {
+pub(crate) fn skip_number(b0: u8, s: &mut Stream) -> Option<()> {
     match b0 {
         28 => s.skip::<u16>(),
         29 => s.skip::<u32>(),
@@ -1395,7 +2073,7 @@ pub fn skip_number(b0: u8, s: &mut Stream) -> Option<()> {
 }
 
 
-pub struct ArgumentsStack<'a> {
+pub(crate) struct ArgumentsStack<'a> {
     pub data: &'a mut [f32],
     pub len: usize,
     pub max_len: usize,
@@ -1452,37 +2130,746 @@ impl<'a> ArgumentsStack<'a> {
     }
 }
 
-impl core::fmt::Debug for ArgumentsStack<'_> {
-    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
-        f.debug_list().entries(&self.data[..self.len]).finish()
-    }
+impl core::fmt::Debug for ArgumentsStack<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_list().entries(&self.data[..self.len]).finish()
+    }
+}
+
+
+pub(crate) trait IsEven {
+    fn is_even(&self) -> bool;
+    fn is_odd(&self) -> bool;
+}
+
+impl IsEven for usize {
+    #[inline]
+    fn is_even(&self) -> bool { (*self) & 1 == 0 }
+
+    #[inline]
+    fn is_odd(&self) -> bool { !self.is_even() }
+}
+
+#[cfg(feature = "std")]
+#[inline]
+pub(crate) fn f32_abs(n: f32) -> f32 {
+    n.abs()
+}
+
+#[cfg(not(feature = "std"))]
+#[inline]
+pub(crate) fn f32_abs(n: f32) -> f32 {
+    if n.is_sign_negative() { -n } else { n }
+}
+
+
+/// A [String ID](https://wwwimages.adobe.com/content/dam/Adobe/en/devnet/font/pdfs/5176.CFF.pdf).
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct StringId(u16);
+
+impl FromData for StringId {
+    const SIZE: usize = 2;
+
+    #[inline]
+    fn parse(data: &[u8]) -> Option<Self> {
+        u16::parse(data).map(StringId)
+    }
+}
+
+
+#[derive(Clone, Copy, Debug)]
+struct Format1Range {
+    first: StringId,
+    left: u8,
+}
+
+impl FromData for Format1Range {
+    const SIZE: usize = 3;
+
+    #[inline]
+    fn parse(data: &[u8]) -> Option<Self> {
+        let mut s = Stream::new(data);
+        Some(Format1Range {
+            first: s.read()?,
+            left: s.read()?,
+        })
+    }
+}
+
+
+#[derive(Clone, Copy, Debug)]
+struct Format2Range {
+    first: StringId,
+    left: u16,
+}
+
+impl FromData for Format2Range {
+    const SIZE: usize = 4;
+
+    #[inline]
+    fn parse(data: &[u8]) -> Option<Self> {
+        let mut s = Stream::new(data);
+        Some(Format2Range {
+            first: s.read()?,
+            left: s.read()?,
+        })
+    }
+}
+
+
+// Adobe Technical Note #5176, Chapter 13 "Charsets"
+#[derive(Clone, Copy, Debug)]
+enum Charset<'a> {
+    ISOAdobe,
+    Expert,
+    ExpertSubset,
+    Format0(LazyArray16<'a, StringId>),
+    Format1(LazyArray16<'a, Format1Range>),
+    Format2(LazyArray16<'a, Format2Range>),
+}
+
+impl Charset<'_> {
+    fn gid_to_sid(&self, glyph_id: GlyphId) -> Option<StringId> {
+        // `.notdef` is always the first glyph and is not stored in the charset.
+        if glyph_id.0 == 0 {
+            return Some(StringId(0));
+        }
+
+        match self {
+            Charset::ISOAdobe => {
+                if glyph_id.0 <= 228 { Some(StringId(glyph_id.0)) } else { None }
+            }
+            Charset::Expert => {
+                EXPERT_CHARSET.get(usize::from(glyph_id.0)).cloned().map(StringId)
+            }
+            Charset::ExpertSubset => {
+                EXPERT_SUBSET_CHARSET.get(usize::from(glyph_id.0)).cloned().map(StringId)
+            }
+            Charset::Format0(ref array) => {
+                array.get(glyph_id.0 - 1)
+            }
+            Charset::Format1(array) => {
+                let mut glyph_id = glyph_id.0 - 1;
+                for range in *array {
+                    let left = u16::from(range.left);
+                    if glyph_id <= left {
+                        return range.first.0.checked_add(glyph_id).map(StringId);
+                    }
+
+                    glyph_id -= left + 1;
+                }
+
+                None
+            }
+            Charset::Format2(array) => {
+                let mut glyph_id = glyph_id.0 - 1;
+                for range in *array {
+                    if glyph_id <= range.left {
+                        return range.first.0.checked_add(glyph_id).map(StringId);
+                    }
+
+                    glyph_id -= range.left + 1;
+                }
+
+                None
+            }
+        }
+    }
+
+    fn sid_to_gid(&self, sid: StringId, number_of_glyphs: u16) -> Option<GlyphId> {
+        if sid.0 == 0 {
+            return Some(GlyphId(0));
+        }
+
+        let glyph_id = match self {
+            Charset::ISOAdobe => {
+                if sid.0 <= 228 { Some(sid.0) } else { None }
+            }
+            Charset::Expert => {
+                EXPERT_CHARSET.iter().position(|n| *n == sid.0).map(|n| n as u16)
+            }
+            Charset::ExpertSubset => {
+                EXPERT_SUBSET_CHARSET.iter().position(|n| *n == sid.0).map(|n| n as u16)
+            }
+            Charset::Format0(array) => {
+                array.into_iter().position(|n| n == sid).map(|n| n as u16 + 1)
+            }
+            Charset::Format1(array) => {
+                let mut glyph_id = 1u16;
+                let mut result = None;
+                for range in *array {
+                    let last = u32::from(range.first.0) + u32::from(range.left);
+                    if range.first.0 <= sid.0 && u32::from(sid.0) <= last {
+                        result = glyph_id.checked_add(sid.0 - range.first.0);
+                        break;
+                    }
+
+                    glyph_id = glyph_id.checked_add(u16::from(range.left) + 1)?;
+                }
+
+                result
+            }
+            Charset::Format2(array) => {
+                let mut glyph_id = 1u16;
+                let mut result = None;
+                for range in *array {
+                    let last = u32::from(range.first.0) + u32::from(range.left);
+                    if range.first.0 <= sid.0 && u32::from(sid.0) <= last {
+                        result = glyph_id.checked_add(sid.0 - range.first.0);
+                        break;
+                    }
+
+                    glyph_id = glyph_id.checked_add(range.left)?.checked_add(1)?;
+                }
+
+                result
+            }
+        };
+
+        glyph_id.filter(|id| *id < number_of_glyphs).map(GlyphId)
+    }
+}
+
+fn parse_charset(number_of_glyphs: u16, data: &[u8]) -> Option<Charset<'_>> {
+    if number_of_glyphs < 2 {
+        return None;
+    }
+
+    let mut s = Stream::new(data);
+    let format: u8 = s.read()?;
+
+    // The `.notdef` glyph is not stored.
+    let total_left = number_of_glyphs - 1;
+
+    match format {
+        0 => Some(Charset::Format0(s.read_array16(total_left)?)),
+        1 => {
+            // The number of ranges is not defined, so we have to
+            // read until no glyphs are left.
+            let mut count = 0;
+            {
+                let mut s = s;
+                let mut left = total_left;
+                while left > 0 {
+                    s.skip::<StringId>(); // first
+                    let n_left: u8 = s.read()?;
+                    left = left.saturating_sub(u16::from(n_left) + 1);
+                    count += 1;
+                }
+            }
+
+            Some(Charset::Format1(s.read_array16(count)?))
+        }
+        2 => {
+            let mut count = 0;
+            {
+                let mut s = s;
+                let mut left = total_left;
+                while left > 0 {
+                    s.skip::<StringId>(); // first
+                    let n_left: u16 = s.read()?;
+                    left = left.saturating_sub(n_left).saturating_sub(1);
+                    count += 1;
+                }
+            }
+
+            Some(Charset::Format2(s.read_array16(count)?))
+        }
+        _ => None,
+    }
+}
+
+
+// Adobe Technical Note #5176, Chapter 19 "FDSelect"
+#[derive(Clone, Copy, Debug)]
+enum FDSelect<'a> {
+    Format0(LazyArray16<'a, u8>),
+    Format3(&'a [u8]), // It's easier to parse it in-place.
 }
 
+impl FDSelect<'_> {
+    fn font_dict_index(&self, glyph_id: GlyphId) -> Option<u8> {
+        match *self {
+            FDSelect::Format0(ref array) => array.get(glyph_id.0),
+            FDSelect::Format3(data) => {
+                let mut s = Stream::new(data);
+                let number_of_ranges: u16 = s.read()?;
+                if number_of_ranges == 0 {
+                    return None;
+                }
 
-pub trait IsEven {
-    fn is_even(&self) -> bool;
-    fn is_odd(&self) -> bool;
-}
+                // 'A sentinel GID follows the last range element and serves
+                // to delimit the last range in the array.'
+                // So we can simply increase the number of ranges by one.
+                let number_of_ranges = number_of_ranges.checked_add(1)?;
+
+                // Range is: GlyphId + u8
+                let mut prev_first_glyph: GlyphId = s.read()?;
+                let mut prev_index: u8 = s.read()?;
+                for _ in 1..number_of_ranges {
+                    let curr_first_glyph: GlyphId = s.read()?;
+                    if (prev_first_glyph..curr_first_glyph).contains(&glyph_id) {
+                        return Some(prev_index);
+                    } else {
+                        prev_index = s.read()?;
+                    }
 
-impl IsEven for usize {
-    #[inline]
-    fn is_even(&self) -> bool { (*self) & 1 == 0 }
+                    prev_first_glyph = curr_first_glyph;
+                }
 
-    #[inline]
-    fn is_odd(&self) -> bool { !self.is_even() }
+                None
+            }
+        }
+    }
 }
 
-#[cfg(feature = "std")]
-#[inline]
-pub fn f32_abs(n: f32) -> f32 {
-    n.abs()
+fn parse_fd_select<'a>(number_of_glyphs: u16, mut s: Stream<'a>) -> Option<FDSelect<'a>> {
+    let format: u8 = s.read()?;
+    match format {
+        0 => Some(FDSelect::Format0(s.read_array16(number_of_glyphs)?)),
+        3 => Some(FDSelect::Format3(s.tail()?)),
+        _ => None,
+    }
 }
 
-#[cfg(not(feature = "std"))]
-#[inline]
-pub fn f32_abs(n: f32) -> f32 {
-    if n.is_sign_negative() { -n } else { n }
-}
+
+// Adobe Technical Note #5176, Appendix A Standard Strings
+const STANDARD_NAMES: &[&str] = &[
+    ".notdef",
+    "space",
+    "exclam",
+    "quotedbl",
+    "numbersign",
+    "dollar",
+    "percent",
+    "ampersand",
+    "quoteright",
+    "parenleft",
+    "parenright",
+    "asterisk",
+    "plus",
+    "comma",
+    "hyphen",
+    "period",
+    "slash",
+    "zero",
+    "one",
+    "two",
+    "three",
+    "four",
+    "five",
+    "six",
+    "seven",
+    "eight",
+    "nine",
+    "colon",
+    "semicolon",
+    "less",
+    "equal",
+    "greater",
+    "question",
+    "at",
+    "A",
+    "B",
+    "C",
+    "D",
+    "E",
+    "F",
+    "G",
+    "H",
+    "I",
+    "J",
+    "K",
+    "L",
+    "M",
+    "N",
+    "O",
+    "P",
+    "Q",
+    "R",
+    "S",
+    "T",
+    "U",
+    "V",
+    "W",
+    "X",
+    "Y",
+    "Z",
+    "bracketleft",
+    "backslash",
+    "bracketright",
+    "asciicircum",
+    "underscore",
+    "quoteleft",
+    "a",
+    "b",
+    "c",
+    "d",
+    "e",
+    "f",
+    "g",
+    "h",
+    "i",
+    "j",
+    "k",
+    "l",
+    "m",
+    "n",
+    "o",
+    "p",
+    "q",
+    "r",
+    "s",
+    "t",
+    "u",
+    "v",
+    "w",
+    "x",
+    "y",
+    "z",
+    "braceleft",
+    "bar",
+    "braceright",
+    "asciitilde",
+    "exclamdown",
+    "cent",
+    "sterling",
+    "fraction",
+    "yen",
+    "florin",
+    "section",
+    "currency",
+    "quotesingle",
+    "quotedblleft",
+    "guillemotleft",
+    "guilsinglleft",
+    "guilsinglright",
+    "fi",
+    "fl",
+    "endash",
+    "dagger",
+    "daggerdbl",
+    "periodcentered",
+    "paragraph",
+    "bullet",
+    "quotesinglbase",
+    "quotedblbase",
+    "quotedblright",
+    "guillemotright",
+    "ellipsis",
+    "perthousand",
+    "questiondown",
+    "grave",
+    "acute",
+    "circumflex",
+    "tilde",
+    "macron",
+    "breve",
+    "dotaccent",
+    "dieresis",
+    "ring",
+    "cedilla",
+    "hungarumlaut",
+    "ogonek",
+    "caron",
+    "emdash",
+    "AE",
+    "ordfeminine",
+    "Lslash",
+    "Oslash",
+    "OE",
+    "ordmasculine",
+    "ae",
+    "dotlessi",
+    "lslash",
+    "oslash",
+    "oe",
+    "germandbls",
+    "onesuperior",
+    "logicalnot",
+    "mu",
+    "trademark",
+    "Eth",
+    "onehalf",
+    "plusminus",
+    "Thorn",
+    "onequarter",
+    "divide",
+    "brokenbar",
+    "degree",
+    "thorn",
+    "threequarters",
+    "twosuperior",
+    "registered",
+    "minus",
+    "eth",
+    "multiply",
+    "threesuperior",
+    "copyright",
+    "Aacute",
+    "Acircumflex",
+    "Adieresis",
+    "Agrave",
+    "Aring",
+    "Atilde",
+    "Ccedilla",
+    "Eacute",
+    "Ecircumflex",
+    "Edieresis",
+    "Egrave",
+    "Iacute",
+    "Icircumflex",
+    "Idieresis",
+    "Igrave",
+    "Ntilde",
+    "Oacute",
+    "Ocircumflex",
+    "Odieresis",
+    "Ograve",
+    "Otilde",
+    "Scaron",
+    "Uacute",
+    "Ucircumflex",
+    "Udieresis",
+    "Ugrave",
+    "Yacute",
+    "Ydieresis",
+    "Zcaron",
+    "aacute",
+    "acircumflex",
+    "adieresis",
+    "agrave",
+    "aring",
+    "atilde",
+    "ccedilla",
+    "eacute",
+    "ecircumflex",
+    "edieresis",
+    "egrave",
+    "iacute",
+    "icircumflex",
+    "idieresis",
+    "igrave",
+    "ntilde",
+    "oacute",
+    "ocircumflex",
+    "odieresis",
+    "ograve",
+    "otilde",
+    "scaron",
+    "uacute",
+    "ucircumflex",
+    "udieresis",
+    "ugrave",
+    "yacute",
+    "ydieresis",
+    "zcaron",
+    "exclamsmall",
+    "Hungarumlautsmall",
+    "dollaroldstyle",
+    "dollarsuperior",
+    "ampersandsmall",
+    "Acutesmall",
+    "parenleftsuperior",
+    "parenrightsuperior",
+    "twodotenleader",
+    "onedotenleader",
+    "zerooldstyle",
+    "oneoldstyle",
+    "twooldstyle",
+    "threeoldstyle",
+    "fouroldstyle",
+    "fiveoldstyle",
+    "sixoldstyle",
+    "sevenoldstyle",
+    "eightoldstyle",
+    "nineoldstyle",
+    "commasuperior",
+    "threequartersemdash",
+    "periodsuperior",
+    "questionsmall",
+    "asuperior",
+    "bsuperior",
+    "centsuperior",
+    "dsuperior",
+    "esuperior",
+    "isuperior",
+    "lsuperior",
+    "msuperior",
+    "nsuperior",
+    "osuperior",
+    "rsuperior",
+    "ssuperior",
+    "tsuperior",
+    "ff",
+    "ffi",
+    "ffl",
+    "parenleftinferior",
+    "parenrightinferior",
+    "Circumflexsmall",
+    "hyphensuperior",
+    "Gravesmall",
+    "Asmall",
+    "Bsmall",
+    "Csmall",
+    "Dsmall",
+    "Esmall",
+    "Fsmall",
+    "Gsmall",
+    "Hsmall",
+    "Ismall",
+    "Jsmall",
+    "Ksmall",
+    "Lsmall",
+    "Msmall",
+    "Nsmall",
+    "Osmall",
+    "Psmall",
+    "Qsmall",
+    "Rsmall",
+    "Ssmall",
+    "Tsmall",
+    "Usmall",
+    "Vsmall",
+    "Wsmall",
+    "Xsmall",
+    "Ysmall",
+    "Zsmall",
+    "colonmonetary",
+    "onefitted",
+    "rupiah",
+    "Tildesmall",
+    "exclamdownsmall",
+    "centoldstyle",
+    "Lslashsmall",
+    "Scaronsmall",
+    "Zcaronsmall",
+    "Dieresissmall",
+    "Brevesmall",
+    "Caronsmall",
+    "Dotaccentsmall",
+    "Macronsmall",
+    "figuredash",
+    "hypheninferior",
+    "Ogoneksmall",
+    "Ringsmall",
+    "Cedillasmall",
+    "questiondownsmall",
+    "oneeighth",
+    "threeeighths",
+    "fiveeighths",
+    "seveneighths",
+    "onethird",
+    "twothirds",
+    "zerosuperior",
+    "foursuperior",
+    "fivesuperior",
+    "sixsuperior",
+    "sevensuperior",
+    "eightsuperior",
+    "ninesuperior",
+    "zeroinferior",
+    "oneinferior",
+    "twoinferior",
+    "threeinferior",
+    "fourinferior",
+    "fiveinferior",
+    "sixinferior",
+    "seveninferior",
+    "eightinferior",
+    "nineinferior",
+    "centinferior",
+    "dollarinferior",
+    "periodinferior",
+    "commainferior",
+    "Agravesmall",
+    "Aacutesmall",
+    "Acircumflexsmall",
+    "Atildesmall",
+    "Adieresissmall",
+    "Aringsmall",
+    "AEsmall",
+    "Ccedillasmall",
+    "Egravesmall",
+    "Eacutesmall",
+    "Ecircumflexsmall",
+    "Edieresissmall",
+    "Igravesmall",
+    "Iacutesmall",
+    "Icircumflexsmall",
+    "Idieresissmall",
+    "Ethsmall",
+    "Ntildesmall",
+    "Ogravesmall",
+    "Oacutesmall",
+    "Ocircumflexsmall",
+    "Otildesmall",
+    "Odieresissmall",
+    "OEsmall",
+    "Oslashsmall",
+    "Ugravesmall",
+    "Uacutesmall",
+    "Ucircumflexsmall",
+    "Udieresissmall",
+    "Yacutesmall",
+    "Thornsmall",
+    "Ydieresissmall",
+    "001.000",
+    "001.001",
+    "001.002",
+    "001.003",
+    "Black",
+    "Bold",
+    "Book",
+    "Light",
+    "Medium",
+    "Regular",
+    "Roman",
+    "Semibold",
+];
+
+// Adobe Technical Note #5176, Appendix B Predefined Encodings
+//
+// Maps Standard Encoding codes to String IDs.
+const STANDARD_ENCODING: [u8; 256] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, // 0
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, // 16
+    1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, // 32
+    17, 18, 19, 20, 21, 22, 23, 24, 25, 26, 27, 28, 29, 30, 31, 32, // 48
+    33, 34, 35, 36, 37, 38, 39, 40, 41, 42, 43, 44, 45, 46, 47, 48, // 64
+    49, 50, 51, 52, 53, 54, 55, 56, 57, 58, 59, 60, 61, 62, 63, 64, // 80
+    65, 66, 67, 68, 69, 70, 71, 72, 73, 74, 75, 76, 77, 78, 79, 80, // 96
+    81, 82, 83, 84, 85, 86, 87, 88, 89, 90, 91, 92, 93, 94, 95, 0, // 112
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, // 128
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, // 144
+    0, 96, 97, 98, 99, 100, 101, 102, 103, 104, 105, 106, 107, 108, 109, 110, // 160
+    0, 111, 112, 113, 114, 0, 115, 116, 117, 118, 119, 120, 121, 122, 0, 123, // 176
+    0, 124, 125, 126, 127, 128, 129, 130, 131, 0, 132, 133, 0, 134, 135, 136, // 192
+    137, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, // 208
+    0, 138, 0, 139, 0, 0, 0, 0, 140, 141, 142, 143, 0, 0, 0, 0, // 224
+    0, 144, 0, 0, 0, 145, 0, 0, 146, 147, 148, 149, 0, 0, 0, 0, // 240
+];
+
+// Adobe Technical Note #5176, Appendix C Predefined Charsets
+const EXPERT_CHARSET: &[u16] = &[
+    0, 1, 229, 230, 231, 232, 233, 234, 235, 236, 237, 238, 13, 14, 15, 99, 239, 240, 241, 242, 243,
+    244, 245, 246, 247, 248, 27, 28, 249, 250, 251, 252, 253, 254, 255, 256, 257, 258, 259, 260,
+    261, 262, 263, 264, 265, 266, 109, 110, 267, 268, 269, 270, 271, 272, 273, 274, 275, 276, 277,
+    278, 279, 280, 281, 282, 283, 284, 285, 286, 287, 288, 289, 290, 291, 292, 293, 294, 295, 296,
+    297, 298, 299, 300, 301, 302, 303, 304, 305, 306, 307, 308, 309, 310, 311, 312, 313, 314, 315,
+    316, 317, 318, 158, 155, 163, 319, 320, 321, 322, 323, 324, 325, 326, 150, 164, 169, 327, 328,
+    329, 330, 331, 332, 333, 334, 335, 336, 337, 338, 339, 340, 341, 342, 343, 344, 345, 346, 347,
+    348, 349, 350, 351, 352, 353, 354, 355, 356, 357, 358, 359, 360, 361, 362, 363, 364, 365, 366,
+    367, 368, 369, 370, 371, 372, 373, 374, 375, 376, 377, 378,
+];
+
+const EXPERT_SUBSET_CHARSET: &[u16] = &[
+    0, 1, 231, 232, 235, 236, 237, 238, 13, 14, 15, 99, 239, 240, 241, 242, 243, 244, 245, 246, 247,
+    248, 27, 28, 249, 250, 251, 253, 254, 255, 256, 257, 258, 259, 260, 261, 262, 263, 264, 265,
+    266, 109, 110, 267, 268, 269, 270, 272, 300, 301, 302, 305, 314, 315, 158, 155, 163, 320, 321,
+    322, 323, 324, 325, 326, 150, 164, 169, 327, 328, 329, 330, 331, 332, 333, 334, 335, 336, 337,
+    338, 339, 340, 341, 342, 343, 344, 345, 346,
+];
 
 
 #[cfg(test)]
@@ -1565,6 +2952,12 @@ mod tests {
                 CFFError::BlendRegionsLimitReached => {
                     write!(f, "only up to 64 blend regions are supported")
                 }
+                CFFError::NoLocalSubroutines => {
+                    write!(f, "no local subroutines for a glyph")
+                }
+                CFFError::InvalidSeacCode => {
+                    write!(f, "invalid seac code")
+                }
             }
         }
     }
@@ -1751,7 +3144,7 @@ mod tests {
         let metadata = parse_metadata(&data).unwrap();
         let mut builder = Builder(String::new());
         let char_str = metadata.char_strings.get(0).unwrap();
-        let rect = parse_char_string(char_str, &metadata, &mut builder).unwrap();
+        let rect = parse_char_string(char_str, &metadata, GlyphId(0), &mut builder).unwrap();
 
         assert_eq!(builder.0, "M 10 0 Z ");
         assert_eq!(rect, Rect { x_min: 10, y_min: 0, x_max: 10, y_max: 0 });
@@ -1769,7 +3162,7 @@ mod tests {
                 let metadata = parse_metadata(&data).unwrap();
                 let mut builder = Builder(String::new());
                 let char_str = metadata.char_strings.get(0).unwrap();
-                let rect = parse_char_string(char_str, &metadata, &mut builder).unwrap();
+                let rect = parse_char_string(char_str, &metadata, GlyphId(0), &mut builder).unwrap();
 
                 assert_eq!(builder.0, $path);
                 assert_eq!(rect, $rect_res);
@@ -1791,7 +3184,7 @@ mod tests {
                 let metadata = parse_metadata(&data).unwrap();
                 let mut builder = Builder(String::new());
                 let char_str = metadata.char_strings.get(0).unwrap();
-                let res = parse_char_string(char_str, &metadata, &mut builder);
+                let res = parse_char_string(char_str, &metadata, GlyphId(0), &mut builder);
 
                 assert_eq!(res.unwrap_err().to_string(), $err);
             }
@@ -1961,7 +3354,7 @@ mod tests {
         let metadata = parse_metadata(&data).unwrap();
         let mut builder = Builder(String::new());
         let char_str = metadata.char_strings.get(0).unwrap();
-        assert!(parse_char_string(char_str, &metadata, &mut builder).is_err());
+        assert!(parse_char_string(char_str, &metadata, GlyphId(0), &mut builder).is_err());
     }
 
     test_cs_with_subrs!(local_subr,
@@ -2162,6 +3555,10 @@ mod tests {
         UInt8(operator::ENDCHAR),
     ], "outline's bounding box is too large");
 
+    test_cs_err!(seac_with_invalid_code, &[
+        CFFInt(0), CFFInt(0), CFFInt(65), CFFInt(1), UInt8(operator::ENDCHAR),
+    ], "invalid seac code");
+
     #[test]
     fn endchar_in_subr_with_extra_data_1() {
         let data = gen_cff(
@@ -2186,7 +3583,7 @@ mod tests {
         let metadata = parse_metadata(&data).unwrap();
         let mut builder = Builder(String::new());
         let char_str = metadata.char_strings.get(0).unwrap();
-        let res = parse_char_string(char_str, &metadata, &mut builder);
+        let res = parse_char_string(char_str, &metadata, GlyphId(0), &mut builder);
         assert_eq!(res.unwrap_err().to_string(),
                    "unused data left after 'endchar' operator");
     }
@@ -2215,7 +3612,7 @@ mod tests {
         let metadata = parse_metadata(&data).unwrap();
         let mut builder = Builder(String::new());
         let char_str = metadata.char_strings.get(0).unwrap();
-        let res = parse_char_string(char_str, &metadata, &mut builder);
+        let res = parse_char_string(char_str, &metadata, GlyphId(0), &mut builder);
         assert_eq!(res.unwrap_err().to_string(),
                    "unused data left after 'endchar' operator");
     }
@@ -2244,7 +3641,7 @@ mod tests {
         let metadata = parse_metadata(&data).unwrap();
         let mut builder = Builder(String::new());
         let char_str = metadata.char_strings.get(0).unwrap();
-        let res = parse_char_string(char_str, &metadata, &mut builder);
+        let res = parse_char_string(char_str, &metadata, GlyphId(0), &mut builder);
         assert_eq!(res.unwrap_err().to_string(),
                    "unused data left after 'endchar' operator");
     }
@@ -2268,7 +3665,7 @@ mod tests {
         let metadata = parse_metadata(&data).unwrap();
         let mut builder = Builder(String::new());
         let char_str = metadata.char_strings.get(0).unwrap();
-        let res = parse_char_string(char_str, &metadata, &mut builder);
+        let res = parse_char_string(char_str, &metadata, GlyphId(0), &mut builder);
         assert_eq!(res.unwrap_err().to_string(),
                    "subroutines nesting limit reached");
     }
@@ -2292,7 +3689,7 @@ mod tests {
         let metadata = parse_metadata(&data).unwrap();
         let mut builder = Builder(String::new());
         let char_str = metadata.char_strings.get(0).unwrap();
-        let res = parse_char_string(char_str, &metadata, &mut builder);
+        let res = parse_char_string(char_str, &metadata, GlyphId(0), &mut builder);
         assert_eq!(res.unwrap_err().to_string(),
                    "subroutines nesting limit reached");
     }
@@ -2319,7 +3716,7 @@ mod tests {
         let metadata = parse_metadata(&data).unwrap();
         let mut builder = Builder(String::new());
         let char_str = metadata.char_strings.get(0).unwrap();
-        let res = parse_char_string(char_str, &metadata, &mut builder);
+        let res = parse_char_string(char_str, &metadata, GlyphId(0), &mut builder);
         assert_eq!(res.unwrap_err().to_string(),
                    "subroutines nesting limit reached");
     }
@@ -2425,8 +3822,9 @@ mod tests {
             UInt8(top_dict_operator::PRIVATE_DICT_SIZE_AND_OFFSET as u8),
         ]);
 
-        assert_eq!(parse_top_dict(&mut Stream::new(&data)).unwrap(),
-                   (5, Some(2147483647..4294967294)));
+        let top_dict = parse_top_dict(&mut Stream::new(&data)).unwrap();
+        assert_eq!(top_dict.char_strings_offset, 5);
+        assert_eq!(top_dict.private_dict_range, Some(2147483647..4294967294));
     }
 
     #[test]
@@ -2502,4 +3900,493 @@ mod tests {
         assert_eq!(parse_number(0x1D, &mut Stream::new(&[0x00, 0x01, 0x86, 0xA0])).unwrap(), 100000);
         assert_eq!(parse_number(0x1D, &mut Stream::new(&[0xFF, 0xFE, 0x79, 0x60])).unwrap(), -100000);
     }
+
+    #[test]
+    fn charset_format_0() {
+        let data = writer::convert(&[
+            UInt8(0), // format
+            UInt16(34), // A
+            UInt16(400), // custom
+        ]);
+
+        let charset = parse_charset(3, &data).unwrap();
+        assert_eq!(charset.gid_to_sid(GlyphId(0)), Some(StringId(0)));
+        assert_eq!(charset.gid_to_sid(GlyphId(1)), Some(StringId(34)));
+        assert_eq!(charset.gid_to_sid(GlyphId(2)), Some(StringId(400)));
+        assert_eq!(charset.gid_to_sid(GlyphId(3)), None);
+        assert_eq!(charset.sid_to_gid(StringId(400), 3), Some(GlyphId(2)));
+        assert_eq!(charset.sid_to_gid(StringId(35), 3), None);
+    }
+
+    #[test]
+    fn charset_format_1() {
+        let data = writer::convert(&[
+            UInt8(1), // format
+            // Range 1
+            UInt16(34), // first
+            UInt8(2), // left
+            // Range 2
+            UInt16(400), // first
+            UInt8(0), // left
+        ]);
+
+        let charset = parse_charset(5, &data).unwrap();
+        assert_eq!(charset.gid_to_sid(GlyphId(1)), Some(StringId(34)));
+        assert_eq!(charset.gid_to_sid(GlyphId(3)), Some(StringId(36)));
+        assert_eq!(charset.gid_to_sid(GlyphId(4)), Some(StringId(400)));
+        assert_eq!(charset.gid_to_sid(GlyphId(5)), None);
+        assert_eq!(charset.sid_to_gid(StringId(35), 5), Some(GlyphId(2)));
+        assert_eq!(charset.sid_to_gid(StringId(400), 5), Some(GlyphId(4)));
+        assert_eq!(charset.sid_to_gid(StringId(401), 5), None);
+    }
+
+    #[test]
+    fn charset_format_2() {
+        let data = writer::convert(&[
+            UInt8(2), // format
+            // Range 1
+            UInt16(391), // first
+            UInt16(299), // left
+        ]);
+
+        let charset = parse_charset(301, &data).unwrap();
+        assert_eq!(charset.gid_to_sid(GlyphId(1)), Some(StringId(391)));
+        assert_eq!(charset.gid_to_sid(GlyphId(300)), Some(StringId(690)));
+        assert_eq!(charset.gid_to_sid(GlyphId(301)), None);
+        assert_eq!(charset.sid_to_gid(StringId(690), 301), Some(GlyphId(300)));
+        assert_eq!(charset.sid_to_gid(StringId(690), 300), None);
+    }
+
+    #[test]
+    fn charset_truncated() {
+        let data = writer::convert(&[
+            UInt8(1), // format
+            UInt16(34), // first
+            UInt8(0), // left
+        ]);
+
+        assert!(parse_charset(5, &data).is_none());
+    }
+
+    #[test]
+    fn fd_select_format_0() {
+        let data = writer::convert(&[
+            UInt8(0), // format
+            UInt8(1),
+            UInt8(0),
+            UInt8(2),
+        ]);
+
+        let fd_select = parse_fd_select(3, Stream::new(&data)).unwrap();
+        assert_eq!(fd_select.font_dict_index(GlyphId(0)), Some(1));
+        assert_eq!(fd_select.font_dict_index(GlyphId(2)), Some(2));
+        assert_eq!(fd_select.font_dict_index(GlyphId(3)), None);
+    }
+
+    #[test]
+    fn cid_font() {
+        let local_subrs = |x: i32, y: i32| [
+            UInt16(1), // count
+            UInt8(1), // offset size
+            UInt8(1), // index[0]
+            UInt8(8), // index[1]
+            CFFInt(x), CFFInt(y), UInt8(operator::MOVE_TO),
+            CFFInt(30), CFFInt(40), UInt8(operator::LINE_TO),
+            UInt8(operator::RETURN),
+        ];
+
+        let mut w = writer::Writer::new();
+        // Header
+        w.write(UInt8(1)); // major version
+        w.write(UInt8(0)); // minor version
+        w.write(UInt8(4)); // header size
+        w.write(UInt8(0)); // absolute offset
+
+        // Name INDEX
+        w.write(UInt16(0)); // count
+
+        // Top DICT INDEX
+        w.write(UInt16(1)); // count
+        w.write(UInt8(1)); // offset size
+        w.write(UInt8(1)); // index[0]
+        w.write(UInt8(32)); // index[1]
+        // ROS
+        w.write(CFFInt(0));
+        w.write(CFFInt(1));
+        w.write(CFFInt(0));
+        w.write(UInt8(12));
+        w.write(UInt8(30));
+        // charset
+        w.write(UInt8(29));
+        w.write(Int32(131));
+        w.write(UInt8(top_dict_operator::CHARSET_OFFSET as u8));
+        // CharStrings
+        w.write(UInt8(29));
+        w.write(Int32(46));
+        w.write(UInt8(top_dict_operator::CHAR_STRINGS_OFFSET as u8));
+        // FDArray
+        w.write(UInt8(29));
+        w.write(Int32(69));
+        w.write(UInt8(12));
+        w.write(UInt8(36));
+        // FDSelect
+        w.write(UInt8(29));
+        w.write(Int32(58));
+        w.write(UInt8(12));
+        w.write(UInt8(37));
+
+        // String INDEX
+        w.write(UInt16(0)); // count
+
+        // Global Subroutines INDEX
+        w.write(UInt16(0)); // count
+
+        // CharString INDEX
+        assert_eq!(w.offset(), 46);
+        w.write(UInt16(2)); // count
+        w.write(UInt8(1)); // offset size
+        w.write(UInt8(1)); // index[0]
+        w.write(UInt8(4)); // index[1]
+        w.write(UInt8(7)); // index[2]
+        for _ in 0..2 {
+            w.write(CFFInt(-107)); // subroutine 0 with bias
+            w.write(UInt8(operator::CALL_LOCAL_SUBROUTINE));
+            w.write(UInt8(operator::ENDCHAR));
+        }
+
+        // FDSelect
+        assert_eq!(w.offset(), 58);
+        w.write(UInt8(3)); // format
+        w.write(UInt16(2)); // number of ranges
+        w.write(UInt16(0)); // first glyph
+        w.write(UInt8(0)); // font dict index
+        w.write(UInt16(1)); // first glyph
+        w.write(UInt8(1)); // font dict index
+        w.write(UInt16(2)); // sentinel
+
+        // FDArray INDEX
+        assert_eq!(w.offset(), 69);
+        w.write(UInt16(2)); // count
+        w.write(UInt8(1)); // offset size
+        w.write(UInt8(1)); // index[0]
+        w.write(UInt8(8)); // index[1]
+        w.write(UInt8(29)); // index[2]
+        for (i, offset) in [103, 117].iter().enumerate() {
+            if i == 1 {
+                // FontMatrix
+                w.write(Raw(&[30, 0xA0, 0x02, 0xFF])); // 0.002
+                w.write(CFFInt(0));
+                w.write(CFFInt(0));
+                w.write(Raw(&[30, 0xA0, 0x02, 0xFF])); // 0.002
+                w.write(CFFInt(0));
+                w.write(CFFInt(0));
+                w.write(UInt8(12));
+                w.write(UInt8(7));
+            }
+
+            w.write(CFFInt(2)); // Private DICT size
+            w.write(UInt8(29));
+            w.write(Int32(*offset));
+            w.write(UInt8(top_dict_operator::PRIVATE_DICT_SIZE_AND_OFFSET as u8));
+        }
+
+        // Private DICT and Local Subroutines INDEX for each Font DICT
+        for &(x, y) in &[(10, 20), (50, 60)] {
+            w.write(CFFInt(2));
+            w.write(UInt8(private_dict_operator::LOCAL_SUBROUTINES_OFFSET as u8));
+            for v in local_subrs(x, y).iter() {
+                w.write(*v);
+            }
+        }
+
+        // Charset
+        assert_eq!(w.offset(), 131);
+        w.write(UInt8(0)); // format
+        w.write(UInt16(500)); // CID
+
+        let metadata = parse_metadata(&w.data).unwrap();
+        assert_eq!(metadata.glyph_cid(GlyphId(0)), Some(0));
+        assert_eq!(metadata.glyph_cid(GlyphId(1)), Some(500));
+        assert_eq!(metadata.glyph_name(GlyphId(1)), None);
+        assert_eq!(metadata.units_per_em(), 1000);
+
+        let mut builder = Builder(String::new());
+        let bbox = metadata.outline(GlyphId(0), &mut builder).unwrap();
+        assert_eq!(builder.0, "M 10 20 L 40 60 Z ");
+        assert_eq!(bbox, rect(10, 20, 40, 60));
+
+        let mut builder = Builder(String::new());
+        let bbox = metadata.outline(GlyphId(1), &mut builder).unwrap();
+        // Scaled by the Font DICT `FontMatrix`.
+        assert_eq!(builder.0, "M 100 120 L 160 200 Z ");
+        assert_eq!(bbox, rect(100, 120, 160, 200));
+    }
+
+    #[test]
+    fn parse_float_number() {
+        assert_eq!(parse_float(&mut Stream::new(&[0xA0, 0x01, 0xFF])).unwrap(), 0.001);
+        assert_eq!(parse_float(&mut Stream::new(&[0xE2, 0xA2, 0x5F])).unwrap(), -2.25);
+        assert_eq!(parse_float(&mut Stream::new(&[0x1A, 0x5B, 0x2F])).unwrap(), 150.0);
+        assert_eq!(parse_float(&mut Stream::new(&[0x0A, 0x14, 0x05, 0x41, 0xC3, 0xFF])).unwrap(), 0.000140541);
+        assert!(parse_float(&mut Stream::new(&[0x1D, 0xFF])).is_none()); // reserved nibble
+        assert!(parse_float(&mut Stream::new(&[0x12])).is_none()); // no end of number
+    }
+
+    #[test]
+    fn bare_cff() {
+        let data = writer::convert(&[
+            // Header
+            UInt8(1), // major version
+            UInt8(0), // minor version
+            UInt8(4), // header size
+            UInt8(0), // absolute offset
+
+            // Name INDEX
+            UInt16(1), // count
+            UInt8(1), // offset size
+            UInt8(1), // index[0]
+            UInt8(5), // index[1]
+            Raw(b"Test"),
+
+            // Top DICT INDEX
+            UInt16(1), // count
+            UInt8(1), // offset size
+            UInt8(1), // index[0]
+            UInt8(17), // index[1]
+            // FontMatrix
+            Raw(&[30, 0xA0, 0x00, 0x5F]), // 0.0005
+            CFFInt(0),
+            CFFInt(0),
+            Raw(&[30, 0xA0, 0x00, 0x5F]), // 0.0005
+            CFFInt(0),
+            CFFInt(0),
+            UInt8(12), UInt8(7),
+            // CharStrings
+            CFFInt(38),
+            UInt8(top_dict_operator::CHAR_STRINGS_OFFSET as u8),
+
+            // String INDEX
+            UInt16(0), // count
+
+            // Global Subroutines INDEX
+            UInt16(0), // count
+
+            // CharString INDEX
+            UInt16(1), // count
+            UInt8(1), // offset size
+            UInt8(1), // index[0]
+            UInt8(5), // index[1]
+            CFFInt(10), CFFInt(20), UInt8(operator::MOVE_TO),
+            UInt8(operator::ENDCHAR),
+        ]);
+
+        let mut cff = Cff::parse(&data).unwrap();
+        assert_eq!(cff.name(), Some("Test"));
+        assert_eq!(cff.units_per_em(), 2000);
+        assert_eq!(cff.number_of_glyphs(), 1);
+        assert_eq!(cff.font_matrix(), Matrix { a: 0.0005, b: 0.0, c: 0.0, d: 0.0005, e: 0.0, f: 0.0 });
+        assert_eq!(cff.glyph_name(GlyphId(0)), Some(".notdef"));
+        assert_eq!(cff.glyph_index_by_name(".notdef"), Some(GlyphId(0)));
+
+        let mut builder = Builder(String::new());
+        let bbox = cff.outline(GlyphId(0), &mut builder).unwrap();
+        assert_eq!(builder.0, "M 10 20 Z ");
+        assert_eq!(bbox, rect(10, 20, 10, 20));
+
+        // Like when `head.unitsPerEm` doesn't match `FontMatrix`.
+        // Outlines are still in font units.
+        cff.units_per_em = 1000;
+        let mut builder = Builder(String::new());
+        let bbox = cff.outline(GlyphId(0), &mut builder).unwrap();
+        assert_eq!(builder.0, "M 10 20 Z ");
+        assert_eq!(bbox, rect(10, 20, 10, 20));
+    }
+
+    #[test]
+    fn missing_font_matrix() {
+        let data = writer::convert(&[
+            // Header
+            UInt8(1), // major version
+            UInt8(0), // minor version
+            UInt8(4), // header size
+            UInt8(0), // absolute offset
+
+            // Name INDEX
+            UInt16(1), // count
+            UInt8(1), // offset size
+            UInt8(1), // index[0]
+            UInt8(5), // index[1]
+            Raw(b"Test"),
+
+            // Top DICT INDEX
+            UInt16(1), // count
+            UInt8(1), // offset size
+            UInt8(1), // index[0]
+            UInt8(3), // index[1]
+            // CharStrings
+            CFFInt(24),
+            UInt8(top_dict_operator::CHAR_STRINGS_OFFSET as u8),
+
+            // String INDEX
+            UInt16(0), // count
+
+            // Global Subroutines INDEX
+            UInt16(0), // count
+
+            // CharString INDEX
+            UInt16(1), // count
+            UInt8(1), // offset size
+            UInt8(1), // index[0]
+            UInt8(5), // index[1]
+            CFFInt(10), CFFInt(20), UInt8(operator::MOVE_TO),
+            UInt8(operator::ENDCHAR),
+        ]);
+
+        let mut cff = Cff::parse(&data).unwrap();
+        assert_eq!(cff.units_per_em(), 1000);
+        assert_eq!(cff.font_matrix(), Matrix::default());
+
+        // Like when `head.unitsPerEm` is set.
+        // Outlines must stay in font units and must not be scaled by `unitsPerEm / 1000`.
+        cff.units_per_em = 2048;
+        let mut builder = Builder(String::new());
+        let bbox = cff.outline(GlyphId(0), &mut builder).unwrap();
+        assert_eq!(builder.0, "M 10 20 Z ");
+        assert_eq!(bbox, rect(10, 20, 10, 20));
+    }
+
+    #[test]
+    fn default_font_matrix() {
+        let data = writer::convert(&[
+            // Header
+            UInt8(1), // major version
+            UInt8(0), // minor version
+            UInt8(4), // header size
+            UInt8(0), // absolute offset
+
+            // Name INDEX
+            UInt16(1), // count
+            UInt8(1), // offset size
+            UInt8(1), // index[0]
+            UInt8(5), // index[1]
+            Raw(b"Test"),
+
+            // Top DICT INDEX
+            UInt16(1), // count
+            UInt8(1), // offset size
+            UInt8(1), // index[0]
+            UInt8(17), // index[1]
+            // FontMatrix
+            Raw(&[30, 0xA0, 0x01, 0xFF]), // 0.001
+            CFFInt(0),
+            CFFInt(0),
+            Raw(&[30, 0xA0, 0x01, 0xFF]), // 0.001
+            CFFInt(0),
+            CFFInt(0),
+            UInt8(12), UInt8(7),
+            // CharStrings
+            CFFInt(38),
+            UInt8(top_dict_operator::CHAR_STRINGS_OFFSET as u8),
+
+            // String INDEX
+            UInt16(0), // count
+
+            // Global Subroutines INDEX
+            UInt16(0), // count
+
+            // CharString INDEX
+            UInt16(1), // count
+            UInt8(1), // offset size
+            UInt8(1), // index[0]
+            UInt8(5), // index[1]
+            CFFInt(10), CFFInt(20), UInt8(operator::MOVE_TO),
+            UInt8(operator::ENDCHAR),
+        ]);
+
+        let mut cff = Cff::parse(&data).unwrap();
+        assert_eq!(cff.units_per_em(), 1000);
+        assert_eq!(cff.font_matrix(), Matrix::default());
+
+        // Must be the same as without a `FontMatrix`.
+        cff.units_per_em = 2048;
+        let mut builder = Builder(String::new());
+        let bbox = cff.outline(GlyphId(0), &mut builder).unwrap();
+        assert_eq!(builder.0, "M 10 20 Z ");
+        assert_eq!(bbox, rect(10, 20, 10, 20));
+    }
+
+    #[test]
+    fn seac() {
+        let mut w = writer::Writer::new();
+        // Header
+        w.write(UInt8(1)); // major version
+        w.write(UInt8(0)); // minor version
+        w.write(UInt8(4)); // header size
+        w.write(UInt8(0)); // absolute offset
+
+        // Name INDEX
+        w.write(UInt16(0)); // count
+
+        // Top DICT INDEX
+        w.write(UInt16(1)); // count
+        w.write(UInt8(1)); // offset size
+        w.write(UInt8(1)); // index[0]
+        w.write(UInt8(13)); // index[1]
+        // charset
+        w.write(UInt8(29));
+        w.write(Int32(59));
+        w.write(UInt8(top_dict_operator::CHARSET_OFFSET as u8));
+        // CharStrings
+        w.write(UInt8(29));
+        w.write(Int32(27));
+        w.write(UInt8(top_dict_operator::CHAR_STRINGS_OFFSET as u8));
+
+        // String INDEX
+        w.write(UInt16(0)); // count
+
+        // Global Subroutines INDEX
+        w.write(UInt16(0)); // count
+
+        // CharString INDEX
+        assert_eq!(w.offset(), 27);
+        w.write(UInt16(4)); // count
+        w.write(UInt8(1)); // offset size
+        w.write(UInt8(1)); // index[0]
+        w.write(UInt8(2)); // index[1]
+        w.write(UInt8(9)); // index[2]
+        w.write(UInt8(16)); // index[3]
+        w.write(UInt8(25)); // index[4]
+        // .notdef
+        w.write(UInt8(operator::ENDCHAR));
+        // A
+        w.write(CFFInt(10)); w.write(CFFInt(20)); w.write(UInt8(operator::MOVE_TO));
+        w.write(CFFInt(30)); w.write(CFFInt(40)); w.write(UInt8(operator::LINE_TO));
+        w.write(UInt8(operator::ENDCHAR));
+        // acute
+        w.write(CFFInt(5)); w.write(CFFInt(5)); w.write(UInt8(operator::MOVE_TO));
+        w.write(CFFInt(10)); w.write(CFFInt(0)); w.write(UInt8(operator::LINE_TO));
+        w.write(UInt8(operator::ENDCHAR));
+        // Aacute
+        w.write(CFFInt(100)); // width
+        w.write(CFFInt(200)); // adx
+        w.write(CFFInt(300)); // ady
+        w.write(CFFInt(65)); // bchar, A
+        w.write(CFFInt(194)); // achar, acute
+        w.write(UInt8(operator::ENDCHAR));
+
+        // Charset
+        assert_eq!(w.offset(), 59);
+        w.write(UInt8(0)); // format
+        w.write(UInt16(34)); // A
+        w.write(UInt16(125)); // acute
+        w.write(UInt16(171)); // Aacute
+
+        let cff = Cff::parse(&w.data).unwrap();
+        assert_eq!(cff.glyph_name(GlyphId(3)), Some("Aacute"));
+
+        let mut builder = Builder(String::new());
+        let bbox = cff.outline(GlyphId(3), &mut builder).unwrap();
+        assert_eq!(builder.0, "M 10 20 L 40 60 Z M 205 305 L 215 305 Z ");
+        assert_eq!(bbox, rect(10, 20, 215, 305));
+    }
 }
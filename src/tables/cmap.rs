@@ -1,9 +1,16 @@
-// https://docs.microsoft.com/en-us/typography/opentype/spec/cmap
+/*!
+A [Character to Glyph Index Mapping Table](
+https://docs.microsoft.com/en-us/typography/opentype/spec/cmap) implementation.
+
+This module provides a low-level access to individual subtables.
+In most cases `Font::glyph_index()` should be used instead.
+*/
 
 use core::convert::TryFrom;
 
 use crate::{GlyphId, PlatformId};
-use crate::parser::{Stream, FromData, Offset, Offset32, U24, LazyArray16, NumFrom};
+use crate::parser::{Stream, FromData, Offset, Offset32, U24, LazyArray16, LazyArrayIter16};
+use crate::parser::{LazyArray32, NumFrom};
 
 
 #[derive(Clone, Copy)]
@@ -29,7 +36,7 @@ impl FromData for EncodingRecord {
 
 
 #[derive(Clone, Copy)]
-pub struct Table<'a> {
+pub(crate) struct Table<'a> {
     data: &'a [u8],
     records: LazyArray16<'a, EncodingRecord>,
 }
@@ -46,81 +53,317 @@ impl<'a> Table<'a> {
             records,
         })
     }
+
+    #[inline]
+    pub fn subtables(&self) -> Subtables<'a> {
+        Subtables {
+            data: self.data,
+            records: self.records.into_iter(),
+        }
+    }
 }
 
-pub fn glyph_index(table: &Table, c: char) -> Option<GlyphId> {
-    for record in table.records {
-        let subtable_data = table.data.get(usize::num_from(record.offset)..)?;
-        let mut s = Stream::new(subtable_data);
-        let format = match parse_format(s.read()?) {
-            Some(format) => format,
-            None => continue,
-        };
 
-        let platform_id = match PlatformId::from_u16(record.platform_id) {
-            Some(v) => v,
-            None => continue,
+/// A character encoding subtable.
+#[derive(Clone, Copy)]
+pub struct Subtable<'a> {
+    /// Subtable platform.
+    pub platform_id: PlatformId,
+    /// Subtable encoding.
+    pub encoding_id: u16,
+    /// Subtable format.
+    pub format: Format,
+    /// Subtable language.
+    ///
+    /// Used only by the Macintosh platform. Zero means language-independent.
+    /// Always zero for `Format::UnicodeVariationSequences`.
+    pub language: u32,
+    data: &'a [u8],
+}
+
+impl<'a> Subtable<'a> {
+    fn parse(platform_id: u16, encoding_id: u16, data: &'a [u8]) -> Option<Self> {
+        let mut s = Stream::new(data);
+        let format = parse_format(s.read()?)?;
+        let language = match format {
+            Format::ByteEncodingTable |
+            Format::HighByteMappingThroughTable |
+            Format::SegmentMappingToDeltaValues |
+            Format::TrimmedTableMapping => {
+                s.skip::<u16>(); // length
+                u32::from(s.read::<u16>()?)
+            }
+            Format::MixedCoverage |
+            Format::TrimmedArray |
+            Format::SegmentedCoverage |
+            Format::ManyToOneRangeMappings => {
+                s.skip::<u16>(); // reserved
+                s.skip::<u32>(); // length
+                s.read::<u32>()?
+            }
+            Format::UnicodeVariationSequences => 0,
         };
 
-        if !is_unicode_encoding(format, platform_id, record.encoding_id) {
-            continue;
-        }
+        Some(Subtable {
+            platform_id: PlatformId::from_u16(platform_id)?,
+            encoding_id,
+            format,
+            language,
+            data,
+        })
+    }
 
-        let c = u32::from(c);
-        let glyph = match format {
-            Format::ByteEncodingTable => {
-                parse_byte_encoding_table(s, c)
-            }
-            Format::HighByteMappingThroughTable => {
-                parse_high_byte_mapping_through_table(subtable_data, c)
+    /// Checks that subtable is used by `Font::glyph_index()`.
+    ///
+    /// Windows Symbol subtables are not treated as Unicode.
+    #[inline]
+    pub fn is_unicode(&self) -> bool {
+        is_unicode_encoding(self.format, self.platform_id, self.encoding_id)
+    }
+
+    /// Resolves a Glyph ID for a code point.
+    ///
+    /// Code point is in the subtable's encoding, which is not necessarily Unicode.
+    ///
+    /// Always returns `None` for `Format::UnicodeVariationSequences`.
+    #[inline]
+    pub fn glyph_index(&self, code_point: u32) -> Option<GlyphId> {
+        subtable_glyph_index(self.format, self.data, code_point).map(GlyphId)
+    }
+
+    /// Calls `f` for each code point defined in this subtable.
+    ///
+    /// Code points mapped to the glyph 0 are skipped.
+    /// Code points are in the subtable's encoding, which is not necessarily Unicode.
+    #[inline]
+    pub fn codepoints<F: FnMut(u32, GlyphId)>(&self, mut f: F) {
+        subtable_codepoints(self.format, self.data, &mut |c, id| {
+            if id != 0 {
+                f(c, GlyphId(id));
             }
-            Format::SegmentMappingToDeltaValues => {
-                parse_segment_mapping_to_delta_values(subtable_data, c)
+        });
+    }
+
+    /// Returns an iterator over variation selector records.
+    ///
+    /// Empty for all formats except `Format::UnicodeVariationSequences`.
+    #[inline]
+    pub fn variation_selectors(&self) -> VariationSelectors<'a> {
+        if self.format != Format::UnicodeVariationSequences {
+            return VariationSelectors::default();
+        }
+
+        VariationSelectors::parse(self.data).unwrap_or_default()
+    }
+}
+
+impl core::fmt::Debug for Subtable<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Subtable")
+            .field("platform_id", &self.platform_id)
+            .field("encoding_id", &self.encoding_id)
+            .field("format", &self.format)
+            .field("language", &self.language)
+            .finish()
+    }
+}
+
+
+/// An iterator over character encoding subtables.
+///
+/// Subtables with an unknown format or platform are skipped.
+#[derive(Clone, Copy, Default)]
+#[allow(missing_debug_implementations)]
+pub struct Subtables<'a> {
+    data: &'a [u8],
+    records: LazyArrayIter16<'a, EncodingRecord>,
+}
+
+impl<'a> Iterator for Subtables<'a> {
+    type Item = Subtable<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let record = self.records.next()?;
+            let subtable = self.data.get(usize::num_from(record.offset)..)
+                .and_then(|data| Subtable::parse(record.platform_id, record.encoding_id, data));
+
+            if subtable.is_some() {
+                return subtable;
             }
-            Format::TrimmedTableMapping => {
-                parse_trimmed_table_mapping(s, c)
+        }
+    }
+}
+
+
+// Returns Unicode subtables in the order they should be checked.
+fn unicode_subtables<'a>(table: &Table<'a>) -> impl Iterator<Item = Subtable<'a>> {
+    table.subtables().filter(|subtable| subtable.is_unicode())
+}
+
+pub(crate) fn glyph_index(table: &Table, c: char) -> Option<GlyphId> {
+    let c = u32::from(c);
+    for subtable in unicode_subtables(table) {
+        if let Some(id) = subtable.glyph_index(c) {
+            return Some(id);
+        }
+    }
+
+    None
+}
+
+fn subtable_glyph_index(format: Format, data: &[u8], c: u32) -> Option<u16> {
+    let mut s = Stream::new(data);
+    s.skip::<u16>(); // format
+    match format {
+        Format::ByteEncodingTable => {
+            parse_byte_encoding_table(s, c)
+        }
+        Format::HighByteMappingThroughTable => {
+            parse_high_byte_mapping_through_table(data, c)
+        }
+        Format::SegmentMappingToDeltaValues => {
+            parse_segment_mapping_to_delta_values(data, c)
+        }
+        Format::TrimmedTableMapping => {
+            parse_trimmed_table_mapping(s, c)
+        }
+        Format::MixedCoverage => {
+            parse_mixed_coverage(s, c)
+        }
+        Format::TrimmedArray => {
+            parse_trimmed_array(s, c)
+        }
+        Format::SegmentedCoverage | Format::ManyToOneRangeMappings => {
+            parse_segmented_coverage(s, c, format)
+        }
+        Format::UnicodeVariationSequences => {
+            // This subtable is used only by glyph_variation_index().
+            None
+        }
+    }
+}
+
+pub(crate) fn codepoints(table: &Table, mut f: impl FnMut(u32, GlyphId)) {
+    for (index, subtable) in unicode_subtables(table).enumerate() {
+        // Multiple encoding records can point to the same subtable,
+        // like (0, 4) and (3, 10), so there is no need to process it twice.
+        let is_duplicate = unicode_subtables(table).take(index).any(|prev| {
+            prev.format == subtable.format && prev.data.as_ptr() == subtable.data.as_ptr()
+        });
+
+        if is_duplicate {
+            continue;
+        }
+
+        subtable.codepoints(|c, id| {
+            // Skip code points that are already mapped by one of the previous subtables,
+            // since `glyph_index()` will never reach the current one for them.
+            let is_mapped = unicode_subtables(table).take(index)
+                .any(|subtable| subtable.glyph_index(c).is_some());
+
+            if !is_mapped {
+                f(c, id);
             }
-            Format::MixedCoverage => {
-                // Unsupported.
-                continue;
+        });
+    }
+}
+
+fn subtable_codepoints(format: Format, data: &[u8], f: &mut dyn FnMut(u32, u16)) -> Option<()> {
+    let mut s = Stream::new(data);
+    s.skip::<u16>(); // format
+    match format {
+        Format::ByteEncodingTable => {
+            s.skip::<u16>(); // length
+            s.skip::<u16>(); // language
+            for (c, id) in s.tail()?.iter().take(256).enumerate() {
+                f(c as u32, u16::from(*id));
             }
-            Format::TrimmedArray => {
-                parse_trimmed_array(s, c)
+        }
+        Format::HighByteMappingThroughTable => {
+            codepoints_high_byte_mapping_through_table(data, f)?;
+        }
+        Format::SegmentMappingToDeltaValues => {
+            codepoints_segment_mapping_to_delta_values(data, f)?;
+        }
+        Format::TrimmedTableMapping => {
+            s.skip::<u16>(); // length
+            s.skip::<u16>(); // language
+            let first_code_point: u16 = s.read()?;
+            let count: u16 = s.read()?;
+            let glyphs = s.read_array16::<u16>(count)?;
+            for (i, id) in glyphs.into_iter().enumerate() {
+                f(u32::from(first_code_point) + i as u32, id);
             }
-            Format::SegmentedCoverage | Format::ManyToOneRangeMappings => {
-                parse_segmented_coverage(s, c, format)
+        }
+        Format::TrimmedArray => {
+            s.skip::<u16>(); // reserved
+            s.skip::<u32>(); // length
+            s.skip::<u32>(); // language
+            let first_code_point: u32 = s.read()?;
+            let count: u32 = s.read()?;
+            let glyphs = s.read_array32::<u16>(count)?;
+            for i in 0..glyphs.len() {
+                f(first_code_point.checked_add(i)?, glyphs.get(i)?);
             }
-            Format::UnicodeVariationSequences => {
-                // This subtable is used only by glyph_variation_index().
-                continue;
+        }
+        Format::MixedCoverage | Format::SegmentedCoverage | Format::ManyToOneRangeMappings => {
+            s.skip::<u16>(); // reserved
+            s.skip::<u32>(); // length
+            s.skip::<u32>(); // language
+            if format == Format::MixedCoverage {
+                s.advance_checked(8192)?; // is32
             }
-        };
 
-        if let Some(id) = glyph {
-            return Some(GlyphId(id));
+            let count: u32 = s.read()?;
+            let groups = s.read_array32::<SequentialMapGroup>(count)?;
+            for group in groups {
+                // Code points above U+10FFFF are invalid anyway.
+                let end = group.end_char_code.min(MAX_CODE_POINT);
+                for c in group.start_char_code..=end {
+                    let id = if format == Format::ManyToOneRangeMappings {
+                        group.start_glyph_id
+                    } else {
+                        group.start_glyph_id.checked_add(c - group.start_char_code)?
+                    };
+
+                    if let Ok(id) = u16::try_from(id) {
+                        f(c, id);
+                    }
+                }
+            }
         }
+        Format::UnicodeVariationSequences => {}
     }
 
-    None
+    Some(())
 }
 
-pub fn glyph_variation_index(table: &Table, c: char, variation: char) -> Option<GlyphId> {
-    for record in table.records {
-        let subtable_data = table.data.get(usize::num_from(record.offset)..)?;
-        let mut s = Stream::new(subtable_data);
-        let format = match parse_format(s.read()?) {
-            Some(format) => format,
-            None => continue,
-        };
+const MAX_CODE_POINT: u32 = 0x10FFFF;
 
-        if format != Format::UnicodeVariationSequences {
-            continue;
-        }
 
-        return parse_unicode_variation_sequences(table, subtable_data, c, u32::from(variation));
+pub(crate) fn glyph_variation_index(table: &Table, c: char, variation: char) -> Option<GlyphId> {
+    let selectors = variation_selectors(table);
+    let (_, record) = selectors.records
+        .binary_search_by(|v| v.var_selector.cmp(&u32::from(variation)))?;
+    let selector = VariationSelector::parse(record, selectors.data)?;
+
+    let cp = u32::from(c);
+    if selector.default_ranges.into_iter().any(|range| range.contains(cp)) {
+        // This is a default glyph.
+        return glyph_index(table, c);
     }
 
-    None
+    selector.non_default_mappings
+        .binary_search_by(|v| v.code_point.cmp(&cp))
+        .map(|(_, mapping)| mapping.glyph_id)
+}
+
+pub(crate) fn variation_selectors<'a>(table: &Table<'a>) -> VariationSelectors<'a> {
+    table.subtables()
+        .find(|subtable| subtable.format == Format::UnicodeVariationSequences)
+        .map(|subtable| subtable.variation_selectors())
+        .unwrap_or_default()
 }
 
 
@@ -146,104 +389,155 @@ impl FromData for VariationSelectorRecord {
 }
 
 
-#[derive(Clone, Copy)]
-struct UVSMappingRecord {
-    unicode_value: u32,
-    glyph_id: GlyphId,
+/// A variation sequence mapped to a non-default glyph.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct VariationMapping {
+    /// Base character code point.
+    pub code_point: u32,
+    /// Glyph ID of the variation.
+    pub glyph_id: GlyphId,
 }
 
-impl FromData for UVSMappingRecord {
+impl FromData for VariationMapping {
     const SIZE: usize = 5;
 
     #[inline]
     fn parse(data: &[u8]) -> Option<Self> {
         let mut s = Stream::new(data);
-        Some(UVSMappingRecord {
-            unicode_value: s.read::<U24>()?.0,
+        Some(VariationMapping {
+            code_point: s.read::<U24>()?.0,
             glyph_id: s.read()?,
         })
     }
 }
 
 
-#[derive(Clone, Copy)]
-struct UnicodeRangeRecord {
-    start_unicode_value: u32,
-    additional_count: u8,
+/// A range of base characters that use the default glyph for a variation sequence.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct UnicodeRange {
+    /// First code point in the range.
+    pub start: u32,
+    /// Last code point in the range. Inclusive.
+    pub end: u32,
 }
 
-impl UnicodeRangeRecord {
-    fn contains(&self, c: char) -> bool {
-        let end = self.start_unicode_value + u32::from(self.additional_count);
-        self.start_unicode_value >= u32::from(c) && u32::from(c) < end
+impl UnicodeRange {
+    /// Checks that range contains the specified code point.
+    #[inline]
+    pub fn contains(&self, code_point: u32) -> bool {
+        code_point >= self.start && code_point <= self.end
     }
 }
 
-impl FromData for UnicodeRangeRecord {
+impl FromData for UnicodeRange {
     const SIZE: usize = 4;
 
     #[inline]
     fn parse(data: &[u8]) -> Option<Self> {
         let mut s = Stream::new(data);
-        Some(UnicodeRangeRecord {
-            start_unicode_value: s.read::<U24>()?.0,
-            additional_count: s.read()?,
+        let start = s.read::<U24>()?.0;
+        let additional_count: u8 = s.read()?;
+        Some(UnicodeRange {
+            start,
+            end: start + u32::from(additional_count),
         })
     }
 }
 
 
-fn parse_unicode_variation_sequences(
-    table: &Table,
-    data: &[u8],
-    c: char,
-    variation: u32,
-) -> Option<GlyphId> {
-    let cp = u32::from(c);
-
-    let mut s = Stream::new(data);
-    s.skip::<u16>(); // format
-    s.skip::<u32>(); // length
-    let count: u32 = s.read()?;
-    let records = s.read_array32::<VariationSelectorRecord>(count)?;
+/// A [Variation Selector Record](
+/// https://docs.microsoft.com/en-us/typography/opentype/spec/cmap#variation-selector-records).
+#[derive(Clone, Copy, Debug)]
+pub struct VariationSelector<'a> {
+    /// Variation selector code point.
+    pub variation: u32,
+    /// Base characters that use the default glyph with this selector.
+    ///
+    /// The default glyph is the one resolved by `Font::glyph_index()`.
+    pub default_ranges: LazyArray32<'a, UnicodeRange>,
+    /// Base characters that use a non-default glyph with this selector.
+    pub non_default_mappings: LazyArray32<'a, VariationMapping>,
+}
 
-    let (_, record) = match records.binary_search_by(|v| v.var_selector.cmp(&variation)) {
-        Some(v) => v,
-        None => return None,
-    };
+impl<'a> VariationSelector<'a> {
+    fn parse(record: VariationSelectorRecord, data: &'a [u8]) -> Option<Self> {
+        let default_ranges = match record.default_uvs_offset {
+            Some(offset) => {
+                let mut s = Stream::new_at(data, offset.to_usize())?;
+                let count: u32 = s.read()?;
+                s.read_array32(count)?
+            }
+            None => LazyArray32::default(),
+        };
 
-    if let Some(offset) = record.default_uvs_offset {
-        let data = data.get(offset.to_usize()..)?;
-        let mut s = Stream::new(data);
-        let count: u32 = s.read()?;
-        let ranges = s.read_array32::<UnicodeRangeRecord>(count)?;
-        for range in ranges {
-            if range.contains(c) {
-                // This is a default glyph.
-                return glyph_index(table, c);
+        let non_default_mappings = match record.non_default_uvs_offset {
+            Some(offset) => {
+                let mut s = Stream::new_at(data, offset.to_usize())?;
+                let count: u32 = s.read()?;
+                s.read_array32(count)?
             }
-        }
+            None => LazyArray32::default(),
+        };
+
+        Some(VariationSelector {
+            variation: record.var_selector,
+            default_ranges,
+            non_default_mappings,
+        })
     }
+}
+
 
-    if let Some(offset) = record.non_default_uvs_offset {
-        let data = data.get(offset.to_usize()..)?;
+/// An iterator over [`VariationSelector`]s.
+///
+/// [`VariationSelector`]: struct.VariationSelector.html
+#[derive(Clone, Copy, Default)]
+#[allow(missing_debug_implementations)]
+pub struct VariationSelectors<'a> {
+    data: &'a [u8],
+    records: LazyArray32<'a, VariationSelectorRecord>,
+    index: u32,
+}
+
+impl<'a> VariationSelectors<'a> {
+    fn parse(data: &'a [u8]) -> Option<Self> {
         let mut s = Stream::new(data);
+        s.skip::<u16>(); // format
+        s.skip::<u32>(); // length
         let count: u32 = s.read()?;
-        let uvs_mappings = s.read_array32::<UVSMappingRecord>(count)?;
-        if let Some((_, mapping)) = uvs_mappings.binary_search_by(|v| v.unicode_value.cmp(&cp)) {
-            return Some(mapping.glyph_id);
-        }
+        let records = s.read_array32(count)?;
+        Some(VariationSelectors {
+            data,
+            records,
+            index: 0,
+        })
     }
+}
 
-    None
+impl<'a> Iterator for VariationSelectors<'a> {
+    type Item = VariationSelector<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.index < self.records.len() {
+            let record = self.records.get(self.index)?;
+            self.index += 1;
+
+            // Skip malformed records.
+            if let Some(selector) = VariationSelector::parse(record, self.data) {
+                return Some(selector);
+            }
+        }
+
+        None
+    }
 }
 
 // https://docs.microsoft.com/en-us/typography/opentype/spec/cmap#format-0-byte-encoding-table
 fn parse_byte_encoding_table(mut s: Stream, code_point: u32) -> Option<u16> {
-    let length: u16 = s.read()?;
+    s.skip::<u16>(); // length
     s.skip::<u16>(); // language
 
-    if code_point < u32::from(length) {
+    if code_point < 256 {
         s.advance(usize::num_from(code_point));
         Some(u16::from(s.read::<u8>()?))
     } else {
@@ -340,6 +634,42 @@ fn parse_high_byte_mapping_through_table(data: &[u8], code_point: u32) -> Option
     u16::try_from((i32::from(glyph) + i32::from(sub_header.id_delta)) % 65536).ok()
 }
 
+fn codepoints_high_byte_mapping_through_table(
+    data: &[u8],
+    f: &mut dyn FnMut(u32, u16),
+) -> Option<()> {
+    let mut s = Stream::new(data);
+    s.skip::<u16>(); // format
+    s.skip::<u16>(); // length
+    s.skip::<u16>(); // language
+    let sub_header_keys = s.read_array16::<u16>(256)?;
+    let sub_headers_count = sub_header_keys.into_iter().map(|n| n / 8).max()? + 1;
+    let sub_headers = s.read_array16::<SubHeaderRecord>(sub_headers_count)?;
+
+    for high_byte in 0..256u16 {
+        let i = sub_header_keys.get(high_byte)? / 8;
+        if i == 0 {
+            // 'SubHeader 0 is special: it is used for single-byte character codes.'
+            let c = u32::from(high_byte);
+            if let Some(id) = parse_high_byte_mapping_through_table(data, c) {
+                f(c, id);
+            }
+        } else {
+            let sub_header = sub_headers.get(i)?;
+            let first_code = sub_header.first_code;
+            let range_end = first_code.checked_add(sub_header.entry_count)?.min(256);
+            for low_byte in first_code..range_end {
+                let c = u32::from(high_byte) << 8 | u32::from(low_byte);
+                if let Some(id) = parse_high_byte_mapping_through_table(data, c) {
+                    f(c, id);
+                }
+            }
+        }
+    }
+
+    Some(())
+}
+
 // https://docs.microsoft.com/en-us/typography/opentype/spec/cmap#format-4-segment-mapping-to-delta-values
 fn parse_segment_mapping_to_delta_values(data: &[u8], code_point: u32) -> Option<u16> {
     // This subtable supports code points only in a u16 range.
@@ -401,6 +731,30 @@ fn parse_segment_mapping_to_delta_values(data: &[u8], code_point: u32) -> Option
     None
 }
 
+fn codepoints_segment_mapping_to_delta_values(
+    data: &[u8],
+    f: &mut dyn FnMut(u32, u16),
+) -> Option<()> {
+    let mut s = Stream::new(data);
+    s.advance(6); // format + length + language
+    let seg_count = s.read::<u16>()? / 2;
+    s.advance(6); // searchRange + entrySelector + rangeShift
+    let end_codes = s.read_array16::<u16>(seg_count)?;
+    s.skip::<u16>(); // reservedPad
+    let start_codes = s.read_array16::<u16>(seg_count)?;
+
+    for (start, end) in start_codes.into_iter().zip(end_codes) {
+        // Segments are usually small, so we can simply use a lookup for each code point.
+        for c in u32::from(start)..=u32::from(end) {
+            if let Some(id) = parse_segment_mapping_to_delta_values(data, c) {
+                f(c, id);
+            }
+        }
+    }
+
+    Some(())
+}
+
 // https://docs.microsoft.com/en-us/typography/opentype/spec/cmap#format-6-trimmed-table-mapping
 fn parse_trimmed_table_mapping(mut s: Stream, code_point: u32) -> Option<u16> {
     // This subtable supports code points only in a u16 range.
@@ -450,6 +804,19 @@ impl FromData for SequentialMapGroup {
     }
 }
 
+// https://docs.microsoft.com/en-us/typography/opentype/spec/cmap#format-8-mixed-16-bit-and-32-bit-coverage
+fn parse_mixed_coverage(mut s: Stream, code_point: u32) -> Option<u16> {
+    s.skip::<u16>(); // reserved
+    s.skip::<u32>(); // length
+    s.skip::<u32>(); // language
+    // The `is32` bitmap is only needed to split a byte stream into 16-bit and 32-bit
+    // character codes. Since we already have a code point, it can be ignored.
+    s.advance_checked(8192)?;
+    let count: u32 = s.read()?;
+    let groups = s.read_array32::<SequentialMapGroup>(count)?;
+    parse_sequential_map_groups(groups, code_point, true)
+}
+
 // + ManyToOneRangeMappings
 // https://docs.microsoft.com/en-us/typography/opentype/spec/cmap#format-12-segmented-coverage
 // https://docs.microsoft.com/en-us/typography/opentype/spec/cmap#format-13-many-to-one-range-mappings
@@ -459,25 +826,41 @@ fn parse_segmented_coverage(mut s: Stream, code_point: u32, format: Format) -> O
     s.skip::<u32>(); // language
     let count: u32 = s.read()?;
     let groups = s.read_array32::<SequentialMapGroup>(count)?;
-    for group in groups {
-        let start_char_code = group.start_char_code;
-        if code_point >= start_char_code && code_point <= group.end_char_code {
-            let id = if format == Format::SegmentedCoverage {
-                group.start_glyph_id.checked_add(code_point)?.checked_sub(start_char_code)?
-            } else {
-                group.start_glyph_id
-            };
+    parse_sequential_map_groups(groups, code_point, format == Format::SegmentedCoverage)
+}
 
-            return u16::try_from(id).ok();
+fn parse_sequential_map_groups(
+    groups: LazyArray32<SequentialMapGroup>,
+    code_point: u32,
+    is_sequential: bool,
+) -> Option<u16> {
+    use core::cmp::Ordering;
+
+    // Groups are sorted by the start character code and cannot overlap.
+    let (_, group) = groups.binary_search_by(|group| {
+        if group.start_char_code > code_point {
+            Ordering::Greater
+        } else if group.end_char_code < code_point {
+            Ordering::Less
+        } else {
+            Ordering::Equal
         }
-    }
+    })?;
 
-    None
+    let id = if is_sequential {
+        group.start_glyph_id.checked_add(code_point)?.checked_sub(group.start_char_code)?
+    } else {
+        group.start_glyph_id
+    };
+
+    u16::try_from(id).ok()
 }
 
 
+/// A [subtable format](https://docs.microsoft.com/en-us/typography/opentype/spec/cmap#subtable-formats).
 #[derive(Clone, Copy, PartialEq, Debug)]
-enum Format {
+#[allow(missing_docs)]
+pub enum Format {
     ByteEncodingTable = 0,
     HighByteMappingThroughTable = 2,
     SegmentMappingToDeltaValues = 4,
@@ -518,8 +901,339 @@ fn is_unicode_encoding(format: Format, platform_id: PlatformId, encoding_id: u16
             // on the Windows platform must have a format 12 subtable for platform ID 3,
             // encoding ID 10."
                encoding_id == WINDOWS_UNICODE_FULL_REPERTOIRE_ENCODING_ID
-            && format == Format::SegmentedCoverage
+            && (format == Format::SegmentedCoverage || format == Format::MixedCoverage)
         }
         _ => false,
     }
 }
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::writer;
+    use writer::TtfType::*;
+
+    fn gen_cmap(subtables: &[&writer::Writer]) -> std::vec::Vec<u8> {
+        let mut w = writer::Writer::new();
+        w.write(UInt16(0)); // version
+        w.write(UInt16(subtables.len() as u16)); // number of encoding records
+
+        let mut offset = 4 + subtables.len() * 8;
+        for subtable in subtables {
+            w.write(UInt16(0)); // platform ID
+            w.write(UInt16(4)); // encoding ID
+            w.write(UInt32(offset as u32)); // subtable offset
+            offset += subtable.data.len();
+        }
+
+        for subtable in subtables {
+            w.data.extend_from_slice(&subtable.data);
+        }
+
+        w.data
+    }
+
+    fn collect_codepoints(table: &Table) -> std::vec::Vec<(u32, u16)> {
+        let mut mappings = std::vec::Vec::new();
+        codepoints(table, |c, id| mappings.push((c, id.0)));
+        mappings.sort();
+        mappings
+    }
+
+    fn mixed_coverage_subtable() -> writer::Writer {
+        let mut w = writer::Writer::new();
+        w.write(UInt16(8)); // format
+        w.write(UInt16(0)); // reserved
+        w.write(UInt32(0)); // length
+        w.write(UInt32(0)); // language
+
+        // is32
+        // Mark U+20000 (high surrogate 0xD840) as a start of a 32-bit character code.
+        for i in 0..8192 {
+            w.write(UInt8(if i == 0xD840 / 8 { 0x80 >> (0xD840 % 8) } else { 0 }));
+        }
+
+        w.write(UInt32(3)); // number of groups
+        // Group 1
+        w.write(UInt32(0x41)); // start char code
+        w.write(UInt32(0x43)); // end char code
+        w.write(UInt32(10)); // start glyph ID
+        // Group 2
+        w.write(UInt32(0x4E00)); // start char code
+        w.write(UInt32(0x4E00)); // end char code
+        w.write(UInt32(20)); // start glyph ID
+        // Group 3
+        w.write(UInt32(0x20000)); // start char code
+        w.write(UInt32(0x20002)); // end char code
+        w.write(UInt32(30)); // start glyph ID
+        w
+    }
+
+    #[test]
+    fn mixed_coverage() {
+        let data = gen_cmap(&[&mixed_coverage_subtable()]);
+        let table = Table::parse(&data).unwrap();
+        assert_eq!(glyph_index(&table, 'A'), Some(GlyphId(10)));
+        assert_eq!(glyph_index(&table, 'C'), Some(GlyphId(12)));
+        assert_eq!(glyph_index(&table, 'D'), None);
+        assert_eq!(glyph_index(&table, '\u{4E00}'), Some(GlyphId(20)));
+        assert_eq!(glyph_index(&table, '\u{20000}'), Some(GlyphId(30)));
+        assert_eq!(glyph_index(&table, '\u{20002}'), Some(GlyphId(32)));
+        assert_eq!(glyph_index(&table, '\u{20003}'), None);
+    }
+
+    #[test]
+    fn mixed_coverage_truncated() {
+        let mut w = writer::Writer::new();
+        w.write(UInt16(8)); // format
+        w.write(UInt16(0)); // reserved
+        w.write(UInt32(0)); // length
+        w.write(UInt32(0)); // language
+        // is32 is missing
+
+        let data = gen_cmap(&[&w]);
+        let table = Table::parse(&data).unwrap();
+        assert_eq!(glyph_index(&table, 'A'), None);
+    }
+
+    #[test]
+    fn mixed_coverage_glyph_overflow() {
+        let mut w = writer::Writer::new();
+        w.write(UInt16(8)); // format
+        w.write(UInt16(0)); // reserved
+        w.write(UInt32(0)); // length
+        w.write(UInt32(0)); // language
+        for _ in 0..8192 {
+            w.write(UInt8(0)); // is32
+        }
+        w.write(UInt32(1)); // number of groups
+        w.write(UInt32(0x41)); // start char code
+        w.write(UInt32(0x42)); // end char code
+        w.write(UInt32(0xFFFF)); // start glyph ID
+
+        let data = gen_cmap(&[&w]);
+        let table = Table::parse(&data).unwrap();
+        assert_eq!(glyph_index(&table, 'A'), Some(GlyphId(0xFFFF)));
+        assert_eq!(glyph_index(&table, 'B'), None);
+    }
+
+    #[test]
+    fn mixed_coverage_codepoints() {
+        let data = gen_cmap(&[&mixed_coverage_subtable()]);
+        let table = Table::parse(&data).unwrap();
+        assert_eq!(collect_codepoints(&table), &[
+            (0x41, 10), (0x42, 11), (0x43, 12),
+            (0x4E00, 20),
+            (0x20000, 30), (0x20001, 31), (0x20002, 32),
+        ]);
+    }
+
+    #[test]
+    fn byte_encoding_codepoints() {
+        let mut w = writer::Writer::new();
+        w.write(UInt16(0)); // format
+        w.write(UInt16(262)); // length
+        w.write(UInt16(0)); // language
+        for i in 0..256 {
+            w.write(UInt8(if i == 0x41 { 5 } else if i == 0x61 { 6 } else { 0 }));
+        }
+
+        let data = gen_cmap(&[&w]);
+        let table = Table::parse(&data).unwrap();
+        assert_eq!(collect_codepoints(&table), &[(0x41, 5), (0x61, 6)]);
+    }
+
+    #[test]
+    fn codepoints_subtables_priority() {
+        let mut w1 = writer::Writer::new();
+        w1.write(UInt16(6)); // format
+        w1.write(UInt16(0)); // length
+        w1.write(UInt16(0)); // language
+        w1.write(UInt16(0x41)); // first code point
+        w1.write(UInt16(2)); // count
+        w1.write(UInt16(1)); // glyph
+        w1.write(UInt16(0)); // glyph
+
+        let mut w2 = writer::Writer::new();
+        w2.write(UInt16(12)); // format
+        w2.write(UInt16(0)); // reserved
+        w2.write(UInt32(0)); // length
+        w2.write(UInt32(0)); // language
+        w2.write(UInt32(1)); // number of groups
+        w2.write(UInt32(0x41)); // start char code
+        w2.write(UInt32(0x43)); // end char code
+        w2.write(UInt32(10)); // start glyph ID
+
+        let data = gen_cmap(&[&w1, &w2]);
+        let table = Table::parse(&data).unwrap();
+        // U+0041 and U+0042 are resolved by the first subtable,
+        // even though U+0042 maps to glyph 0 there.
+        assert_eq!(collect_codepoints(&table), &[(0x41, 1), (0x43, 12)]);
+        assert_eq!(glyph_index(&table, 'B'), Some(GlyphId(0)));
+    }
+
+    #[test]
+    fn codepoints_shared_subtable() {
+        let mut w = writer::Writer::new();
+        w.write(UInt16(0)); // version
+        w.write(UInt16(2)); // number of encoding records
+        // Record 1
+        w.write(UInt16(0)); // platform ID
+        w.write(UInt16(4)); // encoding ID
+        w.write(UInt32(20)); // subtable offset
+        // Record 2
+        w.write(UInt16(3)); // platform ID
+        w.write(UInt16(10)); // encoding ID
+        w.write(UInt32(20)); // subtable offset
+
+        // Subtable
+        w.write(UInt16(12)); // format
+        w.write(UInt16(0)); // reserved
+        w.write(UInt32(0)); // length
+        w.write(UInt32(0)); // language
+        w.write(UInt32(3)); // number of groups
+        // Group 1
+        w.write(UInt32(0x41)); // start char code
+        w.write(UInt32(0x42)); // end char code
+        w.write(UInt32(1)); // start glyph ID
+        // Group 2
+        w.write(UInt32(0x61)); // start char code
+        w.write(UInt32(0x61)); // end char code
+        w.write(UInt32(3)); // start glyph ID
+        // Group 3
+        w.write(UInt32(0x1F600)); // start char code
+        w.write(UInt32(0x1F600)); // end char code
+        w.write(UInt32(4)); // start glyph ID
+
+        let table = Table::parse(&w.data).unwrap();
+        assert_eq!(glyph_index(&table, 'A'), Some(GlyphId(1)));
+        assert_eq!(glyph_index(&table, 'B'), Some(GlyphId(2)));
+        assert_eq!(glyph_index(&table, 'a'), Some(GlyphId(3)));
+        assert_eq!(glyph_index(&table, '\u{1F600}'), Some(GlyphId(4)));
+        assert_eq!(glyph_index(&table, '@'), None);
+        assert_eq!(glyph_index(&table, 'C'), None);
+        assert_eq!(glyph_index(&table, '\u{1F601}'), None);
+
+        assert_eq!(collect_codepoints(&table), &[(0x41, 1), (0x42, 2), (0x61, 3), (0x1F600, 4)]);
+    }
+
+    #[test]
+    fn subtables_metadata() {
+        let mut w = writer::Writer::new();
+        w.write(UInt16(0)); // version
+        w.write(UInt16(2)); // number of encoding records
+        // Record 1
+        w.write(UInt16(1)); // platform ID
+        w.write(UInt16(0)); // encoding ID
+        w.write(UInt32(20)); // subtable offset
+        // Record 2
+        w.write(UInt16(3)); // platform ID
+        w.write(UInt16(10)); // encoding ID
+        w.write(UInt32(282)); // subtable offset
+
+        // Subtable 1
+        w.write(UInt16(0)); // format
+        w.write(UInt16(262)); // length
+        w.write(UInt16(17)); // language
+        for i in 0..256 {
+            w.write(UInt8(if i == 0x41 { 5 } else { 0 }));
+        }
+
+        // Subtable 2
+        w.write(UInt16(12)); // format
+        w.write(UInt16(0)); // reserved
+        w.write(UInt32(0)); // length
+        w.write(UInt32(0)); // language
+        w.write(UInt32(1)); // number of groups
+        w.write(UInt32(0x1F600)); // start char code
+        w.write(UInt32(0x1F601)); // end char code
+        w.write(UInt32(7)); // start glyph ID
+
+        let table = Table::parse(&w.data).unwrap();
+        let mut subtables = table.subtables();
+
+        let subtable = subtables.next().unwrap();
+        assert_eq!(subtable.platform_id, PlatformId::Macintosh);
+        assert_eq!(subtable.encoding_id, 0);
+        assert_eq!(subtable.format, Format::ByteEncodingTable);
+        assert_eq!(subtable.language, 17);
+        assert!(!subtable.is_unicode());
+        assert_eq!(subtable.glyph_index(0x41), Some(GlyphId(5)));
+        assert_eq!(subtable.glyph_index(0x100), None);
+
+        let subtable = subtables.next().unwrap();
+        assert_eq!(subtable.platform_id, PlatformId::Windows);
+        assert_eq!(subtable.encoding_id, 10);
+        assert_eq!(subtable.format, Format::SegmentedCoverage);
+        assert_eq!(subtable.language, 0);
+        assert!(subtable.is_unicode());
+        assert_eq!(subtable.glyph_index(0x1F601), Some(GlyphId(8)));
+
+        assert!(subtables.next().is_none());
+
+        // The Macintosh subtable must not be used for Unicode lookups.
+        assert_eq!(glyph_index(&table, 'A'), None);
+    }
+
+    #[test]
+    fn unicode_variation_sequences() {
+        let mut w1 = writer::Writer::new();
+        w1.write(UInt16(12)); // format
+        w1.write(UInt16(0)); // reserved
+        w1.write(UInt32(0)); // length
+        w1.write(UInt32(0)); // language
+        w1.write(UInt32(1)); // number of groups
+        w1.write(UInt32(0x41)); // start char code
+        w1.write(UInt32(0x43)); // end char code
+        w1.write(UInt32(10)); // start glyph ID
+
+        let mut w2 = writer::Writer::new();
+        w2.write(UInt16(14)); // format
+        w2.write(UInt32(0)); // length
+        w2.write(UInt32(2)); // number of variation selector records
+        // Record 1
+        w2.write(UInt24(0xFE00)); // variation selector
+        w2.write(UInt32(32)); // default UVS offset
+        w2.write(UInt32(40)); // non-default UVS offset
+        // Record 2
+        w2.write(UInt24(0xFE0F)); // variation selector
+        w2.write(UInt32(0)); // default UVS offset
+        w2.write(UInt32(40)); // non-default UVS offset
+        // Default UVS
+        w2.write(UInt32(1)); // number of ranges
+        w2.write(UInt24(0x41)); // start code point
+        w2.write(UInt8(1)); // additional count
+        // Non-default UVS
+        w2.write(UInt32(1)); // number of mappings
+        w2.write(UInt24(0x43)); // code point
+        w2.write(UInt16(20)); // glyph ID
+
+        let data = gen_cmap(&[&w1, &w2]);
+        let table = Table::parse(&data).unwrap();
+
+        // Both the first and the last code points of a range are included.
+        assert_eq!(glyph_variation_index(&table, 'A', '\u{FE00}'), Some(GlyphId(10)));
+        assert_eq!(glyph_variation_index(&table, 'B', '\u{FE00}'), Some(GlyphId(11)));
+        assert_eq!(glyph_variation_index(&table, 'C', '\u{FE00}'), Some(GlyphId(20)));
+        assert_eq!(glyph_variation_index(&table, 'A', '\u{FE0F}'), None);
+        assert_eq!(glyph_variation_index(&table, 'A', '\u{FE01}'), None);
+
+        let mut selectors = variation_selectors(&table);
+
+        let selector = selectors.next().unwrap();
+        assert_eq!(selector.variation, 0xFE00);
+        assert_eq!(selector.default_ranges.len(), 1);
+        assert_eq!(selector.default_ranges.get(0), Some(UnicodeRange { start: 0x41, end: 0x42 }));
+        assert_eq!(selector.non_default_mappings.len(), 1);
+        assert_eq!(selector.non_default_mappings.get(0),
+                   Some(VariationMapping { code_point: 0x43, glyph_id: GlyphId(20) }));
+
+        let selector = selectors.next().unwrap();
+        assert_eq!(selector.variation, 0xFE0F);
+        assert_eq!(selector.default_ranges.len(), 0);
+        assert_eq!(selector.non_default_mappings.len(), 1);
+
+        assert!(selectors.next().is_none());
+    }
+}
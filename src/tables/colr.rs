@@ -0,0 +1,1416 @@
+/*!
+A [Color Table](https://docs.microsoft.com/en-us/typography/opentype/spec/colr)
+implementation.
+
+COLRv0 layers are accessible via `Font::glyph_color_layers()`.
+Both COLRv0 and COLRv1 glyphs can be drawn via `Font::paint_color_glyph()`
+using the `Painter` trait.
+
+Colors are resolved using the `CPAL` table and variation deltas
+are applied using the current variation coordinates.
+*/
+
+use core::fmt;
+
+use crate::{GlyphId, NormalizedCoord, RgbaColor};
+use crate::parser::{Stream, FromData, LazyArray16, LazyArrayIter16, LazyArray32};
+use crate::parser::{Offset, Offset32, U24, F2DOT14, Fixed, NumFrom, f32_bound};
+use crate::tables::cpal;
+use crate::tables::hvar::DeltaSetIndexMap;
+use crate::var_store::ItemVariationStore;
+
+// Paint graphs are allowed to reference the same paint multiple times,
+// so we have to protect ourselves from cycles.
+const MAX_PAINT_DEPTH: u8 = 64;
+
+// A depth limit alone is not enough, since each `PaintColrLayers` level
+// can reference up to 255 paints. So the total number of visited paints is limited too.
+const MAX_PAINT_VISITS: u32 = 1 << 16;
+
+const FOREGROUND_PALETTE_ENTRY: u16 = 0xFFFF;
+const NO_VARIATION_INDEX: u32 = 0xFFFF_FFFF;
+
+
+#[derive(Clone, Copy)]
+struct BaseGlyphRecord {
+    glyph_id: GlyphId,
+    first_layer_index: u16,
+    layers_count: u16,
+}
+
+impl FromData for BaseGlyphRecord {
+    const SIZE: usize = 6;
+
+    #[inline]
+    fn parse(data: &[u8]) -> Option<Self> {
+        let mut s = Stream::new(data);
+        Some(BaseGlyphRecord {
+            glyph_id: s.read()?,
+            first_layer_index: s.read()?,
+            layers_count: s.read()?,
+        })
+    }
+}
+
+
+/// A COLRv0 glyph layer.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Layer {
+    /// A glyph that defines the layer's shape.
+    pub glyph_id: GlyphId,
+    /// An entry index in the current `CPAL` palette.
+    ///
+    /// `0xFFFF` indicates a text foreground color.
+    pub palette_index: u16,
+}
+
+impl Layer {
+    /// Checks that layer uses a text foreground color.
+    #[inline]
+    pub fn is_foreground(&self) -> bool {
+        self.palette_index == FOREGROUND_PALETTE_ENTRY
+    }
+}
+
+impl FromData for Layer {
+    const SIZE: usize = 4;
+
+    #[inline]
+    fn parse(data: &[u8]) -> Option<Self> {
+        let mut s = Stream::new(data);
+        Some(Layer {
+            glyph_id: s.read()?,
+            palette_index: s.read()?,
+        })
+    }
+}
+
+
+/// An iterator over COLRv0 glyph layers.
+///
+/// Layers are ordered from bottom to top.
+pub type Layers<'a> = LazyArrayIter16<'a, Layer>;
+
+
+#[derive(Clone, Copy)]
+struct BaseGlyphPaintRecord {
+    glyph_id: GlyphId,
+    paint_offset: Offset32,
+}
+
+impl FromData for BaseGlyphPaintRecord {
+    const SIZE: usize = 6;
+
+    #[inline]
+    fn parse(data: &[u8]) -> Option<Self> {
+        let mut s = Stream::new(data);
+        Some(BaseGlyphPaintRecord {
+            glyph_id: s.read()?,
+            paint_offset: s.read()?,
+        })
+    }
+}
+
+
+#[derive(Clone, Copy)]
+struct ClipRecord {
+    start_glyph_id: GlyphId,
+    end_glyph_id: GlyphId,
+    clip_box_offset: U24,
+}
+
+impl FromData for ClipRecord {
+    const SIZE: usize = 7;
+
+    #[inline]
+    fn parse(data: &[u8]) -> Option<Self> {
+        let mut s = Stream::new(data);
+        Some(ClipRecord {
+            start_glyph_id: s.read()?,
+            end_glyph_id: s.read()?,
+            clip_box_offset: s.read()?,
+        })
+    }
+}
+
+
+/// A clip box.
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[allow(missing_docs)]
+pub struct ClipBox {
+    pub x_min: f32,
+    pub y_min: f32,
+    pub x_max: f32,
+    pub y_max: f32,
+}
+
+
+/// A 2D affine transform.
+///
+/// Maps `(x, y)` to `(a * x + c * y + e, b * x + d * y + f)`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[allow(missing_docs)]
+pub struct Transform {
+    pub a: f32,
+    pub b: f32,
+    pub c: f32,
+    pub d: f32,
+    pub e: f32,
+    pub f: f32,
+}
+
+impl Transform {
+    #[inline]
+    fn new(a: f32, b: f32, c: f32, d: f32, e: f32, f: f32) -> Self {
+        Transform { a, b, c, d, e, f }
+    }
+
+    #[inline]
+    fn new_translate(tx: f32, ty: f32) -> Self {
+        Transform::new(1.0, 0.0, 0.0, 1.0, tx, ty)
+    }
+
+    #[inline]
+    fn new_scale(sx: f32, sy: f32) -> Self {
+        Transform::new(sx, 0.0, 0.0, sy, 0.0, 0.0)
+    }
+
+    /// `angle` is in half-turns, i.e. 1.0 is 180°.
+    #[inline]
+    fn new_rotate(angle: f32) -> Self {
+        let (sin, cos) = (sin_pi(angle), cos_pi(angle));
+        Transform::new(cos, sin, -sin, cos, 0.0, 0.0)
+    }
+
+    /// Angles are in half-turns, i.e. 1.0 is 180°.
+    #[inline]
+    fn new_skew(x_angle: f32, y_angle: f32) -> Self {
+        let x = tan_pi(-x_angle);
+        let y = tan_pi(y_angle);
+        Transform::new(1.0, y, x, 1.0, 0.0, 0.0)
+    }
+
+    /// Makes the transform to be applied around the specified point.
+    #[inline]
+    fn around_center(self, cx: f32, cy: f32) -> Self {
+        Transform {
+            e: self.e + cx - self.a * cx - self.c * cy,
+            f: self.f + cy - self.b * cx - self.d * cy,
+            ..self
+        }
+    }
+}
+
+
+/// A gradient extend mode.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[allow(missing_docs)]
+pub enum GradientExtend {
+    Pad,
+    Repeat,
+    Reflect,
+}
+
+
+/// A [composite mode](https://docs.microsoft.com/en-us/typography/opentype/spec/colr#compositemode-enumeration).
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[allow(missing_docs)]
+pub enum CompositeMode {
+    Clear,
+    Source,
+    Destination,
+    SourceOver,
+    DestinationOver,
+    SourceIn,
+    DestinationIn,
+    SourceOut,
+    DestinationOut,
+    SourceAtop,
+    DestinationAtop,
+    Xor,
+    Plus,
+    Screen,
+    Overlay,
+    Darken,
+    Lighten,
+    ColorDodge,
+    ColorBurn,
+    HardLight,
+    SoftLight,
+    Difference,
+    Exclusion,
+    Multiply,
+    Hue,
+    Saturation,
+    Color,
+    Luminosity,
+}
+
+impl CompositeMode {
+    fn from_u8(n: u8) -> Option<Self> {
+        Some(match n {
+            0 => CompositeMode::Clear,
+            1 => CompositeMode::Source,
+            2 => CompositeMode::Destination,
+            3 => CompositeMode::SourceOver,
+            4 => CompositeMode::DestinationOver,
+            5 => CompositeMode::SourceIn,
+            6 => CompositeMode::DestinationIn,
+            7 => CompositeMode::SourceOut,
+            8 => CompositeMode::DestinationOut,
+            9 => CompositeMode::SourceAtop,
+            10 => CompositeMode::DestinationAtop,
+            11 => CompositeMode::Xor,
+            12 => CompositeMode::Plus,
+            13 => CompositeMode::Screen,
+            14 => CompositeMode::Overlay,
+            15 => CompositeMode::Darken,
+            16 => CompositeMode::Lighten,
+            17 => CompositeMode::ColorDodge,
+            18 => CompositeMode::ColorBurn,
+            19 => CompositeMode::HardLight,
+            20 => CompositeMode::SoftLight,
+            21 => CompositeMode::Difference,
+            22 => CompositeMode::Exclusion,
+            23 => CompositeMode::Multiply,
+            24 => CompositeMode::Hue,
+            25 => CompositeMode::Saturation,
+            26 => CompositeMode::Color,
+            27 => CompositeMode::Luminosity,
+            _ => return None,
+        })
+    }
+}
+
+
+/// A gradient color stop.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ColorStop {
+    /// A stop position on the color line.
+    pub offset: f32,
+    /// A resolved stop color.
+    pub color: RgbaColor,
+}
+
+
+/// A gradient color line.
+#[derive(Clone, Copy)]
+pub struct ColorLine<'a> {
+    ctx: Context<'a>,
+    extend: GradientExtend,
+    stops: &'a [u8],
+    is_variable: bool,
+}
+
+impl<'a> ColorLine<'a> {
+    fn parse(ctx: Context<'a>, data: &'a [u8], is_variable: bool) -> Option<Self> {
+        let mut s = Stream::new(data);
+        let extend = match s.read::<u8>()? {
+            1 => GradientExtend::Repeat,
+            2 => GradientExtend::Reflect,
+            // 'If a ColorLine has an unrecognized extend value, it must be treated as pad.'
+            _ => GradientExtend::Pad,
+        };
+
+        let count: u16 = s.read()?;
+        let stops = s.read_bytes(usize::from(count) * ColorLine::stop_size(is_variable))?;
+
+        Some(ColorLine {
+            ctx,
+            extend,
+            stops,
+            is_variable,
+        })
+    }
+
+    #[inline]
+    fn stop_size(is_variable: bool) -> usize {
+        if is_variable { 10 } else { 6 }
+    }
+
+    /// Returns gradient's extend mode.
+    #[inline]
+    pub fn extend(&self) -> GradientExtend {
+        self.extend
+    }
+
+    /// Returns gradient's color stops.
+    ///
+    /// Stops are returned in the font order and are not guaranteed to be sorted.
+    #[inline]
+    pub fn stops(&self) -> ColorStops<'a> {
+        ColorStops {
+            line: *self,
+            offset: 0,
+        }
+    }
+}
+
+impl fmt::Debug for ColorLine<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ColorLine")
+            .field("extend", &self.extend)
+            .field("stops", &DebugStops(*self))
+            .finish()
+    }
+}
+
+struct DebugStops<'a>(ColorLine<'a>);
+
+impl fmt::Debug for DebugStops<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.0.stops()).finish()
+    }
+}
+
+
+/// An iterator over gradient color stops.
+#[derive(Clone, Copy)]
+#[allow(missing_debug_implementations)]
+pub struct ColorStops<'a> {
+    line: ColorLine<'a>,
+    offset: usize,
+}
+
+impl Iterator for ColorStops<'_> {
+    type Item = ColorStop;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut s = Stream::new_at(self.line.stops, self.offset)?;
+        let mut offset = s.read::<F2DOT14>()?.to_f32();
+        let palette_index: u16 = s.read()?;
+        let mut alpha = s.read::<F2DOT14>()?.to_f32();
+        if self.line.is_variable {
+            let base: u32 = s.read()?;
+            offset += self.line.ctx.f2dot14_delta(base, 0);
+            alpha += self.line.ctx.f2dot14_delta(base, 1);
+        }
+
+        self.offset += ColorLine::stop_size(self.line.is_variable);
+
+        Some(ColorStop {
+            offset,
+            color: self.line.ctx.color(palette_index, alpha),
+        })
+    }
+}
+
+
+/// A linear gradient.
+#[derive(Clone, Copy, Debug)]
+#[allow(missing_docs)]
+pub struct LinearGradient<'a> {
+    pub x0: f32,
+    pub y0: f32,
+    pub x1: f32,
+    pub y1: f32,
+    /// A rotation point.
+    pub x2: f32,
+    /// A rotation point.
+    pub y2: f32,
+    pub color_line: ColorLine<'a>,
+}
+
+
+/// A radial gradient.
+#[derive(Clone, Copy, Debug)]
+#[allow(missing_docs)]
+pub struct RadialGradient<'a> {
+    pub x0: f32,
+    pub y0: f32,
+    pub r0: f32,
+    pub x1: f32,
+    pub y1: f32,
+    pub r1: f32,
+    pub color_line: ColorLine<'a>,
+}
+
+
+/// A sweep gradient.
+#[derive(Clone, Copy, Debug)]
+pub struct SweepGradient<'a> {
+    /// Center X.
+    pub center_x: f32,
+    /// Center Y.
+    pub center_y: f32,
+    /// Start angle in counter-clockwise degrees.
+    pub start_angle: f32,
+    /// End angle in counter-clockwise degrees.
+    pub end_angle: f32,
+    /// A color line.
+    pub color_line: ColorLine<'a>,
+}
+
+
+/// A paint that fills the current clip area.
+#[derive(Clone, Copy, Debug)]
+#[allow(missing_docs)]
+pub enum Paint<'a> {
+    Solid(RgbaColor),
+    LinearGradient(LinearGradient<'a>),
+    RadialGradient(RadialGradient<'a>),
+    SweepGradient(SweepGradient<'a>),
+}
+
+
+/// A trait for color glyphs painting.
+///
+/// Every `push_*` method call will be followed by a matching `pop_*` call.
+/// COLRv0 glyphs are represented as a sequence of glyph clips filled with a solid color.
+pub trait Painter<'a> {
+    /// Pushes a clip path defined by a glyph outline.
+    ///
+    /// The outline can be retrieved via `Font::outline_glyph()`.
+    fn push_clip(&mut self, glyph_id: GlyphId);
+
+    /// Pushes a rectangular clip.
+    fn push_clip_box(&mut self, clip_box: ClipBox);
+
+    /// Pops the last clip.
+    fn pop_clip(&mut self);
+
+    /// Fills the current clip area.
+    fn paint(&mut self, paint: Paint<'a>);
+
+    /// Pushes a new layer.
+    ///
+    /// Everything painted until the matching `pop_layer` must be composited
+    /// onto the content below using the specified mode.
+    fn push_layer(&mut self, mode: CompositeMode);
+
+    /// Pops the last layer.
+    fn pop_layer(&mut self);
+
+    /// Pushes a transform that should be combined with the current one.
+    fn push_transform(&mut self, transform: Transform);
+
+    /// Pops the last transform.
+    fn pop_transform(&mut self);
+}
+
+
+#[derive(Clone, Copy)]
+pub(crate) struct Table<'a> {
+    base_glyphs: LazyArray16<'a, BaseGlyphRecord>,
+    layers: LazyArray16<'a, Layer>,
+    // Offsets are from the beginning of the BaseGlyphList.
+    base_glyph_paints_data: &'a [u8],
+    base_glyph_paints: LazyArray32<'a, BaseGlyphPaintRecord>,
+    // Offsets are from the beginning of the LayerList.
+    layer_paints_data: &'a [u8],
+    layer_paints: LazyArray32<'a, Offset32>,
+    // Offsets are from the beginning of the ClipList.
+    clips_data: &'a [u8],
+    clips: LazyArray32<'a, ClipRecord>,
+    var_index_map: Option<DeltaSetIndexMap<'a>>,
+    variation_store: Option<ItemVariationStore<'a>>,
+}
+
+impl<'a> Table<'a> {
+    pub fn parse(data: &'a [u8]) -> Option<Self> {
+        let mut s = Stream::new(data);
+
+        let version: u16 = s.read()?;
+        if version > 1 {
+            return None;
+        }
+
+        let base_glyphs_count: u16 = s.read()?;
+        let base_glyphs_offset: Offset32 = s.read()?;
+        let layers_offset: Offset32 = s.read()?;
+        let layers_count: u16 = s.read()?;
+
+        let base_glyphs = Stream::new_at(data, base_glyphs_offset.to_usize())?
+            .read_array16(base_glyphs_count)?;
+        let layers = Stream::new_at(data, layers_offset.to_usize())?
+            .read_array16(layers_count)?;
+
+        let mut table = Table {
+            base_glyphs,
+            layers,
+            base_glyph_paints_data: &[],
+            base_glyph_paints: LazyArray32::default(),
+            layer_paints_data: &[],
+            layer_paints: LazyArray32::default(),
+            clips_data: &[],
+            clips: LazyArray32::default(),
+            var_index_map: None,
+            variation_store: None,
+        };
+
+        if version == 0 {
+            return Some(table);
+        }
+
+        let base_glyph_list_offset: Option<Offset32> = s.read()?;
+        let layer_list_offset: Option<Offset32> = s.read()?;
+        let clip_list_offset: Option<Offset32> = s.read()?;
+        let var_index_map_offset: Option<Offset32> = s.read()?;
+        let var_store_offset: Option<Offset32> = s.read()?;
+
+        if let Some(offset) = base_glyph_list_offset {
+            let data = data.get(offset.to_usize()..)?;
+            let mut s = Stream::new(data);
+            let count: u32 = s.read()?;
+            table.base_glyph_paints = s.read_array32(count)?;
+            table.base_glyph_paints_data = data;
+        }
+
+        if let Some(offset) = layer_list_offset {
+            let data = data.get(offset.to_usize()..)?;
+            let mut s = Stream::new(data);
+            let count: u32 = s.read()?;
+            table.layer_paints = s.read_array32(count)?;
+            table.layer_paints_data = data;
+        }
+
+        if let Some(offset) = clip_list_offset {
+            let data = data.get(offset.to_usize()..)?;
+            let mut s = Stream::new(data);
+            let format: u8 = s.read()?;
+            if format == 1 {
+                let count: u32 = s.read()?;
+                table.clips = s.read_array32(count)?;
+                table.clips_data = data;
+            }
+        }
+
+        if let Some(offset) = var_index_map_offset {
+            table.var_index_map = data.get(offset.to_usize()..).map(DeltaSetIndexMap::new);
+        }
+
+        if let Some(offset) = var_store_offset {
+            table.variation_store = ItemVariationStore::parse(
+                Stream::new_at(data, offset.to_usize())?
+            );
+        }
+
+        Some(table)
+    }
+
+    pub fn layers(&self, glyph_id: GlyphId) -> Option<Layers<'a>> {
+        let (_, record) = self.base_glyphs.binary_search_by(|r| r.glyph_id.cmp(&glyph_id))?;
+        let end = record.first_layer_index.checked_add(record.layers_count)?;
+        self.layers.slice(record.first_layer_index..end).map(|layers| layers.into_iter())
+    }
+
+    fn base_glyph_paint(&self, glyph_id: GlyphId) -> Option<&'a [u8]> {
+        let (_, record) = self.base_glyph_paints
+            .binary_search_by(|r| r.glyph_id.cmp(&glyph_id))?;
+        self.base_glyph_paints_data.get(record.paint_offset.to_usize()..)
+    }
+
+    fn clip_box(&self, ctx: &Context, glyph_id: GlyphId) -> Option<ClipBox> {
+        use core::cmp::Ordering;
+
+        let (_, record) = self.clips.binary_search_by(|r| {
+            if glyph_id < r.start_glyph_id {
+                Ordering::Greater
+            } else if glyph_id > r.end_glyph_id {
+                Ordering::Less
+            } else {
+                Ordering::Equal
+            }
+        })?;
+
+        let offset = usize::num_from(record.clip_box_offset.0);
+        let mut s = Stream::new_at(self.clips_data, offset)?;
+        let format: u8 = s.read()?;
+        let mut x_min = f32::from(s.read::<i16>()?);
+        let mut y_min = f32::from(s.read::<i16>()?);
+        let mut x_max = f32::from(s.read::<i16>()?);
+        let mut y_max = f32::from(s.read::<i16>()?);
+        if format == 2 {
+            let base: u32 = s.read()?;
+            x_min += ctx.delta(base, 0);
+            y_min += ctx.delta(base, 1);
+            x_max += ctx.delta(base, 2);
+            y_max += ctx.delta(base, 3);
+        }
+
+        Some(ClipBox { x_min, y_min, x_max, y_max })
+    }
+}
+
+
+#[derive(Clone, Copy)]
+struct Context<'a> {
+    table: Table<'a>,
+    palettes: Option<cpal::Table<'a>>,
+    palette: u16,
+    foreground: RgbaColor,
+    coords: &'a [NormalizedCoord],
+}
+
+impl Context<'_> {
+    fn color(&self, palette_entry: u16, alpha: f32) -> RgbaColor {
+        let mut color = if palette_entry == FOREGROUND_PALETTE_ENTRY {
+            self.foreground
+        } else {
+            self.palettes
+                .and_then(|palettes| palettes.get(self.palette, palette_entry))
+                .unwrap_or(RgbaColor::new(0, 0, 0, 0))
+        };
+
+        // We can't use `round()` in `no_std`, so this is the next best thing.
+        color.alpha = (f32::from(color.alpha) * f32_bound(0.0, alpha, 1.0) + 0.5) as u8;
+        color
+    }
+
+    /// Returns a delta in the units of the delta-set, i.e. without any scaling.
+    fn delta(&self, var_index_base: u32, offset: u32) -> f32 {
+        if var_index_base == NO_VARIATION_INDEX || self.coords.is_empty() {
+            return 0.0;
+        }
+
+        self.delta_impl(var_index_base, offset).unwrap_or(0.0)
+    }
+
+    fn delta_impl(&self, var_index_base: u32, offset: u32) -> Option<f32> {
+        let index = var_index_base.checked_add(offset)?;
+        let (outer_index, inner_index) = match self.table.var_index_map {
+            Some(map) => map.map_index(index)?,
+            // 'If no DeltaSetIndexMap is present, then the variation index
+            // is interpreted as an outer/inner index pair.'
+            None => ((index >> 16) as u16, (index & 0xFFFF) as u16),
+        };
+
+        self.table.variation_store?.parse_delta(outer_index, inner_index, self.coords)
+    }
+
+    #[inline]
+    fn f2dot14_delta(&self, var_index_base: u32, offset: u32) -> f32 {
+        self.delta(var_index_base, offset) / 16384.0
+    }
+
+    #[inline]
+    fn fixed_delta(&self, var_index_base: u32, offset: u32) -> f32 {
+        self.delta(var_index_base, offset) / 65536.0
+    }
+}
+
+
+pub(crate) fn paint<'a>(
+    table: Table<'a>,
+    palettes: Option<cpal::Table<'a>>,
+    palette: u16,
+    foreground: RgbaColor,
+    coords: &'a [NormalizedCoord],
+    glyph_id: GlyphId,
+    painter: &mut dyn Painter<'a>,
+) -> Option<()> {
+    let ctx = Context { table, palettes, palette, foreground, coords };
+
+    if table.base_glyph_paint(glyph_id).is_some() {
+        let mut budget = MAX_PAINT_VISITS;
+        return paint_base_glyph(&ctx, glyph_id, painter, 0, &mut budget);
+    }
+
+    for layer in table.layers(glyph_id)? {
+        painter.push_clip(layer.glyph_id);
+        painter.paint(Paint::Solid(ctx.color(layer.palette_index, 1.0)));
+        painter.pop_clip();
+    }
+
+    Some(())
+}
+
+fn paint_base_glyph<'a>(
+    ctx: &Context<'a>,
+    glyph_id: GlyphId,
+    painter: &mut dyn Painter<'a>,
+    depth: u8,
+    budget: &mut u32,
+) -> Option<()> {
+    let data = ctx.table.base_glyph_paint(glyph_id)?;
+    let clip_box = ctx.table.clip_box(ctx, glyph_id);
+    if let Some(clip_box) = clip_box {
+        painter.push_clip_box(clip_box);
+    }
+
+    let res = paint_impl(ctx, data, painter, depth, budget);
+
+    if clip_box.is_some() {
+        painter.pop_clip();
+    }
+
+    res
+}
+
+fn paint_impl<'a>(
+    ctx: &Context<'a>,
+    data: &'a [u8],
+    painter: &mut dyn Painter<'a>,
+    depth: u8,
+    budget: &mut u32,
+) -> Option<()> {
+    if depth >= MAX_PAINT_DEPTH {
+        return None;
+    }
+
+    *budget = budget.checked_sub(1)?;
+
+    let mut s = Stream::new(data);
+    let format: u8 = s.read()?;
+    // Variable paints have the same layout as their non-variable counterparts
+    // followed by a variation index base.
+    let is_variable = format < 32 && format % 2 == 1;
+    match format {
+        1 => {
+            let count: u8 = s.read()?;
+            let first_index: u32 = s.read()?;
+            for i in 0..u32::from(count) {
+                let offset = ctx.table.layer_paints.get(first_index.checked_add(i)?)?;
+                let data = ctx.table.layer_paints_data.get(offset.to_usize()..)?;
+                paint_impl(ctx, data, painter, depth + 1, budget)?;
+            }
+        }
+        2 | 3 => {
+            let palette_index: u16 = s.read()?;
+            let mut alpha = s.read::<F2DOT14>()?.to_f32();
+            if is_variable {
+                alpha += ctx.f2dot14_delta(s.read()?, 0);
+            }
+
+            painter.paint(Paint::Solid(ctx.color(palette_index, alpha)));
+        }
+        4 | 5 => {
+            let color_line = read_offset(data, &mut s)?;
+            let mut x0 = f32::from(s.read::<i16>()?);
+            let mut y0 = f32::from(s.read::<i16>()?);
+            let mut x1 = f32::from(s.read::<i16>()?);
+            let mut y1 = f32::from(s.read::<i16>()?);
+            let mut x2 = f32::from(s.read::<i16>()?);
+            let mut y2 = f32::from(s.read::<i16>()?);
+            if is_variable {
+                let base: u32 = s.read()?;
+                x0 += ctx.delta(base, 0);
+                y0 += ctx.delta(base, 1);
+                x1 += ctx.delta(base, 2);
+                y1 += ctx.delta(base, 3);
+                x2 += ctx.delta(base, 4);
+                y2 += ctx.delta(base, 5);
+            }
+
+            painter.paint(Paint::LinearGradient(LinearGradient {
+                x0, y0, x1, y1, x2, y2,
+                color_line: ColorLine::parse(*ctx, color_line, is_variable)?,
+            }));
+        }
+        6 | 7 => {
+            let color_line = read_offset(data, &mut s)?;
+            let mut x0 = f32::from(s.read::<i16>()?);
+            let mut y0 = f32::from(s.read::<i16>()?);
+            let mut r0 = f32::from(s.read::<u16>()?);
+            let mut x1 = f32::from(s.read::<i16>()?);
+            let mut y1 = f32::from(s.read::<i16>()?);
+            let mut r1 = f32::from(s.read::<u16>()?);
+            if is_variable {
+                let base: u32 = s.read()?;
+                x0 += ctx.delta(base, 0);
+                y0 += ctx.delta(base, 1);
+                r0 += ctx.delta(base, 2);
+                x1 += ctx.delta(base, 3);
+                y1 += ctx.delta(base, 4);
+                r1 += ctx.delta(base, 5);
+            }
+
+            painter.paint(Paint::RadialGradient(RadialGradient {
+                x0, y0, r0, x1, y1, r1,
+                color_line: ColorLine::parse(*ctx, color_line, is_variable)?,
+            }));
+        }
+        8 | 9 => {
+            let color_line = read_offset(data, &mut s)?;
+            let mut center_x = f32::from(s.read::<i16>()?);
+            let mut center_y = f32::from(s.read::<i16>()?);
+            let mut start_angle = s.read::<F2DOT14>()?.to_f32();
+            let mut end_angle = s.read::<F2DOT14>()?.to_f32();
+            if is_variable {
+                let base: u32 = s.read()?;
+                center_x += ctx.delta(base, 0);
+                center_y += ctx.delta(base, 1);
+                start_angle += ctx.f2dot14_delta(base, 2);
+                end_angle += ctx.f2dot14_delta(base, 3);
+            }
+
+            // 'Add 1.0 and multiply by 180° to retrieve counter-clockwise degrees.'
+            painter.paint(Paint::SweepGradient(SweepGradient {
+                center_x,
+                center_y,
+                start_angle: (start_angle + 1.0) * 180.0,
+                end_angle: (end_angle + 1.0) * 180.0,
+                color_line: ColorLine::parse(*ctx, color_line, is_variable)?,
+            }));
+        }
+        10 => {
+            let paint = read_offset(data, &mut s)?;
+            let glyph_id: GlyphId = s.read()?;
+            painter.push_clip(glyph_id);
+            let res = paint_impl(ctx, paint, painter, depth + 1, budget);
+            painter.pop_clip();
+            res?;
+        }
+        11 => {
+            let glyph_id: GlyphId = s.read()?;
+            paint_base_glyph(ctx, glyph_id, painter, depth + 1, budget)?;
+        }
+        12 | 13 => {
+            let paint = read_offset(data, &mut s)?;
+            let mut s = Stream::new(read_offset(data, &mut s)?);
+            let mut transform = Transform::new(
+                s.read::<Fixed>()?.0,
+                s.read::<Fixed>()?.0,
+                s.read::<Fixed>()?.0,
+                s.read::<Fixed>()?.0,
+                s.read::<Fixed>()?.0,
+                s.read::<Fixed>()?.0,
+            );
+            if is_variable {
+                let base: u32 = s.read()?;
+                transform.a += ctx.fixed_delta(base, 0);
+                transform.b += ctx.fixed_delta(base, 1);
+                transform.c += ctx.fixed_delta(base, 2);
+                transform.d += ctx.fixed_delta(base, 3);
+                transform.e += ctx.fixed_delta(base, 4);
+                transform.f += ctx.fixed_delta(base, 5);
+            }
+
+            paint_transformed(ctx, paint, transform, painter, depth, budget)?;
+        }
+        14 | 15 => {
+            let paint = read_offset(data, &mut s)?;
+            let mut dx = f32::from(s.read::<i16>()?);
+            let mut dy = f32::from(s.read::<i16>()?);
+            if is_variable {
+                let base: u32 = s.read()?;
+                dx += ctx.delta(base, 0);
+                dy += ctx.delta(base, 1);
+            }
+
+            let transform = Transform::new_translate(dx, dy);
+            paint_transformed(ctx, paint, transform, painter, depth, budget)?;
+        }
+        16..=23 => {
+            let paint = read_offset(data, &mut s)?;
+            let is_uniform = format >= 20;
+            let is_centered = format == 18 || format == 19 || format >= 22;
+
+            let mut sx = s.read::<F2DOT14>()?.to_f32();
+            let mut sy = if is_uniform { sx } else { s.read::<F2DOT14>()?.to_f32() };
+            let (mut cx, mut cy) = if is_centered {
+                (f32::from(s.read::<i16>()?), f32::from(s.read::<i16>()?))
+            } else {
+                (0.0, 0.0)
+            };
+
+            if is_variable {
+                let base: u32 = s.read()?;
+                let mut i = 0;
+                sx += ctx.f2dot14_delta(base, i);
+                if is_uniform {
+                    sy = sx;
+                } else {
+                    i += 1;
+                    sy += ctx.f2dot14_delta(base, i);
+                }
+
+                if is_centered {
+                    cx += ctx.delta(base, i + 1);
+                    cy += ctx.delta(base, i + 2);
+                }
+            }
+
+            let transform = Transform::new_scale(sx, sy).around_center(cx, cy);
+            paint_transformed(ctx, paint, transform, painter, depth, budget)?;
+        }
+        24..=27 => {
+            let paint = read_offset(data, &mut s)?;
+            let is_centered = format >= 26;
+
+            let mut angle = s.read::<F2DOT14>()?.to_f32();
+            let (mut cx, mut cy) = if is_centered {
+                (f32::from(s.read::<i16>()?), f32::from(s.read::<i16>()?))
+            } else {
+                (0.0, 0.0)
+            };
+
+            if is_variable {
+                let base: u32 = s.read()?;
+                angle += ctx.f2dot14_delta(base, 0);
+                if is_centered {
+                    cx += ctx.delta(base, 1);
+                    cy += ctx.delta(base, 2);
+                }
+            }
+
+            let transform = Transform::new_rotate(angle).around_center(cx, cy);
+            paint_transformed(ctx, paint, transform, painter, depth, budget)?;
+        }
+        28..=31 => {
+            let paint = read_offset(data, &mut s)?;
+            let is_centered = format >= 30;
+
+            let mut x_angle = s.read::<F2DOT14>()?.to_f32();
+            let mut y_angle = s.read::<F2DOT14>()?.to_f32();
+            let (mut cx, mut cy) = if is_centered {
+                (f32::from(s.read::<i16>()?), f32::from(s.read::<i16>()?))
+            } else {
+                (0.0, 0.0)
+            };
+
+            if is_variable {
+                let base: u32 = s.read()?;
+                x_angle += ctx.f2dot14_delta(base, 0);
+                y_angle += ctx.f2dot14_delta(base, 1);
+                if is_centered {
+                    cx += ctx.delta(base, 2);
+                    cy += ctx.delta(base, 3);
+                }
+            }
+
+            let transform = Transform::new_skew(x_angle, y_angle).around_center(cx, cy);
+            paint_transformed(ctx, paint, transform, painter, depth, budget)?;
+        }
+        32 => {
+            let source = read_offset(data, &mut s)?;
+            let mode = CompositeMode::from_u8(s.read()?)?;
+            let backdrop = read_offset(data, &mut s)?;
+
+            painter.push_layer(CompositeMode::SourceOver);
+            let mut res = paint_impl(ctx, backdrop, painter, depth + 1, budget);
+            if res.is_some() {
+                painter.push_layer(mode);
+                res = paint_impl(ctx, source, painter, depth + 1, budget);
+                painter.pop_layer();
+            }
+            painter.pop_layer();
+            res?;
+        }
+        _ => return None,
+    }
+
+    Some(())
+}
+
+fn paint_transformed<'a>(
+    ctx: &Context<'a>,
+    data: &'a [u8],
+    transform: Transform,
+    painter: &mut dyn Painter<'a>,
+    depth: u8,
+    budget: &mut u32,
+) -> Option<()> {
+    painter.push_transform(transform);
+    let res = paint_impl(ctx, data, painter, depth + 1, budget);
+    painter.pop_transform();
+    res
+}
+
+// Paint offsets are 24-bit and from the beginning of the current paint table.
+#[inline]
+fn read_offset<'a>(data: &'a [u8], s: &mut Stream) -> Option<&'a [u8]> {
+    let offset: U24 = s.read()?;
+    data.get(usize::num_from(offset.0)..)
+}
+
+
+// `no_std` doesn't provide trigonometric functions, so we have to implement them ourselves.
+// All angles are in half-turns, which simplifies the range reduction.
+
+fn sin_pi(x: f32) -> f32 {
+    // Reduce to -1..1.
+    let mut x = x % 2.0;
+    if x > 1.0 {
+        x -= 2.0;
+    } else if x < -1.0 {
+        x += 2.0;
+    }
+
+    // Reduce to -0.5..0.5, since sin(π - t) == sin(t).
+    if x > 0.5 {
+        x = 1.0 - x;
+    } else if x < -0.5 {
+        x = -1.0 - x;
+    }
+
+    // Taylor series, which is precise enough in the -π/2..π/2 range.
+    let t = x * core::f32::consts::PI;
+    let t2 = t * t;
+    t * (1.0 - t2 / 6.0 * (1.0 - t2 / 20.0 * (1.0 - t2 / 42.0
+        * (1.0 - t2 / 72.0 * (1.0 - t2 / 110.0)))))
+}
+
+#[inline]
+fn cos_pi(x: f32) -> f32 {
+    sin_pi(x + 0.5)
+}
+
+#[inline]
+fn tan_pi(x: f32) -> f32 {
+    sin_pi(x) / cos_pi(x)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::writer;
+    use writer::TtfType::*;
+    use std::string::String;
+    use std::vec::Vec;
+
+    const FOREGROUND: RgbaColor = RgbaColor::new(0, 0, 0, 255);
+
+    fn cpal_data() -> Vec<u8> {
+        writer::convert(&[
+            UInt16(0), // version
+            UInt16(1), // number of palette entries
+            UInt16(1), // number of palettes
+            UInt16(1), // number of colors
+            UInt32(14), // offset to colors
+            UInt16(0), // index of the first color in palette 0
+            // Colors
+            UInt8(1), UInt8(2), UInt8(3), UInt8(255), // BGRA
+        ])
+    }
+
+    struct Recorder(Vec<String>);
+
+    impl Recorder {
+        fn color(c: RgbaColor) -> String {
+            format!("{} {} {} {}", c.red, c.green, c.blue, c.alpha)
+        }
+    }
+
+    impl<'a> Painter<'a> for Recorder {
+        fn push_clip(&mut self, glyph_id: GlyphId) {
+            self.0.push(format!("clip {}", glyph_id.0));
+        }
+
+        fn push_clip_box(&mut self, b: ClipBox) {
+            self.0.push(format!("clip box {} {} {} {}", b.x_min, b.y_min, b.x_max, b.y_max));
+        }
+
+        fn pop_clip(&mut self) {
+            self.0.push("pop clip".into());
+        }
+
+        fn paint(&mut self, paint: Paint<'a>) {
+            match paint {
+                Paint::Solid(c) => self.0.push(format!("solid {}", Recorder::color(c))),
+                Paint::LinearGradient(g) => {
+                    let stops: Vec<_> = g.color_line.stops()
+                        .map(|s| format!("{} {}", s.offset, Recorder::color(s.color)))
+                        .collect();
+                    self.0.push(format!(
+                        "linear {} {} {} {} {} {} {:?} {}",
+                        g.x0, g.y0, g.x1, g.y1, g.x2, g.y2,
+                        g.color_line.extend(), stops.join(", "),
+                    ));
+                }
+                _ => self.0.push("gradient".into()),
+            }
+        }
+
+        fn push_layer(&mut self, mode: CompositeMode) {
+            self.0.push(format!("layer {:?}", mode));
+        }
+
+        fn pop_layer(&mut self) {
+            self.0.push("pop layer".into());
+        }
+
+        fn push_transform(&mut self, ts: Transform) {
+            self.0.push(format!(
+                "transform {:.3} {:.3} {:.3} {:.3} {:.3} {:.3}",
+                ts.a, ts.b, ts.c, ts.d, ts.e, ts.f,
+            ));
+        }
+
+        fn pop_transform(&mut self) {
+            self.0.push("pop transform".into());
+        }
+    }
+
+    fn paint_glyph(colr_data: &[u8], glyph_id: u16) -> (Option<()>, Vec<String>) {
+        let cpal_data = cpal_data();
+        let table = Table::parse(colr_data).unwrap();
+        let palettes = cpal::Table::parse(&cpal_data);
+        let mut recorder = Recorder(Vec::new());
+        let res = paint(table, palettes, 0, FOREGROUND, &[], GlyphId(glyph_id), &mut recorder);
+        (res, recorder.0)
+    }
+
+    #[test]
+    fn v0_layers() {
+        let data = writer::convert(&[
+            UInt16(0), // version
+            UInt16(1), // number of base glyphs
+            UInt32(14), // offset to base glyphs
+            UInt32(20), // offset to layers
+            UInt16(2), // number of layers
+            // Base glyph record
+            UInt16(5), // glyph
+            UInt16(0), // first layer index
+            UInt16(2), // number of layers
+            // Layer records
+            UInt16(10), // glyph
+            UInt16(0), // palette index
+            UInt16(11), // glyph
+            UInt16(0xFFFF), // palette index
+        ]);
+
+        let table = Table::parse(&data).unwrap();
+        let layers: Vec<_> = table.layers(GlyphId(5)).unwrap().collect();
+        assert_eq!(layers, &[
+            Layer { glyph_id: GlyphId(10), palette_index: 0 },
+            Layer { glyph_id: GlyphId(11), palette_index: 0xFFFF },
+        ]);
+        assert!(layers[1].is_foreground());
+        assert!(table.layers(GlyphId(6)).is_none());
+
+        let (res, log) = paint_glyph(&data, 5);
+        assert!(res.is_some());
+        assert_eq!(log, &[
+            "clip 10", "solid 3 2 1 255", "pop clip",
+            "clip 11", "solid 0 0 0 255", "pop clip",
+        ]);
+    }
+
+    #[test]
+    fn v1_layers() {
+        let data = writer::convert(&[
+            UInt16(1), // version
+            UInt16(0), // number of base glyphs
+            UInt32(0), // offset to base glyphs
+            UInt32(0), // offset to layers
+            UInt16(0), // number of layers
+            UInt32(34), // offset to base glyph list
+            UInt32(44), // offset to layer list
+            UInt32(56), // offset to clip list
+            UInt32(0), // offset to var index map
+            UInt32(0), // offset to item variation store
+            // Base glyph list
+            UInt32(1), // count
+            UInt16(5), // glyph
+            UInt32(43), // paint offset
+            // Layer list
+            UInt32(2), // count
+            UInt32(39), // paint offset
+            UInt32(50), // paint offset
+            // Clip list
+            UInt8(1), // format
+            UInt32(1), // count
+            UInt16(5), // start glyph
+            UInt16(5), // end glyph
+            UInt24(12), // clip box offset
+            // Clip box
+            UInt8(1), // format
+            Int16(0), // x min
+            Int16(-10), // y min
+            Int16(100), // x max
+            Int16(90), // y max
+            // PaintColrLayers
+            UInt8(1), // format
+            UInt8(2), // number of layers
+            UInt32(0), // first layer index
+            // PaintGlyph
+            UInt8(10), // format
+            UInt24(6), // paint offset
+            UInt16(10), // glyph
+            // PaintSolid
+            UInt8(2), // format
+            UInt16(0), // palette index
+            Int16(8192), // alpha
+            // PaintTranslate
+            UInt8(14), // format
+            UInt24(8), // paint offset
+            Int16(10), // dx
+            Int16(-20), // dy
+            // PaintGlyph
+            UInt8(10), // format
+            UInt24(6), // paint offset
+            UInt16(11), // glyph
+            // PaintLinearGradient
+            UInt8(4), // format
+            UInt24(16), // color line offset
+            Int16(0), // x0
+            Int16(0), // y0
+            Int16(100), // x1
+            Int16(0), // y1
+            Int16(0), // x2
+            Int16(100), // y2
+            // ColorLine
+            UInt8(1), // extend
+            UInt16(2), // number of stops
+            Int16(0), // stop offset
+            UInt16(0), // palette index
+            Int16(16384), // alpha
+            Int16(16384), // stop offset
+            UInt16(0xFFFF), // palette index
+            Int16(16384), // alpha
+        ]);
+
+        let (res, log) = paint_glyph(&data, 5);
+        assert!(res.is_some());
+        assert_eq!(log, &[
+            "clip box 0 -10 100 90",
+            "clip 10", "solid 3 2 1 128", "pop clip",
+            "transform 1.000 0.000 0.000 1.000 10.000 -20.000",
+            "clip 11",
+            "linear 0 0 100 0 0 100 Repeat 0 3 2 1 255, 1 0 0 0 255",
+            "pop clip",
+            "pop transform",
+            "pop clip",
+        ]);
+
+        // Version 0 data is not present.
+        let table = Table::parse(&data).unwrap();
+        assert!(table.layers(GlyphId(5)).is_none());
+    }
+
+    #[test]
+    fn v1_composite_and_cycle() {
+        let data = writer::convert(&[
+            UInt16(1), // version
+            UInt16(0), // number of base glyphs
+            UInt32(0), // offset to base glyphs
+            UInt32(0), // offset to layers
+            UInt16(0), // number of layers
+            UInt32(34), // offset to base glyph list
+            UInt32(0), // offset to layer list
+            UInt32(0), // offset to clip list
+            UInt32(0), // offset to var index map
+            UInt32(0), // offset to item variation store
+            // Base glyph list
+            UInt32(2), // count
+            UInt16(1), // glyph
+            UInt32(16), // paint offset
+            UInt16(2), // glyph
+            UInt32(39), // paint offset
+            // PaintComposite
+            UInt8(32), // format
+            UInt24(8), // source paint offset
+            UInt8(23), // composite mode
+            UInt24(18), // backdrop paint offset
+            // PaintRotateAroundCenter
+            UInt8(26), // format
+            UInt24(10), // paint offset
+            Int16(8192), // angle
+            Int16(50), // center x
+            Int16(50), // center y
+            // PaintSolid
+            UInt8(2), // format
+            UInt16(0xFFFF), // palette index
+            Int16(16384), // alpha
+            // PaintTranslate that references itself
+            UInt8(14), // format
+            UInt24(0), // paint offset
+            Int16(1), // dx
+            Int16(1), // dy
+        ]);
+
+        let (res, log) = paint_glyph(&data, 1);
+        assert!(res.is_some());
+        assert_eq!(log, &[
+            "layer SourceOver",
+            "solid 0 0 0 255",
+            "layer Multiply",
+            "transform 0.000 1.000 -1.000 0.000 100.000 0.000",
+            "solid 0 0 0 255",
+            "pop transform",
+            "pop layer",
+            "pop layer",
+        ]);
+
+        let (res, log) = paint_glyph(&data, 2);
+        assert!(res.is_none());
+        let pushes = log.iter().filter(|s| s.starts_with("transform")).count();
+        let pops = log.iter().filter(|s| s.starts_with("pop transform")).count();
+        assert_eq!(pushes, usize::from(MAX_PAINT_DEPTH));
+        assert_eq!(pushes, pops);
+    }
+
+    #[test]
+    fn v1_layers_cycle() {
+        const LEVELS: u32 = 8;
+        const LAYER_LIST_LEN: u32 = 4 + 4 * (1 + 255 * LEVELS);
+
+        let mut data = vec![
+            UInt16(1), // version
+            UInt16(0), // number of base glyphs
+            UInt32(0), // offset to base glyphs
+            UInt32(0), // offset to layers
+            UInt16(0), // number of layers
+            UInt32(34), // offset to base glyph list
+            UInt32(50), // offset to layer list
+            UInt32(0), // offset to clip list
+            UInt32(0), // offset to var index map
+            UInt32(0), // offset to item variation store
+            // Base glyph list
+            UInt32(2), // count
+            UInt16(5), // glyph
+            UInt32(16 + LAYER_LIST_LEN), // paint offset
+            UInt16(6), // glyph
+            UInt32(16 + LAYER_LIST_LEN + 6), // paint offset
+            // Layer list
+            UInt32(1 + 255 * LEVELS), // count
+            UInt32(LAYER_LIST_LEN), // paint offset
+        ];
+        // Each layer of a level references the next level.
+        for level in 1..=LEVELS {
+            data.extend((0..255).map(|_| UInt32(LAYER_LIST_LEN + 6 * (level + 1))));
+        }
+        // PaintColrLayers that references itself.
+        data.extend_from_slice(&[
+            UInt8(1), // format
+            UInt8(1), // number of layers
+            UInt32(0), // first layer index
+        ]);
+        // Nested PaintColrLayers.
+        for level in 0..LEVELS {
+            data.extend_from_slice(&[
+                UInt8(1), // format
+                UInt8(255), // number of layers
+                UInt32(1 + 255 * level), // first layer index
+            ]);
+        }
+        data.extend_from_slice(&[
+            // PaintSolid
+            UInt8(2), // format
+            UInt16(0), // palette index
+            Int16(16384), // alpha
+        ]);
+        let data = writer::convert(&data);
+
+        let (res, _) = paint_glyph(&data, 5);
+        assert!(res.is_none());
+
+        // Must stop long before visiting 255^8 paints.
+        let (res, log) = paint_glyph(&data, 6);
+        assert!(res.is_none());
+        assert!(!log.is_empty());
+        assert!(log.len() < usize::num_from(MAX_PAINT_VISITS));
+    }
+
+    #[test]
+    fn palette() {
+        let data = cpal_data();
+        let table = cpal::Table::parse(&data).unwrap();
+        assert_eq!(table.palettes(), 1);
+        assert_eq!(table.get(0, 0), Some(RgbaColor::new(3, 2, 1, 255)));
+        assert_eq!(table.get(0, 1), None);
+        assert_eq!(table.get(1, 0), None);
+    }
+
+    #[test]
+    fn trigonometry() {
+        assert!((sin_pi(1.0 / 6.0) - 0.5).abs() < 1e-6);
+        assert!((sin_pi(-0.5) + 1.0).abs() < 1e-6);
+        assert!((cos_pi(1.0) + 1.0).abs() < 1e-6);
+        assert!((cos_pi(-1.5)).abs() < 1e-6);
+        assert!((tan_pi(0.25) - 1.0).abs() < 1e-6);
+        assert!((sin_pi(2.25) - sin_pi(0.25)).abs() < 1e-6);
+    }
+}
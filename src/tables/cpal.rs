@@ -0,0 +1,75 @@
+// https://docs.microsoft.com/en-us/typography/opentype/spec/cpal
+
+use crate::RgbaColor;
+use crate::parser::{Stream, FromData, LazyArray16, Offset, Offset32};
+
+
+// CPAL stores colors in the BGRA order.
+#[derive(Clone, Copy)]
+struct BgraColor(RgbaColor);
+
+impl FromData for BgraColor {
+    const SIZE: usize = 4;
+
+    #[inline]
+    fn parse(data: &[u8]) -> Option<Self> {
+        let mut s = Stream::new(data);
+        let blue = s.read()?;
+        let green = s.read()?;
+        let red = s.read()?;
+        let alpha = s.read()?;
+        Some(BgraColor(RgbaColor::new(red, green, blue, alpha)))
+    }
+}
+
+
+#[derive(Clone, Copy)]
+pub struct Table<'a> {
+    // Index of each palette's first color in the color records array.
+    color_indices: LazyArray16<'a, u16>,
+    colors: LazyArray16<'a, BgraColor>,
+    palette_entries: u16,
+}
+
+impl<'a> Table<'a> {
+    pub fn parse(data: &'a [u8]) -> Option<Self> {
+        let mut s = Stream::new(data);
+
+        let version: u16 = s.read()?;
+        if version > 1 {
+            return None;
+        }
+
+        let palette_entries: u16 = s.read()?;
+        let palettes_count: u16 = s.read()?;
+        let colors_count: u16 = s.read()?;
+        let colors_offset: Offset32 = s.read()?;
+        let color_indices = s.read_array16(palettes_count)?;
+
+        // Version 1 fields (palette types and labels) are ignored.
+
+        let mut s = Stream::new_at(data, colors_offset.to_usize())?;
+        let colors = s.read_array16(colors_count)?;
+
+        Some(Table {
+            color_indices,
+            colors,
+            palette_entries,
+        })
+    }
+
+    #[inline]
+    pub fn palettes(&self) -> u16 {
+        self.color_indices.len()
+    }
+
+    #[inline]
+    pub fn get(&self, palette_index: u16, palette_entry: u16) -> Option<RgbaColor> {
+        if palette_entry >= self.palette_entries {
+            return None;
+        }
+
+        let index = self.color_indices.get(palette_index)?.checked_add(palette_entry)?;
+        self.colors.get(index).map(|c| c.0)
+    }
+}
@@ -0,0 +1,218 @@
+/*!
+A [Control Value Table](https://docs.microsoft.com/en-us/typography/opentype/spec/cvt)
+implementation.
+
+Control values are accessible via `Font::control_values()`.
+Deltas from the [CVT Variations Table](https://docs.microsoft.com/en-us/typography/opentype/spec/cvar)
+are applied automatically.
+*/
+
+use core::convert::TryFrom;
+
+use crate::NormalizedCoord;
+use crate::parser::{Stream, Offset, Offset16, LazyArray16, LazyArrayIter16};
+use crate::gvar::{self, PackedPointsIter, SetPointsIter, PackedSingleDeltasIter};
+
+pub(crate) fn parse(data: &[u8]) -> Option<LazyArray16<'_, i16>> {
+    let count = u16::try_from(data.len() / 2).ok()?;
+    Stream::new(data).read_array16(count)
+}
+
+
+#[derive(Clone, Copy, Default)]
+struct VariationTuple<'a> {
+    set_points: Option<SetPointsIter<'a>>,
+    deltas: PackedSingleDeltasIter<'a>,
+}
+
+
+/// An iterator over control values.
+///
+/// Values are returned as `f32`, since variation deltas can be fractional.
+#[allow(missing_debug_implementations)]
+#[derive(Clone, Copy, Default)]
+pub struct ControlValues<'a> {
+    values: LazyArrayIter16<'a, i16>,
+    tuples: [VariationTuple<'a>; gvar::MAX_TUPLES_LEN as usize],
+    tuples_len: u16,
+}
+
+impl<'a> ControlValues<'a> {
+    pub(crate) fn new(
+        values: LazyArray16<'a, i16>,
+        cvar: Option<&'a [u8]>,
+        coordinates: &[NormalizedCoord],
+    ) -> Self {
+        let mut iter = ControlValues {
+            values: values.into_iter(),
+            ..ControlValues::default()
+        };
+
+        if let Some(data) = cvar {
+            // Malformed variations should not affect default values.
+            if parse_variations(data, coordinates, &mut iter).is_none() {
+                iter.tuples_len = 0;
+            }
+        }
+
+        iter
+    }
+}
+
+impl<'a> Iterator for ControlValues<'a> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut value = f32::from(self.values.next()?);
+        for tuple in &mut self.tuples[0..usize::from(self.tuples_len)] {
+            let is_set = match tuple.set_points {
+                Some(ref mut set_points) => set_points.next().unwrap_or(false),
+                None => true, // All values are set.
+            };
+
+            if is_set {
+                if let Some(delta) = tuple.deltas.next() {
+                    value += delta;
+                }
+            }
+        }
+
+        Some(value)
+    }
+}
+
+// https://docs.microsoft.com/en-us/typography/opentype/spec/cvar
+fn parse_variations<'a>(
+    data: &'a [u8],
+    coordinates: &[NormalizedCoord],
+    iter: &mut ControlValues<'a>,
+) -> Option<()> {
+    const SHARED_POINT_NUMBERS_FLAG: u16 = 0x8000;
+    const COUNT_MASK: u16 = 0x0FFF;
+
+    let mut s = Stream::new(data);
+    let major_version: u16 = s.read()?;
+    s.skip::<u16>(); // minor version
+    if major_version != 1 {
+        return None;
+    }
+
+    let tuple_variation_count: u16 = s.read()?;
+    let data_offset: Offset16 = s.read()?;
+
+    let has_shared_point_numbers = tuple_variation_count & SHARED_POINT_NUMBERS_FLAG != 0;
+    let tuple_variation_count = tuple_variation_count & COUNT_MASK;
+    if tuple_variation_count >= gvar::MAX_TUPLES_LEN {
+        return None;
+    }
+
+    let mut serialized_s = Stream::new_at(data, data_offset.to_usize())?;
+
+    let mut shared_point_numbers = None;
+    if has_shared_point_numbers {
+        shared_point_numbers = PackedPointsIter::new(&mut serialized_s)?;
+    }
+
+    // `cvar` doesn't have shared tuples, so all peak tuples must be embedded.
+    let shared_tuple_records = LazyArray16::default();
+
+    for _ in 0..tuple_variation_count {
+        let header = gvar::parse_tuple_variation_header(coordinates, &shared_tuple_records, &mut s)?;
+        if header.scalar <= 0.0 {
+            // Serialized data for headers with non-positive scalar should be skipped.
+            serialized_s.advance(usize::from(header.serialized_data_len));
+            continue;
+        }
+
+        let serialized_data_start = serialized_s.offset();
+
+        let point_numbers = if header.has_private_point_numbers {
+            PackedPointsIter::new(&mut serialized_s)?
+        } else {
+            shared_point_numbers
+        };
+
+        let left = usize::from(header.serialized_data_len)
+            .checked_sub(serialized_s.offset() - serialized_data_start)?;
+        let deltas_data = serialized_s.read_bytes(left)?;
+
+        iter.tuples[usize::from(iter.tuples_len)] = VariationTuple {
+            set_points: point_numbers.map(SetPointsIter::new),
+            deltas: PackedSingleDeltasIter::new(header.scalar, deltas_data),
+        };
+        iter.tuples_len += 1;
+    }
+
+    Some(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::vec::Vec;
+    use crate::writer;
+    use writer::TtfType::*;
+
+    #[test]
+    fn values_without_variations() {
+        let data = writer::convert(&[Int16(10), Int16(-20), Int16(30)]);
+        let values = parse(&data).unwrap();
+        let values: Vec<_> = ControlValues::new(values, None, &[]).collect();
+        assert_eq!(values, &[10.0, -20.0, 30.0]);
+    }
+
+    #[test]
+    fn variations() {
+        let data = writer::convert(&[Int16(10), Int16(-20), Int16(30)]);
+        let cvt = parse(&data).unwrap();
+
+        let cvar = writer::convert(&[
+            UInt16(1), // major version
+            UInt16(0), // minor version
+            UInt16(2), // tuple variation count
+            UInt16(20), // offset to serialized data
+            // Tuple 1.
+            UInt16(4), // variation data size
+            UInt16(0x8000), // EMBEDDED_PEAK_TUPLE
+            Int16(0x4000), // peak tuple
+            // Tuple 2.
+            UInt16(5), // variation data size
+            UInt16(0xA000), // EMBEDDED_PEAK_TUPLE | PRIVATE_POINT_NUMBERS
+            Int16(0x4000), // peak tuple
+            // Tuple 1 serialized data. Deltas for all values.
+            UInt8(2), // deltas control
+            Int8(2), Int8(4), Int8(6),
+            // Tuple 2 serialized data.
+            UInt8(1), // points count
+            UInt8(0), // points control
+            UInt8(2), // point 2
+            UInt8(0), // deltas control
+            Int8(100),
+        ]);
+
+        let coords = [NormalizedCoord::from(0.5)];
+        let values: Vec<_> = ControlValues::new(cvt, Some(&cvar), &coords).collect();
+        assert_eq!(values, &[11.0, -18.0, 83.0]);
+
+        let coords = [NormalizedCoord::from(-0.5)];
+        let values: Vec<_> = ControlValues::new(cvt, Some(&cvar), &coords).collect();
+        assert_eq!(values, &[10.0, -20.0, 30.0]);
+    }
+
+    #[test]
+    fn malformed_variations() {
+        let data = writer::convert(&[Int16(10), Int16(-20)]);
+        let cvar = writer::convert(&[
+            UInt16(1), // major version
+            UInt16(0), // minor version
+            UInt16(1), // tuple variation count
+            UInt16(100), // offset to serialized data
+        ]);
+
+        let values = parse(&data).unwrap();
+        let coords = [NormalizedCoord::from(1.0)];
+        let values: Vec<_> = ControlValues::new(values, Some(&cvar), &coords).collect();
+        assert_eq!(values, &[10.0, -20.0]);
+    }
+}
@@ -40,9 +40,113 @@ impl VariationAxis {
 }
 
 
+/// A [named instance](https://docs.microsoft.com/en-us/typography/opentype/spec/fvar#instancerecord).
+#[derive(Clone, Copy, Debug)]
+pub struct NamedInstance<'a> {
+    /// An instance subfamily name in the `name` table.
+    pub subfamily_name_id: u16,
+    /// An instance PostScript name in the `name` table.
+    ///
+    /// `None` when the instance record doesn't have one or it's set to 0xFFFF.
+    pub post_script_name_id: Option<u16>,
+    coordinates: LazyArray16<'a, Fixed>,
+}
+
+impl<'a> NamedInstance<'a> {
+    /// Returns an iterator over instance's user-space coordinates.
+    ///
+    /// Coordinates are in the same order as `Font::variation_axes()`.
+    #[inline]
+    pub fn coordinates(&self) -> NamedInstanceCoordinates<'a> {
+        NamedInstanceCoordinates { iter: self.coordinates.into_iter() }
+    }
+}
+
+
+/// An iterator over named instance's user-space coordinates.
+#[allow(missing_debug_implementations)]
+#[derive(Clone, Copy, Default)]
+pub struct NamedInstanceCoordinates<'a> {
+    iter: LazyArrayIter16<'a, Fixed>,
+}
+
+impl<'a> Iterator for NamedInstanceCoordinates<'a> {
+    type Item = f32;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next().map(|v| v.0)
+    }
+
+    #[inline]
+    fn count(self) -> usize {
+        self.iter.count()
+    }
+}
+
+
+/// An iterator over named instances.
+#[allow(missing_debug_implementations)]
+#[derive(Clone, Copy, Default)]
+pub struct NamedInstances<'a> {
+    data: &'a [u8],
+    axis_count: u16,
+    // Instance records without a PostScript name ID are 2 bytes shorter.
+    instance_size: usize,
+    index: u16,
+    count: u16,
+}
+
+impl<'a> NamedInstances<'a> {
+    /// Returns an instance at `index`.
+    ///
+    /// Ignores the iterator position.
+    pub fn get(&self, index: u16) -> Option<NamedInstance<'a>> {
+        if index >= self.count {
+            return None;
+        }
+
+        let offset = usize::from(index) * self.instance_size;
+        let data = self.data.get(offset..offset + self.instance_size)?;
+        let mut s = Stream::new(data);
+        let subfamily_name_id: u16 = s.read()?;
+        s.skip::<u16>(); // flags
+        let coordinates = s.read_array16(self.axis_count)?;
+        // 0xFFFF indicates that there is no PostScript name.
+        let post_script_name_id = s.read().filter(|id| *id != 0xFFFF);
+
+        Some(NamedInstance {
+            subfamily_name_id,
+            post_script_name_id,
+            coordinates,
+        })
+    }
+}
+
+impl<'a> Iterator for NamedInstances<'a> {
+    type Item = NamedInstance<'a>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.index < self.count {
+            self.index += 1;
+            self.get(self.index - 1)
+        } else {
+            None
+        }
+    }
+
+    #[inline]
+    fn count(self) -> usize {
+        usize::from(self.count - self.index)
+    }
+}
+
+
 #[derive(Clone, Copy)]
 pub(crate) struct Table<'a> {
     axes: LazyArray16<'a, VariationAxisRecord>,
+    instances: NamedInstances<'a>,
 }
 
 impl<'a> Table<'a> {
@@ -56,22 +160,48 @@ impl<'a> Table<'a> {
         let axes_array_offset: Offset16 = s.read()?;
         s.skip::<u16>(); // reserved
         let axis_count: u16 = s.read()?;
+        let axis_size: u16 = s.read()?;
+        let instance_count: u16 = s.read()?;
+        let instance_size: u16 = s.read()?;
 
         // 'If axisCount is zero, then the font is not functional as a variable font,
         // and must be treated as a non-variable font;
         // any variation-specific tables or data is ignored.'
         let axis_count = NonZeroU16::new(axis_count)?;
 
-        let mut s = Stream::new_at(data, axes_array_offset.to_usize())?;
-        let axes = s.read_array16(axis_count.get())?;
+        let axes = Stream::new_at(data, axes_array_offset.to_usize())?
+            .read_array16(axis_count.get())?;
+
+        // Instances are stored right after axes.
+        // Malformed instances should not prevent the font from being variable.
+        let mut instances = NamedInstances::default();
+        let instances_offset = axes_array_offset.to_usize()
+            + usize::from(axis_count.get()) * usize::from(axis_size);
+        let min_instance_size = 4 + usize::from(axis_count.get()) * Fixed::SIZE;
+        let instance_size = usize::from(instance_size);
+        if instance_count != 0 && instance_size >= min_instance_size {
+            if let Some(data) = data.get(instances_offset..) {
+                instances = NamedInstances {
+                    data,
+                    axis_count: axis_count.get(),
+                    instance_size,
+                    index: 0,
+                    count: instance_count,
+                };
+            }
+        }
 
-        Some(Table { axes })
+        Some(Table { axes, instances })
     }
 
     pub fn axes(&self) -> VariationAxes<'a> {
         VariationAxes { iter: self.axes.into_iter() }
     }
 
+    pub fn named_instances(&self) -> NamedInstances<'a> {
+        self.instances
+    }
+
     // TODO: add axis_by_tag
 }
 
@@ -137,3 +267,91 @@ impl FromData for VariationAxisRecord {
         })
     }
 }
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::writer;
+    use writer::TtfType::*;
+
+    fn gen_fvar(instance_size: u16) -> std::vec::Vec<u8> {
+        let mut w = writer::Writer::new();
+        w.write(UInt32(0x00010000)); // version
+        w.write(UInt16(16)); // axes array offset
+        w.write(UInt16(2)); // reserved
+        w.write(UInt16(2)); // axis count
+        w.write(UInt16(20)); // axis size
+        w.write(UInt16(2)); // instance count
+        w.write(UInt16(instance_size)); // instance size
+
+        for tag in &[b"wght", b"wdth"] {
+            w.write(Raw(*tag)); // axis tag
+            w.write(Int32(100 << 16)); // min value
+            w.write(Int32(400 << 16)); // default value
+            w.write(Int32(900 << 16)); // max value
+            w.write(UInt16(0)); // flags
+            w.write(UInt16(256)); // axis name ID
+        }
+
+        for (i, value) in [300, 700].iter().enumerate() {
+            w.write(UInt16(257 + i as u16)); // subfamily name ID
+            w.write(UInt16(0)); // flags
+            w.write(Int32(value << 16)); // wght
+            w.write(Int32(100 << 16)); // wdth
+            if instance_size == 14 {
+                w.write(UInt16(300 + i as u16)); // PostScript name ID
+            }
+        }
+
+        w.data
+    }
+
+    #[test]
+    fn named_instances() {
+        let data = gen_fvar(14);
+        let table = Table::parse(&data).unwrap();
+        let mut instances = table.named_instances();
+        assert_eq!(instances.count(), 2);
+
+        let instance = instances.next().unwrap();
+        assert_eq!(instance.subfamily_name_id, 257);
+        assert_eq!(instance.post_script_name_id, Some(300));
+        assert_eq!(instance.coordinates().collect::<std::vec::Vec<_>>(), &[300.0, 100.0]);
+
+        let instance = instances.next().unwrap();
+        assert_eq!(instance.subfamily_name_id, 258);
+        assert_eq!(instance.post_script_name_id, Some(301));
+        assert_eq!(instance.coordinates().collect::<std::vec::Vec<_>>(), &[700.0, 100.0]);
+
+        assert!(instances.next().is_none());
+    }
+
+    #[test]
+    fn named_instances_without_post_script_name() {
+        let data = gen_fvar(12);
+        let table = Table::parse(&data).unwrap();
+        let instance = table.named_instances().get(1).unwrap();
+        assert_eq!(instance.subfamily_name_id, 258);
+        assert_eq!(instance.post_script_name_id, None);
+        assert_eq!(instance.coordinates().collect::<std::vec::Vec<_>>(), &[700.0, 100.0]);
+
+        // An explicit 0xFFFF PostScript name ID.
+        let mut data = gen_fvar(14);
+        let len = data.len();
+        data[len - 2..].copy_from_slice(&[0xFF, 0xFF]);
+        let table = Table::parse(&data).unwrap();
+        let instance = table.named_instances().get(1).unwrap();
+        assert_eq!(instance.subfamily_name_id, 258);
+        assert_eq!(instance.post_script_name_id, None);
+        assert_eq!(table.named_instances().get(0).unwrap().post_script_name_id, Some(300));
+    }
+
+    #[test]
+    fn named_instances_invalid_size() {
+        let data = gen_fvar(8);
+        let table = Table::parse(&data).unwrap();
+        assert_eq!(table.axes().count(), 2);
+        assert_eq!(table.named_instances().count(), 0);
+    }
+}
@@ -1,9 +1,16 @@
-// https://docs.microsoft.com/en-us/typography/opentype/spec/glyf
+/*!
+A [Glyph Data Table](https://docs.microsoft.com/en-us/typography/opentype/spec/glyf)
+implementation.
 
+This module provides an access to composite glyph components and raw glyph points.
+Outlines are accessible via `Font::outline_glyph()`.
+*/
+
+use core::convert::TryFrom;
 use core::num::NonZeroU16;
 
 use crate::parser::{Stream, F2DOT14, LazyArray16, NumFrom};
-use crate::{loca, GlyphId, OutlineBuilder, Rect, BBox};
+use crate::{gvar, loca, GlyphId, OutlineBuilder, Rect, BBox};
 
 pub(crate) struct Builder<'a> {
     pub builder: &'a mut dyn OutlineBuilder,
@@ -145,18 +152,33 @@ impl<'a> Builder<'a> {
 }
 
 
-#[derive(Clone, Copy)]
+/// An affine transform.
+///
+/// A point is transformed as `x' = a * x + c * y + e` and `y' = b * x + d * y + f`.
+#[derive(Clone, Copy, PartialEq)]
 pub struct Transform {
-    pub a: f32, pub b: f32, pub c: f32,
-    pub d: f32, pub e: f32, pub f: f32,
+    /// The X scale.
+    pub a: f32,
+    /// The Y skew.
+    pub b: f32,
+    /// The X skew.
+    pub c: f32,
+    /// The Y scale.
+    pub d: f32,
+    /// The X offset.
+    pub e: f32,
+    /// The Y offset.
+    pub f: f32,
 }
 
 impl Transform {
+    /// Creates a translate transform.
     #[inline]
     pub fn new_translate(tx: f32, ty: f32) -> Self {
         Transform { a: 1.0, b: 0.0, c: 0.0, d: 1.0, e: tx, f: ty }
     }
 
+    /// Combines two transforms. `ts2` is applied first.
     #[inline]
     pub fn combine(ts1: Self, ts2: Self) -> Self {
         Transform {
@@ -169,8 +191,9 @@ impl Transform {
         }
     }
 
+    /// Transforms a point.
     #[inline]
-    fn apply_to(&self, x: &mut f32, y: &mut f32) {
+    pub fn apply_to(&self, x: &mut f32, y: &mut f32) {
         let tx = *x;
         let ty = *y;
         *x = self.a * tx + self.c * ty + self.e;
@@ -204,35 +227,56 @@ impl core::fmt::Debug for Transform {
 }
 
 
+/// A composite glyph component.
 #[derive(Clone, Copy, Debug)]
-pub(crate) struct CompositeGlyphInfo {
+pub struct Component {
+    /// A component glyph ID.
     pub glyph_id: GlyphId,
+    /// A component transform.
+    ///
+    /// When a component is positioned by matching points,
+    /// the offset is always zero.
     pub transform: Transform,
-    pub flags: CompositeGlyphFlags,
+    /// Component flags.
+    pub flags: ComponentFlags,
+    /// Parent and child point numbers.
+    ///
+    /// Set only when `ARGS_ARE_XY_VALUES` is not set.
+    /// In this case, the component should be positioned so that the child point
+    /// matches the parent point.
+    pub matching_points: Option<(u16, u16)>,
 }
 
 
-#[derive(Clone)]
-pub(crate) struct CompositeGlyphIter<'a> {
+/// An iterator over composite glyph components.
+#[allow(missing_debug_implementations)]
+#[derive(Clone, Copy, Default)]
+pub struct Components<'a> {
     stream: Stream<'a>,
+    finished: bool,
 }
 
-impl<'a> CompositeGlyphIter<'a> {
+impl<'a> Components<'a> {
     #[inline]
-    pub fn new(data: &'a [u8]) -> Self {
-        CompositeGlyphIter { stream: Stream::new(data) }
+    pub(crate) fn new(data: &'a [u8]) -> Self {
+        Components { stream: Stream::new(data), finished: false }
     }
 }
 
-impl<'a> Iterator for CompositeGlyphIter<'a> {
-    type Item = CompositeGlyphInfo;
+impl<'a> Iterator for Components<'a> {
+    type Item = Component;
 
     #[inline]
     fn next(&mut self) -> Option<Self::Item> {
-        let flags = CompositeGlyphFlags(self.stream.read()?);
+        if self.finished {
+            return None;
+        }
+
+        let flags = ComponentFlags(self.stream.read()?);
         let glyph_id: GlyphId = self.stream.read()?;
 
         let mut ts = Transform::default();
+        let mut matching_points = None;
 
         if flags.args_are_xy_values() {
             if flags.arg_1_and_2_are_words() {
@@ -242,6 +286,15 @@ impl<'a> Iterator for CompositeGlyphIter<'a> {
                 ts.e = f32::from(self.stream.read::<i8>()?);
                 ts.f = f32::from(self.stream.read::<i8>()?);
             }
+        } else {
+            // Arguments are point numbers and they are always unsigned.
+            if flags.arg_1_and_2_are_words() {
+                matching_points = Some((self.stream.read::<u16>()?, self.stream.read::<u16>()?));
+            } else {
+                let parent: u8 = self.stream.read()?;
+                let child: u8 = self.stream.read()?;
+                matching_points = Some((u16::from(parent), u16::from(child)));
+            }
         }
 
         if flags.we_have_a_two_by_two() {
@@ -259,24 +312,113 @@ impl<'a> Iterator for CompositeGlyphIter<'a> {
 
         if !flags.more_components() {
             // Finish the iterator even if stream still has some data.
-            self.stream.jump_to_end();
+            // The stream is preserved, since composite glyph instructions are stored after it.
+            self.finished = true;
         }
 
-        Some(CompositeGlyphInfo {
+        Some(Component {
             glyph_id,
             transform: ts,
             flags,
+            matching_points,
         })
     }
 }
 
 
+/// A raw glyph point.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct OutlinePoint {
+    /// The X coordinate.
+    pub x: f32,
+    /// The Y coordinate.
+    pub y: f32,
+    /// Indicates that a point is a point on curve
+    /// and not a control point.
+    ///
+    /// Always `false` for phantom points.
+    pub on_curve_point: bool,
+    /// Indicates that a point is the last point of a contour.
+    ///
+    /// Always `false` for phantom points.
+    pub last_point: bool,
+}
+
+
+/// An iterator over raw glyph points.
+///
+/// Yields glyph points in the original order, followed by four phantom points:
+/// the horizontal origin, the horizontal advance, the vertical origin
+/// and the vertical advance.
+/// This is the same numbering that is used by `gvar` and TrueType instructions.
+///
+/// Implied on-curve points are not generated.
+#[allow(missing_debug_implementations)]
+#[derive(Clone)]
+pub struct OutlinePoints<'a> {
+    contour_ends: LazyArray16<'a, u16>,
+    points: GlyphPointsIter<'a>,
+    all_points: GlyphPointsIter<'a>,
+    phantom_points: [(f32, f32); 4],
+    phantom_index: u8,
+    pub(crate) tuples: Option<gvar::VariationTuples<'a>>,
+}
+
+impl<'a> OutlinePoints<'a> {
+    /// Returns indices of the last point of each contour.
+    #[inline]
+    pub fn contour_ends(&self) -> LazyArray16<'a, u16> {
+        self.contour_ends
+    }
+
+    /// Returns the number of glyph points, excluding phantom points.
+    #[inline]
+    pub fn points_len(&self) -> u16 {
+        self.all_points.points_left
+    }
+}
+
+impl<'a> Iterator for OutlinePoints<'a> {
+    type Item = OutlinePoint;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(point) = self.points.next() {
+            let (x, y) = match self.tuples {
+                Some(ref mut tuples) => {
+                    tuples.apply(self.all_points.clone(), self.points.clone(), point)?
+                }
+                None => (f32::from(point.x), f32::from(point.y)),
+            };
+
+            return Some(OutlinePoint {
+                x,
+                y,
+                on_curve_point: point.on_curve_point,
+                last_point: point.last_point,
+            });
+        }
+
+        let (mut x, mut y) = *self.phantom_points.get(usize::from(self.phantom_index))?;
+        self.phantom_index += 1;
+
+        if let Some(ref mut tuples) = self.tuples {
+            // Phantom points are not a part of any contour, so deltas are never inferred.
+            let (dx, dy) = tuples.apply_null()?;
+            x += dx;
+            y += dy;
+        }
+
+        Some(OutlinePoint { x, y, on_curve_point: false, last_point: false })
+    }
+}
+
+
 // Due to some optimization magic, using f32 instead of i16
 // makes the code ~10% slower. At least on my machine.
 // I guess it's due to the fact that with i16 the struct
 // fits into the machine word.
 #[derive(Clone, Copy, Debug)]
-pub struct GlyphPoint {
+pub(crate) struct GlyphPoint {
     pub x: i16,
     pub y: i16,
     /// Indicates that a point is a point on curve
@@ -287,7 +429,7 @@ pub struct GlyphPoint {
 
 
 #[derive(Clone, Default)]
-pub struct GlyphPointsIter<'a> {
+pub(crate) struct GlyphPointsIter<'a> {
     endpoints: EndpointsIter<'a>,
     flags: FlagsIter<'a>,
     x_coords: CoordsIter<'a>,
@@ -477,22 +619,94 @@ impl SimpleGlyphFlags {
 }
 
 
-// https://docs.microsoft.com/en-us/typography/opentype/spec/glyf#composite-glyph-description
-#[derive(Clone, Copy, Debug)]
-pub(crate) struct CompositeGlyphFlags(u16);
+/// [Composite glyph flags](
+/// https://docs.microsoft.com/en-us/typography/opentype/spec/glyf#composite-glyph-description).
+#[derive(Clone, Copy, PartialEq, Default, Debug)]
+pub struct ComponentFlags(pub u16);
+
+impl ComponentFlags {
+    /// Checks that component arguments are 16-bit.
+    #[inline]
+    pub fn arg_1_and_2_are_words(self) -> bool {
+        self.0 & 0x0001 != 0
+    }
+
+    /// Checks that component arguments are an offset and not point numbers.
+    #[inline]
+    pub fn args_are_xy_values(self) -> bool {
+        self.0 & 0x0002 != 0
+    }
+
+    /// Checks that the component offset should be rounded to the grid.
+    #[inline]
+    pub fn round_xy_to_grid(self) -> bool {
+        self.0 & 0x0004 != 0
+    }
+
+    /// Checks that the component has a uniform scale.
+    #[inline]
+    pub fn we_have_a_scale(self) -> bool {
+        self.0 & 0x0008 != 0
+    }
+
+    /// Checks that the component is not the last one.
+    #[inline]
+    pub fn more_components(self) -> bool {
+        self.0 & 0x0020 != 0
+    }
+
+    /// Checks that the component has a non-uniform scale.
+    #[inline]
+    pub fn we_have_an_x_and_y_scale(self) -> bool {
+        self.0 & 0x0040 != 0
+    }
+
+    /// Checks that the component has a 2x2 transform.
+    #[inline]
+    pub fn we_have_a_two_by_two(self) -> bool {
+        self.0 & 0x0080 != 0
+    }
 
-impl CompositeGlyphFlags {
-    #[inline] pub fn arg_1_and_2_are_words(self) -> bool { self.0 & 0x0001 != 0 }
-    #[inline] pub fn args_are_xy_values(self) -> bool { self.0 & 0x0002 != 0 }
-    #[inline] pub fn we_have_a_scale(self) -> bool { self.0 & 0x0008 != 0 }
-    #[inline] pub fn more_components(self) -> bool { self.0 & 0x0020 != 0 }
-    #[inline] pub fn we_have_an_x_and_y_scale(self) -> bool { self.0 & 0x0040 != 0 }
-    #[inline] pub fn we_have_a_two_by_two(self) -> bool { self.0 & 0x0080 != 0 }
+    /// Checks that the composite glyph has instructions after the last component.
+    #[inline]
+    pub fn we_have_instructions(self) -> bool {
+        self.0 & 0x0100 != 0
+    }
+
+    /// Checks that the composite glyph should use metrics of this component.
+    #[inline]
+    pub fn use_my_metrics(self) -> bool {
+        self.0 & 0x0200 != 0
+    }
+
+    /// Checks that components of the composite glyph overlap.
+    #[inline]
+    pub fn overlap_compound(self) -> bool {
+        self.0 & 0x0400 != 0
+    }
+
+    /// Checks that the component offset should be scaled by the component transform.
+    #[inline]
+    pub fn scaled_component_offset(self) -> bool {
+        self.0 & 0x0800 != 0
+    }
+
+    /// Checks that the component offset should not be scaled by the component transform.
+    #[inline]
+    pub fn unscaled_component_offset(self) -> bool {
+        self.0 & 0x1000 != 0
+    }
 }
 
 
 // It's not defined in the spec, so we are using our own value.
-pub const MAX_COMPONENTS: u8 = 32;
+pub(crate) const MAX_COMPONENTS: u8 = 32;
+
+// The maximum number of glyphs visited while matching component points of a single outline.
+//
+// Point matching walks previous components, which can be positioned via points as well,
+// so malformed fonts could make the search extremely slow otherwise.
+pub(crate) const MAX_POINT_SEARCH_STEPS: u32 = 1 << 16;
 
 #[inline]
 pub(crate) fn outline(
@@ -504,7 +718,8 @@ pub(crate) fn outline(
     let mut b = Builder::new(Transform::default(), None, builder);
     let range = loca_table.glyph_range(glyph_id)?;
     let glyph_data = glyf_table.get(range)?;
-    outline_impl(loca_table, glyf_table, glyph_data, 0, &mut b)
+    let mut budget = MAX_POINT_SEARCH_STEPS;
+    outline_impl(loca_table, glyf_table, glyph_data, 0, &mut budget, &mut b)
 }
 
 #[inline]
@@ -526,12 +741,165 @@ pub(crate) fn glyph_bbox(
     })
 }
 
+/// Glyph metrics used to calculate phantom points.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct PhantomMetrics {
+    pub hor_advance: u16,
+    pub hor_side_bearing: i16,
+    /// `None` when a font doesn't have vertical metrics.
+    pub ver_metrics: Option<(u16, i16)>,
+    /// Used when vertical metrics are not set.
+    pub ascender: i16,
+    pub descender: i16,
+}
+
+pub(crate) fn outline_points<'a>(
+    loca_table: loca::Table,
+    glyf_table: &'a [u8],
+    glyph_id: GlyphId,
+    metrics: PhantomMetrics,
+) -> Option<OutlinePoints<'a>> {
+    let mut contour_ends = LazyArray16::default();
+    let mut points = GlyphPointsIter::default();
+    let mut bbox = Rect { x_min: 0, y_min: 0, x_max: 0, y_max: 0 };
+
+    // An empty glyph still has phantom points.
+    if let Some(range) = loca_table.glyph_range(glyph_id) {
+        let mut s = Stream::new(glyf_table.get(range)?);
+        let number_of_contours: i16 = s.read()?;
+        bbox = Rect {
+            x_min: s.read()?,
+            y_min: s.read()?,
+            x_max: s.read()?,
+            y_max: s.read()?,
+        };
+
+        // Points of composite glyphs are defined by components.
+        let number_of_contours = NonZeroU16::new(u16::try_from(number_of_contours).ok()?)?;
+
+        let data = s.tail()?;
+        contour_ends = Stream::new(data).read_array16(number_of_contours.get())?;
+        points = parse_simple_outline(data, number_of_contours)?;
+    }
+
+    Some(OutlinePoints {
+        contour_ends,
+        all_points: points.clone(),
+        points,
+        phantom_points: phantom_points(bbox, metrics),
+        phantom_index: 0,
+        tuples: None,
+    })
+}
+
+/// Calculates four phantom points of a glyph.
+// https://docs.microsoft.com/en-us/typography/opentype/spec/tt_instructing_glyphs#phantom-points
+pub(crate) fn phantom_points(bbox: Rect, metrics: PhantomMetrics) -> [(f32, f32); 4] {
+    let h_origin = f32::from(bbox.x_min) - f32::from(metrics.hor_side_bearing);
+    let h_advance = h_origin + f32::from(metrics.hor_advance);
+    let (v_origin, v_advance) = match metrics.ver_metrics {
+        Some((advance, side_bearing)) => {
+            let origin = f32::from(bbox.y_max) + f32::from(side_bearing);
+            (origin, origin - f32::from(advance))
+        }
+        // Like FreeType, use the ascender as the top side bearing origin
+        // and the line height as the advance.
+        None => (f32::from(metrics.ascender), f32::from(metrics.descender)),
+    };
+
+    [(h_origin, 0.0), (h_advance, 0.0), (0.0, v_origin), (0.0, v_advance)]
+}
+
+#[inline]
+pub(crate) fn components<'a>(
+    loca_table: loca::Table,
+    glyf_table: &'a [u8],
+    glyph_id: GlyphId,
+) -> Option<Components<'a>> {
+    let range = loca_table.glyph_range(glyph_id)?;
+    let glyph_data = glyf_table.get(range)?;
+    let mut s = Stream::new(glyph_data);
+    let number_of_contours: i16 = s.read()?;
+    if number_of_contours >= 0 {
+        // Not a composite glyph.
+        return None;
+    }
+
+    s.advance(8); // Skip bbox.
+    Some(Components::new(s.tail()?))
+}
+
+pub(crate) fn glyph_instructions<'a>(
+    loca_table: loca::Table,
+    glyf_table: &'a [u8],
+    glyph_id: GlyphId,
+) -> Option<&'a [u8]> {
+    let range = loca_table.glyph_range(glyph_id)?;
+    let mut s = Stream::new(glyf_table.get(range)?);
+    let number_of_contours: i16 = s.read()?;
+    s.advance(8); // Skip bbox.
+
+    if number_of_contours > 0 {
+        // Skip endpoints.
+        s.advance_checked(usize::from(number_of_contours as u16) * 2)?;
+    } else if number_of_contours < 0 {
+        let mut components = Components::new(s.tail()?);
+        let mut have_instructions = false;
+        for component in &mut components {
+            have_instructions |= component.flags.we_have_instructions();
+        }
+
+        // Malformed components data or no instructions.
+        if !components.finished || !have_instructions {
+            return None;
+        }
+
+        s = components.stream;
+    } else {
+        return None;
+    }
+
+    let instructions_len: u16 = s.read()?;
+    if instructions_len == 0 {
+        return None;
+    }
+
+    s.read_bytes(usize::from(instructions_len))
+}
+
+/// Returns an ID of a glyph which metrics should be used by the specified glyph.
+///
+/// Composite glyphs can borrow metrics of a component using the `USE_MY_METRICS` flag.
+/// Nested composite glyphs are resolved too.
+///
+/// For simple and empty glyphs only the contour count is read,
+/// so components are parsed only for composite glyphs.
+pub(crate) fn metrics_glyph_id(
+    loca_table: loca::Table,
+    glyf_table: &[u8],
+    mut glyph_id: GlyphId,
+) -> GlyphId {
+    for _ in 0..MAX_COMPONENTS {
+        // `components` returns `None` for non-composite glyphs right after the contour count.
+        let component = components(loca_table, glyf_table, glyph_id)
+            .and_then(|mut components| components.find(|c| c.flags.use_my_metrics()));
+
+        match component {
+            Some(component) => glyph_id = component.glyph_id,
+            None => break,
+        }
+    }
+
+    glyph_id
+}
+
 #[inline]
 fn outline_impl(
     loca_table: loca::Table,
     glyf_table: &[u8],
     data: &[u8],
     depth: u8,
+    budget: &mut u32,
     builder: &mut Builder,
 ) -> Option<Rect> {
     if depth >= MAX_COMPONENTS {
@@ -559,12 +927,16 @@ fn outline_impl(
         }
     } else if number_of_contours < 0 {
         // Composite glyph.
-        for comp in CompositeGlyphIter::new(s.tail()?) {
+        for (index, comp) in Components::new(s.tail()?).enumerate() {
             if let Some(range) = loca_table.glyph_range(comp.glyph_id) {
                 if let Some(glyph_data) = glyf_table.get(range) {
-                    let transform = Transform::combine(builder.transform, comp.transform);
+                    let index = u16::try_from(index).ok()?;
+                    let comp_ts = component_transform(
+                        loca_table, glyf_table, data, index, &comp, depth, budget,
+                    )?;
+                    let transform = Transform::combine(builder.transform, comp_ts);
                     let mut b = Builder::new(transform, None, builder.builder);
-                    outline_impl(loca_table, glyf_table, glyph_data, depth + 1, &mut b)?;
+                    outline_impl(loca_table, glyf_table, glyph_data, depth + 1, budget, &mut b)?;
                 }
             }
         }
@@ -576,8 +948,132 @@ fn outline_impl(
     Some(rect)
 }
 
+/// A point search result.
+pub(crate) enum PointSearch {
+    /// A point in glyph's coordinates.
+    Found(f32, f32),
+    /// A glyph doesn't have a point with the requested index.
+    /// Contains the total number of glyph points.
+    NotFound(u16),
+}
+
+/// Returns a component transform.
+///
+/// When a component is positioned using point numbers, the offset is calculated
+/// so that the child point matches the parent one.
+fn component_transform(
+    loca_table: loca::Table,
+    glyf_table: &[u8],
+    parent_data: &[u8],
+    index: u16,
+    comp: &Component,
+    depth: u8,
+    budget: &mut u32,
+) -> Option<Transform> {
+    if comp.flags.args_are_xy_values() {
+        return Some(comp.transform);
+    }
+
+    let (parent_point, child_point) = comp.matching_points?;
+
+    // A parent point can reference only points of previous components.
+    let parent_point = match find_point(loca_table, glyf_table, parent_data,
+                                        parent_point, index, depth, budget)? {
+        PointSearch::Found(x, y) => (x, y),
+        PointSearch::NotFound(_) => return None,
+    };
+
+    let child_data = glyf_table.get(loca_table.glyph_range(comp.glyph_id)?)?;
+    let child_point = match find_point(loca_table, glyf_table, child_data,
+                                       child_point, core::u16::MAX, depth + 1, budget)? {
+        PointSearch::Found(x, y) => (x, y),
+        PointSearch::NotFound(_) => return None,
+    };
+
+    Some(matched_transform(comp.transform, parent_point, child_point))
+}
+
+/// Adjusts a component transform offset so that the transformed `child` point
+/// would match the `parent` one.
+#[inline]
+pub(crate) fn matched_transform(mut ts: Transform, parent: (f32, f32), child: (f32, f32)) -> Transform {
+    let (mut x, mut y) = child;
+    ts.e = 0.0;
+    ts.f = 0.0;
+    ts.apply_to(&mut x, &mut y);
+    ts.e = parent.0 - x;
+    ts.f = parent.1 - y;
+    ts
+}
+
+/// Looks for a point with the specified index in a glyph.
+///
+/// Points of a composite glyph are points of all its components,
+/// in order and with components transform applied.
+/// Only the first `components_limit` components are checked.
+///
+/// Each visited glyph consumes one `budget` step.
+fn find_point(
+    loca_table: loca::Table,
+    glyf_table: &[u8],
+    data: &[u8],
+    index: u16,
+    components_limit: u16,
+    depth: u8,
+    budget: &mut u32,
+) -> Option<PointSearch> {
+    if depth >= MAX_COMPONENTS {
+        return None;
+    }
+
+    *budget = budget.checked_sub(1)?;
+
+    let mut s = Stream::new(data);
+    let number_of_contours: i16 = s.read()?;
+    s.advance(8); // Skip bbox.
+
+    if number_of_contours > 0 {
+        // u16 casting is safe, since we already checked that the value is positive.
+        let number_of_contours = NonZeroU16::new(number_of_contours as u16)?;
+        let mut points = parse_simple_outline(s.tail()?, number_of_contours)?;
+        let points_len = points.points_left;
+        match points.nth(usize::from(index)) {
+            Some(p) => Some(PointSearch::Found(f32::from(p.x), f32::from(p.y))),
+            None => Some(PointSearch::NotFound(points_len)),
+        }
+    } else if number_of_contours < 0 {
+        let mut points_len = 0u16;
+        let components = Components::new(s.tail()?);
+        for (i, comp) in components.take(usize::from(components_limit)).enumerate() {
+            let range = match loca_table.glyph_range(comp.glyph_id) {
+                Some(v) => v,
+                None => continue, // An empty glyph.
+            };
+            let glyph_data = glyf_table.get(range)?;
+
+            // `points_len` cannot be bigger than `index` here.
+            match find_point(loca_table, glyf_table, glyph_data,
+                             index - points_len, core::u16::MAX, depth + 1, budget)? {
+                PointSearch::Found(mut x, mut y) => {
+                    let i = u16::try_from(i).ok()?;
+                    let ts = component_transform(loca_table, glyf_table, data, i, &comp, depth, budget)?;
+                    ts.apply_to(&mut x, &mut y);
+                    return Some(PointSearch::Found(x, y));
+                }
+                PointSearch::NotFound(n) => {
+                    points_len = points_len.checked_add(n)?;
+                }
+            }
+        }
+
+        Some(PointSearch::NotFound(points_len))
+    } else {
+        Some(PointSearch::NotFound(0))
+    }
+}
+
 #[inline]
-pub fn parse_simple_outline(
+pub(crate) fn parse_simple_outline(
     glyph_data: &[u8],
     number_of_contours: NonZeroU16,
 ) -> Option<GlyphPointsIter> {
@@ -660,3 +1156,384 @@ fn resolve_coords_len(
 
     Some((x_coords_len, y_coords_len))
 }
+
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::string::String;
+    use std::vec::Vec;
+    use std::fmt::Write;
+    use crate::writer;
+    use writer::TtfType::*;
+
+    pub struct Builder(pub String);
+    impl OutlineBuilder for Builder {
+        fn move_to(&mut self, x: f32, y: f32) {
+            write!(&mut self.0, "M {} {} ", x, y).unwrap();
+        }
+
+        fn line_to(&mut self, x: f32, y: f32) {
+            write!(&mut self.0, "L {} {} ", x, y).unwrap();
+        }
+
+        fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
+            write!(&mut self.0, "Q {} {} {} {} ", x1, y1, x, y).unwrap();
+        }
+
+        fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) {
+            write!(&mut self.0, "C {} {} {} {} {} {} ", x1, y1, x2, y2, x, y).unwrap();
+        }
+
+        fn close(&mut self) {
+            write!(&mut self.0, "Z ").unwrap();
+        }
+    }
+
+    // A single contour with on-curve points only.
+    pub fn simple_glyph(points: &[(i16, i16)]) -> Vec<writer::TtfType> {
+        let mut data = vec![
+            Int16(1), // number of contours
+            Int16(0), Int16(0), Int16(0), Int16(0), // bbox
+            UInt16(points.len() as u16 - 1), // end point
+            UInt16(0), // instructions length
+        ];
+
+        data.extend(points.iter().map(|_| UInt8(0x01)));
+
+        let mut prev = 0;
+        for p in points {
+            data.push(Int16(p.0 - prev));
+            prev = p.0;
+        }
+
+        let mut prev = 0;
+        for p in points {
+            data.push(Int16(p.1 - prev));
+            prev = p.1;
+        }
+
+        data
+    }
+
+    pub fn build_glyf(glyphs: &[Vec<writer::TtfType>]) -> (Vec<u8>, Vec<u8>) {
+        let mut glyf = Vec::new();
+        let mut loca = Vec::new();
+        for glyph in glyphs {
+            loca.extend_from_slice(&(glyf.len() as u32).to_be_bytes());
+            glyf.extend_from_slice(&writer::convert(glyph));
+            if glyf.len() % 2 != 0 {
+                glyf.push(0);
+            }
+        }
+        loca.extend_from_slice(&(glyf.len() as u32).to_be_bytes());
+        (glyf, loca)
+    }
+
+    pub fn test_glyphs() -> Vec<Vec<writer::TtfType>> {
+        vec![
+            // An empty glyph.
+            vec![],
+            simple_glyph(&[(0, 0), (100, 0), (100, 100), (0, 100)]),
+            simple_glyph(&[(10, 0), (20, 30), (0, 30)]),
+            vec![
+                Int16(-1), // number of contours
+                Int16(0), Int16(0), Int16(0), Int16(0), // bbox
+                // ARG_1_AND_2_ARE_WORDS | ARGS_ARE_XY_VALUES | MORE_COMPONENTS
+                UInt16(0x0023),
+                UInt16(1), // glyph id
+                Int16(0), Int16(0), // offset
+                UInt16(0x0000), // flags
+                UInt16(2), // glyph id
+                UInt8(2), UInt8(0), // parent and child points
+            ],
+            vec![
+                Int16(-1), // number of contours
+                Int16(0), Int16(0), Int16(0), Int16(0), // bbox
+                // ARG_1_AND_2_ARE_WORDS | ARGS_ARE_XY_VALUES | MORE_COMPONENTS
+                UInt16(0x0023),
+                UInt16(1), // glyph id
+                Int16(0), Int16(0), // offset
+                UInt16(0x0009), // ARG_1_AND_2_ARE_WORDS | WE_HAVE_A_SCALE
+                UInt16(2), // glyph id
+                UInt16(2), UInt16(0), // parent and child points
+                Int16(0x2000), // 0.5 scale
+            ],
+            vec![
+                Int16(-1), // number of contours
+                Int16(0), Int16(0), Int16(0), Int16(0), // bbox
+                // ARG_1_AND_2_ARE_WORDS | ARGS_ARE_XY_VALUES | MORE_COMPONENTS
+                UInt16(0x0023),
+                UInt16(3), // glyph id
+                Int16(0), Int16(0), // offset
+                UInt16(0x0000), // flags
+                UInt16(2), // glyph id
+                UInt8(5), UInt8(0), // parent and child points
+            ],
+            vec![
+                Int16(-1), // number of contours
+                Int16(0), Int16(0), Int16(0), Int16(0), // bbox
+                UInt16(0x0020), // MORE_COMPONENTS
+                UInt16(1), // glyph id
+                UInt8(0), UInt8(0), // parent and child points
+                UInt16(0x0000), // flags
+                UInt16(2), // glyph id
+                UInt8(2), UInt8(0), // parent and child points
+            ],
+            vec![
+                Int16(-1), // number of contours
+                Int16(0), Int16(0), Int16(0), Int16(0), // bbox
+                UInt16(0x0022), // ARGS_ARE_XY_VALUES | MORE_COMPONENTS
+                UInt16(1), // glyph id
+                Int8(0), Int8(0), // offset
+                UInt16(0x0202), // ARGS_ARE_XY_VALUES | USE_MY_METRICS
+                UInt16(2), // glyph id
+                Int8(10), Int8(-10), // offset
+            ],
+            vec![
+                Int16(-1), // number of contours
+                Int16(0), Int16(0), Int16(0), Int16(0), // bbox
+                UInt16(0x0202), // ARGS_ARE_XY_VALUES | USE_MY_METRICS
+                UInt16(7), // glyph id
+                Int8(0), Int8(0), // offset
+            ],
+        ]
+    }
+
+    fn parse_loca(data: &[u8]) -> loca::Table {
+        let number_of_glyphs = NonZeroU16::new(data.len() as u16 / 4 - 1).unwrap();
+        loca::Table::parse(data, number_of_glyphs, crate::IndexToLocationFormat::Long).unwrap()
+    }
+
+    fn outline_glyph(glyph_id: u16) -> Option<String> {
+        let (glyf, loca) = build_glyf(&test_glyphs());
+        let loca = parse_loca(&loca);
+        let mut builder = Builder(String::new());
+        outline(loca, &glyf, GlyphId(glyph_id), &mut builder)?;
+        Some(builder.0)
+    }
+
+    #[test]
+    fn composite_iter_point_numbers() {
+        let data = writer::convert(&test_glyphs()[4]);
+        let mut iter = Components::new(&data[10..]);
+        let comp = iter.next().unwrap();
+        assert_eq!(comp.matching_points, None);
+        let comp = iter.next().unwrap();
+        assert_eq!(comp.glyph_id, GlyphId(2));
+        assert_eq!(comp.matching_points, Some((2, 0)));
+        assert_eq!(comp.transform.a, 0.5);
+        assert_eq!(comp.transform.d, 0.5);
+        assert!(iter.next().is_none());
+    }
+
+    #[test]
+    fn point_matching() {
+        assert_eq!(outline_glyph(3).unwrap(),
+                   "M 0 0 L 100 0 L 100 100 L 0 100 L 0 0 Z \
+                    M 100 100 L 110 130 L 90 130 L 100 100 Z ");
+    }
+
+    #[test]
+    fn point_matching_with_scale() {
+        assert_eq!(outline_glyph(4).unwrap(),
+                   "M 0 0 L 100 0 L 100 100 L 0 100 L 0 0 Z \
+                    M 100 100 L 105 115 L 95 115 L 100 100 Z ");
+    }
+
+    #[test]
+    fn point_matching_nested() {
+        // The parent point is a point of a nested composite glyph.
+        assert_eq!(outline_glyph(5).unwrap(),
+                   "M 0 0 L 100 0 L 100 100 L 0 100 L 0 0 Z \
+                    M 100 100 L 110 130 L 90 130 L 100 100 Z \
+                    M 110 130 L 120 160 L 100 160 L 110 130 Z ");
+    }
+
+    #[test]
+    fn point_matching_out_of_bounds() {
+        // The first component cannot reference parent points.
+        assert!(outline_glyph(6).is_none());
+    }
+
+    #[test]
+    fn point_matching_too_expensive() {
+        // Each component is attached to the last point of the previous one,
+        // so every point lookup has to resolve all the previous components.
+        fn chained(glyph_id: u16, points: u16) -> Vec<writer::TtfType> {
+            let mut data = vec![
+                Int16(-1), // number of contours
+                Int16(0), Int16(0), Int16(0), Int16(0), // bbox
+                UInt16(0x0023), // ARG_1_AND_2_ARE_WORDS | ARGS_ARE_XY_VALUES | MORE_COMPONENTS
+                UInt16(glyph_id),
+                Int16(0), Int16(0), // offset
+            ];
+
+            for i in 1..MAX_COMPONENTS as u16 {
+                let flags = if i + 1 == MAX_COMPONENTS as u16 { 0x0001 } else { 0x0021 };
+                data.push(UInt16(flags)); // ARG_1_AND_2_ARE_WORDS | MORE_COMPONENTS
+                data.push(UInt16(glyph_id));
+                data.push(UInt16(i * points - 1)); // parent point
+                data.push(UInt16(0)); // child point
+            }
+
+            data
+        }
+
+        let glyphs = vec![
+            simple_glyph(&[(0, 0), (100, 0), (100, 100), (0, 100)]),
+            chained(0, 4),
+            chained(1, 4 * MAX_COMPONENTS as u16),
+        ];
+
+        let (glyf, loca) = build_glyf(&glyphs);
+        let loca = parse_loca(&loca);
+
+        // A single chain is fine.
+        let mut builder = Builder(String::new());
+        assert!(outline(loca, &glyf, GlyphId(1), &mut builder).is_some());
+
+        // But a chain of chains exceeds the point search budget.
+        let mut builder = Builder(String::new());
+        assert!(outline(loca, &glyf, GlyphId(2), &mut builder).is_none());
+    }
+
+    #[test]
+    fn components_iter() {
+        let (glyf, loca) = build_glyf(&test_glyphs());
+        let loca = parse_loca(&loca);
+
+        let mut iter = components(loca, &glyf, GlyphId(7)).unwrap();
+        let comp = iter.next().unwrap();
+        assert_eq!(comp.glyph_id, GlyphId(1));
+        assert_eq!(comp.transform, Transform::default());
+        assert!(!comp.flags.use_my_metrics());
+        let comp = iter.next().unwrap();
+        assert_eq!(comp.glyph_id, GlyphId(2));
+        assert_eq!(comp.transform, Transform::new_translate(10.0, -10.0));
+        assert!(comp.flags.use_my_metrics());
+        assert!(comp.flags.args_are_xy_values());
+        assert!(!comp.flags.more_components());
+        assert!(iter.next().is_none());
+
+        // Not a composite glyph.
+        assert!(components(loca, &glyf, GlyphId(1)).is_none());
+        // An empty glyph.
+        assert!(components(loca, &glyf, GlyphId(0)).is_none());
+    }
+
+    #[test]
+    fn use_my_metrics() {
+        let (glyf, loca) = build_glyf(&test_glyphs());
+        let loca = parse_loca(&loca);
+        assert_eq!(metrics_glyph_id(loca, &glyf, GlyphId(1)), GlyphId(1));
+        assert_eq!(metrics_glyph_id(loca, &glyf, GlyphId(3)), GlyphId(3));
+        assert_eq!(metrics_glyph_id(loca, &glyf, GlyphId(7)), GlyphId(2));
+        // Nested composite glyphs.
+        assert_eq!(metrics_glyph_id(loca, &glyf, GlyphId(8)), GlyphId(2));
+    }
+
+    pub const TEST_METRICS: PhantomMetrics = PhantomMetrics {
+        hor_advance: 120,
+        hor_side_bearing: 10,
+        ver_metrics: None,
+        ascender: 800,
+        descender: -200,
+    };
+
+    #[test]
+    fn raw_points() {
+        let (glyf, loca) = build_glyf(&test_glyphs());
+        let loca = parse_loca(&loca);
+
+        let points = outline_points(loca, &glyf, GlyphId(2), TEST_METRICS).unwrap();
+        assert_eq!(points.points_len(), 3);
+        assert_eq!(points.contour_ends().get(0), Some(2));
+        assert_eq!(points.contour_ends().len(), 1);
+
+        let points: Vec<_> = points.map(|p| (p.x, p.y, p.on_curve_point, p.last_point)).collect();
+        assert_eq!(points, vec![
+            (10.0, 0.0, true, false),
+            (20.0, 30.0, true, false),
+            (0.0, 30.0, true, true),
+            // Phantom points. The bbox in the test data is zero.
+            (-10.0, 0.0, false, false),
+            (110.0, 0.0, false, false),
+            (0.0, 800.0, false, false),
+            (0.0, -200.0, false, false),
+        ]);
+    }
+
+    #[test]
+    fn raw_points_vertical_metrics() {
+        let (glyf, loca) = build_glyf(&test_glyphs());
+        let loca = parse_loca(&loca);
+
+        let metrics = PhantomMetrics { ver_metrics: Some((1000, 50)), ..TEST_METRICS };
+        let points: Vec<_> = outline_points(loca, &glyf, GlyphId(1), metrics).unwrap()
+            .skip(4).map(|p| (p.x, p.y)).collect();
+        assert_eq!(points, vec![(-10.0, 0.0), (110.0, 0.0), (0.0, 50.0), (0.0, -950.0)]);
+    }
+
+    #[test]
+    fn raw_points_empty_glyph() {
+        let (glyf, loca) = build_glyf(&test_glyphs());
+        let loca = parse_loca(&loca);
+
+        let points = outline_points(loca, &glyf, GlyphId(0), TEST_METRICS).unwrap();
+        assert_eq!(points.points_len(), 0);
+        assert!(points.contour_ends().is_empty());
+        assert_eq!(points.count(), 4);
+
+        // Composite glyphs don't have their own points.
+        assert!(outline_points(loca, &glyf, GlyphId(3), TEST_METRICS).is_none());
+    }
+
+    #[test]
+    fn instructions() {
+        let glyphs = vec![
+            vec![
+                Int16(1), // number of contours
+                Int16(0), Int16(0), Int16(0), Int16(0), // bbox
+                UInt16(0), // end point
+                UInt16(3), // instructions length
+                UInt8(0xB0), UInt8(1), UInt8(0x2F), // PUSHB[000] 1, MDAP[1]
+                UInt8(0x01), // flags
+                UInt8(0), // x
+                UInt8(0), // y
+            ],
+            vec![
+                Int16(-1), // number of contours
+                Int16(0), Int16(0), Int16(0), Int16(0), // bbox
+                UInt16(0x0022), // ARGS_ARE_XY_VALUES | MORE_COMPONENTS
+                UInt16(0), // glyph id
+                Int8(0), Int8(0), // offset
+                UInt16(0x0102), // ARGS_ARE_XY_VALUES | WE_HAVE_INSTRUCTIONS
+                UInt16(0), // glyph id
+                Int8(10), Int8(0), // offset
+                UInt16(1), // instructions length
+                UInt8(0x30), // IUP[0]
+            ],
+            vec![
+                Int16(-1), // number of contours
+                Int16(0), Int16(0), Int16(0), Int16(0), // bbox
+                UInt16(0x0002), // ARGS_ARE_XY_VALUES
+                UInt16(0), // glyph id
+                Int8(0), Int8(0), // offset
+            ],
+            simple_glyph(&[(0, 0), (10, 0), (10, 10)]),
+            vec![],
+        ];
+
+        let (glyf, loca) = build_glyf(&glyphs);
+        let loca = parse_loca(&loca);
+        assert_eq!(glyph_instructions(loca, &glyf, GlyphId(0)), Some(&[0xB0, 1, 0x2F][..]));
+        assert_eq!(glyph_instructions(loca, &glyf, GlyphId(1)), Some(&[0x30][..]));
+        // Composite glyphs without WE_HAVE_INSTRUCTIONS have no instructions.
+        assert_eq!(glyph_instructions(loca, &glyf, GlyphId(2)), None);
+        // Zero-length instructions.
+        assert_eq!(glyph_instructions(loca, &glyf, GlyphId(3)), None);
+        // An empty glyph.
+        assert_eq!(glyph_instructions(loca, &glyf, GlyphId(4)), None);
+    }
+}
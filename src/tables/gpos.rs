@@ -0,0 +1,1220 @@
+/*!
+A [Glyph Positioning Table](https://docs.microsoft.com/en-us/typography/opentype/spec/gpos)
+implementation.
+
+This module provides only lookup subtables. Scripts, features and lookups themselves
+are accessible via `Font::glyph_positioning()`.
+
+All nine lookup types are supported.
+Extension positioning (type 9) is resolved transparently.
+
+Variation deltas referenced by `Device::Variation` can be resolved
+via `Font::glyph_variation_delta()`.
+*/
+
+use crate::GlyphId;
+use crate::ggg::{Class, ClassDefinitionTable, CoverageTable, ContextLookup, ChainedContextLookup};
+use crate::ggg::{LookupSubtable, parse_coverage};
+use crate::parser::{Stream, FromData, LazyArray16, Offset, Offset16, Offset32};
+
+
+/// A [Device Table](
+/// https://docs.microsoft.com/en-us/typography/opentype/spec/chapter2#device-and-variationindex-tables)
+/// with hinting deltas.
+#[derive(Clone, Copy)]
+pub struct HintingDevice<'a> {
+    start_size: u16,
+    end_size: u16,
+    delta_format: u16,
+    delta_values: LazyArray16<'a, u16>,
+}
+
+impl HintingDevice<'_> {
+    /// Returns a delta in pixels for a specified pixels per EM.
+    ///
+    /// Returns `None` when `pixels_per_em` is not covered by the table.
+    pub fn delta(&self, pixels_per_em: u16) -> Option<i16> {
+        if pixels_per_em < self.start_size || pixels_per_em > self.end_size {
+            return None;
+        }
+
+        // The number of bits per delta value.
+        let bits: u16 = match self.delta_format {
+            1 => 2,
+            2 => 4,
+            3 => 8,
+            _ => return None,
+        };
+
+        let values_per_word = 16 / bits;
+        let index = pixels_per_em - self.start_size;
+        let word = self.delta_values.get(index / values_per_word)?;
+        // Values are packed starting from the most significant bits.
+        let shift = 16 - bits * (index % values_per_word + 1);
+        let mask = (1u16 << bits) - 1;
+        let value = (word >> shift) & mask;
+
+        // Sign-extend.
+        let sign_bit = 1u16 << (bits - 1);
+        let value = if value & sign_bit != 0 {
+            i16::from(value as u8) - (1i16 << bits)
+        } else {
+            value as i16
+        };
+
+        Some(value)
+    }
+}
+
+impl core::fmt::Debug for HintingDevice<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("HintingDevice")
+            .field("start_size", &self.start_size)
+            .field("end_size", &self.end_size)
+            .field("delta_format", &self.delta_format)
+            .finish()
+    }
+}
+
+
+/// A [Device or VariationIndex Table](
+/// https://docs.microsoft.com/en-us/typography/opentype/spec/chapter2#device-and-variationindex-tables).
+#[allow(missing_docs)]
+#[derive(Clone, Copy, Debug)]
+pub enum Device<'a> {
+    Hinting(HintingDevice<'a>),
+    /// Indices into the `GDEF` Item Variation Store.
+    ///
+    /// Can be resolved via `Font::glyph_variation_delta()`.
+    Variation {
+        outer_index: u16,
+        inner_index: u16,
+    },
+}
+
+impl<'a> Device<'a> {
+    fn parse(data: &'a [u8]) -> Option<Self> {
+        let mut s = Stream::new(data);
+        let first: u16 = s.read()?;
+        let second: u16 = s.read()?;
+        let format: u16 = s.read()?;
+        match format {
+            1..=3 => {
+                let start_size = first;
+                let end_size = second;
+                let count = u32::from(end_size.checked_sub(start_size)?) + 1;
+                let bits = 1 << format;
+                // Round up to a whole number of 16-bit words.
+                let words = (count * bits + 15) >> 4;
+                let delta_values = s.read_array16(words as u16)?;
+                Some(Device::Hinting(HintingDevice {
+                    start_size,
+                    end_size,
+                    delta_format: format,
+                    delta_values,
+                }))
+            }
+            0x8000 => {
+                Some(Device::Variation {
+                    outer_index: first,
+                    inner_index: second,
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Parses a device table via a nullable `Offset16` at the current stream position.
+fn parse_device<'a>(data: &'a [u8], s: &mut Stream) -> Option<Option<Device<'a>>> {
+    match s.read::<Option<Offset16>>()? {
+        // Malformed device tables are ignored.
+        Some(offset) => Some(data.get(offset.to_usize()..).and_then(Device::parse)),
+        None => Some(None),
+    }
+}
+
+
+#[derive(Clone, Copy, Default, Debug)]
+struct ValueFormat(u16);
+
+impl ValueFormat {
+    const X_PLACEMENT: u16          = 0x0001;
+    const Y_PLACEMENT: u16          = 0x0002;
+    const X_ADVANCE: u16            = 0x0004;
+    const Y_ADVANCE: u16            = 0x0008;
+    const X_PLACEMENT_DEVICE: u16   = 0x0010;
+    const Y_PLACEMENT_DEVICE: u16   = 0x0020;
+    const X_ADVANCE_DEVICE: u16     = 0x0040;
+    const Y_ADVANCE_DEVICE: u16     = 0x0080;
+
+    #[inline]
+    fn has(self, flag: u16) -> bool {
+        self.0 & flag != 0
+    }
+
+    /// Returns the size of the value record in bytes.
+    #[inline]
+    fn size(self) -> usize {
+        // Each set flag from the lowest 8 bits corresponds to a 16-bit field.
+        (self.0 & 0x00FF).count_ones() as usize * u16::SIZE
+    }
+}
+
+impl FromData for ValueFormat {
+    const SIZE: usize = 2;
+
+    #[inline]
+    fn parse(data: &[u8]) -> Option<Self> {
+        u16::parse(data).map(ValueFormat)
+    }
+}
+
+
+/// A [Value Record](https://docs.microsoft.com/en-us/typography/opentype/spec/gpos#value-record).
+///
+/// Fields that are not set in the font are set to zero or `None`.
+#[allow(missing_docs)]
+#[derive(Clone, Copy, Default, Debug)]
+pub struct ValueRecord<'a> {
+    pub x_placement: i16,
+    pub y_placement: i16,
+    pub x_advance: i16,
+    pub y_advance: i16,
+    pub x_placement_device: Option<Device<'a>>,
+    pub y_placement_device: Option<Device<'a>>,
+    pub x_advance_device: Option<Device<'a>>,
+    pub y_advance_device: Option<Device<'a>>,
+}
+
+impl<'a> ValueRecord<'a> {
+    /// Parses a value record.
+    ///
+    /// `data` is the data device offsets are relative to.
+    fn parse(data: &'a [u8], s: &mut Stream, format: ValueFormat) -> Option<Self> {
+        let mut record = ValueRecord::default();
+
+        if format.has(ValueFormat::X_PLACEMENT) {
+            record.x_placement = s.read()?;
+        }
+
+        if format.has(ValueFormat::Y_PLACEMENT) {
+            record.y_placement = s.read()?;
+        }
+
+        if format.has(ValueFormat::X_ADVANCE) {
+            record.x_advance = s.read()?;
+        }
+
+        if format.has(ValueFormat::Y_ADVANCE) {
+            record.y_advance = s.read()?;
+        }
+
+        if format.has(ValueFormat::X_PLACEMENT_DEVICE) {
+            record.x_placement_device = parse_device(data, s)?;
+        }
+
+        if format.has(ValueFormat::Y_PLACEMENT_DEVICE) {
+            record.y_placement_device = parse_device(data, s)?;
+        }
+
+        if format.has(ValueFormat::X_ADVANCE_DEVICE) {
+            record.x_advance_device = parse_device(data, s)?;
+        }
+
+        if format.has(ValueFormat::Y_ADVANCE_DEVICE) {
+            record.y_advance_device = parse_device(data, s)?;
+        }
+
+        Some(record)
+    }
+}
+
+
+/// An array of value records with the same format.
+#[derive(Clone, Copy)]
+pub struct ValueRecordsArray<'a> {
+    // Device offsets are relative to this data.
+    data: &'a [u8],
+    values: &'a [u8],
+    format: ValueFormat,
+    len: u16,
+}
+
+impl<'a> ValueRecordsArray<'a> {
+    fn parse(data: &'a [u8], s: &mut Stream<'a>, format: ValueFormat, len: u16) -> Option<Self> {
+        let values = s.read_bytes(usize::from(len) * format.size())?;
+        Some(ValueRecordsArray { data, values, format, len })
+    }
+
+    /// Returns the number of records.
+    #[inline]
+    pub fn len(&self) -> u16 {
+        self.len
+    }
+
+    /// Checks if there are any records.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns a record at `index`.
+    #[inline]
+    pub fn get(&self, index: u16) -> Option<ValueRecord<'a>> {
+        if index >= self.len {
+            return None;
+        }
+
+        let mut s = Stream::new_at(self.values, usize::from(index) * self.format.size())?;
+        ValueRecord::parse(self.data, &mut s, self.format)
+    }
+}
+
+impl core::fmt::Debug for ValueRecordsArray<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "ValueRecordsArray({})", self.len)
+    }
+}
+
+
+/// A [Single Adjustment Positioning Subtable](
+/// https://docs.microsoft.com/en-us/typography/opentype/spec/gpos#SP).
+#[allow(missing_docs)]
+#[derive(Clone, Copy, Debug)]
+pub enum SingleAdjustment<'a> {
+    Format1 {
+        coverage: CoverageTable<'a>,
+        value: ValueRecord<'a>,
+    },
+    Format2 {
+        coverage: CoverageTable<'a>,
+        /// Indexed by coverage index.
+        values: ValueRecordsArray<'a>,
+    },
+}
+
+impl<'a> SingleAdjustment<'a> {
+    fn parse(data: &'a [u8]) -> Option<Self> {
+        let mut s = Stream::new(data);
+        let format: u16 = s.read()?;
+        match format {
+            1 => {
+                let coverage = parse_coverage(data, &mut s)?;
+                let value_format: ValueFormat = s.read()?;
+                let value = ValueRecord::parse(data, &mut s, value_format)?;
+                Some(SingleAdjustment::Format1 { coverage, value })
+            }
+            2 => {
+                let coverage = parse_coverage(data, &mut s)?;
+                let value_format: ValueFormat = s.read()?;
+                let count: u16 = s.read()?;
+                let values = ValueRecordsArray::parse(data, &mut s, value_format, count)?;
+                Some(SingleAdjustment::Format2 { coverage, values })
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the subtable coverage.
+    #[inline]
+    pub fn coverage(&self) -> CoverageTable<'a> {
+        match self {
+            SingleAdjustment::Format1 { coverage, .. } => *coverage,
+            SingleAdjustment::Format2 { coverage, .. } => *coverage,
+        }
+    }
+
+    /// Returns a value record for a glyph.
+    pub fn value(&self, glyph_id: GlyphId) -> Option<ValueRecord<'a>> {
+        match self {
+            SingleAdjustment::Format1 { coverage, value } => {
+                coverage.get(glyph_id)?;
+                Some(*value)
+            }
+            SingleAdjustment::Format2 { coverage, values } => {
+                values.get(coverage.get(glyph_id)?)
+            }
+        }
+    }
+}
+
+
+/// A [Pair Set Table](https://docs.microsoft.com/en-us/typography/opentype/spec/gpos#pairSetTbl).
+#[derive(Clone, Copy)]
+pub struct PairSet<'a> {
+    // Device offsets are relative to the set itself.
+    data: &'a [u8],
+    records: &'a [u8],
+    formats: (ValueFormat, ValueFormat),
+    record_len: usize,
+    len: u16,
+}
+
+impl<'a> PairSet<'a> {
+    fn parse(data: &'a [u8], formats: (ValueFormat, ValueFormat)) -> Option<Self> {
+        let mut s = Stream::new(data);
+        let len: u16 = s.read()?;
+        let record_len = GlyphId::SIZE + formats.0.size() + formats.1.size();
+        let records = s.read_bytes(usize::from(len) * record_len)?;
+        Some(PairSet { data, records, formats, record_len, len })
+    }
+
+    /// Returns the number of pairs.
+    #[inline]
+    pub fn len(&self) -> u16 {
+        self.len
+    }
+
+    /// Checks if there are any pairs.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the second glyph and value records of a pair at `index`.
+    pub fn get(&self, index: u16) -> Option<(GlyphId, ValueRecord<'a>, ValueRecord<'a>)> {
+        if index >= self.len {
+            return None;
+        }
+
+        let mut s = Stream::new_at(self.records, usize::from(index) * self.record_len)?;
+        let second_glyph: GlyphId = s.read()?;
+        let value1 = ValueRecord::parse(self.data, &mut s, self.formats.0)?;
+        let value2 = ValueRecord::parse(self.data, &mut s, self.formats.1)?;
+        Some((second_glyph, value1, value2))
+    }
+
+    /// Returns value records for a pair with a specified second glyph.
+    pub fn find(&self, second: GlyphId) -> Option<(ValueRecord<'a>, ValueRecord<'a>)> {
+        // Records are sorted by the second glyph ID, so we can use the binary search.
+        let mut size = self.len;
+        if size == 0 {
+            return None;
+        }
+
+        let mut base = 0;
+        while size > 1 {
+            let half = size / 2;
+            let mid = base + half;
+            if self.second_glyph(mid)? <= second {
+                base = mid;
+            }
+            size -= half;
+        }
+
+        if self.second_glyph(base)? == second {
+            self.get(base).map(|(_, v1, v2)| (v1, v2))
+        } else {
+            None
+        }
+    }
+
+    #[inline]
+    fn second_glyph(&self, index: u16) -> Option<GlyphId> {
+        Stream::read_at(self.records, usize::from(index) * self.record_len)
+    }
+}
+
+impl core::fmt::Debug for PairSet<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "PairSet({})", self.len)
+    }
+}
+
+
+/// A list of pair sets.
+#[derive(Clone, Copy)]
+pub struct PairSets<'a> {
+    data: &'a [u8],
+    offsets: LazyArray16<'a, Option<Offset16>>,
+    formats: (ValueFormat, ValueFormat),
+}
+
+impl<'a> PairSets<'a> {
+    /// Returns the number of sets.
+    #[inline]
+    pub fn len(&self) -> u16 {
+        self.offsets.len()
+    }
+
+    /// Checks if there are any sets.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.offsets.is_empty()
+    }
+
+    /// Returns a pair set at `index`.
+    #[inline]
+    pub fn get(&self, index: u16) -> Option<PairSet<'a>> {
+        let offset = self.offsets.get(index)??.to_usize();
+        PairSet::parse(self.data.get(offset..)?, self.formats)
+    }
+}
+
+impl core::fmt::Debug for PairSets<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "PairSets({})", self.len())
+    }
+}
+
+
+/// A class-based pair adjustment matrix.
+#[derive(Clone, Copy)]
+pub struct ClassMatrix<'a> {
+    // Device offsets are relative to the parent subtable.
+    data: &'a [u8],
+    matrix: &'a [u8],
+    counts: (u16, u16),
+    formats: (ValueFormat, ValueFormat),
+}
+
+impl<'a> ClassMatrix<'a> {
+    /// Returns the number of first and second classes.
+    #[inline]
+    pub fn counts(&self) -> (u16, u16) {
+        self.counts
+    }
+
+    /// Returns value records for a pair of classes.
+    pub fn get(&self, classes: (Class, Class)) -> Option<(ValueRecord<'a>, ValueRecord<'a>)> {
+        let (class1, class2) = (classes.0 .0, classes.1 .0);
+        if class1 >= self.counts.0 || class2 >= self.counts.1 {
+            return None;
+        }
+
+        let record_len = self.formats.0.size() + self.formats.1.size();
+        let index = usize::from(class1) * usize::from(self.counts.1) + usize::from(class2);
+        let mut s = Stream::new_at(self.matrix, index * record_len)?;
+        let value1 = ValueRecord::parse(self.data, &mut s, self.formats.0)?;
+        let value2 = ValueRecord::parse(self.data, &mut s, self.formats.1)?;
+        Some((value1, value2))
+    }
+}
+
+impl core::fmt::Debug for ClassMatrix<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "ClassMatrix({}x{})", self.counts.0, self.counts.1)
+    }
+}
+
+
+/// A [Pair Adjustment Positioning Subtable](
+/// https://docs.microsoft.com/en-us/typography/opentype/spec/gpos#PP).
+#[allow(missing_docs)]
+#[derive(Clone, Copy, Debug)]
+pub enum PairAdjustment<'a> {
+    /// Adjustments for glyph pairs.
+    Format1 {
+        coverage: CoverageTable<'a>,
+        /// Indexed by coverage index.
+        sets: PairSets<'a>,
+    },
+    /// Class pair adjustment.
+    Format2 {
+        coverage: CoverageTable<'a>,
+        classes: (ClassDefinitionTable<'a>, ClassDefinitionTable<'a>),
+        matrix: ClassMatrix<'a>,
+    },
+}
+
+impl<'a> PairAdjustment<'a> {
+    fn parse(data: &'a [u8]) -> Option<Self> {
+        let mut s = Stream::new(data);
+        let format: u16 = s.read()?;
+        match format {
+            1 => {
+                let coverage = parse_coverage(data, &mut s)?;
+                let formats = (s.read::<ValueFormat>()?, s.read::<ValueFormat>()?);
+                let count: u16 = s.read()?;
+                let offsets = s.read_array16(count)?;
+                Some(PairAdjustment::Format1 {
+                    coverage,
+                    sets: PairSets { data, offsets, formats },
+                })
+            }
+            2 => {
+                let coverage = parse_coverage(data, &mut s)?;
+                let formats = (s.read::<ValueFormat>()?, s.read::<ValueFormat>()?);
+                let class1_offset: Offset16 = s.read()?;
+                let class2_offset: Offset16 = s.read()?;
+                let classes = (
+                    ClassDefinitionTable::new(data.get(class1_offset.to_usize()..)?),
+                    ClassDefinitionTable::new(data.get(class2_offset.to_usize()..)?),
+                );
+                let counts = (s.read::<u16>()?, s.read::<u16>()?);
+                let record_len = formats.0.size() + formats.1.size();
+                let len = usize::from(counts.0) * usize::from(counts.1) * record_len;
+                let matrix = s.read_bytes(len)?;
+                Some(PairAdjustment::Format2 {
+                    coverage,
+                    classes,
+                    matrix: ClassMatrix { data, matrix, counts, formats },
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the subtable coverage.
+    #[inline]
+    pub fn coverage(&self) -> CoverageTable<'a> {
+        match self {
+            PairAdjustment::Format1 { coverage, .. } => *coverage,
+            PairAdjustment::Format2 { coverage, .. } => *coverage,
+        }
+    }
+
+    /// Returns value records for a pair of glyphs.
+    ///
+    /// The first record applies to the first glyph, the second one to the second glyph.
+    pub fn values(&self, first: GlyphId, second: GlyphId) -> Option<(ValueRecord<'a>, ValueRecord<'a>)> {
+        match self {
+            PairAdjustment::Format1 { coverage, sets } => {
+                sets.get(coverage.get(first)?)?.find(second)
+            }
+            PairAdjustment::Format2 { coverage, classes, matrix } => {
+                coverage.get(first)?;
+                matrix.get((classes.0.get(first), classes.1.get(second)))
+            }
+        }
+    }
+}
+
+
+/// An [Anchor Table](https://docs.microsoft.com/en-us/typography/opentype/spec/gpos#anchor-tables).
+#[derive(Clone, Copy, Default, Debug)]
+pub struct Anchor<'a> {
+    /// Horizontal value, in design units.
+    pub x: i16,
+    /// Vertical value, in design units.
+    pub y: i16,
+    /// Index to a glyph contour point.
+    ///
+    /// Set only in the format 2 anchor.
+    pub anchor_point: Option<u16>,
+    /// A device table for the X coordinate.
+    pub x_device: Option<Device<'a>>,
+    /// A device table for the Y coordinate.
+    pub y_device: Option<Device<'a>>,
+}
+
+impl<'a> Anchor<'a> {
+    fn parse(data: &'a [u8]) -> Option<Self> {
+        let mut s = Stream::new(data);
+        let format: u16 = s.read()?;
+        match format {
+            1..=3 => {}
+            _ => return None,
+        }
+
+        let mut anchor = Anchor {
+            x: s.read()?,
+            y: s.read()?,
+            ..Anchor::default()
+        };
+
+        if format == 2 {
+            anchor.anchor_point = Some(s.read()?);
+        } else if format == 3 {
+            anchor.x_device = parse_device(data, &mut s)?;
+            anchor.y_device = parse_device(data, &mut s)?;
+        }
+
+        Some(anchor)
+    }
+}
+
+
+/// A matrix of anchors.
+///
+/// Used as a [Base Array Table](https://docs.microsoft.com/en-us/typography/opentype/spec/gpos#base-array-table),
+/// a [Ligature Attach Table](https://docs.microsoft.com/en-us/typography/opentype/spec/gpos#ligature-attach-table)
+/// and a [Mark2 Array Table](https://docs.microsoft.com/en-us/typography/opentype/spec/gpos#mark2-array-table).
+#[derive(Clone, Copy)]
+pub struct AnchorMatrix<'a> {
+    data: &'a [u8],
+    rows: u16,
+    cols: u16,
+    matrix: LazyArray16<'a, Option<Offset16>>,
+}
+
+impl<'a> AnchorMatrix<'a> {
+    fn parse(data: &'a [u8], cols: u16) -> Option<Self> {
+        let mut s = Stream::new(data);
+        let rows: u16 = s.read()?;
+        let matrix = s.read_array16(rows.checked_mul(cols)?)?;
+        Some(AnchorMatrix { data, rows, cols, matrix })
+    }
+
+    /// Returns the number of rows.
+    ///
+    /// Depending on a table, it's the number of base glyphs, ligature components or marks.
+    #[inline]
+    pub fn rows(&self) -> u16 {
+        self.rows
+    }
+
+    /// Returns an anchor for a row and a mark class.
+    ///
+    /// Returns `None` for null anchors.
+    pub fn get(&self, row: u16, class: Class) -> Option<Anchor<'a>> {
+        if row >= self.rows || class.0 >= self.cols {
+            return None;
+        }
+
+        let offset = self.matrix.get(row * self.cols + class.0)??.to_usize();
+        Anchor::parse(self.data.get(offset..)?)
+    }
+}
+
+impl core::fmt::Debug for AnchorMatrix<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "AnchorMatrix({}x{})", self.rows, self.cols)
+    }
+}
+
+
+#[derive(Clone, Copy)]
+struct MarkRecord {
+    class: Class,
+    anchor_offset: Offset16,
+}
+
+impl FromData for MarkRecord {
+    const SIZE: usize = 4;
+
+    #[inline]
+    fn parse(data: &[u8]) -> Option<Self> {
+        let mut s = Stream::new(data);
+        Some(MarkRecord {
+            class: s.read()?,
+            anchor_offset: s.read()?,
+        })
+    }
+}
+
+
+/// A [Mark Array Table](https://docs.microsoft.com/en-us/typography/opentype/spec/gpos_delta#mark-array-table).
+#[derive(Clone, Copy)]
+pub struct MarkArray<'a> {
+    data: &'a [u8],
+    records: LazyArray16<'a, MarkRecord>,
+}
+
+impl<'a> MarkArray<'a> {
+    fn parse(data: &'a [u8]) -> Option<Self> {
+        let mut s = Stream::new(data);
+        let count: u16 = s.read()?;
+        let records = s.read_array16(count)?;
+        Some(MarkArray { data, records })
+    }
+
+    /// Returns the number of marks.
+    #[inline]
+    pub fn len(&self) -> u16 {
+        self.records.len()
+    }
+
+    /// Checks if there are any marks.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// Returns a mark class and an anchor at `index`.
+    pub fn get(&self, index: u16) -> Option<(Class, Anchor<'a>)> {
+        let record = self.records.get(index)?;
+        let anchor = Anchor::parse(self.data.get(record.anchor_offset.to_usize()..)?)?;
+        Some((record.class, anchor))
+    }
+}
+
+impl core::fmt::Debug for MarkArray<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "MarkArray({})", self.len())
+    }
+}
+
+
+/// Entry and exit anchors of a glyph.
+#[derive(Clone, Copy, Debug)]
+pub struct EntryExit<'a> {
+    /// Entry anchor.
+    pub entry: Option<Anchor<'a>>,
+    /// Exit anchor.
+    pub exit: Option<Anchor<'a>>,
+}
+
+
+#[derive(Clone, Copy)]
+struct EntryExitRecord {
+    entry_anchor_offset: Option<Offset16>,
+    exit_anchor_offset: Option<Offset16>,
+}
+
+impl FromData for EntryExitRecord {
+    const SIZE: usize = 4;
+
+    #[inline]
+    fn parse(data: &[u8]) -> Option<Self> {
+        let mut s = Stream::new(data);
+        Some(EntryExitRecord {
+            entry_anchor_offset: s.read()?,
+            exit_anchor_offset: s.read()?,
+        })
+    }
+}
+
+
+/// A list of entry and exit anchors.
+#[derive(Clone, Copy)]
+pub struct CursiveAnchorSet<'a> {
+    data: &'a [u8],
+    records: LazyArray16<'a, EntryExitRecord>,
+}
+
+impl<'a> CursiveAnchorSet<'a> {
+    /// Returns the number of records.
+    #[inline]
+    pub fn len(&self) -> u16 {
+        self.records.len()
+    }
+
+    /// Checks if there are any records.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// Returns entry and exit anchors at `index`.
+    pub fn get(&self, index: u16) -> Option<EntryExit<'a>> {
+        let record = self.records.get(index)?;
+        let anchor = |offset: Option<Offset16>| {
+            self.data.get(offset?.to_usize()..).and_then(Anchor::parse)
+        };
+
+        Some(EntryExit {
+            entry: anchor(record.entry_anchor_offset),
+            exit: anchor(record.exit_anchor_offset),
+        })
+    }
+}
+
+impl core::fmt::Debug for CursiveAnchorSet<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "CursiveAnchorSet({})", self.len())
+    }
+}
+
+
+/// A [Cursive Attachment Positioning Subtable](
+/// https://docs.microsoft.com/en-us/typography/opentype/spec/gpos#CAP).
+#[derive(Clone, Copy, Debug)]
+pub struct CursiveAdjustment<'a> {
+    /// Subtable coverage.
+    pub coverage: CoverageTable<'a>,
+    /// Indexed by coverage index.
+    pub sets: CursiveAnchorSet<'a>,
+}
+
+impl<'a> CursiveAdjustment<'a> {
+    fn parse(data: &'a [u8]) -> Option<Self> {
+        let mut s = Stream::new(data);
+        let format: u16 = s.read()?;
+        if format != 1 {
+            return None;
+        }
+
+        let coverage = parse_coverage(data, &mut s)?;
+        let count: u16 = s.read()?;
+        let records = s.read_array16(count)?;
+        Some(CursiveAdjustment {
+            coverage,
+            sets: CursiveAnchorSet { data, records },
+        })
+    }
+
+    /// Returns entry and exit anchors for a glyph.
+    #[inline]
+    pub fn entry_exit(&self, glyph_id: GlyphId) -> Option<EntryExit<'a>> {
+        self.sets.get(self.coverage.get(glyph_id)?)
+    }
+}
+
+
+/// Parses coverages, a mark class count, a mark array and an attachment table offset
+/// common to all mark attachment subtables.
+fn parse_mark_attachment(
+    data: &[u8],
+) -> Option<(CoverageTable<'_>, CoverageTable<'_>, u16, MarkArray<'_>, &[u8])> {
+    let mut s = Stream::new(data);
+    let format: u16 = s.read()?;
+    if format != 1 {
+        return None;
+    }
+
+    let mark_coverage = parse_coverage(data, &mut s)?;
+    let other_coverage = parse_coverage(data, &mut s)?;
+    let class_count: u16 = s.read()?;
+    let marks_offset: Offset16 = s.read()?;
+    let marks = MarkArray::parse(data.get(marks_offset.to_usize()..)?)?;
+    let other_offset: Offset16 = s.read()?;
+    let other_data = data.get(other_offset.to_usize()..)?;
+    Some((mark_coverage, other_coverage, class_count, marks, other_data))
+}
+
+
+/// A [Mark-to-Base Attachment Positioning Subtable](
+/// https://docs.microsoft.com/en-us/typography/opentype/spec/gpos#MBP).
+#[derive(Clone, Copy, Debug)]
+pub struct MarkToBaseAdjustment<'a> {
+    /// Mark glyphs coverage.
+    pub mark_coverage: CoverageTable<'a>,
+    /// Base glyphs coverage.
+    pub base_coverage: CoverageTable<'a>,
+    /// Indexed by mark coverage index.
+    pub marks: MarkArray<'a>,
+    /// Rows are indexed by base coverage index, columns by mark class.
+    pub anchors: AnchorMatrix<'a>,
+}
+
+impl<'a> MarkToBaseAdjustment<'a> {
+    fn parse(data: &'a [u8]) -> Option<Self> {
+        let (mark_coverage, base_coverage, class_count, marks, base_data) =
+            parse_mark_attachment(data)?;
+        Some(MarkToBaseAdjustment {
+            mark_coverage,
+            base_coverage,
+            marks,
+            anchors: AnchorMatrix::parse(base_data, class_count)?,
+        })
+    }
+
+    /// Returns a mark anchor and a base anchor for a pair of glyphs.
+    pub fn anchors(&self, mark: GlyphId, base: GlyphId) -> Option<(Anchor<'a>, Anchor<'a>)> {
+        let (class, mark_anchor) = self.marks.get(self.mark_coverage.get(mark)?)?;
+        let base_anchor = self.anchors.get(self.base_coverage.get(base)?, class)?;
+        Some((mark_anchor, base_anchor))
+    }
+}
+
+
+/// A [Ligature Array Table](https://docs.microsoft.com/en-us/typography/opentype/spec/gpos#ligature-array-table).
+#[derive(Clone, Copy)]
+pub struct LigatureArray<'a> {
+    data: &'a [u8],
+    class_count: u16,
+    offsets: LazyArray16<'a, Option<Offset16>>,
+}
+
+impl<'a> LigatureArray<'a> {
+    fn parse(data: &'a [u8], class_count: u16) -> Option<Self> {
+        let mut s = Stream::new(data);
+        let count: u16 = s.read()?;
+        let offsets = s.read_array16(count)?;
+        Some(LigatureArray { data, class_count, offsets })
+    }
+
+    /// Returns the number of ligatures.
+    #[inline]
+    pub fn len(&self) -> u16 {
+        self.offsets.len()
+    }
+
+    /// Checks if there are any ligatures.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.offsets.is_empty()
+    }
+
+    /// Returns a ligature attach table at `index`.
+    ///
+    /// Rows are indexed by the ligature component index, columns by mark class.
+    pub fn get(&self, index: u16) -> Option<AnchorMatrix<'a>> {
+        let offset = self.offsets.get(index)??.to_usize();
+        AnchorMatrix::parse(self.data.get(offset..)?, self.class_count)
+    }
+}
+
+impl core::fmt::Debug for LigatureArray<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "LigatureArray({})", self.len())
+    }
+}
+
+
+/// A [Mark-to-Ligature Attachment Positioning Subtable](
+/// https://docs.microsoft.com/en-us/typography/opentype/spec/gpos#MLP).
+#[derive(Clone, Copy, Debug)]
+pub struct MarkToLigatureAdjustment<'a> {
+    /// Mark glyphs coverage.
+    pub mark_coverage: CoverageTable<'a>,
+    /// Ligature glyphs coverage.
+    pub ligature_coverage: CoverageTable<'a>,
+    /// Indexed by mark coverage index.
+    pub marks: MarkArray<'a>,
+    /// Indexed by ligature coverage index.
+    pub ligatures: LigatureArray<'a>,
+}
+
+impl<'a> MarkToLigatureAdjustment<'a> {
+    fn parse(data: &'a [u8]) -> Option<Self> {
+        let (mark_coverage, ligature_coverage, class_count, marks, ligature_data) =
+            parse_mark_attachment(data)?;
+        Some(MarkToLigatureAdjustment {
+            mark_coverage,
+            ligature_coverage,
+            marks,
+            ligatures: LigatureArray::parse(ligature_data, class_count)?,
+        })
+    }
+
+    /// Returns a mark anchor and a ligature anchor for a mark attached
+    /// to a specified ligature component.
+    pub fn anchors(
+        &self,
+        mark: GlyphId,
+        ligature: GlyphId,
+        component: u16,
+    ) -> Option<(Anchor<'a>, Anchor<'a>)> {
+        let (class, mark_anchor) = self.marks.get(self.mark_coverage.get(mark)?)?;
+        let attach = self.ligatures.get(self.ligature_coverage.get(ligature)?)?;
+        let ligature_anchor = attach.get(component, class)?;
+        Some((mark_anchor, ligature_anchor))
+    }
+}
+
+
+/// A [Mark-to-Mark Attachment Positioning Subtable](
+/// https://docs.microsoft.com/en-us/typography/opentype/spec/gpos#MMP).
+#[derive(Clone, Copy, Debug)]
+pub struct MarkToMarkAdjustment<'a> {
+    /// Attaching mark glyphs coverage.
+    pub mark1_coverage: CoverageTable<'a>,
+    /// Base mark glyphs coverage.
+    pub mark2_coverage: CoverageTable<'a>,
+    /// Indexed by mark1 coverage index.
+    pub marks: MarkArray<'a>,
+    /// Rows are indexed by mark2 coverage index, columns by mark1 class.
+    pub mark2_anchors: AnchorMatrix<'a>,
+}
+
+impl<'a> MarkToMarkAdjustment<'a> {
+    fn parse(data: &'a [u8]) -> Option<Self> {
+        let (mark1_coverage, mark2_coverage, class_count, marks, mark2_data) =
+            parse_mark_attachment(data)?;
+        Some(MarkToMarkAdjustment {
+            mark1_coverage,
+            mark2_coverage,
+            marks,
+            mark2_anchors: AnchorMatrix::parse(mark2_data, class_count)?,
+        })
+    }
+
+    /// Returns an attaching mark anchor and a base mark anchor for a pair of glyphs.
+    pub fn anchors(&self, mark1: GlyphId, mark2: GlyphId) -> Option<(Anchor<'a>, Anchor<'a>)> {
+        let (class, mark1_anchor) = self.marks.get(self.mark1_coverage.get(mark1)?)?;
+        let mark2_anchor = self.mark2_anchors.get(self.mark2_coverage.get(mark2)?, class)?;
+        Some((mark1_anchor, mark2_anchor))
+    }
+}
+
+
+/// A glyph positioning lookup subtable.
+#[allow(missing_docs)]
+#[derive(Clone, Copy, Debug)]
+pub enum PositioningSubtable<'a> {
+    Single(SingleAdjustment<'a>),
+    Pair(PairAdjustment<'a>),
+    Cursive(CursiveAdjustment<'a>),
+    MarkToBase(MarkToBaseAdjustment<'a>),
+    MarkToLigature(MarkToLigatureAdjustment<'a>),
+    MarkToMark(MarkToMarkAdjustment<'a>),
+    Context(ContextLookup<'a>),
+    ChainContext(ChainedContextLookup<'a>),
+}
+
+impl<'a> PositioningSubtable<'a> {
+    /// Returns the subtable coverage.
+    ///
+    /// For mark attachment subtables, returns the coverage of marks.
+    #[inline]
+    pub fn coverage(&self) -> CoverageTable<'a> {
+        match self {
+            PositioningSubtable::Single(t) => t.coverage(),
+            PositioningSubtable::Pair(t) => t.coverage(),
+            PositioningSubtable::Cursive(t) => t.coverage,
+            PositioningSubtable::MarkToBase(t) => t.mark_coverage,
+            PositioningSubtable::MarkToLigature(t) => t.mark_coverage,
+            PositioningSubtable::MarkToMark(t) => t.mark1_coverage,
+            PositioningSubtable::Context(t) => t.coverage(),
+            PositioningSubtable::ChainContext(t) => t.coverage(),
+        }
+    }
+}
+
+impl<'a> LookupSubtable<'a> for PositioningSubtable<'a> {
+    fn parse(data: &'a [u8], kind: u16) -> Option<Self> {
+        match kind {
+            1 => SingleAdjustment::parse(data).map(PositioningSubtable::Single),
+            2 => PairAdjustment::parse(data).map(PositioningSubtable::Pair),
+            3 => CursiveAdjustment::parse(data).map(PositioningSubtable::Cursive),
+            4 => MarkToBaseAdjustment::parse(data).map(PositioningSubtable::MarkToBase),
+            5 => MarkToLigatureAdjustment::parse(data).map(PositioningSubtable::MarkToLigature),
+            6 => MarkToMarkAdjustment::parse(data).map(PositioningSubtable::MarkToMark),
+            7 => ContextLookup::parse(data).map(PositioningSubtable::Context),
+            8 => ChainedContextLookup::parse(data).map(PositioningSubtable::ChainContext),
+            9 => parse_extension(data),
+            _ => None,
+        }
+    }
+}
+
+// https://docs.microsoft.com/en-us/typography/opentype/spec/gpos#EP
+fn parse_extension(data: &[u8]) -> Option<PositioningSubtable<'_>> {
+    let mut s = Stream::new(data);
+    let format: u16 = s.read()?;
+    if format != 1 {
+        return None;
+    }
+
+    let kind: u16 = s.read()?;
+    // 'The extensionLookupType field must be set to any lookup type other than 9.'
+    if kind == 9 {
+        return None;
+    }
+
+    let offset: Offset32 = s.read()?;
+    PositioningSubtable::parse(data.get(offset.to_usize()..)?, kind)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::writer;
+    use writer::TtfType::*;
+
+    fn parse(kind: u16, data: &[u8]) -> Option<PositioningSubtable<'_>> {
+        <PositioningSubtable as LookupSubtable>::parse(data, kind)
+    }
+
+    #[test]
+    fn hinting_device() {
+        let data = writer::convert(&[
+            UInt16(10), // startSize
+            UInt16(14), // endSize
+            UInt16(2), // deltaFormat
+            UInt16(0x1F07), // 1, -1, 0, 7
+            UInt16(0x8000), // -8
+        ]);
+
+        let device = match Device::parse(&data).unwrap() {
+            Device::Hinting(d) => d,
+            _ => panic!(),
+        };
+
+        assert_eq!(device.delta(9), None);
+        assert_eq!(device.delta(10), Some(1));
+        assert_eq!(device.delta(11), Some(-1));
+        assert_eq!(device.delta(12), Some(0));
+        assert_eq!(device.delta(13), Some(7));
+        assert_eq!(device.delta(14), Some(-8));
+        assert_eq!(device.delta(15), None);
+    }
+
+    #[test]
+    fn pair_format1() {
+        let data = writer::convert(&[
+            UInt16(1), // format
+            UInt16(14), // coverage offset
+            UInt16(0x0004), // valueFormat1: xAdvance
+            UInt16(0x0041), // valueFormat2: xPlacement, xAdvanceDevice
+            UInt16(1), // pair sets count
+            UInt16(20), // pair set offset
+            UInt16(0), // padding
+            // Coverage
+            UInt16(1), // format
+            UInt16(1), // count
+            UInt16(3), // glyph
+            // Pair set
+            UInt16(2), // count
+            UInt16(5), // second glyph
+            Int16(-50), // xAdvance
+            Int16(7), // xPlacement
+            UInt16(0), // xAdvanceDevice offset
+            UInt16(8), // second glyph
+            Int16(-20), // xAdvance
+            Int16(0), // xPlacement
+            UInt16(18), // xAdvanceDevice offset, relative to the pair set
+            // Device
+            UInt16(1), // deltaSetOuterIndex
+            UInt16(2), // deltaSetInnerIndex
+            UInt16(0x8000), // deltaFormat
+        ]);
+
+        let subtable = match parse(2, &data).unwrap() {
+            PositioningSubtable::Pair(t) => t,
+            _ => panic!(),
+        };
+
+        let (v1, v2) = subtable.values(GlyphId(3), GlyphId(5)).unwrap();
+        assert_eq!(v1.x_advance, -50);
+        assert_eq!(v2.x_placement, 7);
+        assert!(v2.x_advance_device.is_none());
+
+        let (v1, v2) = subtable.values(GlyphId(3), GlyphId(8)).unwrap();
+        assert_eq!(v1.x_advance, -20);
+        match v2.x_advance_device {
+            Some(Device::Variation { outer_index: 1, inner_index: 2 }) => {}
+            _ => panic!(),
+        }
+
+        assert!(subtable.values(GlyphId(3), GlyphId(6)).is_none());
+        assert!(subtable.values(GlyphId(4), GlyphId(5)).is_none());
+    }
+
+    #[test]
+    fn pair_format2() {
+        let data = writer::convert(&[
+            UInt16(2), // format
+            UInt16(24), // coverage offset
+            UInt16(0x0004), // valueFormat1: xAdvance
+            UInt16(0x0000), // valueFormat2
+            UInt16(32), // classDef1 offset
+            UInt16(42), // classDef2 offset
+            UInt16(2), // class1Count
+            UInt16(2), // class2Count
+            Int16(0), // class 0 x class 0
+            Int16(0), // class 0 x class 1
+            Int16(0), // class 1 x class 0
+            Int16(-30), // class 1 x class 1
+            // Coverage
+            UInt16(1), // format
+            UInt16(2), // count
+            UInt16(1), // glyph
+            UInt16(2), // glyph
+            // ClassDef1
+            UInt16(1), // format
+            UInt16(2), // startGlyphID
+            UInt16(1), // count
+            UInt16(1), // class
+            UInt16(0), // padding
+            // ClassDef2
+            UInt16(1), // format
+            UInt16(7), // startGlyphID
+            UInt16(1), // count
+            UInt16(1), // class
+        ]);
+
+        let subtable = match parse(2, &data).unwrap() {
+            PositioningSubtable::Pair(t) => t,
+            _ => panic!(),
+        };
+
+        assert_eq!(subtable.values(GlyphId(2), GlyphId(7)).unwrap().0.x_advance, -30);
+        assert_eq!(subtable.values(GlyphId(1), GlyphId(7)).unwrap().0.x_advance, 0);
+        assert!(subtable.values(GlyphId(3), GlyphId(7)).is_none());
+    }
+}
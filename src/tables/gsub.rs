@@ -0,0 +1,395 @@
+/*!
+A [Glyph Substitution Table](https://docs.microsoft.com/en-us/typography/opentype/spec/gsub)
+implementation.
+
+This module provides only lookup subtables. Scripts, features and lookups themselves
+are accessible via `Font::glyph_substitutions()`.
+
+All eight lookup types are supported.
+Extension substitutions (type 7) are resolved transparently.
+*/
+
+use crate::GlyphId;
+use crate::ggg::{CoverageTable, ContextLookup, ChainedContextLookup, LookupSubtable, parse_coverage};
+use crate::parser::{Stream, FromSlice, LazyArray16, LazyOffsetArray16, Offset, Offset32};
+
+
+/// A [Single Substitution Subtable](
+/// https://docs.microsoft.com/en-us/typography/opentype/spec/gsub#SS).
+#[allow(missing_docs)]
+#[derive(Clone, Copy, Debug)]
+pub enum SingleSubstitution<'a> {
+    Format1 {
+        coverage: CoverageTable<'a>,
+        delta: i16,
+    },
+    Format2 {
+        coverage: CoverageTable<'a>,
+        /// Indexed by coverage index.
+        substitutes: LazyArray16<'a, GlyphId>,
+    },
+}
+
+impl<'a> SingleSubstitution<'a> {
+    fn parse(data: &'a [u8]) -> Option<Self> {
+        let mut s = Stream::new(data);
+        let format: u16 = s.read()?;
+        match format {
+            1 => {
+                let coverage = parse_coverage(data, &mut s)?;
+                let delta: i16 = s.read()?;
+                Some(SingleSubstitution::Format1 { coverage, delta })
+            }
+            2 => {
+                let coverage = parse_coverage(data, &mut s)?;
+                let count: u16 = s.read()?;
+                let substitutes = s.read_array16(count)?;
+                Some(SingleSubstitution::Format2 { coverage, substitutes })
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the subtable coverage.
+    #[inline]
+    pub fn coverage(&self) -> CoverageTable<'a> {
+        match self {
+            SingleSubstitution::Format1 { coverage, .. } => *coverage,
+            SingleSubstitution::Format2 { coverage, .. } => *coverage,
+        }
+    }
+
+    /// Returns a substitute for a glyph.
+    ///
+    /// Returns `None` when glyph is not covered by this subtable.
+    pub fn substitute(&self, glyph_id: GlyphId) -> Option<GlyphId> {
+        match self {
+            SingleSubstitution::Format1 { coverage, delta } => {
+                coverage.get(glyph_id)?;
+                // 'Addition of deltaGlyphID is modulo 65536.'
+                Some(GlyphId((glyph_id.0 as i16).wrapping_add(*delta) as u16))
+            }
+            SingleSubstitution::Format2 { coverage, substitutes } => {
+                substitutes.get(coverage.get(glyph_id)?)
+            }
+        }
+    }
+}
+
+
+/// A glyph sequence used by multiple substitution.
+pub type Sequence<'a> = LazyArray16<'a, GlyphId>;
+
+
+/// A [Multiple Substitution Subtable](
+/// https://docs.microsoft.com/en-us/typography/opentype/spec/gsub#MS).
+#[derive(Clone, Copy, Debug)]
+pub struct MultipleSubstitution<'a> {
+    /// Subtable coverage.
+    pub coverage: CoverageTable<'a>,
+    /// Indexed by coverage index.
+    pub sequences: LazyOffsetArray16<'a, Sequence<'a>>,
+}
+
+impl<'a> MultipleSubstitution<'a> {
+    fn parse(data: &'a [u8]) -> Option<Self> {
+        let mut s = Stream::new(data);
+        let format: u16 = s.read()?;
+        if format != 1 {
+            return None;
+        }
+
+        let coverage = parse_coverage(data, &mut s)?;
+        let sequences = LazyOffsetArray16::read(data, &mut s)?;
+        Some(MultipleSubstitution { coverage, sequences })
+    }
+
+    /// Returns a replacement sequence for a glyph.
+    #[inline]
+    pub fn sequence(&self, glyph_id: GlyphId) -> Option<Sequence<'a>> {
+        self.sequences.get(self.coverage.get(glyph_id)?)
+    }
+}
+
+
+/// A set of alternate glyphs used by alternate substitution.
+pub type AlternateSet<'a> = LazyArray16<'a, GlyphId>;
+
+
+/// An [Alternate Substitution Subtable](
+/// https://docs.microsoft.com/en-us/typography/opentype/spec/gsub#AS).
+#[derive(Clone, Copy, Debug)]
+pub struct AlternateSubstitution<'a> {
+    /// Subtable coverage.
+    pub coverage: CoverageTable<'a>,
+    /// Indexed by coverage index.
+    pub alternate_sets: LazyOffsetArray16<'a, AlternateSet<'a>>,
+}
+
+impl<'a> AlternateSubstitution<'a> {
+    fn parse(data: &'a [u8]) -> Option<Self> {
+        let mut s = Stream::new(data);
+        let format: u16 = s.read()?;
+        if format != 1 {
+            return None;
+        }
+
+        let coverage = parse_coverage(data, &mut s)?;
+        let alternate_sets = LazyOffsetArray16::read(data, &mut s)?;
+        Some(AlternateSubstitution { coverage, alternate_sets })
+    }
+
+    /// Returns a list of alternates for a glyph.
+    #[inline]
+    pub fn alternates(&self, glyph_id: GlyphId) -> Option<AlternateSet<'a>> {
+        self.alternate_sets.get(self.coverage.get(glyph_id)?)
+    }
+}
+
+
+/// A [Ligature Table](https://docs.microsoft.com/en-us/typography/opentype/spec/gsub#LS).
+#[derive(Clone, Copy, Debug)]
+pub struct Ligature<'a> {
+    /// Ligature glyph to substitute.
+    pub glyph: GlyphId,
+    /// Component glyphs, starting with the second one.
+    pub components: LazyArray16<'a, GlyphId>,
+}
+
+impl<'a> FromSlice<'a> for Ligature<'a> {
+    fn parse(data: &'a [u8]) -> Option<Self> {
+        let mut s = Stream::new(data);
+        let glyph: GlyphId = s.read()?;
+        let count: u16 = s.read()?;
+        let components = s.read_array16(count.checked_sub(1)?)?;
+        Some(Ligature { glyph, components })
+    }
+}
+
+/// A set of ligatures starting with the same glyph, ordered by preference.
+pub type LigatureSet<'a> = LazyOffsetArray16<'a, Ligature<'a>>;
+
+
+/// A [Ligature Substitution Subtable](
+/// https://docs.microsoft.com/en-us/typography/opentype/spec/gsub#LS).
+#[derive(Clone, Copy, Debug)]
+pub struct LigatureSubstitution<'a> {
+    /// Subtable coverage.
+    pub coverage: CoverageTable<'a>,
+    /// Indexed by coverage index.
+    pub ligature_sets: LazyOffsetArray16<'a, LigatureSet<'a>>,
+}
+
+impl<'a> LigatureSubstitution<'a> {
+    fn parse(data: &'a [u8]) -> Option<Self> {
+        let mut s = Stream::new(data);
+        let format: u16 = s.read()?;
+        if format != 1 {
+            return None;
+        }
+
+        let coverage = parse_coverage(data, &mut s)?;
+        let ligature_sets = LazyOffsetArray16::read(data, &mut s)?;
+        Some(LigatureSubstitution { coverage, ligature_sets })
+    }
+
+    /// Returns a set of ligatures that start with a glyph.
+    #[inline]
+    pub fn ligature_set(&self, glyph_id: GlyphId) -> Option<LigatureSet<'a>> {
+        self.ligature_sets.get(self.coverage.get(glyph_id)?)
+    }
+}
+
+
+/// A [Reverse Chaining Contextual Single Substitution Subtable](
+/// https://docs.microsoft.com/en-us/typography/opentype/spec/gsub#RCCS).
+#[derive(Clone, Copy, Debug)]
+pub struct ReverseChainSingleSubstitution<'a> {
+    /// Subtable coverage.
+    pub coverage: CoverageTable<'a>,
+    /// Backtrack coverages, in reverse order.
+    pub backtrack_coverages: LazyOffsetArray16<'a, CoverageTable<'a>>,
+    /// Lookahead coverages.
+    pub lookahead_coverages: LazyOffsetArray16<'a, CoverageTable<'a>>,
+    /// Indexed by coverage index.
+    pub substitutes: LazyArray16<'a, GlyphId>,
+}
+
+impl<'a> ReverseChainSingleSubstitution<'a> {
+    fn parse(data: &'a [u8]) -> Option<Self> {
+        let mut s = Stream::new(data);
+        let format: u16 = s.read()?;
+        if format != 1 {
+            return None;
+        }
+
+        let coverage = parse_coverage(data, &mut s)?;
+        let backtrack_coverages = LazyOffsetArray16::read(data, &mut s)?;
+        let lookahead_coverages = LazyOffsetArray16::read(data, &mut s)?;
+        let count: u16 = s.read()?;
+        let substitutes = s.read_array16(count)?;
+        Some(ReverseChainSingleSubstitution {
+            coverage,
+            backtrack_coverages,
+            lookahead_coverages,
+            substitutes,
+        })
+    }
+}
+
+
+/// A glyph substitution lookup subtable.
+#[allow(missing_docs)]
+#[derive(Clone, Copy, Debug)]
+pub enum SubstitutionSubtable<'a> {
+    Single(SingleSubstitution<'a>),
+    Multiple(MultipleSubstitution<'a>),
+    Alternate(AlternateSubstitution<'a>),
+    Ligature(LigatureSubstitution<'a>),
+    Context(ContextLookup<'a>),
+    ChainContext(ChainedContextLookup<'a>),
+    ReverseChainSingle(ReverseChainSingleSubstitution<'a>),
+}
+
+impl<'a> SubstitutionSubtable<'a> {
+    /// Returns the subtable coverage.
+    #[inline]
+    pub fn coverage(&self) -> CoverageTable<'a> {
+        match self {
+            SubstitutionSubtable::Single(t) => t.coverage(),
+            SubstitutionSubtable::Multiple(t) => t.coverage,
+            SubstitutionSubtable::Alternate(t) => t.coverage,
+            SubstitutionSubtable::Ligature(t) => t.coverage,
+            SubstitutionSubtable::Context(t) => t.coverage(),
+            SubstitutionSubtable::ChainContext(t) => t.coverage(),
+            SubstitutionSubtable::ReverseChainSingle(t) => t.coverage,
+        }
+    }
+}
+
+impl<'a> LookupSubtable<'a> for SubstitutionSubtable<'a> {
+    fn parse(data: &'a [u8], kind: u16) -> Option<Self> {
+        match kind {
+            1 => SingleSubstitution::parse(data).map(SubstitutionSubtable::Single),
+            2 => MultipleSubstitution::parse(data).map(SubstitutionSubtable::Multiple),
+            3 => AlternateSubstitution::parse(data).map(SubstitutionSubtable::Alternate),
+            4 => LigatureSubstitution::parse(data).map(SubstitutionSubtable::Ligature),
+            5 => ContextLookup::parse(data).map(SubstitutionSubtable::Context),
+            6 => ChainedContextLookup::parse(data).map(SubstitutionSubtable::ChainContext),
+            7 => parse_extension(data),
+            8 => ReverseChainSingleSubstitution::parse(data)
+                .map(SubstitutionSubtable::ReverseChainSingle),
+            _ => None,
+        }
+    }
+}
+
+// https://docs.microsoft.com/en-us/typography/opentype/spec/gsub#ES
+fn parse_extension(data: &[u8]) -> Option<SubstitutionSubtable<'_>> {
+    let mut s = Stream::new(data);
+    let format: u16 = s.read()?;
+    if format != 1 {
+        return None;
+    }
+
+    let kind: u16 = s.read()?;
+    // 'The extensionLookupType field must be set to any lookup type other than 7.'
+    if kind == 7 {
+        return None;
+    }
+
+    let offset: Offset32 = s.read()?;
+    SubstitutionSubtable::parse(data.get(offset.to_usize()..)?, kind)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ggg::LookupSubtables;
+    use crate::writer;
+    use writer::TtfType::*;
+
+    fn parse(kind: u16, data: &[u8]) -> Option<SubstitutionSubtable<'_>> {
+        <SubstitutionSubtable as LookupSubtable>::parse(data, kind)
+    }
+
+    #[test]
+    fn single_format1() {
+        let data = writer::convert(&[
+            UInt16(1), // format
+            UInt16(6), // coverage offset
+            Int16(-2), // delta
+            // Coverage
+            UInt16(1), // format
+            UInt16(2), // count
+            UInt16(5), // glyph
+            UInt16(9), // glyph
+        ]);
+
+        let subtable = match parse(1, &data).unwrap() {
+            SubstitutionSubtable::Single(t) => t,
+            _ => panic!(),
+        };
+
+        assert_eq!(subtable.substitute(GlyphId(5)), Some(GlyphId(3)));
+        assert_eq!(subtable.substitute(GlyphId(9)), Some(GlyphId(7)));
+        assert_eq!(subtable.substitute(GlyphId(6)), None);
+    }
+
+    #[test]
+    fn ligature_via_extension() {
+        let data = writer::convert(&[
+            // Extension
+            UInt16(1), // format
+            UInt16(4), // extensionLookupType
+            UInt32(8), // extensionOffset
+            // Ligature substitution
+            UInt16(1), // format
+            UInt16(8), // coverage offset
+            UInt16(1), // ligature sets count
+            UInt16(18), // ligature set offset
+            // Coverage
+            UInt16(2), // format
+            UInt16(1), // count
+            UInt16(10), // start glyph
+            UInt16(12), // end glyph
+            UInt16(0), // start coverage index
+            // Ligature set
+            UInt16(1), // count
+            UInt16(4), // ligature offset
+            // Ligature
+            UInt16(20), // ligature glyph
+            UInt16(3), // component count
+            UInt16(11), // component
+            UInt16(12), // component
+        ]);
+
+        let subtable = match parse(7, &data).unwrap() {
+            SubstitutionSubtable::Ligature(t) => t,
+            _ => panic!(),
+        };
+
+        assert_eq!(subtable.coverage.get(GlyphId(10)), Some(0));
+        assert!(subtable.ligature_set(GlyphId(11)).is_none());
+
+        let set = subtable.ligature_set(GlyphId(10)).unwrap();
+        assert_eq!(set.len(), 1);
+        let ligature = set.get(0).unwrap();
+        assert_eq!(ligature.glyph, GlyphId(20));
+        assert_eq!(ligature.components.into_iter().collect::<std::vec::Vec<_>>(),
+                   &[GlyphId(11), GlyphId(12)]);
+    }
+
+    #[test]
+    fn nested_extension() {
+        let data = writer::convert(&[
+            UInt16(1), // format
+            UInt16(7), // extensionLookupType
+            UInt32(0), // extensionOffset
+        ]);
+
+        assert!(parse(7, &data).is_none());
+        assert_eq!(LookupSubtables::default().iter::<SubstitutionSubtable>().count(), 0);
+    }
+}
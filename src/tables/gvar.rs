@@ -7,7 +7,7 @@ use core::num::NonZeroU16;
 
 use crate::{loca, GlyphId, OutlineBuilder, Rect, BBox, NormalizedCoord};
 use crate::parser::{Stream, Offset, Offset16, Offset32, LazyArray16, F2DOT14};
-use crate::glyf::{self, Transform};
+use crate::glyf::{self, PointSearch, Transform};
 
 /// 'The TrueType rasterizer dynamically generates 'phantom' points for each glyph
 /// that represent horizontal and vertical advance widths and side bearings,
@@ -124,11 +124,50 @@ pub(crate) fn outline(
     let range = loca_table.glyph_range(glyph_id)?;
     let glyph_data = glyf_table.get(range)?;
 
+    let mut budget = glyf::MAX_POINT_SEARCH_STEPS;
     outline_var_impl(loca_table, glyf_table, gvar_table,
-                     glyph_id, glyph_data, coordinates, 0, &mut b);
+                     glyph_id, glyph_data, coordinates, 0, &mut budget, &mut b);
     b.bbox.and_then(|bbox| bbox.to_rect())
 }
 
+/// Applies variation deltas to raw glyph points.
+pub(crate) fn outline_points<'a>(
+    gvar_table: &Table<'a>,
+    coordinates: &[NormalizedCoord],
+    glyph_id: GlyphId,
+    mut points: glyf::OutlinePoints<'a>,
+) -> Option<glyf::OutlinePoints<'a>> {
+    let mut tuples = VariationTuples {
+        headers: [VariationTuple::default(); MAX_TUPLES_LEN as usize],
+        len: 0,
+    };
+
+    gvar_table.parse_variation_data(glyph_id, coordinates, points.points_len(), &mut tuples)?;
+    points.tuples = Some(tuples);
+    Some(points)
+}
+
+/// Returns variation tuples of a composite glyph.
+///
+/// `VariationTuples::apply_null()` yields an offset delta for each component,
+/// followed by deltas of four phantom points.
+#[cfg(feature = "hinting")]
+pub(crate) fn composite_tuples<'a>(
+    gvar_table: &Table<'a>,
+    coordinates: &[NormalizedCoord],
+    glyph_id: GlyphId,
+    components_count: u16,
+) -> Option<VariationTuples<'a>> {
+    let mut tuples = VariationTuples {
+        headers: [VariationTuple::default(); MAX_TUPLES_LEN as usize],
+        len: 0,
+    };
+
+    gvar_table.parse_variation_data(glyph_id, coordinates, components_count, &mut tuples)?;
+    Some(tuples)
+}
+
+#[allow(clippy::too_many_arguments)]
 fn outline_var_impl<'a>(
     loca_table: loca::Table,
     glyf_table: &[u8],
@@ -137,6 +176,7 @@ fn outline_var_impl<'a>(
     data: &[u8],
     coordinates: &[NormalizedCoord],
     depth: u8,
+    budget: &mut u32,
     builder: &mut glyf::Builder,
 ) -> Option<()> {
     if depth >= glyf::MAX_COMPONENTS {
@@ -186,29 +226,27 @@ fn outline_var_impl<'a>(
         // Details:
         // https://docs.microsoft.com/en-us/typography/opentype/spec/gvar#point-numbers-and-processing-for-composite-glyphs
 
-        let mut components = glyf::CompositeGlyphIter::new(s.tail()?);
-        let components_count = components.clone().count() as u16;
+        let mut components = glyf::Components::new(s.tail()?);
+        let components_count = components.count() as u16;
         gvar_table.parse_variation_data(glyph_id, coordinates, components_count, &mut tuples)?;
 
+        let mut index = 0;
         while let Some(component) = components.next() {
             let (tx, ty) = tuples.apply_null()?;
 
-            let mut transform = builder.transform;
-
-            // Variation component offset should be applied only when
-            // the ARGS_ARE_XY_VALUES flag is set.
-            if component.flags.args_are_xy_values() {
-                transform = Transform::combine(transform, Transform::new_translate(tx, ty));
-            }
-
-            transform = Transform::combine(transform, component.transform);
+            let component_ts = component_transform(
+                loca_table, glyf_table, gvar_table, glyph_id, data, coordinates,
+                index, &component, (tx, ty), depth, budget,
+            )?;
+            let transform = Transform::combine(builder.transform, component_ts);
+            index += 1;
 
             let mut b = glyf::Builder::new(transform, builder.bbox, builder.builder);
             let range = loca_table.glyph_range(component.glyph_id)?;
             let glyph_data = glyf_table.get(range)?;
             outline_var_impl(
                 loca_table, glyf_table, gvar_table, component.glyph_id,
-                glyph_data, coordinates, depth + 1, &mut b,
+                glyph_data, coordinates, depth + 1, budget, &mut b,
             )?;
 
             // Take updated bbox.
@@ -222,6 +260,143 @@ fn outline_var_impl<'a>(
     }
 }
 
+/// Returns a component transform with variation deltas applied.
+///
+/// Just like in `glyf`, but parent and child points are matched
+/// after variation deltas were applied to them.
+#[allow(clippy::too_many_arguments)]
+fn component_transform(
+    loca_table: loca::Table,
+    glyf_table: &[u8],
+    gvar_table: &Table,
+    parent_glyph_id: GlyphId,
+    parent_data: &[u8],
+    coordinates: &[NormalizedCoord],
+    index: u16,
+    component: &glyf::Component,
+    offset: (f32, f32),
+    depth: u8,
+    budget: &mut u32,
+) -> Option<Transform> {
+    // Variation component offset should be applied only when
+    // the ARGS_ARE_XY_VALUES flag is set.
+    if component.flags.args_are_xy_values() {
+        let ts = Transform::new_translate(offset.0, offset.1);
+        return Some(Transform::combine(ts, component.transform));
+    }
+
+    let (parent_point, child_point) = component.matching_points?;
+
+    // A parent point can reference only points of previous components.
+    let parent_point = match find_point_var(
+        loca_table, glyf_table, gvar_table, parent_glyph_id, parent_data,
+        coordinates, parent_point, index, depth, budget,
+    )? {
+        PointSearch::Found(x, y) => (x, y),
+        PointSearch::NotFound(_) => return None,
+    };
+
+    let child_data = glyf_table.get(loca_table.glyph_range(component.glyph_id)?)?;
+    let child_point = match find_point_var(
+        loca_table, glyf_table, gvar_table, component.glyph_id, child_data,
+        coordinates, child_point, core::u16::MAX, depth + 1, budget,
+    )? {
+        PointSearch::Found(x, y) => (x, y),
+        PointSearch::NotFound(_) => return None,
+    };
+
+    Some(glyf::matched_transform(component.transform, parent_point, child_point))
+}
+
+/// Looks for a point with the specified index in a glyph with variation deltas applied.
+///
+/// Only the first `components_limit` components of a composite glyph are checked.
+///
+/// Each visited glyph consumes one `budget` step.
+#[allow(clippy::too_many_arguments)]
+fn find_point_var(
+    loca_table: loca::Table,
+    glyf_table: &[u8],
+    gvar_table: &Table,
+    glyph_id: GlyphId,
+    data: &[u8],
+    coordinates: &[NormalizedCoord],
+    index: u16,
+    components_limit: u16,
+    depth: u8,
+    budget: &mut u32,
+) -> Option<PointSearch> {
+    if depth >= glyf::MAX_COMPONENTS {
+        return None;
+    }
+
+    *budget = budget.checked_sub(1)?;
+
+    let mut s = Stream::new(data);
+    let number_of_contours: i16 = s.read()?;
+    s.advance(8); // Skip bbox.
+
+    let mut tuples = VariationTuples {
+        headers: [VariationTuple::default(); MAX_TUPLES_LEN as usize],
+        len: 0,
+    };
+
+    if number_of_contours > 0 {
+        let number_of_contours = NonZeroU16::new(number_of_contours as u16)?;
+        let mut glyph_points = glyf::parse_simple_outline(s.tail()?, number_of_contours)?;
+        let all_glyph_points = glyph_points.clone();
+        let points_len = glyph_points.points_left;
+        gvar_table.parse_variation_data(glyph_id, coordinates, points_len, &mut tuples)?;
+
+        // Deltas can be inferred from neighbor points,
+        // so we have to process all points before the requested one.
+        let mut i = 0;
+        while let Some(point) = glyph_points.next() {
+            let (x, y) = tuples.apply(all_glyph_points.clone(), glyph_points.clone(), point)?;
+            if i == index {
+                return Some(PointSearch::Found(x, y));
+            }
+
+            i += 1;
+        }
+
+        Some(PointSearch::NotFound(points_len))
+    } else if number_of_contours < 0 {
+        let components = glyf::Components::new(s.tail()?);
+        let components_count = components.count() as u16;
+        gvar_table.parse_variation_data(glyph_id, coordinates, components_count, &mut tuples)?;
+
+        let mut points_len = 0u16;
+        for (i, component) in components.take(usize::from(components_limit)).enumerate() {
+            let offset = tuples.apply_null()?;
+            let glyph_data = glyf_table.get(loca_table.glyph_range(component.glyph_id)?)?;
+
+            // `points_len` cannot be bigger than `index` here.
+            match find_point_var(
+                loca_table, glyf_table, gvar_table, component.glyph_id, glyph_data,
+                coordinates, index - points_len, core::u16::MAX, depth + 1, budget,
+            )? {
+                PointSearch::Found(mut x, mut y) => {
+                    let i = u16::try_from(i).ok()?;
+                    let ts = component_transform(
+                        loca_table, glyf_table, gvar_table, glyph_id, data, coordinates,
+                        i, &component, offset, depth, budget,
+                    )?;
+                    ts.apply_to(&mut x, &mut y);
+                    return Some(PointSearch::Found(x, y));
+                }
+                PointSearch::NotFound(n) => {
+                    points_len = points_len.checked_add(n)?;
+                }
+            }
+        }
+
+        Some(PointSearch::NotFound(points_len))
+    } else {
+        Some(PointSearch::NotFound(0))
+    }
+}
+
 // https://docs.microsoft.com/en-us/typography/opentype/spec/otvarcommonformats#tuple-variation-store-header
 fn parse_variation_data<'a>(
     coordinates: &[NormalizedCoord],
@@ -300,7 +475,7 @@ struct VariationTuple<'a> {
 ///
 /// The TrueType spec allows up to 4095 tuples, which is way larger
 /// than we do. But in reality, an average font will have less than 10 tuples.
-const MAX_TUPLES_LEN: u16 = 16;
+pub(crate) const MAX_TUPLES_LEN: u16 = 16;
 
 /// A list of variation tuples.
 ///
@@ -308,7 +483,8 @@ const MAX_TUPLES_LEN: u16 = 16;
 /// On stack and not on heap, but still.
 /// This is probably unavoidable due to `gvar` structure,
 /// since we have to iterate all tuples in parallel.
-struct VariationTuples<'a> {
+#[derive(Clone)]
+pub(crate) struct VariationTuples<'a> {
     headers: [VariationTuple<'a>; MAX_TUPLES_LEN as usize], // 1280B
     len: u16,
 }
@@ -319,7 +495,7 @@ impl<'a> VariationTuples<'a> {
         &mut self.headers[0..usize::from(self.len)]
     }
 
-    fn apply(
+    pub(crate) fn apply(
         &mut self,
         all_points: glyf::GlyphPointsIter,
         points: glyf::GlyphPointsIter,
@@ -377,7 +553,7 @@ impl<'a> VariationTuples<'a> {
     // This is just like `apply()`, but without `infer_deltas`,
     // since we use it only for component points and not a contour.
     // And since there are no contour and no points, `infer_deltas()` will do nothing.
-    fn apply_null(&mut self) -> Option<(f32, f32)> {
+    pub(crate) fn apply_null(&mut self) -> Option<(f32, f32)> {
         let mut x = 0.0;
         let mut y = 0.0;
 
@@ -403,10 +579,10 @@ impl<'a> VariationTuples<'a> {
 
 
 #[derive(Clone, Copy, Default, Debug)]
-struct TupleVariationHeaderData {
-    scalar: f32,
-    has_private_point_numbers: bool,
-    serialized_data_len: u16,
+pub(crate) struct TupleVariationHeaderData {
+    pub scalar: f32,
+    pub has_private_point_numbers: bool,
+    pub serialized_data_len: u16,
 }
 
 // https://docs.microsoft.com/en-us/typography/opentype/spec/otvarcommonformats#tuplevariationheader
@@ -475,7 +651,7 @@ fn parse_variation_tuples<'a>(
 }
 
 // https://docs.microsoft.com/en-us/typography/opentype/spec/otvarcommonformats#tuplevariationheader
-fn parse_tuple_variation_header(
+pub(crate) fn parse_tuple_variation_header(
     coordinates: &[NormalizedCoord],
     shared_tuple_records: &LazyArray16<F2DOT14>,
     s: &mut Stream,
@@ -963,7 +1139,7 @@ mod packed_points {
     }
 }
 
-use packed_points::*;
+pub(crate) use packed_points::{PackedPointsIter, SetPointsIter};
 
 
 // https://docs.microsoft.com/en-us/typography/opentype/spec/otvarcommonformats#packed-deltas
@@ -1114,6 +1290,28 @@ mod packed_deltas {
         }
     }
 
+    /// Like `PackedDeltasIter`, but for data with only one delta per point.
+    ///
+    /// Used by `cvar`.
+    #[derive(Clone, Copy, Default)]
+    pub struct PackedSingleDeltasIter<'a> {
+        data: &'a [u8],
+        run: RunState,
+        scalar: f32,
+    }
+
+    impl<'a> PackedSingleDeltasIter<'a> {
+        #[inline]
+        pub fn new(scalar: f32, data: &'a [u8]) -> Self {
+            PackedSingleDeltasIter { data, run: RunState::default(), scalar }
+        }
+
+        #[inline]
+        pub fn next(&mut self) -> Option<f32> {
+            self.run.next(self.data, self.scalar)
+        }
+    }
+
     #[cfg(test)]
     mod tests {
         use super::*;
@@ -1190,6 +1388,21 @@ mod packed_deltas {
             assert!(iter.next().is_none());
         }
 
+        #[test]
+        fn single_deltas() {
+            let data = vec![
+                gen_control(NewControl { deltas_are_zero: false, deltas_are_words: false, run_count: 2 }),
+                2, 3,
+                gen_control(NewControl { deltas_are_zero: true, deltas_are_words: false, run_count: 1 }),
+            ];
+
+            let mut iter = PackedSingleDeltasIter::new(0.5, &data);
+            assert_eq!(iter.next().unwrap(), 1.0);
+            assert_eq!(iter.next().unwrap(), 1.5);
+            assert_eq!(iter.next().unwrap(), 0.0);
+            assert!(iter.next().is_none());
+        }
+
         #[test]
         fn zero_words() {
             // When `deltas_are_zero` is set, `deltas_are_words` should be ignored.
@@ -1390,6 +1603,7 @@ mod packed_deltas {
 }
 
 use packed_deltas::PackedDeltasIter;
+pub(crate) use packed_deltas::PackedSingleDeltasIter;
 
 
 /// Infer unreferenced deltas.
@@ -1566,3 +1780,90 @@ fn infer_delta(
         (1.0 - d) * prev_delta + d * next_delta
     }
 }
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::string::String;
+    use crate::writer;
+    use writer::TtfType::*;
+    use std::vec::Vec;
+    use crate::glyf::tests::{build_glyf, test_glyphs, Builder, TEST_METRICS};
+
+    fn test_gvar() -> Vec<u8> {
+        writer::convert(&[
+            UInt32(0x00010000), // version
+            UInt16(1), // axis count
+            UInt16(0), // shared tuple count
+            UInt32(30), // offset to shared tuples
+            UInt16(4), // glyph count
+            UInt16(0), // flags
+            UInt32(30), // offset to glyph variation data
+            // Glyph variation data offsets.
+            UInt16(0), UInt16(0), UInt16(10), UInt16(10), UInt16(10),
+            // Glyph 1 variation data.
+            UInt16(1), // tuple variation count
+            UInt16(10), // offset to serialized data
+            UInt16(10), // variation data size
+            UInt16(0x8000), // EMBEDDED_PEAK_TUPLE
+            Int16(0x4000), // peak tuple
+            // Serialized data. Move all points by 10 horizontally.
+            UInt8(7), // x deltas control
+            Int8(10), Int8(10), Int8(10), Int8(10), Int8(10), Int8(10), Int8(10), Int8(10),
+            UInt8(0x80 | 7), // y deltas control
+        ])
+    }
+
+    fn test_loca(data: &[u8]) -> loca::Table {
+        loca::Table::parse(
+            data, NonZeroU16::new(4).unwrap(), crate::IndexToLocationFormat::Long,
+        ).unwrap()
+    }
+
+    #[test]
+    fn point_matching_after_deltas() {
+        let (glyf, loca) = build_glyf(&test_glyphs()[..4]);
+        let loca = test_loca(&loca);
+        let gvar = test_gvar();
+        let gvar = Table::parse(&gvar).unwrap();
+
+        let mut builder = Builder(String::new());
+        let coords = [NormalizedCoord::from(1.0)];
+        outline(loca, &glyf, &gvar, &coords, GlyphId(3), &mut builder).unwrap();
+        assert_eq!(builder.0,
+                   "M 10 0 L 110 0 L 110 100 L 10 100 L 10 0 Z \
+                    M 110 100 L 120 130 L 100 130 L 110 100 Z ");
+
+        let mut builder = Builder(String::new());
+        let coords = [NormalizedCoord::from(0.0)];
+        outline(loca, &glyf, &gvar, &coords, GlyphId(3), &mut builder).unwrap();
+        assert_eq!(builder.0,
+                   "M 0 0 L 100 0 L 100 100 L 0 100 L 0 0 Z \
+                    M 100 100 L 110 130 L 90 130 L 100 100 Z ");
+    }
+
+    #[test]
+    fn raw_points_with_deltas() {
+        let (glyf, loca) = build_glyf(&test_glyphs()[..4]);
+        let loca = test_loca(&loca);
+        let gvar = test_gvar();
+        let gvar = Table::parse(&gvar).unwrap();
+
+        let coords = [NormalizedCoord::from(1.0)];
+        let points = glyf::outline_points(loca, &glyf, GlyphId(1), TEST_METRICS).unwrap();
+        let points: Vec<_> = outline_points(&gvar, &coords, GlyphId(1), points).unwrap()
+            .map(|p| (p.x, p.y)).collect();
+        assert_eq!(points, vec![
+            (10.0, 0.0), (110.0, 0.0), (110.0, 100.0), (10.0, 100.0),
+            // Phantom points have deltas too.
+            (0.0, 0.0), (120.0, 0.0), (10.0, 800.0), (10.0, -200.0),
+        ]);
+
+        // A glyph without variation data.
+        let points = glyf::outline_points(loca, &glyf, GlyphId(2), TEST_METRICS).unwrap();
+        let points = outline_points(&gvar, &coords, GlyphId(2), points).unwrap();
+        assert_eq!(points.map(|p| p.x).collect::<Vec<_>>(),
+                   vec![10.0, 20.0, 0.0, -10.0, 110.0, 0.0, 0.0]);
+    }
+}
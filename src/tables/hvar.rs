@@ -3,7 +3,7 @@
 use core::convert::TryFrom;
 
 use crate::{GlyphId, NormalizedCoord};
-use crate::parser::{Stream, Offset, Offset32};
+use crate::parser::{Stream, Offset, Offset32, NumFrom};
 use crate::var_store::ItemVariationStore;
 
 #[derive(Clone, Copy)]
@@ -37,6 +37,7 @@ impl<'a> Table<'a> {
 }
 
 
+#[derive(Clone, Copy)]
 pub struct DeltaSetIndexMap<'a> {
     data: &'a [u8],
 }
@@ -49,11 +50,18 @@ impl<'a> DeltaSetIndexMap<'a> {
 
     #[inline]
     pub fn map(&self, glyph_id: GlyphId) -> Option<(u16, u16)> {
-        let mut idx = glyph_id.0;
+        self.map_index(u32::from(glyph_id.0))
+    }
 
+    pub fn map_index(&self, mut idx: u32) -> Option<(u16, u16)> {
         let mut s = Stream::new(self.data);
-        let entry_format: u16 = s.read()?;
-        let map_count: u16 = s.read()?;
+        let format: u8 = s.read()?;
+        let entry_format: u8 = s.read()?;
+        let map_count: u32 = match format {
+            0 => u32::from(s.read::<u16>()?),
+            1 => s.read()?,
+            _ => return None,
+        };
 
         if map_count == 0 {
             return None;
@@ -67,7 +75,7 @@ impl<'a> DeltaSetIndexMap<'a> {
         let entry_size = ((entry_format >> 4) & 3) + 1;
         let inner_index_bit_count = u32::from((entry_format & 0xF) + 1);
 
-        s.advance(usize::from(entry_size) * usize::from(idx));
+        s.advance_checked(usize::from(entry_size).checked_mul(usize::num_from(idx))?)?;
 
         let mut n = 0u32;
         for b in s.read_bytes(usize::from(entry_size))? {
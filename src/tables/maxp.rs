@@ -15,3 +15,41 @@ pub fn parse(data: &[u8]) -> Option<NonZeroU16> {
     let n: u16 = s.read()?;
     NonZeroU16::new(n)
 }
+
+/// Limits used by the TrueType interpreter.
+#[cfg(feature = "hinting")]
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct InterpreterLimits {
+    pub max_twilight_points: u16,
+    pub max_storage: u16,
+    pub max_function_defs: u16,
+    pub max_instruction_defs: u16,
+    pub max_stack_elements: u16,
+}
+
+/// Parses interpreter limits.
+///
+/// Only the version 1.0 table has them.
+#[cfg(feature = "hinting")]
+pub(crate) fn parse_interpreter_limits(data: &[u8]) -> Option<InterpreterLimits> {
+    let mut s = Stream::new(data);
+    let version: u32 = s.read()?;
+    if version != 0x00010000 {
+        return None;
+    }
+
+    s.skip::<u16>(); // numGlyphs
+    s.skip::<u16>(); // maxPoints
+    s.skip::<u16>(); // maxContours
+    s.skip::<u16>(); // maxCompositePoints
+    s.skip::<u16>(); // maxCompositeContours
+    s.skip::<u16>(); // maxZones
+
+    Some(InterpreterLimits {
+        max_twilight_points: s.read()?,
+        max_storage: s.read()?,
+        max_function_defs: s.read()?,
+        max_instruction_defs: s.read()?,
+        max_stack_elements: s.read()?,
+    })
+}
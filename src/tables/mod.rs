@@ -4,8 +4,13 @@ pub mod cblc;
 pub mod cff2;
 pub mod cff;
 pub mod cmap;
+pub mod colr;
+pub mod cpal;
+pub mod cvt;
 pub mod fvar;
 pub mod gdef;
+pub mod gpos;
+pub mod gsub;
 pub mod glyf;
 pub mod gvar;
 pub mod head;
@@ -20,6 +25,7 @@ pub mod name;
 pub mod os2;
 pub mod post;
 pub mod sbix;
+pub mod stat;
 pub mod svg;
 pub mod vhea;
 pub mod vorg;
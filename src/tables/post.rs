@@ -274,6 +274,8 @@ const MACINTOSH_NAMES: &[&str] = &[
 #[derive(Clone, Copy)]
 pub struct Table<'a> {
     underline: LineMetrics,
+    // Version 1.0 glyphs are in the standard Macintosh order.
+    is_standard_order: bool,
     name_indexes: LazyArray16<'a, u16>,
     names: &'a [u8],
 }
@@ -310,6 +312,7 @@ impl<'a> Table<'a> {
 
         Some(Table {
             underline,
+            is_standard_order: version == 0x00010000,
             name_indexes,
             names,
         })
@@ -322,7 +325,11 @@ impl<'a> Table<'a> {
 
     #[inline]
     pub fn glyph_name(&self, glyph_id: GlyphId) -> Option<&'a str> {
-        let mut index = self.name_indexes.get(glyph_id.0)?;
+        let mut index = if self.is_standard_order {
+            glyph_id.0
+        } else {
+            self.name_indexes.get(glyph_id.0)?
+        };
 
         // 'If the name index is between 0 and 257, treat the name index
         // as a glyph index in the Macintosh standard order.'
@@ -356,4 +363,114 @@ impl<'a> Table<'a> {
             None
         }
     }
+
+    pub fn glyph_index_by_name(&self, name: &str) -> Option<GlyphId> {
+        let standard_index = MACINTOSH_NAMES.iter().position(|n| *n == name);
+        if self.is_standard_order {
+            return standard_index.map(|index| GlyphId(index as u16));
+        }
+
+        if let Some(index) = standard_index {
+            if let Some(id) = self.glyph_index_by_name_index(index as u16) {
+                return Some(id);
+            }
+        }
+
+        // Standard names can be stored as custom strings too.
+        let mut s = Stream::new(self.names);
+        let mut i = 0;
+        let index = loop {
+            if s.at_end() || i == core::u16::MAX - MACINTOSH_NAMES.len() as u16 {
+                return None;
+            }
+
+            let len: u8 = s.read()?;
+            if s.read_bytes(usize::from(len))? == name.as_bytes() {
+                break MACINTOSH_NAMES.len() as u16 + i;
+            }
+
+            i += 1;
+        };
+
+        self.glyph_index_by_name_index(index)
+    }
+
+    fn glyph_index_by_name_index(&self, index: u16) -> Option<GlyphId> {
+        self.name_indexes.into_iter().position(|n| n == index).map(|n| GlyphId(n as u16))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::vec::Vec;
+    use crate::writer;
+    use writer::TtfType::*;
+
+    fn gen_post(version: u32, tail: &[writer::TtfType]) -> Vec<u8> {
+        let mut data = vec![
+            UInt32(version), // version
+            UInt32(0), // italic angle
+            Int16(-100), // underline position
+            Int16(50), // underline thickness
+            UInt32(0), // is fixed pitch
+            UInt32(0), // min memory type 42
+            UInt32(0), // max memory type 42
+            UInt32(0), // min memory type 1
+            UInt32(0), // max memory type 1
+        ];
+        data.extend_from_slice(tail);
+        writer::convert(&data)
+    }
+
+    #[test]
+    fn version_1() {
+        let data = gen_post(0x00010000, &[]);
+        let table = Table::parse(&data).unwrap();
+        assert_eq!(table.glyph_name(GlyphId(3)), Some("space"));
+        assert_eq!(table.glyph_name(GlyphId(258)), None);
+        assert_eq!(table.glyph_index_by_name("space"), Some(GlyphId(3)));
+        assert_eq!(table.glyph_index_by_name("dcroat"), Some(GlyphId(257)));
+        assert_eq!(table.glyph_index_by_name("custom"), None);
+    }
+
+    #[test]
+    fn version_2() {
+        let data = gen_post(0x00020000, &[
+            UInt16(4), // number of glyphs
+            UInt16(0), // .notdef
+            UInt16(258), // custom
+            UInt16(259), // A
+            UInt16(36), // A
+            // Names
+            UInt8(6), Raw(b"custom"),
+            UInt8(1), Raw(b"A"),
+        ]);
+        let table = Table::parse(&data).unwrap();
+        assert_eq!(table.glyph_name(GlyphId(0)), Some(".notdef"));
+        assert_eq!(table.glyph_name(GlyphId(1)), Some("custom"));
+        assert_eq!(table.glyph_name(GlyphId(2)), Some("A"));
+        assert_eq!(table.glyph_name(GlyphId(3)), Some("A"));
+        assert_eq!(table.glyph_name(GlyphId(4)), None);
+        assert_eq!(table.glyph_index_by_name(".notdef"), Some(GlyphId(0)));
+        assert_eq!(table.glyph_index_by_name("custom"), Some(GlyphId(1)));
+        // The standard index is preferred.
+        assert_eq!(table.glyph_index_by_name("A"), Some(GlyphId(3)));
+        assert_eq!(table.glyph_index_by_name("space"), None);
+    }
+
+    #[test]
+    fn standard_name_as_custom_string() {
+        let data = gen_post(0x00020000, &[
+            UInt16(2), // number of glyphs
+            UInt16(0), // .notdef
+            UInt16(258), // space
+            // Names
+            UInt8(5), Raw(b"space"),
+        ]);
+        let table = Table::parse(&data).unwrap();
+        assert_eq!(table.glyph_name(GlyphId(1)), Some("space"));
+        assert_eq!(table.glyph_index_by_name("space"), Some(GlyphId(1)));
+    }
 }
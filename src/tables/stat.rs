@@ -0,0 +1,482 @@
+/*!
+A [Style Attributes Table](
+https://docs.microsoft.com/en-us/typography/opentype/spec/stat) implementation.
+*/
+
+#[cfg(feature = "std")]
+use std::string::String;
+
+use crate::Tag;
+use crate::name::{Name, Names};
+use crate::parser::{Stream, FromData, FromSlice, Fixed, Offset, Offset16, Offset32};
+use crate::parser::{LazyArray16, LazyOffsetArray16, LazyOffsetArrayIter16};
+
+
+/// A [design axis](https://docs.microsoft.com/en-us/typography/opentype/spec/stat#axis-records).
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct AxisRecord {
+    /// Axis tag.
+    pub tag: Tag,
+    /// An axis name in the `name` table.
+    pub name_id: u16,
+    /// A value used to order axes when composing a style name.
+    pub ordering: u16,
+}
+
+impl FromData for AxisRecord {
+    const SIZE: usize = 8;
+
+    #[inline]
+    fn parse(data: &[u8]) -> Option<Self> {
+        let mut s = Stream::new(data);
+        Some(AxisRecord {
+            tag: s.read()?,
+            name_id: s.read()?,
+            ordering: s.read()?,
+        })
+    }
+}
+
+
+/// An iterator over design axes.
+#[derive(Clone, Copy, Default)]
+#[allow(missing_debug_implementations)]
+pub struct Axes<'a> {
+    data: &'a [u8],
+    // Axis records can be bigger than `AxisRecord::SIZE` in future versions,
+    // so we cannot use `LazyArray16` here.
+    record_size: usize,
+    index: u16,
+    count: u16,
+}
+
+impl Axes<'_> {
+    /// Returns an axis at `index`.
+    ///
+    /// Ignores the iterator position.
+    #[inline]
+    pub fn get(&self, index: u16) -> Option<AxisRecord> {
+        if index < self.count {
+            Stream::read_at(self.data, usize::from(index) * self.record_size)
+        } else {
+            None
+        }
+    }
+}
+
+impl Iterator for Axes<'_> {
+    type Item = AxisRecord;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.index < self.count {
+            self.index += 1;
+            self.get(self.index - 1)
+        } else {
+            None
+        }
+    }
+
+    #[inline]
+    fn count(self) -> usize {
+        usize::from(self.count - self.index)
+    }
+}
+
+
+/// An axis-value pair used by the format 4 axis value table.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct AxisValueRecord {
+    /// Index into the design axes.
+    pub axis_index: u16,
+    /// A numeric value for this axis.
+    pub value: f32,
+}
+
+impl FromData for AxisValueRecord {
+    const SIZE: usize = 6;
+
+    #[inline]
+    fn parse(data: &[u8]) -> Option<Self> {
+        let mut s = Stream::new(data);
+        Some(AxisValueRecord {
+            axis_index: s.read()?,
+            value: s.read::<Fixed>()?.0,
+        })
+    }
+}
+
+
+/// An axis value table data.
+#[derive(Clone, Copy, Debug)]
+pub enum AxisValueData<'a> {
+    /// A single value on an axis. Format 1.
+    Single {
+        /// Index into the design axes.
+        axis_index: u16,
+        /// A numeric value for this axis.
+        value: f32,
+    },
+    /// A range of values on an axis. Format 2.
+    Range {
+        /// Index into the design axes.
+        axis_index: u16,
+        /// A nominal numeric value for this axis.
+        nominal_value: f32,
+        /// The minimum value for this axis.
+        min_value: f32,
+        /// The maximum value for this axis.
+        max_value: f32,
+    },
+    /// A single value on an axis that is style-linked to another value. Format 3.
+    ///
+    /// Usually used to link *Regular* with *Bold*.
+    Linked {
+        /// Index into the design axes.
+        axis_index: u16,
+        /// A numeric value for this axis.
+        value: f32,
+        /// A numeric value of the style-linked counterpart.
+        linked_value: f32,
+    },
+    /// A combination of values on multiple axes. Format 4.
+    Combination(LazyArray16<'a, AxisValueRecord>),
+}
+
+
+/// An [axis value table](
+/// https://docs.microsoft.com/en-us/typography/opentype/spec/stat#axis-value-tables).
+#[derive(Clone, Copy, Debug)]
+pub struct AxisValue<'a> {
+    /// Indicates that this value applies to an older sibling font,
+    /// like a non-variable font in the same family.
+    pub older_sibling_font_attribute: bool,
+    /// Indicates that this value is a *normal* value for an axis
+    /// and can be omitted when composing a style name.
+    pub elidable: bool,
+    /// A value name in the `name` table.
+    pub value_name_id: u16,
+    /// Format-specific data.
+    pub data: AxisValueData<'a>,
+}
+
+impl<'a> FromSlice<'a> for AxisValue<'a> {
+    fn parse(data: &'a [u8]) -> Option<Self> {
+        let mut s = Stream::new(data);
+        let format: u16 = s.read()?;
+
+        // An axis index for formats 1-3 and an axis count for format 4.
+        let axis: u16 = s.read()?;
+
+        let flags: u16 = s.read()?;
+        let value_name_id: u16 = s.read()?;
+
+        let data = match format {
+            1 => AxisValueData::Single {
+                axis_index: axis,
+                value: s.read::<Fixed>()?.0,
+            },
+            2 => AxisValueData::Range {
+                axis_index: axis,
+                nominal_value: s.read::<Fixed>()?.0,
+                min_value: s.read::<Fixed>()?.0,
+                max_value: s.read::<Fixed>()?.0,
+            },
+            3 => AxisValueData::Linked {
+                axis_index: axis,
+                value: s.read::<Fixed>()?.0,
+                linked_value: s.read::<Fixed>()?.0,
+            },
+            4 => AxisValueData::Combination(s.read_array16(axis)?),
+            _ => return None,
+        };
+
+        Some(AxisValue {
+            older_sibling_font_attribute: flags & 0x0001 != 0,
+            elidable: flags & 0x0002 != 0,
+            value_name_id,
+            data,
+        })
+    }
+}
+
+
+/// An iterator over axis value tables.
+///
+/// Tables with an unknown format are skipped.
+#[derive(Clone, Copy)]
+#[allow(missing_debug_implementations)]
+pub struct AxisValues<'a> {
+    iter: LazyOffsetArrayIter16<'a, AxisValue<'a>>,
+}
+
+impl<'a> Iterator for AxisValues<'a> {
+    type Item = AxisValue<'a>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next()
+    }
+}
+
+
+#[derive(Clone, Copy)]
+pub(crate) struct Table<'a> {
+    axes: Axes<'a>,
+    axis_values: LazyOffsetArray16<'a, AxisValue<'a>>,
+    elided_fallback_name_id: Option<u16>,
+}
+
+impl<'a> Table<'a> {
+    pub fn parse(data: &'a [u8]) -> Option<Self> {
+        let mut s = Stream::new(data);
+        let major_version: u16 = s.read()?;
+        let minor_version: u16 = s.read()?;
+        if major_version != 1 {
+            return None;
+        }
+
+        let design_axis_size: u16 = s.read()?;
+        let design_axis_count: u16 = s.read()?;
+        let design_axes_offset: Offset32 = s.read()?;
+        let axis_value_count: u16 = s.read()?;
+        let axis_value_offsets_offset: Offset32 = s.read()?;
+        // Version 1.0 is deprecated and doesn't have this field.
+        let elided_fallback_name_id: Option<u16> = if minor_version > 0 {
+            Some(s.read()?)
+        } else {
+            None
+        };
+
+        let record_size = usize::from(design_axis_size);
+        if design_axis_count != 0 && record_size < AxisRecord::SIZE {
+            return None;
+        }
+
+        let axes = Axes {
+            data: data.get(design_axes_offset.to_usize()..)?,
+            record_size,
+            index: 0,
+            count: design_axis_count,
+        };
+
+        // Axis value offsets are from the beginning of the offsets array.
+        let axis_values = if axis_value_count != 0 {
+            let offsets_data = data.get(axis_value_offsets_offset.to_usize()..)?;
+            let mut s = Stream::new(offsets_data);
+            let offsets: LazyArray16<Option<Offset16>> = s.read_array16(axis_value_count)?;
+            LazyOffsetArray16::new(offsets_data, offsets)
+        } else {
+            LazyOffsetArray16::default()
+        };
+
+        Some(Table {
+            axes,
+            axis_values,
+            elided_fallback_name_id,
+        })
+    }
+}
+
+
+/// Style attributes from the
+/// [Style Attributes Table](https://docs.microsoft.com/en-us/typography/opentype/spec/stat).
+///
+/// Names are resolved using the `name` table.
+#[derive(Clone, Copy)]
+#[allow(missing_debug_implementations)]
+pub struct StyleAttributes<'a> {
+    table: Table<'a>,
+    names: Names<'a>,
+}
+
+impl<'a> StyleAttributes<'a> {
+    #[inline]
+    pub(crate) fn new(table: Table<'a>, names: Names<'a>) -> Self {
+        StyleAttributes { table, names }
+    }
+
+    /// Returns an iterator over design axes.
+    ///
+    /// Axis value tables refer to axes by index in this list.
+    #[inline]
+    pub fn axes(&self) -> Axes<'a> {
+        self.table.axes
+    }
+
+    /// Returns an iterator over axis value tables.
+    #[inline]
+    pub fn axis_values(&self) -> AxisValues<'a> {
+        AxisValues { iter: self.table.axis_values.into_iter() }
+    }
+
+    /// Returns a name ID used as a style name when all style attributes are elided.
+    ///
+    /// Returns `None` for the deprecated version 1.0 table.
+    #[inline]
+    pub fn elided_fallback_name_id(&self) -> Option<u16> {
+        self.table.elided_fallback_name_id
+    }
+
+    /// Returns the first Unicode name with the specified ID.
+    #[inline]
+    pub fn name(&self, name_id: u16) -> Option<Name<'a>> {
+        let mut names = self.names;
+        names.find(|name| name.name_id() == name_id && name.is_unicode())
+    }
+
+    /// Returns the first Unicode name with the specified ID as a UTF-8 string.
+    #[cfg(feature = "std")]
+    #[inline]
+    pub fn name_utf8(&self, name_id: u16) -> Option<String> {
+        self.name(name_id).and_then(|name| name.name_from_utf16_be())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::writer;
+    use writer::TtfType::*;
+
+    #[test]
+    fn axis_values() {
+        let data = writer::convert(&[
+            UInt16(1), // major version
+            UInt16(1), // minor version
+            UInt16(8), // design axis size
+            UInt16(2), // design axis count
+            UInt32(20), // design axes offset
+            UInt16(4), // axis value count
+            UInt32(36), // axis value offsets offset
+            UInt16(2), // elided fallback name ID
+
+            // Design axes
+            Raw(b"wght"), // tag
+            UInt16(256), // name ID
+            UInt16(0), // ordering
+            Raw(b"ital"), // tag
+            UInt16(257), // name ID
+            UInt16(1), // ordering
+
+            // Axis value offsets
+            UInt16(8),
+            UInt16(20),
+            UInt16(40),
+            UInt16(56),
+
+            // Format 1
+            UInt16(1), // format
+            UInt16(1), // axis index
+            UInt16(2), // flags
+            UInt16(258), // value name ID
+            Int32(0), // value
+
+            // Format 2
+            UInt16(2), // format
+            UInt16(0), // axis index
+            UInt16(0), // flags
+            UInt16(259), // value name ID
+            Int32(700 << 16), // nominal value
+            Int32(650 << 16), // min value
+            Int32(750 << 16), // max value
+
+            // Format 3
+            UInt16(3), // format
+            UInt16(0), // axis index
+            UInt16(3), // flags
+            UInt16(260), // value name ID
+            Int32(400 << 16), // value
+            Int32(700 << 16), // linked value
+
+            // Format 4
+            UInt16(4), // format
+            UInt16(2), // axis count
+            UInt16(0), // flags
+            UInt16(261), // value name ID
+            UInt16(0), // axis index
+            Int32(700 << 16), // value
+            UInt16(1), // axis index
+            Int32(1 << 16), // value
+        ]);
+
+        let attrs = StyleAttributes::new(Table::parse(&data).unwrap(), Names::default());
+        assert_eq!(attrs.elided_fallback_name_id(), Some(2));
+
+        let mut axes = attrs.axes();
+        assert_eq!(axes.count(), 2);
+        assert_eq!(axes.next(), Some(AxisRecord { tag: Tag::from_bytes(b"wght"), name_id: 256, ordering: 0 }));
+        assert_eq!(axes.next(), Some(AxisRecord { tag: Tag::from_bytes(b"ital"), name_id: 257, ordering: 1 }));
+        assert_eq!(axes.next(), None);
+
+        let mut values = attrs.axis_values();
+
+        let value = values.next().unwrap();
+        assert!(!value.older_sibling_font_attribute);
+        assert!(value.elidable);
+        assert_eq!(value.value_name_id, 258);
+        match value.data {
+            AxisValueData::Single { axis_index, value } => {
+                assert_eq!(axis_index, 1);
+                assert_eq!(value, 0.0);
+            }
+            _ => panic!("invalid format"),
+        }
+
+        let value = values.next().unwrap();
+        assert_eq!(value.value_name_id, 259);
+        match value.data {
+            AxisValueData::Range { axis_index, nominal_value, min_value, max_value } => {
+                assert_eq!(axis_index, 0);
+                assert_eq!(nominal_value, 700.0);
+                assert_eq!(min_value, 650.0);
+                assert_eq!(max_value, 750.0);
+            }
+            _ => panic!("invalid format"),
+        }
+
+        let value = values.next().unwrap();
+        assert!(value.older_sibling_font_attribute);
+        assert!(value.elidable);
+        match value.data {
+            AxisValueData::Linked { axis_index, value, linked_value } => {
+                assert_eq!(axis_index, 0);
+                assert_eq!(value, 400.0);
+                assert_eq!(linked_value, 700.0);
+            }
+            _ => panic!("invalid format"),
+        }
+
+        let value = values.next().unwrap();
+        assert_eq!(value.value_name_id, 261);
+        match value.data {
+            AxisValueData::Combination(records) => {
+                assert_eq!(records.len(), 2);
+                assert_eq!(records.get(0), Some(AxisValueRecord { axis_index: 0, value: 700.0 }));
+                assert_eq!(records.get(1), Some(AxisValueRecord { axis_index: 1, value: 1.0 }));
+            }
+            _ => panic!("invalid format"),
+        }
+
+        assert!(values.next().is_none());
+    }
+
+    #[test]
+    fn version_1_0() {
+        let data = writer::convert(&[
+            UInt16(1), // major version
+            UInt16(0), // minor version
+            UInt16(8), // design axis size
+            UInt16(0), // design axis count
+            UInt32(0), // design axes offset
+            UInt16(0), // axis value count
+            UInt32(0), // axis value offsets offset
+        ]);
+
+        let attrs = StyleAttributes::new(Table::parse(&data).unwrap(), Names::default());
+        assert_eq!(attrs.elided_fallback_name_id(), None);
+        assert_eq!(attrs.axes().count(), 0);
+        assert_eq!(attrs.axis_values().count(), 0);
+    }
+}
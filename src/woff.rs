@@ -0,0 +1,516 @@
+/*!
+A [WOFF 1.0](https://www.w3.org/TR/WOFF/) fonts decoding.
+
+`Font` is a zero-allocation parser and can't decompress tables on the fly,
+therefore WOFF data must be converted into a regular OpenType font first.
+The decoded font can be written either into a caller-provided buffer
+or into a `Vec` (requires the `std` feature).
+
+Available only with the `woff` feature enabled.
+
+```no_run
+let data = std::fs::read("font.woff").unwrap();
+let woff = ttf_parser::woff::Woff::parse(&data).unwrap();
+let sfnt = woff.to_sfnt().unwrap();
+let font = ttf_parser::Font::from_data(&sfnt, 0).unwrap();
+```
+*/
+
+use core::convert::TryFrom;
+
+#[cfg(feature = "std")]
+use std::string::String;
+#[cfg(feature = "std")]
+use std::vec::Vec;
+
+use crate::Tag;
+use crate::parser::{Stream, FromData, LazyArray16, NumFrom, sfnt_search_params};
+
+// https://www.w3.org/TR/WOFF/#WOFFHeader
+const SIGNATURE: u32 = 0x774F4646; // wOFF
+
+const SFNT_HEADER_SIZE: usize = 12;
+const SFNT_TABLE_RECORD_SIZE: usize = 16;
+
+
+/// A WOFF table directory entry.
+#[derive(Clone, Copy, Debug)]
+pub struct TableRecord {
+    /// Table's tag.
+    pub tag: Tag,
+    /// Offset to the table data from the beginning of the WOFF file.
+    pub offset: u32,
+    /// Table's length in the WOFF file.
+    pub compressed_length: u32,
+    /// Table's length after decompression.
+    pub original_length: u32,
+    /// Table's checksum in the original font.
+    pub original_checksum: u32,
+}
+
+impl TableRecord {
+    /// Checks that table is compressed.
+    ///
+    /// Tables that cannot be made smaller are stored as is.
+    #[inline]
+    pub fn is_compressed(&self) -> bool {
+        self.compressed_length < self.original_length
+    }
+}
+
+impl FromData for TableRecord {
+    const SIZE: usize = 20;
+
+    #[inline]
+    fn parse(data: &[u8]) -> Option<Self> {
+        let mut s = Stream::new(data);
+        Some(TableRecord {
+            tag: s.read()?,
+            offset: s.read()?,
+            compressed_length: s.read()?,
+            original_length: s.read()?,
+            original_checksum: s.read()?,
+        })
+    }
+}
+
+
+/// A WOFF font.
+#[derive(Clone, Copy, Debug)]
+pub struct Woff<'a> {
+    data: &'a [u8],
+    flavor: u32,
+    tables: LazyArray16<'a, TableRecord>,
+    metadata: Option<&'a [u8]>,
+    metadata_length: u32,
+    private_data: Option<&'a [u8]>,
+}
+
+impl<'a> Woff<'a> {
+    /// Parses a WOFF header and a table directory.
+    ///
+    /// Tables data is not decompressed here, but their bounds are checked.
+    pub fn parse(data: &'a [u8]) -> Option<Self> {
+        let mut s = Stream::new(data);
+        let signature: u32 = s.read()?;
+        let flavor: u32 = s.read()?;
+        let length: u32 = s.read()?;
+        let number_of_tables: u16 = s.read()?;
+        let reserved: u16 = s.read()?;
+        s.skip::<u32>(); // totalSfntSize
+        s.skip::<u16>(); // majorVersion
+        s.skip::<u16>(); // minorVersion
+        let metadata_offset: u32 = s.read()?;
+        let metadata_compressed_length: u32 = s.read()?;
+        let metadata_length: u32 = s.read()?;
+        let private_data_offset: u32 = s.read()?;
+        let private_data_length: u32 = s.read()?;
+
+        if signature != SIGNATURE || reserved != 0 || number_of_tables == 0 {
+            return None;
+        }
+
+        // Ignore trailing data, if any.
+        let data = data.get(..usize::num_from(length))?;
+
+        let tables: LazyArray16<TableRecord> = s.read_array16(number_of_tables)?;
+        for table in tables {
+            if table.compressed_length > table.original_length {
+                return None;
+            }
+
+            block(data, table.offset, table.compressed_length)?;
+        }
+
+        let metadata = if metadata_offset != 0 {
+            Some(block(data, metadata_offset, metadata_compressed_length)?)
+        } else {
+            None
+        };
+
+        let private_data = if private_data_offset != 0 {
+            Some(block(data, private_data_offset, private_data_length)?)
+        } else {
+            None
+        };
+
+        Some(Woff {
+            data,
+            flavor,
+            tables,
+            metadata,
+            metadata_length,
+            private_data,
+        })
+    }
+
+    /// Returns the "sfnt version" of the original font.
+    ///
+    /// `0x00010000` for TrueType and `OTTO` for CFF-based fonts.
+    #[inline]
+    pub fn flavor(&self) -> Tag {
+        Tag(self.flavor)
+    }
+
+    /// Returns a table directory.
+    #[inline]
+    pub fn tables(&self) -> LazyArray16<'a, TableRecord> {
+        self.tables
+    }
+
+    /// Returns a size of the decoded font.
+    ///
+    /// A buffer passed to `decode_into` should be at least that size.
+    ///
+    /// Returns `None` on overflow.
+    pub fn sfnt_size(&self) -> Option<usize> {
+        let mut size = SFNT_TABLE_RECORD_SIZE
+            .checked_mul(usize::from(self.tables.len()))?
+            .checked_add(SFNT_HEADER_SIZE)?;
+
+        for table in self.tables {
+            size = size.checked_add(padded(usize::num_from(table.original_length))?)?;
+        }
+
+        Some(size)
+    }
+
+    /// Decodes WOFF into a regular OpenType font.
+    ///
+    /// Returns the number of written bytes.
+    ///
+    /// Returns `None` when `buf` is too small or when table decompression failed.
+    pub fn decode_into(&self, buf: &mut [u8]) -> Option<usize> {
+        let size = self.sfnt_size()?;
+        let buf = buf.get_mut(..size)?;
+
+        let number_of_tables = self.tables.len();
+        let (search_range, entry_selector, range_shift) = sfnt_search_params(number_of_tables)?;
+
+        let header = buf.get_mut(..SFNT_HEADER_SIZE)?;
+        header[0..4].copy_from_slice(&self.flavor.to_be_bytes());
+        header[4..6].copy_from_slice(&number_of_tables.to_be_bytes());
+        header[6..8].copy_from_slice(&search_range.to_be_bytes());
+        header[8..10].copy_from_slice(&entry_selector.to_be_bytes());
+        header[10..12].copy_from_slice(&range_shift.to_be_bytes());
+
+        let mut record_offset = SFNT_HEADER_SIZE;
+        let mut data_offset = SFNT_HEADER_SIZE + SFNT_TABLE_RECORD_SIZE * usize::from(number_of_tables);
+        for table in self.tables {
+            let record = buf.get_mut(record_offset..record_offset + SFNT_TABLE_RECORD_SIZE)?;
+            record[0..4].copy_from_slice(&table.tag.0.to_be_bytes());
+            record[4..8].copy_from_slice(&table.original_checksum.to_be_bytes());
+            record[8..12].copy_from_slice(&u32::try_from(data_offset).ok()?.to_be_bytes());
+            record[12..16].copy_from_slice(&table.original_length.to_be_bytes());
+            record_offset += SFNT_TABLE_RECORD_SIZE;
+
+            let length = usize::num_from(table.original_length);
+            let end = data_offset.checked_add(length)?;
+            let src = block(self.data, table.offset, table.compressed_length)?;
+            let dst = buf.get_mut(data_offset..end)?;
+            if table.is_compressed() {
+                if inflate(src, dst)? != length {
+                    return None;
+                }
+            } else {
+                dst.copy_from_slice(src);
+            }
+
+            // Tables must be 4-byte aligned. And the padding must be zeroed,
+            // since we do not know what the caller-provided buffer contains.
+            let padded_end = data_offset.checked_add(padded(length)?)?;
+            for b in buf.get_mut(end..padded_end)? {
+                *b = 0;
+            }
+
+            data_offset = padded_end;
+        }
+
+        Some(size)
+    }
+
+    /// Decodes WOFF into a regular OpenType font.
+    ///
+    /// Returns `None` when table decompression failed.
+    #[cfg(feature = "std")]
+    pub fn to_sfnt(&self) -> Option<Vec<u8>> {
+        let mut buf = vec![0; self.sfnt_size()?];
+        self.decode_into(&mut buf)?;
+        Some(buf)
+    }
+
+    /// Returns an uncompressed size of the extended metadata block.
+    ///
+    /// Returns `None` when metadata is not set.
+    #[inline]
+    pub fn metadata_size(&self) -> Option<usize> {
+        self.metadata.map(|_| usize::num_from(self.metadata_length))
+    }
+
+    /// Decompresses the extended metadata block into `buf`.
+    ///
+    /// Metadata is an XML document in UTF-8.
+    ///
+    /// Returns `None` when metadata is not set, when `buf` is too small
+    /// or when decompression failed.
+    pub fn decode_metadata_into<'b>(&self, buf: &'b mut [u8]) -> Option<&'b str> {
+        let length = self.metadata_size()?;
+        let buf = buf.get_mut(..length)?;
+        if inflate(self.metadata?, buf)? != length {
+            return None;
+        }
+
+        core::str::from_utf8(buf).ok()
+    }
+
+    /// Decompresses the extended metadata block.
+    ///
+    /// Metadata is an XML document in UTF-8.
+    ///
+    /// Returns `None` when metadata is not set or when decompression failed.
+    #[cfg(feature = "std")]
+    pub fn metadata(&self) -> Option<String> {
+        let mut buf = vec![0; self.metadata_size()?];
+        self.decode_metadata_into(&mut buf)?;
+        String::from_utf8(buf).ok()
+    }
+
+    /// Returns a private data block.
+    ///
+    /// Its format is defined by the font vendor.
+    #[inline]
+    pub fn private_data(&self) -> Option<&'a [u8]> {
+        self.private_data
+    }
+}
+
+#[inline]
+fn block(data: &[u8], offset: u32, length: u32) -> Option<&[u8]> {
+    let start = usize::num_from(offset);
+    let end = start.checked_add(usize::num_from(length))?;
+    data.get(start..end)
+}
+
+#[inline]
+fn padded(n: usize) -> Option<usize> {
+    Some(n.checked_add(3)? & !3)
+}
+
+// Tables are compressed using zlib and not just deflate.
+fn inflate(data: &[u8], buf: &mut [u8]) -> Option<usize> {
+    miniz_oxide::inflate::decompress_slice_iter_to_slice(buf, core::iter::once(data), true, false).ok()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::writer;
+    use writer::TtfType::*;
+
+    const HEADER_SIZE: usize = 44;
+
+    #[test]
+    fn search_params_values() {
+        assert_eq!(sfnt_search_params(1), Some((16, 0, 0)));
+        assert_eq!(sfnt_search_params(2), Some((32, 1, 0)));
+        assert_eq!(sfnt_search_params(3), Some((32, 1, 16)));
+        assert_eq!(sfnt_search_params(16), Some((256, 4, 0)));
+        assert_eq!(sfnt_search_params(17), Some((256, 4, 16)));
+        assert_eq!(sfnt_search_params(4095), Some((32768, 11, 32752)));
+        assert_eq!(sfnt_search_params(4096), None);
+        assert_eq!(sfnt_search_params(core::u16::MAX), None);
+    }
+
+    #[test]
+    fn compressed_table() {
+        // zlib.compress(b'abcd' * 16, 9)
+        const COMPRESSED: &[u8] = &[
+            0x78, 0xDA, 0x4B, 0x4C, 0x4A, 0x4E, 0x49, 0xA4, 0x00, 0x03, 0x00, 0x20,
+            0x6D, 0x18, 0xA1,
+        ];
+
+        let data = writer::convert(&[
+            // Header
+            UInt32(SIGNATURE),
+            UInt32(0x00010000), // flavor
+            UInt32(84), // length
+            UInt16(1), // number of tables
+            UInt16(0), // reserved
+            UInt32(92), // total sfnt size
+            UInt16(1), // major version
+            UInt16(0), // minor version
+            UInt32(0), // metadata offset
+            UInt32(0), // metadata length
+            UInt32(0), // metadata original length
+            UInt32(80), // private data offset
+            UInt32(4), // private data length
+
+            // Table directory
+            UInt32(Tag::from_bytes(b"test").0), // tag
+            UInt32(64), // offset
+            UInt32(COMPRESSED.len() as u32), // compressed length
+            UInt32(64), // original length
+            UInt32(0), // original checksum
+
+            Raw(COMPRESSED),
+            UInt8(0), // padding
+            Raw(b"priv"),
+        ]);
+
+        let woff = Woff::parse(&data).unwrap();
+        assert_eq!(woff.flavor(), Tag(0x00010000));
+        assert!(woff.tables().get(0).unwrap().is_compressed());
+        assert_eq!(woff.private_data(), Some(&b"priv"[..]));
+        assert_eq!(woff.metadata_size(), None);
+        assert_eq!(woff.sfnt_size(), Some(92));
+
+        let sfnt = woff.to_sfnt().unwrap();
+        assert_eq!(&sfnt[..12], &[0, 1, 0, 0, 0, 1, 0, 16, 0, 0, 0, 0]);
+        assert_eq!(&sfnt[12..16], b"test");
+        assert_eq!(&sfnt[20..24], &[0, 0, 0, 28]); // offset
+        assert_eq!(&sfnt[28..], &b"abcd".repeat(16)[..]);
+
+        // A too small buffer.
+        let mut buf = [0; 91];
+        assert!(woff.decode_into(&mut buf).is_none());
+    }
+
+    #[test]
+    fn metadata() {
+        // zlib.compress(b'<?xml version="1.0" encoding="UTF-8"?><metadata version="1.0"/>', 9)
+        const METADATA: &[u8] = &[
+            0x78, 0xDA, 0xB3, 0xB1, 0xAF, 0xC8, 0xCD, 0x51, 0x28, 0x4B, 0x2D, 0x2A,
+            0xCE, 0xCC, 0xCF, 0xB3, 0x55, 0x32, 0xD4, 0x33, 0x50, 0x52, 0x48, 0xCD,
+            0x4B, 0xCE, 0x4F, 0xC9, 0xCC, 0x4B, 0xB7, 0x55, 0x0A, 0x0D, 0x71, 0xD3,
+            0xB5, 0x50, 0xB2, 0xB7, 0xB3, 0xC9, 0x4D, 0x2D, 0x49, 0x4C, 0x49, 0x2C,
+            0x49, 0x44, 0x55, 0xA9, 0x6F, 0x07, 0x00, 0x8E, 0x48, 0x13, 0xDC,
+        ];
+
+        let data = writer::convert(&[
+            // Header
+            UInt32(SIGNATURE),
+            UInt32(0x00010000), // flavor
+            UInt32(128), // length
+            UInt16(1), // number of tables
+            UInt16(0), // reserved
+            UInt32(32), // total sfnt size
+            UInt16(1), // major version
+            UInt16(0), // minor version
+            UInt32(68), // metadata offset
+            UInt32(METADATA.len() as u32), // metadata length
+            UInt32(63), // metadata original length
+            UInt32(0), // private data offset
+            UInt32(0), // private data length
+
+            // Table directory
+            UInt32(Tag::from_bytes(b"test").0), // tag
+            UInt32(64), // offset
+            UInt32(4), // compressed length
+            UInt32(4), // original length
+            UInt32(0), // original checksum
+
+            Raw(b"abcd"),
+            Raw(METADATA),
+            UInt8(0), // padding
+        ]);
+
+        let woff = Woff::parse(&data).unwrap();
+        assert!(!woff.tables().get(0).unwrap().is_compressed());
+        assert_eq!(woff.private_data(), None);
+        assert_eq!(woff.metadata_size(), Some(63));
+        assert_eq!(woff.metadata().unwrap(),
+                   "<?xml version=\"1.0\" encoding=\"UTF-8\"?><metadata version=\"1.0\"/>");
+
+        let mut buf = [0; 62];
+        assert!(woff.decode_metadata_into(&mut buf).is_none());
+
+        let sfnt = woff.to_sfnt().unwrap();
+        assert_eq!(&sfnt[28..], b"abcd");
+    }
+
+    #[test]
+    fn invalid_table_length() {
+        let data = writer::convert(&[
+            // Header
+            UInt32(SIGNATURE),
+            UInt32(0x00010000), // flavor
+            UInt32(68), // length
+            UInt16(1), // number of tables
+            UInt16(0), // reserved
+            UInt32(32), // total sfnt size
+            UInt16(1), // major version
+            UInt16(0), // minor version
+            UInt32(0), // metadata offset
+            UInt32(0), // metadata length
+            UInt32(0), // metadata original length
+            UInt32(0), // private data offset
+            UInt32(0), // private data length
+
+            // Table directory
+            UInt32(Tag::from_bytes(b"test").0), // tag
+            UInt32(64), // offset
+            UInt32(4), // compressed length
+            UInt32(2), // original length
+            UInt32(0), // original checksum
+
+            Raw(b"abcd"),
+        ]);
+
+        assert!(Woff::parse(&data).is_none());
+    }
+
+    #[test]
+    fn uncompressed_font() {
+        let font_data = std::fs::read("fonts/SourceSansPro-Regular-Tiny.ttf").unwrap();
+        let font = crate::Font::from_data(&font_data, 0).unwrap();
+
+        // Wrap tables into WOFF as is.
+        let mut s = Stream::new(&font_data);
+        let flavor: u32 = s.read().unwrap();
+        let number_of_tables: u16 = s.read().unwrap();
+        s.advance(6);
+        let records: LazyArray16<crate::TableRecord> = s.read_array16(number_of_tables).unwrap();
+
+        let mut w = writer::Writer::new();
+        w.write(UInt32(SIGNATURE));
+        w.write(UInt32(flavor));
+        w.write(UInt32(0)); // length, will be set later
+        w.write(UInt16(number_of_tables));
+        w.write(UInt16(0)); // reserved
+        w.write(UInt32(font_data.len() as u32)); // total sfnt size
+        w.write(UInt16(1)); // major version
+        w.write(UInt16(0)); // minor version
+        for _ in 0..5 {
+            w.write(UInt32(0)); // no metadata and private data
+        }
+
+        let mut offset = HEADER_SIZE + TableRecord::SIZE * usize::from(number_of_tables);
+        for record in records {
+            w.write(UInt32(record.table_tag.0));
+            w.write(UInt32(offset as u32));
+            w.write(UInt32(record.length));
+            w.write(UInt32(record.length));
+            w.write(UInt32(record.check_sum));
+            offset += padded(record.length as usize).unwrap();
+        }
+
+        for record in records {
+            let start = record.offset as usize;
+            w.data.extend_from_slice(&font_data[start..start + record.length as usize]);
+            while w.data.len() % 4 != 0 {
+                w.write(UInt8(0));
+            }
+        }
+
+        let length = w.data.len() as u32;
+        w.data[8..12].copy_from_slice(&length.to_be_bytes());
+
+        let woff = Woff::parse(&w.data).unwrap();
+        let sfnt = woff.to_sfnt().unwrap();
+        let decoded_font = crate::Font::from_data(&sfnt, 0).unwrap();
+        assert_eq!(decoded_font.number_of_glyphs(), font.number_of_glyphs());
+        assert_eq!(decoded_font.family_name(), font.family_name());
+        assert_eq!(decoded_font.glyph_index('A'), font.glyph_index('A'));
+    }
+}
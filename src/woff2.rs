@@ -0,0 +1,1572 @@
+/*!
+A [WOFF 2.0](https://www.w3.org/TR/WOFF2/) fonts decoding.
+
+Unlike WOFF 1.0, all tables are compressed as a single Brotli stream
+and `glyf`, `loca` and `hmtx` tables can be stored in a transformed form.
+So the original font has to be reconstructed first,
+which cannot be done without allocations. Therefore the decoded font
+is always written into a `Vec`.
+
+Font collections are decoded into a regular TrueType Collection.
+
+Available only with the `woff2` feature enabled.
+
+```no_run
+let data = std::fs::read("font.woff2").unwrap();
+let woff = ttf_parser::woff2::Woff2::parse(&data).unwrap();
+let sfnt = woff.to_sfnt().unwrap();
+let font = ttf_parser::Font::from_data(&sfnt, 0).unwrap();
+```
+*/
+
+use core::convert::TryFrom;
+
+use std::string::String;
+use std::vec::Vec;
+
+use crate::Tag;
+use crate::parser::{Stream, LazyArray16, NumFrom, sfnt_search_params};
+
+// https://www.w3.org/TR/WOFF2/#woff20Header
+const SIGNATURE: u32 = 0x774F4632; // wOF2
+const COLLECTION_FLAVOR: u32 = 0x74746366; // ttcf
+
+const SFNT_HEADER_SIZE: usize = 12;
+const SFNT_TABLE_RECORD_SIZE: usize = 16;
+
+const GLYF: Tag = Tag::from_bytes(b"glyf");
+const LOCA: Tag = Tag::from_bytes(b"loca");
+const HMTX: Tag = Tag::from_bytes(b"hmtx");
+const HHEA: Tag = Tag::from_bytes(b"hhea");
+const MAXP: Tag = Tag::from_bytes(b"maxp");
+
+// https://www.w3.org/TR/WOFF2/#table_dir_format
+const ARBITRARY_TAG_INDEX: u8 = 63;
+const KNOWN_TAGS: [Tag; 63] = [
+    Tag::from_bytes(b"cmap"), Tag::from_bytes(b"head"), Tag::from_bytes(b"hhea"),
+    Tag::from_bytes(b"hmtx"), Tag::from_bytes(b"maxp"), Tag::from_bytes(b"name"),
+    Tag::from_bytes(b"OS/2"), Tag::from_bytes(b"post"), Tag::from_bytes(b"cvt "),
+    Tag::from_bytes(b"fpgm"), Tag::from_bytes(b"glyf"), Tag::from_bytes(b"loca"),
+    Tag::from_bytes(b"prep"), Tag::from_bytes(b"CFF "), Tag::from_bytes(b"VORG"),
+    Tag::from_bytes(b"EBDT"), Tag::from_bytes(b"EBLC"), Tag::from_bytes(b"gasp"),
+    Tag::from_bytes(b"hdmx"), Tag::from_bytes(b"kern"), Tag::from_bytes(b"LTSH"),
+    Tag::from_bytes(b"PCLT"), Tag::from_bytes(b"VDMX"), Tag::from_bytes(b"vhea"),
+    Tag::from_bytes(b"vmtx"), Tag::from_bytes(b"BASE"), Tag::from_bytes(b"GDEF"),
+    Tag::from_bytes(b"GPOS"), Tag::from_bytes(b"GSUB"), Tag::from_bytes(b"EBSC"),
+    Tag::from_bytes(b"JSTF"), Tag::from_bytes(b"MATH"), Tag::from_bytes(b"CBDT"),
+    Tag::from_bytes(b"CBLC"), Tag::from_bytes(b"COLR"), Tag::from_bytes(b"CPAL"),
+    Tag::from_bytes(b"SVG "), Tag::from_bytes(b"sbix"), Tag::from_bytes(b"acnt"),
+    Tag::from_bytes(b"avar"), Tag::from_bytes(b"bdat"), Tag::from_bytes(b"bloc"),
+    Tag::from_bytes(b"bsln"), Tag::from_bytes(b"cvar"), Tag::from_bytes(b"fdsc"),
+    Tag::from_bytes(b"feat"), Tag::from_bytes(b"fmtx"), Tag::from_bytes(b"fvar"),
+    Tag::from_bytes(b"gvar"), Tag::from_bytes(b"hsty"), Tag::from_bytes(b"just"),
+    Tag::from_bytes(b"lcar"), Tag::from_bytes(b"mort"), Tag::from_bytes(b"morx"),
+    Tag::from_bytes(b"opbd"), Tag::from_bytes(b"prop"), Tag::from_bytes(b"trak"),
+    Tag::from_bytes(b"Zapf"), Tag::from_bytes(b"Silf"), Tag::from_bytes(b"Glat"),
+    Tag::from_bytes(b"Gloc"), Tag::from_bytes(b"Feat"), Tag::from_bytes(b"Sill"),
+];
+
+
+/// A WOFF2 table directory entry.
+#[derive(Clone, Copy, Debug)]
+pub struct TableRecord {
+    /// Table's tag.
+    pub tag: Tag,
+    /// Table's length in the original font.
+    pub original_length: u32,
+    /// Table's length in the compressed stream when the table is transformed.
+    ///
+    /// Always zero for a transformed `loca` table, since it will be rebuilt from `glyf`.
+    pub transform_length: Option<u32>,
+}
+
+impl TableRecord {
+    /// Checks that table is stored in a transformed form.
+    #[inline]
+    pub fn is_transformed(&self) -> bool {
+        self.transform_length.is_some()
+    }
+
+    #[inline]
+    fn stored_length(&self) -> u32 {
+        self.transform_length.unwrap_or(self.original_length)
+    }
+}
+
+
+#[derive(Clone, Debug)]
+struct CollectionFont {
+    flavor: u32,
+    // Indices in the table directory.
+    tables: Vec<u16>,
+}
+
+
+/// A WOFF2 font.
+#[derive(Clone, Debug)]
+pub struct Woff2<'a> {
+    flavor: u32,
+    tables: Vec<TableRecord>,
+    collection_version: u32,
+    // A non-collection font is stored as a collection with a single font
+    // that references all tables.
+    fonts: Vec<CollectionFont>,
+    compressed_data: &'a [u8],
+    metadata: Option<&'a [u8]>,
+    metadata_length: u32,
+    private_data: Option<&'a [u8]>,
+}
+
+impl<'a> Woff2<'a> {
+    /// Parses a WOFF2 header, a table directory and a collection directory.
+    ///
+    /// Tables data is not decompressed here.
+    pub fn parse(data: &'a [u8]) -> Option<Self> {
+        let mut s = Stream::new(data);
+        let signature: u32 = s.read()?;
+        let flavor: u32 = s.read()?;
+        let length: u32 = s.read()?;
+        let number_of_tables: u16 = s.read()?;
+        let reserved: u16 = s.read()?;
+        s.skip::<u32>(); // totalSfntSize
+        let compressed_length: u32 = s.read()?;
+        s.skip::<u16>(); // majorVersion
+        s.skip::<u16>(); // minorVersion
+        let metadata_offset: u32 = s.read()?;
+        let metadata_compressed_length: u32 = s.read()?;
+        let metadata_length: u32 = s.read()?;
+        let private_data_offset: u32 = s.read()?;
+        let private_data_length: u32 = s.read()?;
+
+        if signature != SIGNATURE || reserved != 0 || number_of_tables == 0 {
+            return None;
+        }
+
+        // Ignore trailing data, if any.
+        let data = data.get(..usize::num_from(length))?;
+
+        let mut tables = Vec::with_capacity(usize::from(number_of_tables));
+        for _ in 0..number_of_tables {
+            tables.push(read_table_record(&mut s)?);
+        }
+
+        let mut collection_version = 0;
+        let mut fonts = Vec::new();
+        if flavor == COLLECTION_FLAVOR {
+            // https://www.w3.org/TR/WOFF2/#collection_dir_format
+            collection_version = s.read()?;
+            let number_of_fonts = read_u16_255(&mut s)?;
+            for _ in 0..number_of_fonts {
+                let number_of_tables = read_u16_255(&mut s)?;
+                let flavor: u32 = s.read()?;
+                let mut indices = Vec::with_capacity(usize::from(number_of_tables));
+                for _ in 0..number_of_tables {
+                    let index = read_u16_255(&mut s)?;
+                    if usize::from(index) >= tables.len() {
+                        return None;
+                    }
+
+                    indices.push(index);
+                }
+
+                fonts.push(CollectionFont { flavor, tables: indices });
+            }
+        } else {
+            fonts.push(CollectionFont { flavor, tables: (0..number_of_tables).collect() });
+        }
+
+        let compressed_data = s.read_bytes(usize::num_from(compressed_length))?;
+
+        let metadata = if metadata_offset != 0 {
+            Some(block(data, metadata_offset, metadata_compressed_length)?)
+        } else {
+            None
+        };
+
+        let private_data = if private_data_offset != 0 {
+            Some(block(data, private_data_offset, private_data_length)?)
+        } else {
+            None
+        };
+
+        Some(Woff2 {
+            flavor,
+            tables,
+            collection_version,
+            fonts,
+            compressed_data,
+            metadata,
+            metadata_length,
+            private_data,
+        })
+    }
+
+    /// Returns the "sfnt version" of the original font.
+    ///
+    /// `0x00010000` for TrueType, `OTTO` for CFF-based fonts
+    /// and `ttcf` for font collections.
+    #[inline]
+    pub fn flavor(&self) -> Tag {
+        Tag(self.flavor)
+    }
+
+    /// Returns a table directory.
+    ///
+    /// Collection fonts share a single table directory.
+    #[inline]
+    pub fn tables(&self) -> &[TableRecord] {
+        &self.tables
+    }
+
+    /// Returns the number of fonts stored in a WOFF2 collection.
+    ///
+    /// Returns `None` if this is not a collection.
+    #[inline]
+    pub fn fonts_in_collection(&self) -> Option<u32> {
+        if self.flavor == COLLECTION_FLAVOR {
+            u32::try_from(self.fonts.len()).ok()
+        } else {
+            None
+        }
+    }
+
+    /// Decodes WOFF2 into a regular OpenType font or a TrueType Collection.
+    ///
+    /// Table checksums are recalculated, but `head.checkSumAdjustment` is left as is.
+    ///
+    /// Returns `None` when decompression or tables reconstruction failed.
+    pub fn to_sfnt(&self) -> Option<Vec<u8>> {
+        let mut size = 0usize;
+        for table in &self.tables {
+            size = size.checked_add(usize::num_from(table.stored_length()))?;
+        }
+
+        let data = decompress(self.compressed_data, size)?;
+
+        let mut tables_data = Vec::with_capacity(self.tables.len());
+        let mut offset = 0;
+        for table in &self.tables {
+            let end = offset + usize::num_from(table.stored_length());
+            tables_data.push(&data[offset..end]);
+            offset = end;
+        }
+
+        let mut reconstructed = vec![None; self.tables.len()];
+        for font in &self.fonts {
+            self.reconstruct_tables(font, &tables_data, &mut reconstructed)?;
+        }
+
+        let tables_data: Vec<&[u8]> = tables_data.iter().zip(&reconstructed)
+            .map(|(data, table)| table.as_ref().map(Vec::as_slice).unwrap_or(*data))
+            .collect();
+
+        self.write_sfnt(&tables_data)
+    }
+
+    fn reconstruct_tables(
+        &self,
+        font: &CollectionFont,
+        tables_data: &[&[u8]],
+        reconstructed: &mut [Option<Vec<u8>>],
+    ) -> Option<()> {
+        let find = |tag: Tag| {
+            font.tables.iter().map(|i| usize::from(*i)).find(|i| self.tables[*i].tag == tag)
+        };
+
+        let glyf_index = find(GLYF);
+        let loca_index = find(LOCA);
+        let is_transformed = |index: Option<usize>| {
+            index.map(|i| self.tables[i].is_transformed()).unwrap_or(false)
+        };
+
+        // `glyf` and `loca` can be transformed only together.
+        if is_transformed(glyf_index) != is_transformed(loca_index) {
+            return None;
+        }
+
+        let mut x_mins = None;
+        if let (true, Some(glyf_index), Some(loca_index)) =
+            (is_transformed(glyf_index), glyf_index, loca_index)
+        {
+            let glyf = reconstruct_glyf(tables_data[glyf_index])?;
+            if glyf.loca.len() != usize::num_from(self.tables[loca_index].original_length) {
+                return None;
+            }
+
+            reconstructed[glyf_index] = Some(glyf.glyf);
+            reconstructed[loca_index] = Some(glyf.loca);
+            x_mins = Some(glyf.x_mins);
+        }
+
+        let hmtx_index = find(HMTX);
+        if let (true, Some(hmtx_index)) = (is_transformed(hmtx_index), hmtx_index) {
+            // Left side bearings can be omitted only when `glyf` is transformed.
+            let x_mins = x_mins?;
+            let number_of_glyphs: u16 = Stream::read_at(tables_data[find(MAXP)?], 4)?;
+            let number_of_h_metrics: u16 = Stream::read_at(tables_data[find(HHEA)?], 34)?;
+            let hmtx = reconstruct_hmtx(
+                tables_data[hmtx_index], number_of_glyphs, number_of_h_metrics, &x_mins,
+            )?;
+            reconstructed[hmtx_index] = Some(hmtx);
+        }
+
+        Some(())
+    }
+
+    fn write_sfnt(&self, tables_data: &[&[u8]]) -> Option<Vec<u8>> {
+        let is_collection = self.flavor == COLLECTION_FLAVOR;
+
+        // https://docs.microsoft.com/en-us/typography/opentype/spec/otff#ttc-header
+        let mut header_size = 0;
+        if is_collection {
+            header_size = 12 + 4 * self.fonts.len();
+            if self.collection_version == 0x00020000 {
+                header_size += 12;
+            }
+        }
+
+        let mut font_offsets = Vec::with_capacity(self.fonts.len());
+        let mut offset = header_size;
+        for font in &self.fonts {
+            font_offsets.push(offset);
+            offset += SFNT_HEADER_SIZE + SFNT_TABLE_RECORD_SIZE * font.tables.len();
+        }
+
+        let mut table_offsets = Vec::with_capacity(tables_data.len());
+        for data in tables_data {
+            table_offsets.push(u32::try_from(offset).ok()?);
+            offset = offset.checked_add(padded(data.len())?)?;
+        }
+        u32::try_from(offset).ok()?;
+
+        let mut buf = Vec::with_capacity(offset);
+
+        if is_collection {
+            write_u32(&mut buf, COLLECTION_FLAVOR);
+            write_u32(&mut buf, self.collection_version);
+            write_u32(&mut buf, u32::try_from(self.fonts.len()).ok()?);
+            for offset in &font_offsets {
+                write_u32(&mut buf, *offset as u32);
+            }
+
+            if self.collection_version == 0x00020000 {
+                // No digital signature.
+                for _ in 0..3 {
+                    write_u32(&mut buf, 0);
+                }
+            }
+        }
+
+        for font in &self.fonts {
+            let number_of_tables = font.tables.len() as u16;
+            let (search_range, entry_selector, range_shift) = sfnt_search_params(number_of_tables)?;
+            write_u32(&mut buf, font.flavor);
+            write_u16(&mut buf, number_of_tables);
+            write_u16(&mut buf, search_range);
+            write_u16(&mut buf, entry_selector);
+            write_u16(&mut buf, range_shift);
+
+            // Table records must be sorted by tag.
+            let mut indices: Vec<usize> = font.tables.iter().map(|i| usize::from(*i)).collect();
+            indices.sort_by_key(|i| self.tables[*i].tag);
+            for i in indices {
+                let data = tables_data[i];
+                write_u32(&mut buf, self.tables[i].tag.0);
+                write_u32(&mut buf, checksum(data));
+                write_u32(&mut buf, table_offsets[i]);
+                write_u32(&mut buf, data.len() as u32);
+            }
+        }
+
+        for data in tables_data {
+            buf.extend_from_slice(data);
+            buf.resize(padded(buf.len())?, 0);
+        }
+
+        Some(buf)
+    }
+
+    /// Decompresses the extended metadata block.
+    ///
+    /// Metadata is an XML document in UTF-8.
+    ///
+    /// Returns `None` when metadata is not set or when decompression failed.
+    pub fn metadata(&self) -> Option<String> {
+        let data = decompress(self.metadata?, usize::num_from(self.metadata_length))?;
+        String::from_utf8(data).ok()
+    }
+
+    /// Returns a private data block.
+    ///
+    /// Its format is defined by the font vendor.
+    #[inline]
+    pub fn private_data(&self) -> Option<&'a [u8]> {
+        self.private_data
+    }
+}
+
+fn read_table_record(s: &mut Stream) -> Option<TableRecord> {
+    let flags: u8 = s.read()?;
+    let tag_index = flags & 0x3F;
+    let transform_version = flags >> 6;
+
+    let tag = if tag_index == ARBITRARY_TAG_INDEX {
+        s.read()?
+    } else {
+        KNOWN_TAGS[usize::from(tag_index)]
+    };
+
+    let original_length = read_u32_base128(s)?;
+
+    // For `glyf` and `loca` the version 0 indicates a transform
+    // and the version 3 indicates the null transform.
+    // For other tables it's the other way around.
+    let is_transformed = if tag == GLYF || tag == LOCA {
+        match transform_version {
+            0 => true,
+            3 => false,
+            _ => return None,
+        }
+    } else {
+        transform_version != 0
+    };
+
+    let mut transform_length = None;
+    if is_transformed {
+        // Only `glyf`, `loca` and `hmtx` transforms are defined.
+        if !(tag == GLYF || tag == LOCA || (tag == HMTX && transform_version == 1)) {
+            return None;
+        }
+
+        let length = read_u32_base128(s)?;
+        if tag == LOCA && length != 0 {
+            return None;
+        }
+
+        transform_length = Some(length);
+    }
+
+    Some(TableRecord {
+        tag,
+        original_length,
+        transform_length,
+    })
+}
+
+// https://www.w3.org/TR/WOFF2/#DataTypes
+fn read_u32_base128(s: &mut Stream) -> Option<u32> {
+    let mut value = 0u32;
+    for i in 0..5 {
+        let byte: u8 = s.read()?;
+
+        // Leading zeros are not allowed.
+        if i == 0 && byte == 0x80 {
+            return None;
+        }
+
+        // Check for overflow.
+        if value & 0xFE000000 != 0 {
+            return None;
+        }
+
+        value = (value << 7) | u32::from(byte & 0x7F);
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+
+    // More than 5 bytes.
+    None
+}
+
+// https://www.w3.org/TR/WOFF2/#DataTypes
+fn read_u16_255(s: &mut Stream) -> Option<u16> {
+    const WORD_CODE: u8 = 253;
+    const ONE_MORE_BYTE_CODE_2: u8 = 254;
+    const ONE_MORE_BYTE_CODE_1: u8 = 255;
+    const LOWEST_U_CODE: u16 = 253;
+
+    let code: u8 = s.read()?;
+    match code {
+        WORD_CODE => s.read(),
+        ONE_MORE_BYTE_CODE_1 => Some(u16::from(s.read::<u8>()?) + LOWEST_U_CODE),
+        ONE_MORE_BYTE_CODE_2 => Some(u16::from(s.read::<u8>()?) + LOWEST_U_CODE * 2),
+        _ => Some(u16::from(code)),
+    }
+}
+
+
+struct Glyf {
+    glyf: Vec<u8>,
+    loca: Vec<u8>,
+    // Required by the `hmtx` transform.
+    x_mins: Vec<i16>,
+}
+
+#[derive(Clone, Copy)]
+struct Point {
+    x: i32,
+    y: i32,
+    on_curve: bool,
+}
+
+// https://docs.microsoft.com/en-us/typography/opentype/spec/glyf
+mod simple_flags {
+    pub const ON_CURVE_POINT: u8 = 0x01;
+    pub const X_SHORT_VECTOR: u8 = 0x02;
+    pub const Y_SHORT_VECTOR: u8 = 0x04;
+    pub const REPEAT_FLAG: u8 = 0x08;
+    pub const X_IS_SAME_OR_POSITIVE_X_SHORT_VECTOR: u8 = 0x10;
+    pub const Y_IS_SAME_OR_POSITIVE_Y_SHORT_VECTOR: u8 = 0x20;
+    pub const OVERLAP_SIMPLE: u8 = 0x40;
+}
+
+mod composite_flags {
+    pub const ARG_1_AND_2_ARE_WORDS: u16 = 0x0001;
+    pub const WE_HAVE_A_SCALE: u16 = 0x0008;
+    pub const MORE_COMPONENTS: u16 = 0x0020;
+    pub const WE_HAVE_AN_X_AND_Y_SCALE: u16 = 0x0040;
+    pub const WE_HAVE_A_TWO_BY_TWO: u16 = 0x0080;
+    pub const WE_HAVE_INSTRUCTIONS: u16 = 0x0100;
+}
+
+// https://www.w3.org/TR/WOFF2/#glyf_table_format
+fn reconstruct_glyf(data: &[u8]) -> Option<Glyf> {
+    let mut s = Stream::new(data);
+    s.skip::<u16>(); // reserved
+    let option_flags: u16 = s.read()?;
+    let number_of_glyphs: u16 = s.read()?;
+    let index_format: u16 = s.read()?;
+
+    let mut sizes = [0u32; 7];
+    for size in &mut sizes {
+        *size = s.read()?;
+    }
+
+    let mut substreams = [Stream::new(&[]); 7];
+    for (substream, size) in substreams.iter_mut().zip(&sizes) {
+        *substream = Stream::new(s.read_bytes(usize::num_from(*size))?);
+    }
+
+    let [
+        mut n_contour_s, mut n_points_s, mut flag_s, mut glyph_s,
+        mut composite_s, mut bbox_s, mut instruction_s,
+    ] = substreams;
+
+    let bitmap_size = ((usize::from(number_of_glyphs) + 31) >> 5) << 2;
+    let bbox_bitmap = bbox_s.read_bytes(bitmap_size)?;
+
+    let overlap_bitmap = if option_flags & 1 != 0 {
+        Some(s.read_bytes((usize::from(number_of_glyphs) + 7) >> 3)?)
+    } else {
+        None
+    };
+
+    let mut glyf = Vec::new();
+    let mut offsets = Vec::with_capacity(usize::from(number_of_glyphs) + 1);
+    let mut x_mins = Vec::with_capacity(usize::from(number_of_glyphs));
+    let mut points = Vec::new();
+    for glyph_id in 0..number_of_glyphs {
+        offsets.push(glyf.len());
+
+        let has_bbox = is_bit_set(bbox_bitmap, glyph_id)?;
+        let number_of_contours: i16 = n_contour_s.read()?;
+        let x_min = match number_of_contours {
+            0 => {
+                // An empty glyph cannot have a bbox.
+                if has_bbox {
+                    return None;
+                }
+
+                0
+            }
+            -1 => {
+                // A composite glyph must have an explicit bbox.
+                if !has_bbox {
+                    return None;
+                }
+
+                let bbox = bbox_s.read_bytes(8)?;
+                let composite_data = composite_s.tail()?;
+                let start = composite_s.offset();
+                let have_instructions = skip_components(&mut composite_s)?;
+
+                glyf.extend_from_slice(&number_of_contours.to_be_bytes());
+                glyf.extend_from_slice(bbox);
+                glyf.extend_from_slice(composite_data.get(..composite_s.offset() - start)?);
+                if have_instructions {
+                    let length = read_u16_255(&mut glyph_s)?;
+                    glyf.extend_from_slice(&length.to_be_bytes());
+                    glyf.extend_from_slice(instruction_s.read_bytes(usize::from(length))?);
+                }
+
+                Stream::read_at(bbox, 0)?
+            }
+            n if n > 0 => {
+                glyf.extend_from_slice(&number_of_contours.to_be_bytes());
+
+                // Reserve space for bbox, since it can be calculated only after points parsing.
+                let bbox_offset = glyf.len();
+                glyf.extend_from_slice(&[0; 8]);
+
+                let mut number_of_points = 0u16;
+                for _ in 0..n {
+                    number_of_points = number_of_points.checked_add(read_u16_255(&mut n_points_s)?)?;
+                    let end_point = number_of_points.checked_sub(1)?;
+                    glyf.extend_from_slice(&end_point.to_be_bytes());
+                }
+
+                let flags = flag_s.read_bytes(usize::from(number_of_points))?;
+                decode_triplets(flags, &mut glyph_s, &mut points)?;
+
+                let length = read_u16_255(&mut glyph_s)?;
+                glyf.extend_from_slice(&length.to_be_bytes());
+                glyf.extend_from_slice(instruction_s.read_bytes(usize::from(length))?);
+
+                let has_overlap = match overlap_bitmap {
+                    Some(bitmap) => is_bit_set(bitmap, glyph_id)?,
+                    None => false,
+                };
+                store_points(&points, has_overlap, &mut glyf)?;
+
+                let bbox = if has_bbox {
+                    let mut s = Stream::new(bbox_s.read_bytes(8)?);
+                    [s.read()?, s.read()?, s.read()?, s.read()?]
+                } else {
+                    calculate_bbox(&points)?
+                };
+
+                for (i, n) in bbox.iter().enumerate() {
+                    glyf[bbox_offset + i * 2..bbox_offset + i * 2 + 2].copy_from_slice(&n.to_be_bytes());
+                }
+
+                bbox[0]
+            }
+            _ => return None,
+        };
+
+        x_mins.push(x_min);
+
+        // Glyphs are 4-byte aligned, like the reference implementation does.
+        glyf.resize(padded(glyf.len())?, 0);
+    }
+    offsets.push(glyf.len());
+
+    let mut loca = Vec::new();
+    match index_format {
+        0 => {
+            for offset in offsets {
+                loca.extend_from_slice(&u16::try_from(offset / 2).ok()?.to_be_bytes());
+            }
+        }
+        1 => {
+            for offset in offsets {
+                loca.extend_from_slice(&u32::try_from(offset).ok()?.to_be_bytes());
+            }
+        }
+        _ => return None,
+    }
+
+    Some(Glyf { glyf, loca, x_mins })
+}
+
+// Returns `true` when the glyph has instructions.
+fn skip_components(s: &mut Stream) -> Option<bool> {
+    use composite_flags::*;
+
+    let mut have_instructions = false;
+    loop {
+        let flags: u16 = s.read()?;
+        s.skip::<u16>(); // glyph index
+
+        let mut length = if flags & ARG_1_AND_2_ARE_WORDS != 0 { 4 } else { 2 };
+        if flags & WE_HAVE_A_SCALE != 0 {
+            length += 2;
+        } else if flags & WE_HAVE_AN_X_AND_Y_SCALE != 0 {
+            length += 4;
+        } else if flags & WE_HAVE_A_TWO_BY_TWO != 0 {
+            length += 8;
+        }
+        s.advance_checked(length)?;
+
+        if flags & WE_HAVE_INSTRUCTIONS != 0 {
+            have_instructions = true;
+        }
+
+        if flags & MORE_COMPONENTS == 0 {
+            break;
+        }
+    }
+
+    Some(have_instructions)
+}
+
+// https://www.w3.org/TR/WOFF2/#triplet_decoding
+fn decode_triplets(flags: &[u8], s: &mut Stream, points: &mut Vec<Point>) -> Option<()> {
+    fn with_sign(flag: u8, value: i32) -> i32 {
+        if flag & 1 != 0 { value } else { -value }
+    }
+
+    points.clear();
+
+    let mut x = 0i32;
+    let mut y = 0i32;
+    for flag in flags {
+        let on_curve = flag >> 7 == 0;
+        let flag = flag & 0x7F;
+
+        let (dx, dy) = if flag < 10 {
+            let b0 = i32::from(s.read::<u8>()?);
+            (0, with_sign(flag, (i32::from(flag & 14) << 7) + b0))
+        } else if flag < 20 {
+            let b0 = i32::from(s.read::<u8>()?);
+            (with_sign(flag, (i32::from((flag - 10) & 14) << 7) + b0), 0)
+        } else if flag < 84 {
+            let b0 = i32::from(flag - 20);
+            let b1 = i32::from(s.read::<u8>()?);
+            (with_sign(flag, 1 + (b0 & 0x30) + (b1 >> 4)),
+             with_sign(flag >> 1, 1 + ((b0 & 0x0C) << 2) + (b1 & 0x0F)))
+        } else if flag < 120 {
+            let b0 = i32::from(flag - 84);
+            let b1 = i32::from(s.read::<u8>()?);
+            let b2 = i32::from(s.read::<u8>()?);
+            (with_sign(flag, 1 + ((b0 / 12) << 8) + b1),
+             with_sign(flag >> 1, 1 + (((b0 % 12) >> 2) << 8) + b2))
+        } else if flag < 124 {
+            let b1 = i32::from(s.read::<u8>()?);
+            let b2 = i32::from(s.read::<u8>()?);
+            let b3 = i32::from(s.read::<u8>()?);
+            (with_sign(flag, (b1 << 4) + (b2 >> 4)),
+             with_sign(flag >> 1, ((b2 & 0x0F) << 8) + b3))
+        } else {
+            let dx = i32::from(s.read::<u16>()?);
+            let dy = i32::from(s.read::<u16>()?);
+            (with_sign(flag, dx), with_sign(flag >> 1, dy))
+        };
+
+        x = x.checked_add(dx)?;
+        y = y.checked_add(dy)?;
+        points.push(Point { x, y, on_curve });
+    }
+
+    Some(())
+}
+
+// Writes points using the most compact encoding.
+fn store_points(points: &[Point], has_overlap: bool, glyf: &mut Vec<u8>) -> Option<()> {
+    use simple_flags::*;
+
+    let mut flags = Vec::with_capacity(points.len());
+    let mut x_coords = Vec::new();
+    let mut y_coords = Vec::new();
+    let mut last_flag = None;
+    let mut repeat_count = 0u8;
+    let mut prev_x = 0;
+    let mut prev_y = 0;
+    for (i, point) in points.iter().enumerate() {
+        let mut flag = if point.on_curve { ON_CURVE_POINT } else { 0 };
+        if has_overlap && i == 0 {
+            flag |= OVERLAP_SIMPLE;
+        }
+
+        let dx = point.x - prev_x;
+        if dx == 0 {
+            flag |= X_IS_SAME_OR_POSITIVE_X_SHORT_VECTOR;
+        } else if dx > -256 && dx < 256 {
+            flag |= X_SHORT_VECTOR;
+            if dx > 0 {
+                flag |= X_IS_SAME_OR_POSITIVE_X_SHORT_VECTOR;
+            }
+
+            x_coords.push(u8::try_from(dx.abs()).ok()?);
+        } else {
+            x_coords.extend_from_slice(&i16::try_from(dx).ok()?.to_be_bytes());
+        }
+
+        let dy = point.y - prev_y;
+        if dy == 0 {
+            flag |= Y_IS_SAME_OR_POSITIVE_Y_SHORT_VECTOR;
+        } else if dy > -256 && dy < 256 {
+            flag |= Y_SHORT_VECTOR;
+            if dy > 0 {
+                flag |= Y_IS_SAME_OR_POSITIVE_Y_SHORT_VECTOR;
+            }
+
+            y_coords.push(u8::try_from(dy.abs()).ok()?);
+        } else {
+            y_coords.extend_from_slice(&i16::try_from(dy).ok()?.to_be_bytes());
+        }
+
+        if last_flag == Some(flag) && repeat_count != core::u8::MAX {
+            // The first repeat sets the flag and adds a counter, the next ones increment it.
+            if repeat_count == 0 {
+                *flags.last_mut()? |= REPEAT_FLAG;
+                flags.push(0);
+            }
+
+            repeat_count += 1;
+            *flags.last_mut()? = repeat_count;
+        } else {
+            flags.push(flag);
+            repeat_count = 0;
+        }
+
+        prev_x = point.x;
+        prev_y = point.y;
+        last_flag = Some(flag);
+    }
+
+    glyf.extend_from_slice(&flags);
+    glyf.extend_from_slice(&x_coords);
+    glyf.extend_from_slice(&y_coords);
+    Some(())
+}
+
+fn calculate_bbox(points: &[Point]) -> Option<[i16; 4]> {
+    let first = points.first()?;
+    let mut bbox = [first.x, first.y, first.x, first.y];
+    for p in points {
+        bbox[0] = bbox[0].min(p.x);
+        bbox[1] = bbox[1].min(p.y);
+        bbox[2] = bbox[2].max(p.x);
+        bbox[3] = bbox[3].max(p.y);
+    }
+
+    Some([
+        i16::try_from(bbox[0]).ok()?,
+        i16::try_from(bbox[1]).ok()?,
+        i16::try_from(bbox[2]).ok()?,
+        i16::try_from(bbox[3]).ok()?,
+    ])
+}
+
+// https://www.w3.org/TR/WOFF2/#hmtx_table_format
+fn reconstruct_hmtx(
+    data: &[u8],
+    number_of_glyphs: u16,
+    number_of_h_metrics: u16,
+    x_mins: &[i16],
+) -> Option<Vec<u8>> {
+    let mut s = Stream::new(data);
+    let flags: u8 = s.read()?;
+    let has_proportional_lsbs = flags & 1 == 0;
+    let has_monospace_lsbs = flags & 2 == 0;
+
+    // The transform makes sense only when at least one array is omitted.
+    if flags & 0xFC != 0 || (has_proportional_lsbs && has_monospace_lsbs) {
+        return None;
+    }
+
+    if number_of_h_metrics == 0
+        || number_of_h_metrics > number_of_glyphs
+        || x_mins.len() != usize::from(number_of_glyphs)
+    {
+        return None;
+    }
+
+    let advances: LazyArray16<u16> = s.read_array16(number_of_h_metrics)?;
+
+    let proportional_lsbs: Option<LazyArray16<i16>> = if has_proportional_lsbs {
+        Some(s.read_array16(number_of_h_metrics)?)
+    } else {
+        None
+    };
+
+    let number_of_monospace_glyphs = number_of_glyphs - number_of_h_metrics;
+    let monospace_lsbs: Option<LazyArray16<i16>> = if has_monospace_lsbs {
+        Some(s.read_array16(number_of_monospace_glyphs)?)
+    } else {
+        None
+    };
+
+    let mut hmtx = Vec::with_capacity(usize::from(number_of_h_metrics) * 2 + usize::from(number_of_glyphs) * 2);
+    for i in 0..number_of_h_metrics {
+        let lsb = match proportional_lsbs {
+            Some(lsbs) => lsbs.get(i)?,
+            None => x_mins[usize::from(i)],
+        };
+
+        hmtx.extend_from_slice(&advances.get(i)?.to_be_bytes());
+        hmtx.extend_from_slice(&lsb.to_be_bytes());
+    }
+
+    for i in 0..number_of_monospace_glyphs {
+        let lsb = match monospace_lsbs {
+            Some(lsbs) => lsbs.get(i)?,
+            None => x_mins[usize::from(number_of_h_metrics + i)],
+        };
+
+        hmtx.extend_from_slice(&lsb.to_be_bytes());
+    }
+
+    Some(hmtx)
+}
+
+fn decompress(data: &[u8], size: usize) -> Option<Vec<u8>> {
+    use std::io::Read;
+
+    // Do not trust the declared size and read at most one byte more
+    // to detect an unexpected data.
+    let mut buf = Vec::new();
+    let decoder = brotli_decompressor::Decompressor::new(data, 4096);
+    decoder.take(size as u64 + 1).read_to_end(&mut buf).ok()?;
+
+    if buf.len() == size {
+        Some(buf)
+    } else {
+        None
+    }
+}
+
+#[inline]
+fn is_bit_set(bitmap: &[u8], index: u16) -> Option<bool> {
+    let byte = bitmap.get(usize::from(index >> 3))?;
+    Some(byte & (0x80 >> (index & 7)) != 0)
+}
+
+#[inline]
+fn block(data: &[u8], offset: u32, length: u32) -> Option<&[u8]> {
+    let start = usize::num_from(offset);
+    let end = start.checked_add(usize::num_from(length))?;
+    data.get(start..end)
+}
+
+#[inline]
+fn padded(n: usize) -> Option<usize> {
+    Some(n.checked_add(3)? & !3)
+}
+
+// https://docs.microsoft.com/en-us/typography/opentype/spec/otff#calculating-checksums
+fn checksum(data: &[u8]) -> u32 {
+    let mut sum = 0u32;
+    for chunk in data.chunks(4) {
+        let mut word = [0; 4];
+        word[..chunk.len()].copy_from_slice(chunk);
+        sum = sum.wrapping_add(u32::from_be_bytes(word));
+    }
+
+    sum
+}
+
+#[inline]
+fn write_u16(buf: &mut Vec<u8>, n: u16) {
+    buf.extend_from_slice(&n.to_be_bytes());
+}
+
+#[inline]
+fn write_u32(buf: &mut Vec<u8>, n: u32) {
+    buf.extend_from_slice(&n.to_be_bytes());
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fmt::Write;
+
+    #[test]
+    fn u32_base128() {
+        let parse = |data: &[u8]| read_u32_base128(&mut Stream::new(data));
+        assert_eq!(parse(&[0x3F]), Some(63));
+        assert_eq!(parse(&[0x81, 0x00]), Some(128));
+        assert_eq!(parse(&[0x8F, 0xFF, 0xFF, 0xFF, 0x7F]), Some(u32::MAX));
+        assert_eq!(parse(&[0x80, 0x01]), None); // leading zeros
+        assert_eq!(parse(&[0x90, 0x80, 0x80, 0x80, 0x00]), None); // overflow
+        assert_eq!(parse(&[0x81, 0x81, 0x81, 0x81, 0x81, 0x01]), None); // too long
+        assert_eq!(parse(&[0x81]), None);
+    }
+
+    #[test]
+    fn u16_255() {
+        let parse = |data: &[u8]| read_u16_255(&mut Stream::new(data));
+        assert_eq!(parse(&[252]), Some(252));
+        assert_eq!(parse(&[255, 0]), Some(253));
+        assert_eq!(parse(&[254, 0]), Some(506));
+        assert_eq!(parse(&[253, 0x03, 0x20]), Some(800));
+        assert_eq!(parse(&[253, 0x03]), None);
+    }
+
+    #[test]
+    fn transformed_loca_with_data() {
+        let mut data = vec![10]; // glyf
+        data.extend_from_slice(&[0x01, 0x00]);
+        let mut s = Stream::new(&data);
+        let glyf = read_table_record(&mut s).unwrap();
+        assert_eq!(glyf.tag, GLYF);
+        assert_eq!(glyf.transform_length, Some(0));
+
+        // `loca` transform length must be zero.
+        assert!(read_table_record(&mut Stream::new(&[11, 0x01, 0x01])).is_none());
+        // Unknown transform.
+        assert!(read_table_record(&mut Stream::new(&[2 | 0x40, 0x01, 0x01])).is_none());
+    }
+
+    #[test]
+    fn source_sans_pro_ttf() {
+        if let Some((original, decoded)) = round_trip("benches/fonts/SourceSansPro-Regular.ttf", true) {
+            compare_fonts(&original, &decoded);
+        }
+    }
+
+    #[test]
+    fn source_sans_pro_otf() {
+        if let Some((original, decoded)) = round_trip("benches/fonts/SourceSansPro-Regular.otf", false) {
+            compare_fonts(&original, &decoded);
+        }
+    }
+
+    #[test]
+    fn source_sans_variable_ttf() {
+        let (original, decoded) = match round_trip("benches/fonts/SourceSansVariable-Roman.ttf", true) {
+            Some(v) => v,
+            None => return,
+        };
+        compare_fonts(&original, &decoded);
+
+        let mut original = crate::Font::from_data(&original, 0).unwrap();
+        let mut decoded = crate::Font::from_data(&decoded, 0).unwrap();
+        let wght = Tag::from_bytes(b"wght");
+        original.set_variation(wght, 700.0).unwrap();
+        decoded.set_variation(wght, 700.0).unwrap();
+        for id in 0..original.number_of_glyphs() {
+            assert_eq!(outline(&original, id), outline(&decoded, id));
+        }
+    }
+
+    #[test]
+    fn source_sans_variable_otf() {
+        if let Some((original, decoded)) = round_trip("benches/fonts/SourceSansVariable-Roman.otf", false) {
+            compare_fonts(&original, &decoded);
+        }
+    }
+
+    #[test]
+    fn reference_encoder() {
+        // Unlike other tests, this font wasn't produced by our own encoder,
+        // so the decoder cannot rely on the same reading of the spec.
+        let data = std::fs::read("fonts/SourceCodePro-It.ttf.woff2").unwrap();
+        let woff = Woff2::parse(&data).unwrap();
+        assert!(woff.tables().iter().any(TableRecord::is_transformed));
+        let decoded = woff.to_sfnt().unwrap();
+
+        // Table checksums match the tables reconstructed by FreeType,
+        // except `head`, where FreeType zeroes `checkSumAdjustment`,
+        // and `loca`, where FreeType's last offset points past the `glyf` end.
+        let tables: Vec<_> = sfnt_tables(&decoded)[0].1.iter()
+            .map(|(tag, data)| (*tag, data.len(), checksum(data)))
+            .collect();
+        let expected = [
+            (b"BASE", 70, 0x651E5DBD),
+            (b"GDEF", 518, 0x84D486D5),
+            (b"GPOS", 4862, 0x624FEC6A),
+            (b"GSUB", 5822, 0xB5126C09),
+            (b"OS/2", 96, 0x9354D7F5),
+            (b"cmap", 11406, 0x17123ACE),
+            (b"glyf", 75376, 0x55BD92A8),
+            (b"head", 54, 0x391A9412),
+            (b"hhea", 36, 0x06B2041E),
+            (b"hmtx", 2544, 0xCA10C23A),
+            (b"loca", 2544, 0x96C2DC98),
+            (b"maxp", 32, 0x052B0262),
+            (b"name", 3222, 0xEE55B063),
+            (b"post", 11542, 0xBCEE07FA),
+        ];
+        let expected: Vec<_> = expected.iter()
+            .map(|&(tag, len, sum)| (Tag::from_bytes(tag), len, sum))
+            .collect();
+        assert_eq!(tables, expected);
+
+        let font = crate::Font::from_data(&decoded, 0).unwrap();
+        assert_eq!(font.number_of_glyphs(), 1271);
+        assert_eq!(font.family_name(), Some("Source Code Pro".into()));
+        let id = font.glyph_index('A').unwrap();
+        let bbox = crate::Rect { x_min: -23, y_min: 0, x_max: 503, y_max: 656 };
+        assert_eq!(outline(&font, id.0).0, Some(bbox));
+    }
+
+    #[test]
+    fn collection() {
+        let (original, decoded) = round_trip("examples/wasm/TTC.ttc", true).unwrap();
+        assert_eq!(crate::fonts_in_collection(&decoded), Some(2));
+
+        // Shared tables must be stored only once.
+        let fonts = sfnt_tables(&decoded);
+        for (table1, table2) in fonts[0].1.iter().zip(&fonts[1].1) {
+            assert_eq!(table1.1.as_ptr(), table2.1.as_ptr());
+        }
+
+        for index in 0..2 {
+            let original = crate::Font::from_data(&original, index).unwrap();
+            let decoded = crate::Font::from_data(&decoded, index).unwrap();
+            assert_eq!(decoded.family_name(), original.family_name());
+            for id in 0..original.number_of_glyphs() {
+                assert_eq!(outline(&original, id), outline(&decoded, id));
+            }
+        }
+    }
+
+    #[test]
+    fn hmtx_with_monospace_lsbs() {
+        let data = &[
+            0x01, // flags: no proportional lsbs
+            0x01, 0xF4, // advance width[0]
+            0x02, 0x58, // advance width[1]
+            0xFF, 0xF6, // monospace lsb[0]
+        ];
+
+        let hmtx = reconstruct_hmtx(data, 3, 2, &[10, 20, 30]).unwrap();
+        assert_eq!(hmtx, &[0x01, 0xF4, 0, 10, 0x02, 0x58, 0, 20, 0xFF, 0xF6]);
+
+        // Both arrays are present.
+        assert!(reconstruct_hmtx(&[0x00], 3, 2, &[10, 20, 30]).is_none());
+        // Not enough xMin values.
+        assert!(reconstruct_hmtx(data, 3, 2, &[10, 20]).is_none());
+    }
+
+    #[test]
+    fn invalid_compressed_data() {
+        let mut data = encode(&std::fs::read("fonts/SourceSansPro-Regular-Tiny.ttf").unwrap(), true);
+        let woff = Woff2::parse(&data).unwrap();
+        assert!(woff.to_sfnt().is_some());
+
+        // Truncate the compressed stream.
+        let compressed_length: u32 = Stream::read_at(&data, 20).unwrap();
+        data[20..24].copy_from_slice(&(compressed_length / 2).to_be_bytes());
+        assert!(Woff2::parse(&data).unwrap().to_sfnt().is_none());
+    }
+
+
+    struct Builder(String);
+
+    impl crate::OutlineBuilder for Builder {
+        fn move_to(&mut self, x: f32, y: f32) {
+            write!(&mut self.0, "M {} {} ", x, y).unwrap();
+        }
+
+        fn line_to(&mut self, x: f32, y: f32) {
+            write!(&mut self.0, "L {} {} ", x, y).unwrap();
+        }
+
+        fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
+            write!(&mut self.0, "Q {} {} {} {} ", x1, y1, x, y).unwrap();
+        }
+
+        fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) {
+            write!(&mut self.0, "C {} {} {} {} {} {} ", x1, y1, x2, y2, x, y).unwrap();
+        }
+
+        fn close(&mut self) {
+            write!(&mut self.0, "Z ").unwrap();
+        }
+    }
+
+    fn outline(font: &crate::Font, id: u16) -> (Option<crate::Rect>, String) {
+        let mut builder = Builder(String::new());
+        let bbox = font.outline_glyph(crate::GlyphId(id), &mut builder);
+        (bbox, builder.0)
+    }
+
+    // Returns `None` when a font is missing, since `benches` fonts are not packaged.
+    fn round_trip(path: &str, transform: bool) -> Option<(Vec<u8>, Vec<u8>)> {
+        let original = std::fs::read(path).ok()?;
+        let data = encode(&original, transform);
+        let woff = Woff2::parse(&data).unwrap();
+        assert_eq!(woff.tables().iter().any(TableRecord::is_transformed), transform);
+        Some((original, woff.to_sfnt().unwrap()))
+    }
+
+    fn compare_fonts(original_data: &[u8], decoded_data: &[u8]) {
+        let original_tables = sfnt_tables(original_data).remove(0).1;
+        let decoded_tables = sfnt_tables(decoded_data).remove(0).1;
+        assert_eq!(decoded_tables.len(), original_tables.len());
+        for ((tag1, data1), (tag2, data2)) in original_tables.iter().zip(&decoded_tables) {
+            assert_eq!(tag1, tag2);
+
+            // `glyf` and `loca` are not guaranteed to be bit-exact.
+            if *tag1 != GLYF && *tag1 != LOCA {
+                assert!(data1 == data2, "{:?} is different", tag1);
+            }
+        }
+
+        let original = crate::Font::from_data(original_data, 0).unwrap();
+        let decoded = crate::Font::from_data(decoded_data, 0).unwrap();
+        assert_eq!(decoded.number_of_glyphs(), original.number_of_glyphs());
+        for id in 0..original.number_of_glyphs() {
+            assert_eq!(outline(&original, id), outline(&decoded, id));
+        }
+    }
+
+    fn sfnt_tables(data: &[u8]) -> Vec<(u32, Vec<(Tag, &[u8])>)> {
+        let offsets = match crate::fonts_in_collection(data) {
+            Some(n) => (0..n as usize).map(|i| Stream::read_at::<u32>(data, 12 + i * 4).unwrap() as usize).collect(),
+            None => vec![0],
+        };
+
+        let mut fonts = Vec::new();
+        for offset in offsets {
+            let mut s = Stream::new_at(data, offset).unwrap();
+            let flavor: u32 = s.read().unwrap();
+            let number_of_tables: u16 = s.read().unwrap();
+            s.advance(6);
+            let mut tables = Vec::new();
+            for _ in 0..number_of_tables {
+                let tag: Tag = s.read().unwrap();
+                s.skip::<u32>(); // checksum
+                let offset: u32 = s.read().unwrap();
+                let length: u32 = s.read().unwrap();
+                tables.push((tag, block(data, offset, length).unwrap()));
+            }
+
+            fonts.push((flavor, tables));
+        }
+
+        fonts
+    }
+
+    // A minimal WOFF2 encoder. Tables are stored in uncompressed Brotli meta-blocks.
+    fn encode(font_data: &[u8], transform: bool) -> Vec<u8> {
+        let fonts = sfnt_tables(font_data);
+
+        // Collection fonts can share tables.
+        let mut tables: Vec<(Tag, &[u8])> = Vec::new();
+        let mut font_indices = Vec::new();
+        for (_, font_tables) in &fonts {
+            let mut indices = Vec::new();
+            for table in font_tables {
+                let index = match tables.iter().position(|t| t.1.as_ptr() == table.1.as_ptr()) {
+                    Some(index) => index,
+                    None => {
+                        tables.push(*table);
+                        tables.len() - 1
+                    }
+                };
+
+                indices.push(index as u16);
+            }
+
+            font_indices.push(indices);
+        }
+
+        let mut stored: Vec<Vec<u8>> = tables.iter().map(|t| t.1.to_vec()).collect();
+        let mut transformed = vec![false; tables.len()];
+        if transform {
+            for (i, (_, font_tables)) in fonts.iter().enumerate() {
+                let find = |tag: &[u8; 4]| font_tables.iter()
+                    .position(|t| t.0 == Tag::from_bytes(tag))
+                    .map(|n| usize::from(font_indices[i][n]));
+
+                let (glyf, loca, head) = (find(b"glyf").unwrap(), find(b"loca").unwrap(), find(b"head").unwrap());
+                let (hmtx, hhea, maxp) = (find(b"hmtx").unwrap(), find(b"hhea").unwrap(), find(b"maxp").unwrap());
+                let index_format: u16 = Stream::read_at(tables[head].1, 50).unwrap();
+                let number_of_glyphs: u16 = Stream::read_at(tables[maxp].1, 4).unwrap();
+                let (glyf_data, x_mins) = transform_glyf(tables[glyf].1, tables[loca].1, index_format, number_of_glyphs);
+                stored[glyf] = glyf_data;
+                stored[loca] = Vec::new();
+                transformed[glyf] = true;
+                transformed[loca] = true;
+
+                let number_of_h_metrics: u16 = Stream::read_at(tables[hhea].1, 34).unwrap();
+                if let Some(data) = transform_hmtx(tables[hmtx].1, number_of_h_metrics, &x_mins) {
+                    stored[hmtx] = data;
+                    transformed[hmtx] = true;
+                }
+            }
+        }
+
+        let mut w = Vec::new();
+        write_u32(&mut w, SIGNATURE);
+        write_u32(&mut w, Stream::read_at(font_data, 0).unwrap());
+        write_u32(&mut w, 0); // length, will be set later
+        write_u16(&mut w, tables.len() as u16);
+        write_u16(&mut w, 0); // reserved
+        write_u32(&mut w, font_data.len() as u32); // total sfnt size
+        write_u32(&mut w, 0); // total compressed size, will be set later
+        write_u16(&mut w, 1); // major version
+        write_u16(&mut w, 0); // minor version
+        for _ in 0..5 {
+            write_u32(&mut w, 0); // no metadata and private data
+        }
+
+        for (i, (tag, data)) in tables.iter().enumerate() {
+            let is_glyf_or_loca = *tag == GLYF || *tag == LOCA;
+            let version = match (is_glyf_or_loca, transformed[i]) {
+                (true, true) | (false, false) => 0,
+                (true, false) => 3,
+                (false, true) => 1,
+            };
+
+            match KNOWN_TAGS.iter().position(|t| t == tag) {
+                Some(index) => w.push(index as u8 | version << 6),
+                None => {
+                    w.push(ARBITRARY_TAG_INDEX | version << 6);
+                    write_u32(&mut w, tag.0);
+                }
+            }
+
+            write_u32_base128(&mut w, data.len() as u32);
+            if transformed[i] {
+                write_u32_base128(&mut w, stored[i].len() as u32);
+            }
+        }
+
+        if fonts.len() > 1 {
+            write_u32(&mut w, Stream::read_at(font_data, 4).unwrap()); // version
+            write_u16_255(&mut w, fonts.len() as u16);
+            for ((flavor, _), indices) in fonts.iter().zip(&font_indices) {
+                write_u16_255(&mut w, indices.len() as u16);
+                write_u32(&mut w, *flavor);
+                for index in indices {
+                    write_u16_255(&mut w, *index);
+                }
+            }
+        }
+
+        let compressed = brotli_store(&stored.concat());
+        w.extend_from_slice(&compressed);
+        w.resize(padded(w.len()).unwrap(), 0);
+
+        let length = w.len() as u32;
+        w[8..12].copy_from_slice(&length.to_be_bytes());
+        w[20..24].copy_from_slice(&(compressed.len() as u32).to_be_bytes());
+        w
+    }
+
+    fn transform_glyf(glyf: &[u8], loca: &[u8], index_format: u16, number_of_glyphs: u16) -> (Vec<u8>, Vec<i16>) {
+        let offset = |i: usize| if index_format == 0 {
+            Stream::read_at::<u16>(loca, i * 2).unwrap() as usize * 2
+        } else {
+            Stream::read_at::<u32>(loca, i * 4).unwrap() as usize
+        };
+
+        let mut n_contour = Vec::new();
+        let mut n_points = Vec::new();
+        let mut flags = Vec::new();
+        let mut glyph = Vec::new();
+        let mut composite = Vec::new();
+        let mut bbox_bitmap = vec![0u8; ((usize::from(number_of_glyphs) + 31) >> 5) << 2];
+        let mut bbox = Vec::new();
+        let mut instructions = Vec::new();
+        let mut x_mins = Vec::new();
+        for glyph_id in 0..usize::from(number_of_glyphs) {
+            let data = &glyf[offset(glyph_id)..offset(glyph_id + 1)];
+            if data.is_empty() {
+                write_u16(&mut n_contour, 0);
+                x_mins.push(0);
+                continue;
+            }
+
+            let mut s = Stream::new(data);
+            let number_of_contours: i16 = s.read().unwrap();
+            let glyph_bbox = s.read_bytes(8).unwrap();
+            x_mins.push(Stream::read_at(glyph_bbox, 0).unwrap());
+            n_contour.extend_from_slice(&number_of_contours.to_be_bytes());
+
+            if number_of_contours < 0 {
+                bbox_bitmap[glyph_id >> 3] |= 0x80 >> (glyph_id & 7);
+                bbox.extend_from_slice(glyph_bbox);
+
+                let start = s.offset();
+                let have_instructions = skip_components(&mut s).unwrap();
+                composite.extend_from_slice(&data[start..s.offset()]);
+                if have_instructions {
+                    let length: u16 = s.read().unwrap();
+                    write_u16_255(&mut glyph, length);
+                    instructions.extend_from_slice(s.read_bytes(usize::from(length)).unwrap());
+                }
+
+                continue;
+            }
+
+            let mut number_of_points = 0;
+            for _ in 0..number_of_contours {
+                let end_point: u16 = s.read().unwrap();
+                write_u16_255(&mut n_points, end_point + 1 - number_of_points);
+                number_of_points = end_point + 1;
+            }
+
+            let length: u16 = s.read().unwrap();
+            let glyph_instructions = s.read_bytes(usize::from(length)).unwrap();
+
+            let points = parse_simple_points(&mut s, number_of_points);
+            let mut prev = Point { x: 0, y: 0, on_curve: true };
+            for p in &points {
+                write_triplet(p.on_curve, p.x - prev.x, p.y - prev.y, &mut flags, &mut glyph);
+                prev = *p;
+            }
+
+            write_u16_255(&mut glyph, length);
+            instructions.extend_from_slice(glyph_instructions);
+
+            let calculated_bbox: Vec<u8> = calculate_bbox(&points).unwrap()
+                .iter().flat_map(|n| n.to_be_bytes().to_vec()).collect();
+            if calculated_bbox != glyph_bbox {
+                bbox_bitmap[glyph_id >> 3] |= 0x80 >> (glyph_id & 7);
+                bbox.extend_from_slice(glyph_bbox);
+            }
+        }
+
+        let bbox = [bbox_bitmap, bbox].concat();
+        let streams = [n_contour, n_points, flags, glyph, composite, bbox, instructions];
+
+        let mut data = Vec::new();
+        write_u16(&mut data, 0); // reserved
+        write_u16(&mut data, 0); // option flags
+        write_u16(&mut data, number_of_glyphs);
+        write_u16(&mut data, index_format);
+        for stream in &streams {
+            write_u32(&mut data, stream.len() as u32);
+        }
+
+        for stream in &streams {
+            data.extend_from_slice(stream);
+        }
+
+        (data, x_mins)
+    }
+
+    fn parse_simple_points(s: &mut Stream, number_of_points: u16) -> Vec<Point> {
+        use simple_flags::*;
+
+        let mut flags = Vec::new();
+        while flags.len() < usize::from(number_of_points) {
+            let flag: u8 = s.read().unwrap();
+            flags.push(flag);
+            if flag & REPEAT_FLAG != 0 {
+                let count: u8 = s.read().unwrap();
+                for _ in 0..count {
+                    flags.push(flag);
+                }
+            }
+        }
+
+        let mut read_coords = |short: u8, same_or_positive: u8| {
+            let mut coords = Vec::new();
+            let mut n = 0i32;
+            for flag in &flags {
+                if flag & short != 0 {
+                    let delta = i32::from(s.read::<u8>().unwrap());
+                    n += if flag & same_or_positive != 0 { delta } else { -delta };
+                } else if flag & same_or_positive == 0 {
+                    n += i32::from(s.read::<i16>().unwrap());
+                }
+
+                coords.push(n);
+            }
+
+            coords
+        };
+
+        let xs = read_coords(X_SHORT_VECTOR, X_IS_SAME_OR_POSITIVE_X_SHORT_VECTOR);
+        let ys = read_coords(Y_SHORT_VECTOR, Y_IS_SAME_OR_POSITIVE_Y_SHORT_VECTOR);
+        flags.iter().zip(xs.iter().zip(&ys))
+            .map(|(flag, (x, y))| Point { x: *x, y: *y, on_curve: flag & ON_CURVE_POINT != 0 })
+            .collect()
+    }
+
+    // Uses the most compact encoding, like the reference implementation does.
+    fn write_triplet(on_curve: bool, x: i32, y: i32, flags: &mut Vec<u8>, glyph: &mut Vec<u8>) {
+        let (abs_x, abs_y) = (x.abs(), y.abs());
+        let on_curve_bit = if on_curve { 0 } else { 128 };
+        let x_sign_bit = if x < 0 { 0 } else { 1 };
+        let y_sign_bit = if y < 0 { 0 } else { 1 };
+        let xy_sign_bits = x_sign_bit + 2 * y_sign_bit;
+
+        let (flag, bytes) = if x == 0 && abs_y < 1280 {
+            (((abs_y & 0xF00) >> 7) + y_sign_bit, vec![abs_y])
+        } else if y == 0 && abs_x < 1280 {
+            (10 + ((abs_x & 0xF00) >> 7) + x_sign_bit, vec![abs_x])
+        } else if abs_x < 65 && abs_y < 65 {
+            (20 + ((abs_x - 1) & 0x30) + (((abs_y - 1) & 0x30) >> 2) + xy_sign_bits,
+             vec![(((abs_x - 1) & 0xF) << 4) | ((abs_y - 1) & 0xF)])
+        } else if abs_x < 769 && abs_y < 769 {
+            (84 + 12 * (((abs_x - 1) & 0x300) >> 8) + (((abs_y - 1) & 0x300) >> 6) + xy_sign_bits,
+             vec![abs_x - 1, abs_y - 1])
+        } else if abs_x < 4096 && abs_y < 4096 {
+            (120 + xy_sign_bits, vec![abs_x >> 4, ((abs_x & 0xF) << 4) | (abs_y >> 8), abs_y])
+        } else {
+            (124 + xy_sign_bits, vec![abs_x >> 8, abs_x, abs_y >> 8, abs_y])
+        };
+
+        flags.push((on_curve_bit + flag) as u8);
+        glyph.extend(bytes.iter().map(|b| *b as u8));
+    }
+
+    fn transform_hmtx(hmtx: &[u8], number_of_h_metrics: u16, x_mins: &[i16]) -> Option<Vec<u8>> {
+        let mut advances = Vec::new();
+        for (i, x_min) in x_mins.iter().enumerate() {
+            let lsb: i16 = if i < usize::from(number_of_h_metrics) {
+                advances.extend_from_slice(&hmtx[i * 4..i * 4 + 2]);
+                Stream::read_at(hmtx, i * 4 + 2)?
+            } else {
+                Stream::read_at(hmtx, usize::from(number_of_h_metrics) * 2 + i * 2)?
+            };
+
+            if lsb != *x_min {
+                return None;
+            }
+        }
+
+        Some([&[3][..], &advances].concat())
+    }
+
+    fn brotli_store(data: &[u8]) -> Vec<u8> {
+        let mut bits = BitWriter { data: Vec::new(), used: 0 };
+        bits.write(0, 1); // WBITS = 16
+        for chunk in data.chunks(0x10000) {
+            bits.write(0, 1); // ISLAST
+            bits.write(0, 2); // MNIBBLES = 4
+            bits.write(chunk.len() as u32 - 1, 16); // MLEN - 1
+            bits.write(1, 1); // ISUNCOMPRESSED
+            bits.used = 0; // data is byte aligned
+            bits.data.extend_from_slice(chunk);
+        }
+        bits.write(1, 1); // ISLAST
+        bits.write(1, 1); // ISLASTEMPTY
+        bits.data
+    }
+
+    struct BitWriter {
+        data: Vec<u8>,
+        used: u8,
+    }
+
+    impl BitWriter {
+        fn write(&mut self, value: u32, count: u8) {
+            for i in 0..count {
+                if self.used == 0 {
+                    self.data.push(0);
+                }
+
+                *self.data.last_mut().unwrap() |= (((value >> i) & 1) as u8) << self.used;
+                self.used = (self.used + 1) % 8;
+            }
+        }
+    }
+
+    fn write_u32_base128(w: &mut Vec<u8>, n: u32) {
+        let mut bytes = Vec::new();
+        let mut n = n;
+        loop {
+            bytes.push((n & 0x7F) as u8 | if bytes.is_empty() { 0 } else { 0x80 });
+            n >>= 7;
+            if n == 0 {
+                break;
+            }
+        }
+
+        bytes.reverse();
+        w.extend_from_slice(&bytes);
+    }
+
+    fn write_u16_255(w: &mut Vec<u8>, n: u16) {
+        match n {
+            0..=252 => w.push(n as u8),
+            253..=505 => w.extend_from_slice(&[255, (n - 253) as u8]),
+            506..=761 => w.extend_from_slice(&[254, (n - 506) as u8]),
+            _ => {
+                w.push(253);
+                write_u16(w, n);
+            }
+        }
+    }
+}
@@ -16,6 +16,7 @@ pub enum TtfType {
     UInt8(u8),
     Int16(i16),
     UInt16(u16),
+    UInt24(u32),
     Int32(i32),
     UInt32(u32),
     CFFInt(i32),
@@ -56,6 +57,9 @@ pub fn convert_type(value: TtfType, data: &mut Vec<u8>) {
         TtfType::UInt16(n) => {
             data.extend_from_slice(&u16::to_be_bytes(n));
         }
+        TtfType::UInt24(n) => {
+            data.extend_from_slice(&u32::to_be_bytes(n)[1..]);
+        }
         TtfType::Int32(n) => {
             data.extend_from_slice(&i32::to_be_bytes(n));
         }
@@ -8,6 +8,13 @@ cargo build --no-default-features
 cargo build
 
 cargo test
+# test optional features
+cargo test --features hinting
+# `miniz_oxide` and `brotli-decompressor` require a newer Rust than the minimum supported one
+if [ "$TRAVIS_RUST_VERSION" != "1.35.0" ]; then
+    cargo test --features woff
+    cargo test --features woff2
+fi
 
 
 cd c-api
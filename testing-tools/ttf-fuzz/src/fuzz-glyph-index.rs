@@ -12,7 +12,7 @@ const CHARS: &[char] = &[
 
 fn main() {
     afl::fuzz!(|data: &[u8]| {
-        if let Some(font) = ttf_parser::Font::from_data(data, 0) {
+        if let Ok(font) = ttf_parser::Font::from_data(data, 0) {
             for c in CHARS {
                 let _ = font.glyph_index(*c);
             }